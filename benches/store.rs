@@ -0,0 +1,176 @@
+//! Cold-start benchmarks for the task store.
+//!
+//! Run with `cargo bench`. Pass `--bench-check` (`cargo bench -- --bench-check`)
+//! to exit non-zero when any measurement exceeds its budget in [`BUDGET`].
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::{
+    hint::black_box,
+    path::Path,
+    time::{Duration, Instant},
+};
+use task_cli::{
+    store::Store,
+    task::{filter_by_status, sort_tasks, SortKey, Status, Task},
+};
+use ulid::Ulid;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// Median time allowed per operation and fixture size, measured in release mode.
+const BUDGET: &[(&str, usize, Duration)] = &[
+    ("load", 1_000, Duration::from_millis(5)),
+    ("load", 10_000, Duration::from_millis(40)),
+    ("load", 100_000, Duration::from_millis(400)),
+    ("filter", 1_000, Duration::from_millis(1)),
+    ("filter", 10_000, Duration::from_millis(8)),
+    ("filter", 100_000, Duration::from_millis(80)),
+    ("sort", 1_000, Duration::from_millis(1)),
+    ("sort", 10_000, Duration::from_millis(8)),
+    ("sort", 100_000, Duration::from_millis(100)),
+    ("save", 1_000, Duration::from_millis(5)),
+    ("save", 10_000, Duration::from_millis(40)),
+    ("save", 100_000, Duration::from_millis(400)),
+];
+
+/// Small xorshift generator so fixtures are identical between runs.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn fixture(count: usize) -> Vec<Task> {
+    const WORDS: [&str; 8] = [
+        "write",
+        "review",
+        "deploy",
+        "report",
+        "invoice",
+        "groceries",
+        "backup",
+        "meeting",
+    ];
+
+    let mut rng = Rng(0x5eed_0000 + count as u64);
+    let epoch = DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap();
+
+    (0..count)
+        .map(|i| {
+            let created_at = epoch + ChronoDuration::seconds(i as i64 * 60);
+            let updated_at = created_at + ChronoDuration::seconds((rng.next() % 86_400) as i64);
+            let status = match rng.next() % 3 {
+                0 => Status::ToDo,
+                1 => Status::InProgress,
+                _ => Status::Done,
+            };
+            let description = (0..4)
+                .map(|_| WORDS[(rng.next() % WORDS.len() as u64) as usize])
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let mut task = Task::new(description);
+            task.id = Ulid::from_parts(created_at.timestamp_millis() as u64, rng.next() as u128);
+            task.status = status;
+            task.created_at = created_at;
+            task.updated_at = updated_at;
+            task
+        })
+        .collect()
+}
+
+fn iterations(count: usize) -> usize {
+    match count {
+        0..=1_000 => 50,
+        1_001..=10_000 => 20,
+        _ => 5,
+    }
+}
+
+fn median(mut f: impl FnMut(), iterations: usize) -> Duration {
+    let mut samples = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect::<Vec<_>>();
+
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+fn measure(dir: &Path, count: usize) -> Vec<(&'static str, Duration)> {
+    let mut store = Store::new(dir.join(format!("tasks-{count}.json")), fixture(count));
+    store.save().unwrap();
+    let tasks = &store.tasks;
+
+    let runs = iterations(count);
+
+    vec![
+        (
+            "load",
+            median(|| drop(black_box(Store::open(store.path.clone()))), runs),
+        ),
+        (
+            "filter",
+            median(
+                || drop(black_box(filter_by_status(tasks, &Status::InProgress))),
+                runs,
+            ),
+        ),
+        (
+            "sort",
+            median(
+                || {
+                    let mut tasks = tasks.clone();
+                    sort_tasks(&mut tasks, SortKey::Updated);
+                    black_box(tasks);
+                },
+                runs,
+            ),
+        ),
+        ("save", median(|| store.save().unwrap(), runs)),
+    ]
+}
+
+fn main() {
+    let check = std::env::args().any(|arg| arg == "--bench-check");
+
+    let dir = std::env::temp_dir().join(format!("task-cli-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("could not create bench directory");
+
+    let mut over_budget = Vec::new();
+
+    for count in SIZES {
+        for (op, elapsed) in measure(&dir, count) {
+            let budget = BUDGET
+                .iter()
+                .find(|(name, size, _)| *name == op && *size == count)
+                .map(|(_, _, budget)| *budget)
+                .expect("missing budget entry");
+
+            let marker = if elapsed > budget { "OVER" } else { "ok" };
+            println!(
+                "{op:<8} {count:>7} tasks  median {:>10.3?}  budget {:>8?}  {marker}",
+                elapsed, budget
+            );
+
+            if elapsed > budget {
+                over_budget.push((op, count));
+            }
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    if check && !over_budget.is_empty() {
+        eprintln!("performance budget exceeded: {over_budget:?}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,194 @@
+//! Optional policy that keeps the active list honest: open tasks nobody has
+//! touched for some months are demoted one priority level or moved to
+//! someday. Each change can be undone for a few days.
+
+use chrono::{DateTime, Duration, Months, Utc};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    pause::Pause,
+    store::Store,
+    task::{section, Priority, Section, Task},
+    time::format_local,
+};
+
+pub const SOMEDAY_TAG: &str = "someday";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Lower the priority one level at a time, down to `low`.
+    #[default]
+    Demote,
+    /// Tag the task `someday`, which moves it out of the active section.
+    Someday,
+}
+
+/// The `[aging]` table of the config file; without `months` nothing ages.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Policy {
+    pub months: Option<u32>,
+    pub action: Action,
+    /// How long `aging undo` can revert a change.
+    pub undo_days: u32,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            months: None,
+            action: Action::Demote,
+            undo_days: 7,
+        }
+    }
+}
+
+/// What aging last did to a task, kept so it can be undone.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct Aged {
+    pub at: DateTime<Utc>,
+    /// Priority before a demotion; unset when the task was moved to someday.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub someday: bool,
+}
+
+impl Policy {
+    /// Tasks last active before this are due to age.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        now.checked_sub_months(Months::new(self.months?))
+    }
+
+    pub fn undo_until(&self, aged: &Aged) -> DateTime<Utc> {
+        aged.at + Duration::days(self.undo_days.into())
+    }
+}
+
+/// When the task was last changed, counting a change made by aging so a
+/// demoted task only ages again after another full period.
+fn last_active(task: &Task) -> DateTime<Utc> {
+    task.aged
+        .as_ref()
+        .map_or(task.updated_at, |aged| aged.at.max(task.updated_at))
+}
+
+fn demoted(priority: Option<Priority>) -> Option<Priority> {
+    match priority {
+        Some(Priority::Urgent) => Some(Priority::High),
+        Some(Priority::High) => Some(Priority::Medium),
+        Some(Priority::Medium) | None => Some(Priority::Low),
+        Some(Priority::Low) => None,
+    }
+}
+
+/// Active tasks that have been idle past the cutoff and can still age; tasks
+/// on hold never do. Time spent paused does not count as idle, and nothing
+/// ages during a pause.
+pub fn due_to_age(
+    tasks: &[Task],
+    policy: &Policy,
+    pause: Option<&Pause>,
+    now: DateTime<Utc>,
+) -> Vec<Ulid> {
+    let Some(cutoff) = policy.cutoff(now) else {
+        return Vec::new();
+    };
+    if pause.is_some_and(|pause| pause.active(now)) {
+        return Vec::new();
+    }
+    let idle_since = |task: &Task| {
+        let last = last_active(task);
+        last + pause.map_or(Duration::zero(), |pause| pause.overlap(last, now))
+    };
+
+    tasks
+        .iter()
+        .filter(|task| section(task, tasks, now) == Section::Active)
+        .filter(|task| task.hold.is_none())
+        .filter(|task| idle_since(task) < cutoff)
+        .filter(|task| policy.action == Action::Someday || demoted(task.priority).is_some())
+        .map(|task| task.id)
+        .collect()
+}
+
+/// Ages the tasks due to, returning their ids.
+pub fn apply(
+    store: &mut Store,
+    policy: &Policy,
+    pause: Option<&Pause>,
+    now: DateTime<Utc>,
+) -> Vec<Ulid> {
+    let ids = due_to_age(&store.tasks, policy, pause, now);
+
+    for id in &ids {
+        let task = store.find_mut(*id).expect("ids come from the store");
+        task.aged = Some(match policy.action {
+            Action::Demote => {
+                let before = task.priority;
+                task.set_priority(demoted(before));
+                Aged {
+                    at: now,
+                    priority: before,
+                    someday: false,
+                }
+            }
+            Action::Someday => {
+                task.add_tag(SOMEDAY_TAG);
+                Aged {
+                    at: now,
+                    priority: None,
+                    someday: true,
+                }
+            }
+        });
+    }
+
+    ids
+}
+
+/// What to tell the user after `aged` tasks aged, if any did.
+pub fn notice(aged: &[Ulid], policy: &Policy, now: DateTime<Utc>) -> Option<String> {
+    if aged.is_empty() {
+        return None;
+    }
+
+    let count = match aged.len() {
+        1 => "1 task".to_owned(),
+        n => format!("{n} tasks"),
+    };
+    let done = match policy.action {
+        Action::Demote => "demoted",
+        Action::Someday => "moved to someday",
+    };
+    let until = now + Duration::days(policy.undo_days.into());
+    Some(format!(
+        "{count} idle for {} months {done}; `task-cli aging undo` reverts this until {}",
+        policy.months.unwrap_or_default(),
+        format_local(&until)
+    ))
+}
+
+/// Whether the last aging of `task` can still be undone.
+pub fn undoable(task: &Task, policy: &Policy, now: DateTime<Utc>) -> bool {
+    task.aged
+        .as_ref()
+        .is_some_and(|aged| now <= policy.undo_until(aged))
+}
+
+/// Reverts the last aging of `task`. Undoing counts as activity, so the
+/// task does not age again straight away.
+pub fn undo(task: &mut Task, now: DateTime<Utc>) {
+    let Some(aged) = task.aged.take() else {
+        return;
+    };
+
+    if aged.someday {
+        task.remove_tag(SOMEDAY_TAG);
+    } else {
+        task.set_priority(aged.priority);
+    }
+    task.updated_at = now;
+}
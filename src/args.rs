@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::CliError;
+
+/// Options that never take a value.
+const SWITCHES: &[&str] = &[
+    "check",
+    "clear",
+    "fix",
+    "flat",
+    "force",
+    "keep",
+    "notify",
+    "no-color",
+    "no-links",
+    "purge",
+    "quiet",
+    "require-revision",
+    "version",
+    "yes",
+];
+
+/// Command line split into positional arguments and `--name [value]` options.
+#[derive(Default, Debug)]
+pub struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    pub fn parse(raw: impl IntoIterator<Item = String>) -> Self {
+        let mut args = Self::default();
+        let mut raw = raw.into_iter().peekable();
+
+        while let Some(arg) = raw.next() {
+            if arg == "--" {
+                args.positional.extend(raw.by_ref());
+            } else if let Some(name) = arg.strip_prefix("--") {
+                if let Some((name, value)) = name.split_once('=') {
+                    args.options.insert(name.to_owned(), Some(value.to_owned()));
+                } else if SWITCHES.contains(&name) {
+                    args.options.insert(name.to_owned(), None);
+                } else {
+                    let value = raw.next_if(|next| !next.starts_with("--"));
+                    args.options.insert(name.to_owned(), value);
+                }
+            } else {
+                args.positional.push(arg);
+            }
+        }
+
+        args
+    }
+
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(String::as_str)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(Option::as_deref)
+    }
+
+    /// Names of all options given, in no particular order.
+    pub fn option_names(&self) -> impl Iterator<Item = &str> {
+        self.options.keys().map(String::as_str)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    pub fn push_positional(&mut self, arg: String) {
+        self.positional.push(arg);
+    }
+
+    /// The same options with the first `count` positional arguments
+    /// dropped, for commands that run another command.
+    pub fn shifted(&self, count: usize) -> Self {
+        Self {
+            positional: self.positional.iter().skip(count).cloned().collect(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+/// Splits an interactive input line into words, honouring single and double
+/// quotes and backslash escapes like a shell would.
+pub fn split_words(line: &str) -> Result<Vec<String>, CliError> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => word.get_or_insert_with(String::new).push(c),
+            ('\\', _) => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| CliError::invalid_args(line, "a backslash ends the line"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, _) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(quote) = quote {
+        return Err(CliError::invalid_args(line, format!("unclosed {quote}")));
+    }
+
+    words.extend(word);
+    Ok(words)
+}
@@ -0,0 +1,185 @@
+//! Large descriptions and notes live in separate files next to the store so
+//! the store itself stays small and quick to parse. The task keeps a short
+//! preview plus the blob's file name, which is a hash of the text: a blob
+//! never changes once written, and an edit writes a new one rather than
+//! overwriting what readers of the old store or another machine still use.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::task::Task;
+
+/// Texts longer than this many bytes are moved out of the store.
+pub const THRESHOLD: usize = 4 * 1024;
+const PREVIEW_CHARS: usize = 80;
+
+pub fn blob_dir(store_path: &Path) -> PathBuf {
+    store_path.with_extension("blobs")
+}
+
+fn preview(text: &str) -> String {
+    let first_line = text.lines().next().unwrap_or_default();
+    let mut preview = first_line.chars().take(PREVIEW_CHARS).collect::<String>();
+    preview.push('…');
+    preview
+}
+
+/// FNV-1a, which is plenty to tell apart the texts of one store.
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Writes `text` out, unless a blob holds it already, and returns the name.
+fn write_blob(dir: &Path, text: &str) -> io::Result<String> {
+    let name = format!("{:016x}.txt", hash(text));
+    let path = dir.join(&name);
+    if !path.exists() {
+        // Through a sibling file, so an interrupted write leaves no blob
+        // under the name of a text it does not hold.
+        std::fs::create_dir_all(dir)?;
+        let temp = dir.join(format!(".{name}.tmp-{}", std::process::id()));
+        std::fs::write(&temp, text).and_then(|()| std::fs::rename(&temp, &path))?;
+    }
+    Ok(name)
+}
+
+/// Whether `name` is one that blobs are written under: the hash of the text
+/// and `.txt`. Names come from store files, imports and shared boards, so
+/// anything else, such as a path, is refused rather than read or written.
+pub fn is_blob_name(name: &str) -> bool {
+    name.strip_suffix(".txt").is_some_and(|hash| {
+        hash.len() == 16
+            && hash
+                .bytes()
+                .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+    })
+}
+
+fn read_blob(dir: &Path, name: &str) -> io::Result<String> {
+    if !is_blob_name(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not the name of a blob",
+        ));
+    }
+    std::fs::read_to_string(dir.join(name))
+}
+
+/// Copy of `task` as it should be written to the store, with oversized texts
+/// written out to blob files.
+pub fn externalize(task: &Task, dir: &Path) -> io::Result<Task> {
+    let mut stored = task.clone();
+
+    if stored.description_blob.is_none() && stored.description.len() > THRESHOLD {
+        stored.description_blob = Some(write_blob(dir, &task.description)?);
+        stored.description = preview(&task.description);
+    }
+
+    if let Some(notes) = &task.notes {
+        if stored.notes_blob.is_none() && notes.len() > THRESHOLD {
+            stored.notes_blob = Some(write_blob(dir, notes)?);
+            stored.notes = Some(preview(notes));
+        }
+    }
+
+    Ok(stored)
+}
+
+/// Copy of `task` with blob contents inlined back into its fields. A blob
+/// that cannot be read, say one another process just removed, leaves the
+/// preview and the reference to it in place.
+pub fn inline(task: &Task, dir: &Path) -> Task {
+    let mut full = task.clone();
+    let read = |name: &str| {
+        read_blob(dir, name)
+            .inspect_err(|err| eprintln!("warning: cannot read blob `{name}`: {err}"))
+            .ok()
+    };
+
+    if let Some(text) = full.description_blob.as_deref().and_then(read) {
+        full.description = text;
+        full.description_blob = None;
+    }
+
+    if let Some(text) = full.notes_blob.as_deref().and_then(read) {
+        full.notes = Some(text);
+        full.notes_blob = None;
+    }
+
+    full
+}
+
+/// Copies of `tasks` with blob contents inlined.
+pub fn inline_all(tasks: &[Task], dir: &Path) -> Vec<Task> {
+    tasks.iter().map(|task| inline(task, dir)).collect()
+}
+
+/// Removes blob files no longer referenced by any task.
+pub fn collect_garbage<'a>(tasks: impl IntoIterator<Item = &'a Task>, dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let referenced = tasks
+        .into_iter()
+        .flat_map(|task| [&task.description_blob, &task.notes_blob])
+        .flatten()
+        .collect::<HashSet<_>>();
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !referenced.contains(&name) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_write_new_blobs_next_to_the_old() {
+        let dir = std::env::temp_dir().join(format!("task-cli-blob-{}", ulid::Ulid::new()));
+        let mut task = Task::new(format!("{}a", "x".repeat(THRESHOLD)));
+        let first = externalize(&task, &dir).unwrap();
+        task.set_description(format!("{}b", "x".repeat(THRESHOLD)));
+        let second = externalize(&task, &dir).unwrap();
+
+        assert_eq!(first.description, second.description);
+        assert_ne!(first.description_blob, second.description_blob);
+        assert!(inline(&first, &dir).description.ends_with('a'));
+        assert_eq!(inline(&second, &dir).description, task.description);
+        assert_eq!(
+            externalize(&task, &dir).unwrap().description_blob,
+            second.description_blob
+        );
+    }
+
+    #[test]
+    fn only_blob_names_are_read() {
+        assert!(is_blob_name("02e7be993f1c9d35.txt"));
+        for name in [
+            "/etc/passwd",
+            "../02e7be993f1c9d35.txt",
+            "02E7BE993F1C9D35.txt",
+            "x.txt",
+        ] {
+            assert!(!is_blob_name(name), "{name}");
+        }
+
+        let dir = std::env::temp_dir().join(format!("task-cli-blob-{}", ulid::Ulid::new()));
+        let mut task = Task::new("a preview…".to_owned());
+        for name in ["/etc/hostname", "0000000000000000.txt"] {
+            task.description_blob = Some(name.to_owned());
+            let full = inline(&task, &dir);
+            assert_eq!(full.description, "a preview…");
+            assert_eq!(full.description_blob, task.description_blob);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! `task-cli capture`: an entry point for other tools (dictation, phone
+//! shortcuts, transcription pipelines) to drop captured tasks into the inbox.
+//!
+//! Input is plain text, one task per line with `--split-lines`, or with
+//! `--json` a JSON object, an array of objects or one object per line:
+//!
+//! ```json
+//! {"description": "Call Sam", "notes": "...", "due": "tomorrow",
+//!  "priority": "high", "tags": ["calls"], "id": "memo-42#1"}
+//! ```
+//!
+//! Only `description` is required. `id` is the sender's own key for the
+//! entry: an entry whose key was captured before is skipped, so a batch can
+//! safely be sent again after a failure. A batch is all or nothing.
+
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::{
+    task::{Priority, Task},
+    time::parse_datetime,
+    CliError,
+};
+
+/// Tag put on every captured task.
+pub const INBOX_TAG: &str = "inbox";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub description: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Idempotency key chosen by the sender.
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+impl Entry {
+    fn text(description: &str) -> Self {
+        Self {
+            description: description.to_owned(),
+            notes: None,
+            due: None,
+            priority: None,
+            tags: Vec::new(),
+            id: None,
+        }
+    }
+
+    pub fn import_id(&self) -> Option<String> {
+        self.id.as_ref().map(|id| format!("capture:{id}"))
+    }
+
+    /// Builds the inbox task, checking the fields.
+    pub fn to_task(&self) -> Result<Task, String> {
+        let description = self.description.trim();
+        if description.is_empty() {
+            return Err("empty description".to_owned());
+        }
+
+        let mut task = Task::new(description.to_owned());
+        task.set_notes(self.notes.clone().filter(|notes| !notes.trim().is_empty()));
+        if let Some(due) = &self.due {
+            task.due = Some(parse_datetime(due).map_err(|_| format!("invalid due `{due}`"))?);
+        }
+        if let Some(priority) = &self.priority {
+            task.priority = Some(
+                Priority::from_str(priority)
+                    .map_err(|_| format!("invalid priority `{priority}`"))?,
+            );
+        }
+        for tag in std::iter::once(INBOX_TAG).chain(self.tags.iter().map(String::as_str)) {
+            if !task.has_tag(tag) {
+                task.tags.push(tag.to_owned());
+            }
+        }
+        task.import_id = self.import_id();
+
+        Ok(task)
+    }
+}
+
+/// A list marker such as `- `, `* `, `[ ] ` or `3. ` at the start of a line.
+fn strip_marker(line: &str) -> &str {
+    let line = line.trim();
+    for marker in ["- [ ] ", "* [ ] ", "[ ] ", "- ", "* ", "• "] {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return rest.trim_start();
+        }
+    }
+    match line.split_once(['.', ')']) {
+        Some((number, rest))
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            rest.trim_start()
+        }
+        _ => line,
+    }
+}
+
+/// One entry per non-empty line, without list markers.
+pub fn from_lines(text: &str) -> Vec<Entry> {
+    text.lines()
+        .map(strip_marker)
+        .filter(|line| !line.is_empty())
+        .map(Entry::text)
+        .collect()
+}
+
+/// A single entry: the first non-empty line describes the task and the rest
+/// becomes its notes.
+pub fn from_text(text: &str) -> Vec<Entry> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let mut entry = Entry::text(first.trim());
+    entry.notes = Some(rest.trim().to_owned()).filter(|notes| !notes.is_empty());
+    vec![entry]
+}
+
+pub fn from_json(text: &str) -> Result<Vec<Entry>, CliError> {
+    let invalid = |err: serde_json::Error| CliError::Import(format!("invalid capture JSON: {err}"));
+    let trimmed = text.trim_start();
+
+    if trimmed.starts_with('[') {
+        return serde_json::from_str(text).map_err(invalid);
+    }
+
+    // A single object, or JSON lines.
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|err| {
+                CliError::Import(format!("invalid capture JSON on line {}: {err}", index + 1))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .or_else(|err| {
+            serde_json::from_str(text)
+                .map(|entry| vec![entry])
+                .map_err(|_| err)
+        })
+}
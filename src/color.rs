@@ -0,0 +1,288 @@
+use std::io::IsTerminal;
+use ulid::Ulid;
+
+use crate::{
+    args::Args,
+    config::{ColorMode, Config, Theme},
+    task::{Priority, Status},
+    CliError,
+};
+
+/// SGR parameters for a style such as `"bold bright-red"`. Unknown words are
+/// ignored so a typo in the theme only loses that attribute.
+fn sgr(style: &str) -> String {
+    style
+        .split_whitespace()
+        .filter_map(|word| {
+            let code = match word {
+                "bold" => 1,
+                "dim" => 2,
+                "italic" => 3,
+                "underline" => 4,
+                "black" => 30,
+                "red" => 31,
+                "green" => 32,
+                "yellow" => 33,
+                "blue" => 34,
+                "magenta" => 35,
+                "cyan" => 36,
+                "white" => 37,
+                "bright-black" | "gray" | "grey" => 90,
+                "bright-red" => 91,
+                "bright-green" => 92,
+                "bright-yellow" => 93,
+                "bright-blue" => 94,
+                "bright-magenta" => 95,
+                "bright-cyan" => 96,
+                "bright-white" => 97,
+                _ => return None,
+            };
+            Some(code.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// ASCII stand-ins for the symbols used in output.
+const GLYPHS: &[(char, &str)] = &[
+    ('✓', "x"),
+    ('✗', "x"),
+    ('…', "..."),
+    ('•', "*"),
+    ('—', "-"),
+    ('–', "-"),
+    ('→', "->"),
+    ('←', "<-"),
+    ('█', "#"),
+    ('░', "."),
+    ('▏', "|"),
+];
+
+/// Number of columns `text` takes up, skipping SGR and OSC 8 sequences.
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            width += 1;
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x1b' {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    width
+}
+
+/// Applies theme styles to output text when color is enabled, and turns
+/// text into terminal hyperlinks when those are. A rendering profile can
+/// also limit the width of lines and keep them to ASCII.
+pub struct Painter {
+    enabled: bool,
+    links: bool,
+    web_url: Option<String>,
+    theme: Theme,
+    width: Option<usize>,
+    ascii: bool,
+}
+
+impl Painter {
+    /// Color is used when stdout is a terminal, unless `--no-color`, a
+    /// non-empty `NO_COLOR`, the config file or a monochrome profile say
+    /// otherwise. The profile comes from `--profile` or the `profile` key.
+    pub fn new(args: &Args, config: &Config) -> Result<Self, CliError> {
+        let profile = match args.value("profile").or(config.profile.as_deref()) {
+            Some(name) => config
+                .profile(name)
+                .ok_or_else(|| CliError::UnknownProfile(name.to_owned()))?,
+            None => Default::default(),
+        };
+        let monochrome = profile.monochrome.unwrap_or(false);
+
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+        let enabled = !args.flag("no-color")
+            && !monochrome
+            && match config.color {
+                ColorMode::Always => true,
+                ColorMode::Never => false,
+                ColorMode::Auto => !no_color_env && std::io::stdout().is_terminal(),
+            };
+
+        let links = !args.flag("no-links")
+            && !monochrome
+            && match config.hyperlinks {
+                ColorMode::Always => true,
+                ColorMode::Never => false,
+                ColorMode::Auto => {
+                    std::io::stdout().is_terminal()
+                        && std::env::var("TERM").map_or(true, |term| term != "dumb")
+                }
+            };
+
+        Ok(Self {
+            enabled,
+            links,
+            web_url: config.web_url.clone(),
+            theme: config.theme.clone(),
+            width: profile.width.filter(|width| *width > 0),
+            ascii: profile.ascii.unwrap_or(false),
+        })
+    }
+
+    /// Whether the profile limits the width of lines.
+    pub fn narrow(&self) -> bool {
+        self.width.is_some()
+    }
+
+    /// `text` made to suit the profile: symbols swapped for ASCII and each
+    /// line word-wrapped to the maximum width, continuing with an indent.
+    pub fn fit(&self, text: &str) -> String {
+        let text = if self.ascii {
+            text.chars()
+                .map(|c| match GLYPHS.iter().find(|(glyph, _)| *glyph == c) {
+                    Some((_, ascii)) => (*ascii).to_owned(),
+                    None => c.to_string(),
+                })
+                .collect()
+        } else {
+            text.to_owned()
+        };
+        let Some(width) = self.width else {
+            return text;
+        };
+
+        let mut fitted = Vec::new();
+        for line in text.lines() {
+            let indent = line.len() - line.trim_start().len();
+            let mut current = line[..indent].to_owned();
+            let mut current_width = indent;
+            let mut empty = true;
+
+            for word in line.split_whitespace() {
+                let word_width = visible_width(word);
+                if !empty && current_width + 1 + word_width > width {
+                    fitted.push(current);
+                    current = " ".repeat(indent + 2);
+                    current_width = indent + 2;
+                    empty = true;
+                }
+                if !empty {
+                    current.push(' ');
+                    current_width += 1;
+                }
+                current.push_str(word);
+                current_width += word_width;
+                empty = false;
+            }
+            fitted.push(current);
+        }
+
+        fitted.join("\n")
+    }
+
+    /// An OSC 8 hyperlink to `url` showing `text`.
+    pub fn link(&self, url: &str, text: &str) -> String {
+        if self.links {
+            format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// `text` linked to the task: its page under the configured `web-url`,
+    /// or a `task-cli://` URL otherwise.
+    pub fn task_link(&self, id: &Ulid, text: &str) -> String {
+        let url = match &self.web_url {
+            Some(base) => format!("{}/tasks/{id}", base.trim_end_matches('/')),
+            None => format!("task-cli://{id}"),
+        };
+        self.link(&url, text)
+    }
+
+    /// `text` with the `http://` and `https://` URLs in it made clickable.
+    pub fn urls(&self, text: &str) -> String {
+        if !self.links {
+            return text.to_owned();
+        }
+
+        let mut linked = String::new();
+        let mut rest = text;
+        while let Some(start) = ["http://", "https://"]
+            .iter()
+            .filter_map(|scheme| rest.find(scheme))
+            .min()
+        {
+            let end = rest[start..]
+                .find(char::is_whitespace)
+                .map_or(rest.len(), |end| start + end);
+            // Punctuation right after a URL usually belongs to the sentence.
+            let url = rest[start..end].trim_end_matches(['.', ',', ';', ':', ')', '!', '?']);
+
+            linked.push_str(&rest[..start]);
+            linked.push_str(&self.link(url, url));
+            rest = &rest[start + url.len()..];
+        }
+        linked.push_str(rest);
+
+        linked
+    }
+
+    pub fn paint(&self, style: &str, text: &str) -> String {
+        let codes = sgr(style);
+
+        if !self.enabled || codes.is_empty() {
+            text.to_owned()
+        } else {
+            format!("\x1b[{codes}m{text}\x1b[0m")
+        }
+    }
+
+    pub fn id(&self, text: &str) -> String {
+        self.paint(&self.theme.id, text)
+    }
+
+    pub fn status(&self, status: &Status) -> String {
+        let style = match status {
+            Status::ToDo => &self.theme.todo,
+            Status::InProgress => &self.theme.in_progress,
+            Status::Done => &self.theme.done,
+        };
+        self.paint(style, &status.to_string())
+    }
+
+    pub fn priority(&self, priority: Priority) -> String {
+        let style = match priority {
+            Priority::Low => &self.theme.low,
+            Priority::Medium => &self.theme.medium,
+            Priority::High => &self.theme.high,
+            Priority::Urgent => &self.theme.urgent,
+        };
+        self.paint(style, &priority.to_string())
+    }
+
+    pub fn tag(&self, tag: &str) -> String {
+        self.paint(&self.theme.tag, &format!("+{tag}"))
+    }
+
+    pub fn overdue(&self, text: &str) -> String {
+        self.paint(&self.theme.overdue, text)
+    }
+}
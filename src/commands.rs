@@ -0,0 +1,3544 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use ulid::Ulid;
+
+use crate::{
+    aging,
+    args::Args,
+    blob, capture,
+    color::Painter,
+    compat,
+    config::Config,
+    dashboard::{self, Entry},
+    edit::{self, Scratch},
+    history, ics, import, lint,
+    locale::Locale,
+    location,
+    lock::{StoreLock, TaskLocks},
+    meeting::{self, Meeting},
+    pause::{self, Pause},
+    phrases, picker, query, rebase, remind,
+    remote::{self, Remote},
+    retention::{self, Hold},
+    rules, runbook, scan, schema, stats,
+    store::{self, Confirm, Store},
+    task::{
+        filter_by_status, inherit, open_blockers, parse_tags, pass_down_defaults, reference_spans,
+        references, section, sort_tasks, update_rollups, urgency, Defaults, Delegation, Priority,
+        Rollup, Section, SortKey, Source, Status, Task, TimeBlock,
+    },
+    template::{self, Template},
+    time::{format_duration, format_local, parse_datetime, parse_duration},
+    usage, CliError,
+};
+
+#[derive(Clone, Copy)]
+pub enum Commands {
+    Add,
+    Update,
+    Delete,
+    Mark,
+    List,
+    Due,
+    Remind,
+    Daemon,
+    Interactive,
+    Note,
+    Show,
+    Export,
+    Trash,
+    Tag,
+    Priority,
+    Done,
+    Template,
+    Edit,
+    Rebase,
+    Usage,
+    Serve,
+    Tour,
+    Demo,
+    Generate,
+    Block,
+    Wait,
+    Locate,
+    Jump,
+    Scan,
+    Gate,
+    Lint,
+    Calendar,
+    Import,
+    Schedule,
+    Plan,
+    Capture,
+    Schema,
+    Rules,
+    Defaults,
+    Aging,
+    Estimate,
+    Track,
+    Blame,
+    Stats,
+    ShellInit,
+    Context,
+    Greet,
+    Sync,
+    SelfUpdate,
+    Backup,
+    Runbook,
+    Meeting,
+    Delegate,
+    Hold,
+    Pause,
+    Next,
+    Then,
+    Dashboard,
+    QuickAdd,
+}
+
+/// Names accepted by [`Commands::from_str`], used for completion.
+pub const COMMAND_NAMES: &[&str] = &[
+    "add",
+    "update",
+    "delete",
+    "mark",
+    "list",
+    "due",
+    "remind",
+    "daemon",
+    "interactive",
+    "note",
+    "show",
+    "export",
+    "trash",
+    "tag",
+    "priority",
+    "done",
+    "template",
+    "edit",
+    "rebase",
+    "usage",
+    "serve",
+    "tour",
+    "demo",
+    "generate",
+    "block",
+    "wait",
+    "locate",
+    "jump",
+    "scan",
+    "gate",
+    "lint",
+    "calendar",
+    "week",
+    "import",
+    "schedule",
+    "plan",
+    "capture",
+    "schema",
+    "rules",
+    "defaults",
+    "aging",
+    "estimate",
+    "track",
+    "blame",
+    "stats",
+    "shell-init",
+    "context",
+    "greet",
+    "sync",
+    "self-update",
+    "backup",
+    "runbook",
+    "meeting",
+    "delegate",
+    "hold",
+    "pause",
+    "next",
+    "then",
+    "dashboard",
+    "quick-add",
+];
+
+impl FromStr for Commands {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "add" => Ok(Self::Add),
+            "update" => Ok(Self::Update),
+            "delete" => Ok(Self::Delete),
+            "mark" => Ok(Self::Mark),
+            "list" => Ok(Self::List),
+            "due" => Ok(Self::Due),
+            "remind" => Ok(Self::Remind),
+            "daemon" => Ok(Self::Daemon),
+            "interactive" => Ok(Self::Interactive),
+            "note" => Ok(Self::Note),
+            "show" => Ok(Self::Show),
+            "export" => Ok(Self::Export),
+            "trash" => Ok(Self::Trash),
+            "tag" => Ok(Self::Tag),
+            "priority" => Ok(Self::Priority),
+            "done" => Ok(Self::Done),
+            "template" => Ok(Self::Template),
+            "edit" => Ok(Self::Edit),
+            "rebase" => Ok(Self::Rebase),
+            "usage" => Ok(Self::Usage),
+            "serve" => Ok(Self::Serve),
+            "tour" => Ok(Self::Tour),
+            "demo" => Ok(Self::Demo),
+            "generate" => Ok(Self::Generate),
+            "block" => Ok(Self::Block),
+            "wait" => Ok(Self::Wait),
+            "locate" => Ok(Self::Locate),
+            "jump" => Ok(Self::Jump),
+            "scan" => Ok(Self::Scan),
+            "gate" => Ok(Self::Gate),
+            "lint" => Ok(Self::Lint),
+            "calendar" | "week" => Ok(Self::Calendar),
+            "import" => Ok(Self::Import),
+            "schedule" => Ok(Self::Schedule),
+            "plan" => Ok(Self::Plan),
+            "capture" => Ok(Self::Capture),
+            "schema" => Ok(Self::Schema),
+            "rules" => Ok(Self::Rules),
+            "defaults" => Ok(Self::Defaults),
+            "aging" => Ok(Self::Aging),
+            "estimate" => Ok(Self::Estimate),
+            "track" => Ok(Self::Track),
+            "blame" => Ok(Self::Blame),
+            "stats" => Ok(Self::Stats),
+            "shell-init" => Ok(Self::ShellInit),
+            "context" => Ok(Self::Context),
+            "greet" => Ok(Self::Greet),
+            "sync" => Ok(Self::Sync),
+            "self-update" => Ok(Self::SelfUpdate),
+            "backup" => Ok(Self::Backup),
+            "runbook" => Ok(Self::Runbook),
+            "meeting" => Ok(Self::Meeting),
+            "delegate" => Ok(Self::Delegate),
+            "hold" => Ok(Self::Hold),
+            "pause" => Ok(Self::Pause),
+            "next" => Ok(Self::Next),
+            "then" => Ok(Self::Then),
+            "dashboard" => Ok(Self::Dashboard),
+            "quick-add" => Ok(Self::QuickAdd),
+            _ => Err(CliError::InvalidCommand),
+        }
+    }
+}
+
+impl Commands {
+    /// Whether the command takes the store lock itself once it is ready to
+    /// change the store, rather than from the start. `edit` locks only the
+    /// tasks being edited while the editor is open.
+    pub fn locks_late(&self) -> bool {
+        matches!(self, Self::Edit | Self::Rebase)
+    }
+
+    /// Whether the command changes the store and needs a save afterwards.
+    pub fn mutates(&self, args: &Args) -> bool {
+        match self {
+            Self::Add
+            | Self::Update
+            | Self::Delete
+            | Self::Mark
+            | Self::Due
+            | Self::Note
+            | Self::Tag
+            | Self::Priority
+            | Self::Done
+            | Self::Edit
+            | Self::Rebase
+            | Self::Block
+            | Self::Wait
+            | Self::Locate
+            | Self::Scan
+            | Self::Import
+            | Self::Schedule
+            | Self::Capture
+            | Self::Estimate
+            | Self::Track
+            | Self::Delegate
+            | Self::Then
+            | Self::QuickAdd => true,
+            Self::Trash => args.positional(1) != Some("list"),
+            Self::Defaults => args.flag("priority") || args.flag("tag"),
+            Self::Aging => args.positional(1) == Some("undo"),
+            Self::Runbook => matches!(args.positional(1), Some("start" | "ack")),
+            Self::Meeting => args.positional(1) == Some("end"),
+            Self::Backup => args.positional(1) == Some("restore-task"),
+            Self::Hold => args.positional(1).is_some(),
+            Self::Lint => args.flag("fix"),
+            _ => false,
+        }
+    }
+}
+
+pub fn print_help() {
+    const HELP_TEXT: &str = r#"Usage: task-cli [command] [args]
+
+Commands:
+    add          Adds a new task
+    update       Update a task
+    delete       Move a task to the trash
+    mark         Change status of a task
+    list         List all tasks
+    due          Set the due date of a task
+    remind       Show tasks whose reminders are due
+    daemon       Periodically send reminder notifications
+    interactive  Run several commands in one session
+    note         Attach notes to a task
+    show         Show all details of a task
+    export       Print all tasks as JSON or iCalendar
+    trash        List, restore or empty deleted tasks
+    tag          Add or remove tags of a task
+    priority     Set the priority of a task
+    block        Mark a task as blocked by other tasks
+    wait         Park a task until a date
+    locate       Set where a task has to be done
+    delegate     Hand a task to someone else and chase them later
+    hold         Keep a task from ever being purged or aged
+    pause        Hold back reminders and nags while away, then catch up
+    next         Show the tasks to work on next
+    then         Set up a follow-up to create when a task is done
+    dashboard    Show the morning view: overdue, today, reviews, deadlines
+    jump         Open the editor where a task came from in the code
+    scan         Turn TODO comments in source files into tasks
+    gate         Fail while matching tasks are open, e.g. in a git hook
+    lint         Point out tasks likely to get lost, and fix some of them
+    calendar     Show open tasks by due week and day (`week`: this week only)
+    import       Create tasks from calendar deadlines
+    schedule     Plan a time block for working on a task
+    plan         Show the day's time blocks as a timeline
+    capture      Add captured notes or transcripts to the inbox
+    quick-add    Prompt for one line to add to the inbox, e.g. from a hotkey
+    schema       Print the JSON Schema of tasks or events
+    rules        Show the automation rules or try them out
+    defaults     Set the priority and tags new subtasks inherit
+    aging        Show or undo what the aging policy did to idle tasks
+    estimate     Set how long a task should take
+    track        Log time spent on a task
+    blame        Show when and how each field of a task last changed
+    stats        Show when tasks get created and completed
+    done         Mark a task as done
+    template     Save, list or delete task templates
+    edit         Edit several tasks at once in $EDITOR
+    rebase       Keep, finish, delete or snooze many tasks from one list
+    usage        Show which commands and filters you use most
+    serve        Serve the tasks over a local HTTP/JSON API
+    tour         Learn the basics with a guided tour on a scratch list
+    demo         Try commands on a temporary list of generated tasks
+    generate     Fill an empty store with generated tasks for benchmarks
+    shell-init   Print a shell hook that switches stores on `cd`
+    context      Show the store the current directory uses
+    greet        Say what needs attention, for shell rc files
+    sync         Show how each remote store last synced, and why it failed
+    backup       Copy the store to a file, bucket or host, or restore from one
+    runbook      Work through an incident checklist and export its timeline
+    meeting      Collect action items during a meeting and file them after
+    self-update  Install the latest release in place of this binary
+
+Options:
+    --file <path>                use another store file (also TASK_CLI_FILE);
+                                 ssh://[user@]host[:port]/path uses one on
+                                 another machine, s3://bucket/key and
+                                 gs://bucket/key one in object storage and
+                                 redis://host[:port]/<board>[?ttl=<duration>]
+                                 a shared board in Redis
+    --version [--check]          print the version; --check also asks GitHub
+                                 whether a newer release is out
+    --no-color                   disable colored output (also NO_COLOR)
+    --no-links                   disable terminal hyperlinks
+    --profile <name>             render for a constrained display, e.g.
+                                 `narrow` (40 columns, no color, ASCII)
+    --format json                with any command, errors go to stderr as
+                                 JSON: code, message, argument, suggestion
+    add <description> [--due <date>] [--remind <offset>] [--priority <level>]
+        [--tag <tag,...>] [--parent <id>] [--source <file>:<line>]
+        [--location <place>|geo:<lat>,<lon>]
+    add [<text>] --template <name> [--var <name>=<value>,...]
+    list [--status <status>] [--tag <tag>] [--where <filter>] [--sort <key>]
+        [--due <phrase>] [--created <phrase>] [--updated <phrase>]
+        [--location <place>|geo:<lat>,<lon> [--radius <distance>]]
+        [--format plain|json] [--flat] [--as-of <phrase>] [--watch]
+                                 groups tasks into active, blocked, waiting,
+                                 someday (tagged `someday`) and done unless
+                                 --flat is given; --as-of lists the tasks as
+                                 they were then, e.g. `--as-of 2024-06-01`
+                                 for the end of that day; --watch lists them
+                                 again whenever the store changes;
+                                 --location matches place names by text,
+                                 or with a geo URI tasks within --radius
+                                 (default 1km, e.g. `500m`) of the point
+    mark <id> --status todo|in-progress|done
+                                 `list <status>` and `mark <id> <status>`
+                                 still work, with a warning unless
+                                 `old-syntax = "allow"` in the config file
+                                 (or "deny" to refuse them)
+    delete <id> [--purge]        --purge deletes permanently
+    due <id> <date> [--remind <offset>]
+    note <id> [<text>]           reads the notes from stdin without <text>
+    show <id> [--as-of <phrase>] the task as it was then, from the event log
+    blame <id>                   each field with its last change from the
+                                 event log, and who made it when more than
+                                 one person (TASK_CLI_USER or the login
+                                 name) has changed the task
+    stats rhythm [--since <time>|<duration>] [--tag <tag>] [--where <filter>]
+                                 creations and completions by hour and day
+                                 of the week, in local time; `--since 12w`
+                                 counts the last twelve weeks
+    remind [--within <duration>] [--notify]
+    daemon [--interval <duration>]
+    trash list | restore <id> | empty [--older-than <duration>]
+    tag <id> <tag>... | -<tag>...
+    priority <id> low|medium|high|urgent|none
+    block <id> <blocker-id>... | -<blocker-id>...
+    wait <id> <date>|none
+    locate <id> <place>|geo:<lat>,<lon>|none
+                                 shown by `show` as a map link and
+                                 exported as the LOCATION of iCalendar
+                                 items, GEO too for geo URIs
+    delegate <id> --to <name> [--follow-up <duration>|<date>] | <id> none
+                                 delegated tasks wait on the other person,
+                                 out of `next`; `remind` and the daemon say
+                                 when to follow up on open ones
+    hold [<id> [--reason <text>] | <id> none]
+                                 a task on hold is never purged, by the
+                                 retention policy or `delete --purge`, or
+                                 aged; without <id>, lists the tasks on hold
+    pause [--until <date> | none]
+                                 until the date, holds back reminders and
+                                 follow-ups, `due within` and `overdue`
+                                 rules, aging and quota nags; the first
+                                 command after, or `none`, prints a digest
+                                 of what came due meanwhile
+    next [--count <n>] [--tag <tag>] [--where <filter>]
+                                 the open tasks that are not blocked,
+                                 waiting, delegated or someday, most urgent
+                                 first (5 unless --count)
+    then <id> <description> [--due +<duration>] [--priority <level>]
+        [--tag <tag,...>] | <id> --template <name> | <id> none
+                                 creates the follow-up once the task is done,
+                                 due that long after; `then` again adds the
+                                 next step of the chain, and {task} is the
+                                 finished task's description
+    dashboard                    sections and their sources are set in the
+                                 `[dashboard]` table of the config file
+    jump <id>                    opens $VISUAL/$EDITOR at the task's --source
+    calendar [--weeks <n>] [--tag <tag>] [--where <filter>]
+                                 weeks start on the locale's first day, or
+                                 `week-start` in the config file
+    import json <file>           tasks as written by `export`, checked against
+                                 `schema task`; copies of existing tasks at a
+                                 higher revision replace them
+    import ics <file> [--filter <text>] [--tag <tag,...>]
+                                 events and to-dos with a date become tasks;
+                                 --filter keeps those whose summary or
+                                 categories contain <text>; importing again
+                                 updates them, as it does for items from
+                                 `export --format ics`
+    import --resume-report <report>
+                                 records an import rejected are listed with
+                                 suggested fixes in <file>.report.json; this
+                                 retries them once fixed there
+    export [--format json|ics]   ics has due dates as to-dos and time blocks
+                                 as events
+    schedule <id> [<day>] <HH:MM>-<HH:MM> | none
+                                 e.g. `schedule 12 tomorrow 14:00-15:30`
+    plan timeline [--date <day>]
+    schema [task|event]          JSON Schema of exported tasks or of the
+                                 event log next to the store
+    defaults <id> [--priority <level>|none] [--tag <tag,...>|none]
+                                 subtasks added under the task, or under its
+                                 subtasks, get these unless given their own;
+                                 changes reach existing subtasks that still
+                                 have the inherited values
+    estimate <id> <duration>|none
+    track <id> [-]<duration>     adds to the time tracked, or takes it off
+                                 with a leading -; parents show the totals
+                                 and progress of their subtasks
+    aging [list] | undo [<id>]   the policy is the [aging] table of the config
+                                 file; undo reverts recent changes, of all
+                                 tasks without <id>
+    rules [list]                 rules come from rules.toml in the config
+                                 directory
+    rules test [<command> ...]   runs the command without saving and shows
+                                 which rules it would set off and what they
+                                 would do
+    capture [<text>] [--from-file <path>] [--split-lines] [--json]
+                                 reads stdin without <text> or --from-file;
+                                 --json takes {"description", "notes", "due",
+                                 "priority", "tags", "id"} objects and prints
+                                 the result as JSON
+    quick-add [<text>]           prompts for the text without it, in a dialog
+                                 when there is no terminal (`gui` builds)
+    gate [--tag <tag>] [--where <filter>] [--install-hook]
+                                 exits non-zero while matching open tasks
+                                 exist; --install-hook runs it on git push
+    lint [--fix] [--tag <tag>] [--where <filter>]
+                                 exits non-zero on high priority tasks
+                                 without a due date, untagged or overlong
+                                 ones, or done tasks never started; --fix
+                                 tags the untagged inbox and moves the end
+                                 of long descriptions to the notes
+    scan [<path>] [--pattern <keyword>|...]
+                                 defaults to `.` and TODO|FIXME; tasks whose
+                                 comment is gone are marked done
+    done <id>|<description>
+    edit [--status <status>] [--tag <tag>] [--where <filter>]
+                                 the store stays usable while the editor is
+                                 open; other commands warn before changing
+                                 the tasks being edited, their changes are
+                                 merged in field by field, and you choose
+                                 when both sides changed the same field
+    rebase [<filter>] [--tag <tag>] [--snooze <time>]
+                                 open tasks matching the filter (see
+                                 --where), a line each as in `git rebase
+                                 -i`: start it with keep, done, delete or
+                                 snooze; edit descriptions or move lines to
+                                 rename or reorder; all applied in one save
+    template list | save <name> <id> [--description <text>] | delete <name>
+                                 descriptions may use {placeholders}
+    usage [--top <n>] [--clear]  needs `usage = true` in the config file
+    demo [--count <n>] [--seed <n>] [--keep]
+                                 --keep leaves the demo store in place
+    generate [--count <n>] [--seed <n>] [--at <time>]
+                                 the same seed and --at give the same tasks,
+                                 with dates up to six weeks before --at
+                                 (the start of today, UTC); 1000 unless
+                                 --count, seed 1 unless --seed
+    serve [--port <port>] [--host <addr>] [--token <token>] [--require-revision]
+                                 token also from TASK_CLI_TOKEN;
+                                 --require-revision refuses changes that do
+                                 not name the revision they were made from
+    shell-init zsh|bash          add `eval "$(task-cli shell-init zsh)"` to the
+                                 rc file; in a directory with a .taskcli
+                                 marker or a .tasks.json, or under one, the
+                                 commands use that store
+    context [--export]           --export prints the commands the hook runs
+    greet [--inbox <n>] [--quiet]
+                                 one line on overdue tasks, tasks due today
+                                 and an inbox of more than <n> (the inbox
+                                 quota) when there are any; --quiet prints
+                                 nothing and exits 1 when all is well
+    sync status [--format json]  for each remote store used here: when it
+                                 last pulled and pushed, local changes not
+                                 sent, where its event log was read up to
+                                 and the last error, with a hint; reads the
+                                 local records only, never the network
+    backup <path>|<url>          writes the tasks and trash with long texts
+                                 inline; a url or path ending in / gets a
+                                 timestamped tasks-<time>.json in it, e.g.
+                                 `backup s3://bucket/backups/`
+    backup show <backup> [--id <id>]
+                                 the tasks and trash in a backup, or every
+                                 detail of one of them
+    backup restore-task <backup> <id>
+                                 puts one task back as it is in the backup,
+                                 over its current copy or out of the trash,
+                                 and leaves the other tasks alone
+    runbook start <file> [--var <name>=<value>,...]
+                                 creates a task for the run with a subtask
+                                 per [[step]], owned and due as the file says
+    runbook ack <step>           marks a step in progress, and yours if it
+                                 has no owner; `done` finishes it
+    runbook status [<run>]       the open runs, or the steps of one with
+                                 their timers
+    runbook timeline <run> [--format markdown|json]
+                                 what happened when, for the postmortem
+    meeting start <title> [--agenda <topic>,...] [--attendees <name>,...]
+    meeting item <text> [--to <name>] [--due <date>]
+                                 an action item; `@name` and `due:<date>` in
+                                 the text work too
+    meeting note <text> | agenda <topic> | status | cancel
+    meeting end [--output <file>] [--tag <tag,...>]
+                                 files the action items as tasks and prints
+                                 the minutes in Markdown, or writes them
+    self-update [--force]        downloads this platform's binary of the
+                                 latest release, checks its signature with
+                                 ssh-keygen and swaps it in; --force
+                                 reinstalls a release that is not newer
+
+Commands that take an <id> also accept a task number such as #12, a unique id
+prefix or words from the description of an open task; description matches are
+confirmed first, or need --yes when not running at a terminal. Descriptions and
+notes can mention other tasks as #12; `show` links them both ways.
+
+Dates are `today`, `tomorrow`, `+3d`, `2024-08-20` or `2024-08-20 14:00`;
+durations look like `15m`, `1h30m` or `2d`. Sort keys are created, updated,
+status, description, priority and due.
+
+Filters compare fields (id, description, status, priority, tag, location,
+created, updated, due) with =, !=, <, <=, >, >= or ~ (contains) and combine
+them with and, or, not and parentheses:
+    status != done and (tag:work or priority >= high) and created > 2024-01-01
+
+Phrases name a day or period (`today`, `friday`, `this week`, `last month`,
+`past 3 days`), a named time from the config file (`next standup`) or a
+date, optionally after before, after, since or until:
+    list --due "before next standup" --created "last week""#;
+
+    println!("{HELP_TEXT}");
+}
+
+fn remind_offset(args: &Args) -> Result<Option<i64>, CliError> {
+    args.value("remind")
+        .map(|offset| Ok(parse_duration(offset)?.num_seconds()))
+        .transpose()
+}
+
+pub fn run_daemon(args: &Args) -> ! {
+    let interval = parse_duration(args.value("interval").unwrap_or("15m"))
+        .expect("invalid interval")
+        .to_std()
+        .expect("interval must be positive");
+    let path = store::tasks_path(args);
+    remote::local_only(&path, "daemon");
+    let mut notified = HashSet::new();
+    let mut followed_up = HashSet::new();
+
+    loop {
+        let config = Config::load();
+        if let Err(err) = age_tasks(&path, &config) {
+            eprintln!("error: {err}");
+        }
+
+        let tasks = Store::open(path.clone()).tasks;
+        let now = Utc::now();
+        // What comes due during a pause goes to its digest instead.
+        let pause = pause::load();
+        let held = |at: Option<DateTime<Utc>>| {
+            pause
+                .as_ref()
+                .is_some_and(|pause| pause.active(now) || at.is_some_and(|at| pause.covers(at)))
+        };
+
+        for task in remind::due_reminders(&tasks, now, Duration::zero()) {
+            if notified.insert((task.id, task.due))
+                && !held(remind::reminder_time(task))
+                && !remind::notify(task, now)
+            {
+                println!("{}", remind::summary_line(task, now));
+            }
+        }
+        for task in remind::due_follow_ups(&tasks, now, Duration::zero()) {
+            let follow_up = task
+                .delegated
+                .as_ref()
+                .and_then(|delegated| delegated.follow_up);
+            if followed_up.insert((task.id, follow_up))
+                && !held(follow_up)
+                && !notify_follow_up(task)
+            {
+                println!("{}", remind::follow_up_line(task, now));
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// `list --watch`: lists the tasks again whenever the store changes, until
+/// interrupted. Redis boards say when they change; other remote stores are
+/// pulled every few seconds and local ones checked twice a second.
+pub fn run_watch(args: &Args) -> ! {
+    let location = store::tasks_path(args);
+    let remote = Remote::parse(&location);
+    let terminal = std::io::stdout().is_terminal();
+    let stamp = |path: &Path| {
+        std::fs::metadata(path)
+            .ok()
+            .map(|metadata| (metadata.modified().ok(), metadata.len()))
+    };
+
+    loop {
+        let path = match &remote {
+            Some(remote) => remote
+                .cache_path()
+                .and_then(|path| remote.pull(&path).map(|_| path)),
+            None => Ok(location.clone()),
+        };
+        let result = path.and_then(|path| {
+            let config = Config::load();
+            let mut store = Store::open(path.clone());
+            if terminal {
+                print!("\x1b[2J\x1b[H");
+            }
+            execute(Commands::List, args, &config, &mut store).map(|()| path)
+        });
+        if !terminal {
+            println!();
+        }
+
+        match (&remote, result) {
+            (_, Err(err)) => {
+                eprintln!("error: {err}");
+                std::thread::sleep(remote::POLL);
+            }
+            (Some(remote), Ok(_)) => {
+                if let Err(err) = remote.wait_for_change() {
+                    eprintln!("error: {err}");
+                    std::thread::sleep(remote::POLL);
+                }
+            }
+            (None, Ok(path)) => {
+                let seen = stamp(&path);
+                while stamp(&path) == seen {
+                    std::thread::sleep(std::time::Duration::from_millis(500));
+                }
+            }
+        }
+    }
+}
+
+fn notify_follow_up(task: &Task) -> bool {
+    let delegated = task.delegated.as_ref().expect("follow-ups are delegated");
+    remind::notify_message(
+        &format!("Follow up with {}", delegated.to),
+        &task.description,
+    )
+}
+
+/// Lets the aging and retention policies run while nothing else changes
+/// the store, with a desktop notification when tasks aged.
+fn age_tasks(path: &Path, config: &Config) -> Result<(), CliError> {
+    let now = Utc::now();
+    let store = Store::open(path.to_owned());
+    let limits = config.retention.limits(path);
+    let pause = pause::load();
+    if aging::due_to_age(&store.tasks, &config.aging, pause.as_ref(), now).is_empty()
+        && retention::due_to_purge(&store, limits, now).is_empty()
+    {
+        return Ok(());
+    }
+
+    let _lock = StoreLock::acquire(path)?;
+    let mut store = Store::open(path.to_owned());
+    let aged = aging::apply(&mut store, &config.aging, pause.as_ref(), now);
+    if let Some(message) = aging::notice(&aged, &config.aging, now) {
+        if !remind::notify_message("Tasks aged", &message) {
+            println!("{message}");
+        }
+    }
+    rules::save(&mut store, config)
+}
+
+/// `waiting on bob, follow up 2024-06-03 09:00`.
+fn describe_delegation(delegated: &Delegation) -> String {
+    let mut text = format!("waiting on {}", delegated.to);
+    if let Some(follow_up) = delegated.follow_up {
+        text.push_str(&format!(", follow up {}", format_local(&follow_up)));
+    }
+    text
+}
+
+/// The run `query` names; its steps resolve to the run they belong to.
+fn runbook_run<'s>(store: &'s Store, query: &str, args: &Args) -> Result<&'s Task, CliError> {
+    let task = store
+        .find(store.resolve(query, confirmation(args))?)
+        .expect("resolved ids exist");
+    let run = match task.runbook {
+        Some(_) => task,
+        None => task
+            .parent
+            .and_then(|parent| store.find(parent))
+            .filter(|parent| parent.runbook.is_some())
+            .ok_or_else(|| CliError::Runbook(format!("`{query}` is not a runbook run")))?,
+    };
+    Ok(run)
+}
+
+/// A runbook step with its owner, state and timer.
+fn runbook_step(painter: &Painter, step: &Task, progress: &runbook::Progress) -> String {
+    let mark = match step.status {
+        Status::Done => "[x]",
+        Status::InProgress => "[~]",
+        Status::ToDo => "[ ]",
+    };
+    let number = step
+        .number
+        .map(|number| format!("#{number} "))
+        .unwrap_or_default();
+    let mut details = Vec::new();
+    if let Some(owner) = &step.owner {
+        details.push(format!("@{owner}"));
+    }
+    let by = |event: &history::Event| {
+        let by = event
+            .by
+            .as_ref()
+            .map(|by| format!(" by {by}"))
+            .unwrap_or_default();
+        format!("{}{by}", format_local(&event.at))
+    };
+    if let Some(event) = progress.done {
+        details.push(format!("done {}", by(event)));
+    } else if let Some(event) = progress.acknowledged {
+        details.push(format!("acknowledged {}", by(event)));
+    }
+    if let (Some(due), false) = (step.due, step.status == Status::Done) {
+        let left = due - Utc::now();
+        if left < Duration::zero() {
+            details.push(painter.overdue(&format!("overdue by {}", format_duration(left))));
+        } else {
+            details.push(format!("due in {}", format_duration(left)));
+        }
+    }
+
+    let mut line = format!("{mark} {number}{}", step.description);
+    if !details.is_empty() {
+        line.push_str(&format!(" ({})", details.join(", ")));
+    }
+    line
+}
+
+/// Local due date, highlighted when an open task is past it.
+fn format_due(painter: &Painter, task: &Task, due: DateTime<Utc>) -> String {
+    let text = format_local(&due);
+
+    if task.status != Status::Done && due < Utc::now() {
+        painter.overdue(&text)
+    } else {
+        text
+    }
+}
+
+fn list_line(painter: &Painter, task: &Task) -> String {
+    painter.fit(&list_entry(painter, task))
+}
+
+/// A task on one line; narrow profiles show its number in place of the id.
+fn list_entry(painter: &Painter, task: &Task) -> String {
+    let mut details = vec![painter.status(&task.status)];
+    if let Some(priority) = task.priority {
+        details.push(painter.priority(priority));
+    }
+    if let Some(owner) = &task.owner {
+        details.push(format!("@{owner}"));
+    }
+    if let Some(due) = task.due {
+        details.push(format!("due {}", format_due(painter, task, due)));
+    }
+    if let Some(rollup) = &task.rollup {
+        details.push(format!("{}% of {}", rollup.percent_done(), rollup.total));
+    }
+    if let Some(effort) = describe_effort(task) {
+        details.push(effort);
+    }
+
+    let mut line = match task.number {
+        Some(number) if painter.narrow() => format!(
+            "{} {} ({})",
+            painter.task_link(&task.id, &painter.id(&format!("#{number}"))),
+            painter.urls(&task.description),
+            details.join(", ")
+        ),
+        number => format!(
+            "{}. {}{} ({})",
+            painter.task_link(&task.id, &painter.id(&task.id.to_string())),
+            number
+                .map(|number| format!("#{number} "))
+                .unwrap_or_default(),
+            painter.urls(&task.description),
+            details.join(", ")
+        ),
+    };
+    for tag in &task.tags {
+        line.push(' ');
+        line.push_str(&painter.tag(tag));
+    }
+
+    line
+}
+
+/// `text` with its URLs and `#12` references turned into hyperlinks, the
+/// latter to the tasks they name.
+fn link_references(painter: &Painter, store: &Store, text: &str) -> String {
+    let mut linked = String::new();
+    let mut rest = 0;
+
+    for (span, number) in reference_spans(text) {
+        if let Some(task) = store.find_number(number) {
+            linked.push_str(&painter.urls(&text[rest..span.start]));
+            linked.push_str(&painter.task_link(&task.id, &text[span.clone()]));
+            rest = span.end;
+        }
+    }
+    linked.push_str(&painter.urls(&text[rest..]));
+
+    linked
+}
+
+/// Tracked time against the estimate, with subtasks included, as in
+/// `1h30m of 4h`.
+fn describe_effort(task: &Task) -> Option<String> {
+    let rollup = task.rollup.unwrap_or_default();
+    let estimate = task.estimate_secs.unwrap_or(0) + rollup.estimate_secs;
+    let tracked = task.tracked_secs + rollup.tracked_secs;
+    let format = |secs| format_duration(Duration::seconds(secs));
+
+    match (tracked, estimate) {
+        (0, 0) => None,
+        (tracked, 0) => Some(format!("{} tracked", format(tracked))),
+        (0, estimate) => Some(format!("est. {}", format(estimate))),
+        (tracked, estimate) => Some(format!("{} of {}", format(tracked), format(estimate))),
+    }
+}
+
+fn describe_rollup(rollup: &Rollup) -> String {
+    format!(
+        "{} of {} subtasks done ({}%)",
+        rollup.done,
+        rollup.total,
+        rollup.percent_done()
+    )
+}
+
+/// The priority and tags a task passes on, as in `priority high, +client`.
+fn describe_defaults(painter: &Painter, defaults: &Defaults) -> String {
+    defaults
+        .priority
+        .map(|priority| format!("priority {}", painter.priority(priority)))
+        .into_iter()
+        .chain(defaults.tags.iter().map(|tag| painter.tag(tag)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_inherited(painter: &Painter, task: &Task) -> String {
+    task.inherited
+        .priority
+        .then_some("priority".to_owned())
+        .into_iter()
+        .chain(task.inherited.tags.iter().map(|tag| painter.tag(tag)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prints `tasks` grouped by [`Section`], with a count per section and what
+/// blocked or waiting tasks are held up by.
+fn print_sections(painter: &Painter, tasks: &[Task], all: &[Task]) {
+    let now = Utc::now();
+    let mut first = true;
+
+    for current in Section::ALL {
+        let members = tasks
+            .iter()
+            .filter(|task| section(task, all, now) == current)
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            continue;
+        }
+
+        if !first {
+            println!();
+        }
+        first = false;
+        println!(
+            "{}",
+            painter.paint("bold", &format!("{} ({})", current.title(), members.len()))
+        );
+
+        for task in members {
+            let mut line = list_entry(painter, task);
+            match current {
+                Section::Blocked => {
+                    let blockers = open_blockers(task, all)
+                        .iter()
+                        .map(|blocker| blocker.description.as_str())
+                        .collect::<Vec<_>>();
+                    line.push_str(&format!(" [blocked by {}]", blockers.join(", ")));
+                }
+                Section::Waiting => match (&task.delegated, task.wait_until) {
+                    (Some(delegated), _) => {
+                        line.push_str(&format!(" [{}]", describe_delegation(delegated)))
+                    }
+                    (None, until) => {
+                        let until = until.expect("waiting tasks have a date");
+                        line.push_str(&format!(" [until {}]", format_local(&until)));
+                    }
+                },
+                _ => {}
+            }
+            println!("{}", painter.fit(&line));
+        }
+    }
+}
+
+/// Adds the tasks of an `export --format json` document; see
+/// [`import_tasks`].
+fn import_json(store: &mut Store, text: &str) -> Result<import::Outcome, CliError> {
+    let value = serde_json::from_str::<serde_json::Value>(text)
+        .map_err(|err| CliError::Import(format!("invalid JSON: {err}")))?;
+    let serde_json::Value::Array(items) = value else {
+        return Err(CliError::Import("expected an array of tasks".to_owned()));
+    };
+
+    Ok(import_tasks(
+        store,
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| (format!("/{index}"), item))
+            .collect(),
+    ))
+}
+
+/// Adds tasks, each with where it was found, after checking them against
+/// the task schema; those with problems are rejected. Tasks already in the
+/// store are replaced when the imported copy is at a later revision, or at
+/// the same one but updated later; copies at an earlier revision are stale
+/// and counted. Numbers in use by another task are dropped so the store
+/// assigns new ones.
+fn import_tasks(store: &mut Store, items: Vec<(String, serde_json::Value)>) -> import::Outcome {
+    let task_schema = schema::task();
+    let mut outcome = import::Outcome::default();
+    let mut imported = Vec::new();
+
+    for (at, item) in items {
+        let mut problems = import::check_task(&task_schema, &item);
+        let mut task = match serde_json::from_value::<Task>(item.clone()) {
+            Ok(task) if problems.is_empty() => task,
+            result => {
+                if let (Err(err), true) = (result, problems.is_empty()) {
+                    problems.push(import::Problem {
+                        field: "task".to_owned(),
+                        reason: err.to_string(),
+                        fix: None,
+                    });
+                }
+                outcome.rejected.push(import::Rejected {
+                    at,
+                    problems,
+                    record: item,
+                });
+                continue;
+            }
+        };
+        // Blobs belong to the store that wrote them, and exports hold the
+        // texts themselves.
+        task.description_blob = None;
+        task.notes_blob = None;
+        imported.push(task.id);
+
+        let existing = store
+            .tasks
+            .iter_mut()
+            .chain(&mut store.trash)
+            .find(|existing| existing.id == task.id);
+        if let Some(existing) = existing {
+            if (task.revision, task.updated_at) > (existing.revision, existing.updated_at) {
+                task.number = existing.number;
+                *existing = task;
+                outcome.updated += 1;
+            } else if task.revision < existing.revision {
+                outcome.stale += 1;
+            }
+            continue;
+        }
+
+        let taken = store
+            .tasks
+            .iter()
+            .chain(&store.trash)
+            .any(|other| other.number.is_some() && other.number == task.number);
+        if taken {
+            task.number = None;
+        }
+        if task.deleted_at.is_some() {
+            store.trash.push(task);
+        } else {
+            store.tasks.push(task);
+        }
+        outcome.added += 1;
+    }
+
+    // Cached rollups describe the other store's subtasks.
+    update_rollups(&mut store.tasks, imported);
+
+    outcome
+}
+
+/// Adds or updates tasks from calendar components, as found by
+/// [`ics::components`] with the line each starts on. Items without a date
+/// are skipped, and those [`import::check_item`] finds problems with are
+/// rejected.
+fn import_ics(
+    store: &mut Store,
+    components: Vec<(usize, Vec<String>)>,
+    filter: Option<&str>,
+    tags: &[String],
+) -> import::Outcome {
+    let filter = filter.map(str::to_lowercase);
+    let mut outcome = import::Outcome::default();
+
+    for (start, lines) in components {
+        let (item, invalid) = ics::read(start, &lines);
+        if let Some(filter) = &filter {
+            let matches = item.summary.to_lowercase().contains(filter)
+                || item
+                    .categories
+                    .iter()
+                    .any(|category| category.to_lowercase().contains(filter));
+            if !matches {
+                continue;
+            }
+        }
+        if invalid.is_empty() && item.due.is_none() {
+            continue;
+        }
+        let problems = import::check_item(&item, &invalid);
+        if !problems.is_empty() {
+            outcome.rejected.push(import::Rejected {
+                at: format!("line {start}"),
+                problems,
+                record: serde_json::Value::from(lines),
+            });
+            continue;
+        }
+        let due = item.due.expect("items without a date were skipped");
+
+        match item.uid.as_deref().and_then(ics::parse_uid) {
+            Some(ics::Exported::Due(id)) => {
+                if let Some(task) = store.find_mut(id).filter(|task| task.due != Some(due)) {
+                    task.due = Some(due);
+                    task.updated_at = Utc::now();
+                    outcome.updated += 1;
+                }
+                continue;
+            }
+            Some(ics::Exported::Block(id, exported_start)) => {
+                let (Some(start), Some(end)) = (item.start, item.end) else {
+                    continue;
+                };
+                let Some(task) = store.find_mut(id) else {
+                    continue;
+                };
+                let block = task
+                    .blocks
+                    .iter_mut()
+                    .find(|block| block.start.timestamp() == exported_start);
+                match block {
+                    Some(block) if block.start == start && block.end == end => {}
+                    Some(block) => {
+                        (block.start, block.end) = (start, end);
+                        outcome.updated += 1;
+                    }
+                    None => {
+                        task.blocks.push(TimeBlock { start, end });
+                        outcome.added += 1;
+                    }
+                }
+                task.blocks.sort_by_key(|block| block.start);
+                task.updated_at = Utc::now();
+                continue;
+            }
+            None => {}
+        }
+
+        let import_id = item.uid.as_ref().map(|uid| format!("ics:{uid}"));
+        let existing = import_id.as_ref().and_then(|import_id| {
+            store
+                .tasks
+                .iter_mut()
+                .find(|task| task.import_id.as_ref() == Some(import_id))
+        });
+        let summary = if item.summary.is_empty() {
+            "(untitled event)".to_owned()
+        } else {
+            item.summary.clone()
+        };
+
+        let task = match existing {
+            Some(task) => {
+                if task.description == summary && task.due == Some(due) {
+                    continue;
+                }
+                outcome.updated += 1;
+                task
+            }
+            None => {
+                let mut task = Task::new(summary.clone());
+                task.import_id = import_id;
+                for tag in tags.iter().chain(&item.categories) {
+                    if !task.has_tag(tag) {
+                        task.tags.push(tag.to_owned());
+                    }
+                }
+                task.set_notes(item.description.clone());
+                task.location = item.location.clone();
+                if item.completed {
+                    task.status = Status::Done;
+                }
+                outcome.added += 1;
+                store.tasks.push(task);
+                store.tasks.last_mut().expect("just added")
+            }
+        };
+
+        if task.description != summary {
+            task.set_description(summary);
+        }
+        task.due = Some(due);
+        task.updated_at = Utc::now();
+    }
+
+    outcome
+}
+
+/// The backup at the path or url `location`, as written by `backup`.
+fn open_backup(location: Option<&str>) -> Result<Store, CliError> {
+    let location = PathBuf::from(location.ok_or_else(|| CliError::missing("backup path or url"))?);
+    let path = match Remote::parse(&location) {
+        Some(remote) => {
+            let path = remote.cache_path()?;
+            remote.pull(&path)?;
+            path
+        }
+        None => {
+            std::fs::metadata(&location).map_err(|error| CliError::Read {
+                path: location.clone(),
+                error,
+            })?;
+            location
+        }
+    };
+    Ok(Store::open(path))
+}
+
+/// Prints every detail of `task`, one of the tasks of `store`.
+fn print_task(painter: &Painter, store: &Store, task: &Task) {
+    let blob_dir = store.blob_dir();
+    println!(
+        "id:          {}",
+        painter.task_link(&task.id, &painter.id(&task.id.to_string()))
+    );
+    if let Some(number) = task.number {
+        println!("number:      #{number}");
+    }
+    println!(
+        "{}",
+        painter.fit(&format!(
+            "description: {}",
+            link_references(painter, store, &task.description)
+        ))
+    );
+    println!("status:      {}", painter.status(&task.status));
+    if let Some(priority) = task.priority {
+        println!("priority:    {}", painter.priority(priority));
+    }
+    if let Some(owner) = &task.owner {
+        println!("owner:       {owner}");
+    }
+    if let Some(place) = &task.location {
+        println!(
+            "location:    {}",
+            painter.link(&location::link(place), place)
+        );
+    }
+    if let Some(runbook) = &task.runbook {
+        println!("runbook:     {runbook}");
+    }
+    if !task.tags.is_empty() {
+        let tags = task
+            .tags
+            .iter()
+            .map(|tag| painter.tag(tag))
+            .collect::<Vec<_>>();
+        println!("tags:        {}", tags.join(" "));
+    }
+    if !task.inherited.is_empty() {
+        println!("inherited:   {}", describe_inherited(painter, task));
+    }
+    if !task.defaults.is_empty() {
+        println!(
+            "defaults:    {}",
+            describe_defaults(painter, &task.defaults)
+        );
+    }
+    if let Some(parent) = task.parent.and_then(|parent| store.find(parent)) {
+        println!(
+            "parent:      {}. {}",
+            painter.id(&parent.id.to_string()),
+            parent.description
+        );
+    }
+    println!("created:     {}", format_local(&task.created_at));
+    println!("updated:     {}", format_local(&task.updated_at));
+    if let Some(due) = task.due {
+        println!("due:         {}", format_due(painter, task, due));
+    }
+    if let Some(secs) = task.remind_before_secs {
+        println!(
+            "remind:      {} before due",
+            format_duration(Duration::seconds(secs))
+        );
+    }
+    if let Some(until) = task.wait_until {
+        println!("waiting:     until {}", format_local(&until));
+    }
+    if let Some(then) = &task.on_done {
+        println!(
+            "{}",
+            painter.fit(&format!("then:        {}", template::describe_chain(then)))
+        );
+    }
+    if let Some(hold) = &task.hold {
+        match &hold.reason {
+            Some(reason) => println!("hold:        since {} ({reason})", format_local(&hold.at)),
+            None => println!("hold:        since {}", format_local(&hold.at)),
+        }
+    }
+    if let Some(delegated) = &task.delegated {
+        println!(
+            "delegated:   {}, since {}",
+            describe_delegation(delegated),
+            format_local(&delegated.at)
+        );
+    }
+    let rollup = task.rollup.unwrap_or_default();
+    let with_subtasks = |own: i64, below: i64| {
+        let format = |secs| format_duration(Duration::seconds(secs));
+        match (own, below) {
+            (own, 0) => format(own),
+            (0, below) => format!("{} from subtasks", format(below)),
+            (own, below) => {
+                format!("{} ({} with subtasks)", format(own), format(own + below))
+            }
+        }
+    };
+    if task.estimate_secs.is_some() || rollup.estimate_secs > 0 {
+        println!(
+            "estimate:    {}",
+            with_subtasks(task.estimate_secs.unwrap_or(0), rollup.estimate_secs)
+        );
+    }
+    if task.tracked_secs != 0 || rollup.tracked_secs != 0 {
+        println!(
+            "tracked:     {}",
+            with_subtasks(task.tracked_secs, rollup.tracked_secs)
+        );
+    }
+    if let Some(rollup) = &task.rollup {
+        println!("progress:    {}", describe_rollup(rollup));
+    }
+    if let Some(source) = &task.source {
+        println!("source:      {source}");
+    }
+    for block in &task.blocks {
+        println!(
+            "scheduled:   {} to {}",
+            format_local(&block.start),
+            block.end.with_timezone(&Local).format("%H:%M")
+        );
+    }
+    for blocker in task
+        .blocked_by
+        .iter()
+        .filter_map(|blocker| store.find(*blocker))
+    {
+        println!("blocked by:  {}", list_line(painter, blocker));
+    }
+    let subtasks = store
+        .tasks
+        .iter()
+        .filter(|subtask| subtask.parent == Some(task.id))
+        .collect::<Vec<_>>();
+    if !subtasks.is_empty() {
+        println!("subtasks:");
+        for subtask in subtasks {
+            println!("    {}", list_line(painter, subtask));
+        }
+    }
+    let mentioned = references(&task.description)
+        .into_iter()
+        .chain(task.notes.as_deref().map(references).unwrap_or_default())
+        .filter(|number| Some(*number) != task.number)
+        .collect::<Vec<_>>();
+    let mut referenced = mentioned
+        .iter()
+        .filter_map(|number| store.find_number(*number))
+        .collect::<Vec<_>>();
+    referenced.dedup_by_key(|other| other.id);
+    if !referenced.is_empty() {
+        println!("references:");
+        for other in referenced {
+            println!("    {}", list_line(painter, other));
+        }
+    }
+    let referenced_by = task.number.map_or_else(Vec::new, |number| {
+        store
+            .tasks
+            .iter()
+            .filter(|other| other.id != task.id)
+            .filter(|other| {
+                let other = blob::inline(other, &blob_dir);
+                references(&other.description)
+                    .into_iter()
+                    .chain(other.notes.as_deref().map(references).unwrap_or_default())
+                    .any(|mentioned| mentioned == number)
+            })
+            .collect::<Vec<_>>()
+    });
+    if !referenced_by.is_empty() {
+        println!("referenced by:");
+        for other in referenced_by {
+            println!("    {}", list_line(painter, other));
+        }
+    }
+    if let Some(notes) = &task.notes {
+        println!("notes:");
+        for line in notes.lines() {
+            println!(
+                "{}",
+                painter.fit(&format!("    {}", link_references(painter, store, line)))
+            );
+        }
+    }
+}
+
+/// Marks `tasks` as being edited here, warning about those being edited
+/// elsewhere; `None` when the user would rather not go on.
+fn hold_for_editing(store: &Store, tasks: &[Task]) -> Option<TaskLocks> {
+    let ids = tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+    let (editing, held) = TaskLocks::acquire(&store.path, &ids);
+    for holder in &held {
+        let task = store.find(holder.task).expect("selected tasks exist");
+        eprintln!(
+            "warning: `{}` is being edited elsewhere ({holder})",
+            task.description
+        );
+    }
+    if !held.is_empty() && picker::is_interactive() && !picker::confirm("edit anyway?") {
+        return None;
+    }
+    Some(editing)
+}
+
+/// Opens the editor on `path` until `parse` accepts what was saved, or the
+/// user gives up on an error.
+fn edit_until_valid<T>(
+    path: &Path,
+    parse: impl Fn(&str) -> Result<T, CliError>,
+) -> Result<T, CliError> {
+    loop {
+        edit::open_editor(path)?;
+        let buffer = std::fs::read_to_string(path).unwrap_or_default();
+
+        match parse(&buffer) {
+            Ok(parsed) => return Ok(parsed),
+            Err(err) if picker::is_interactive() => {
+                eprintln!("error: {err}");
+                if !picker::confirm("edit again?") {
+                    eprintln!("your changes are kept in {}", path.display());
+                    return Err(err);
+                }
+            }
+            Err(err) => {
+                eprintln!("your changes are kept in {}", path.display());
+                return Err(err);
+            }
+        }
+    }
+}
+
+pub(crate) fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Writes a git `pre-push` hook running `gate` with the same filters. An
+/// existing hook is only replaced if it was installed by us.
+fn install_pre_push_hook(args: &Args) -> Result<PathBuf, CliError> {
+    const MARKER: &str = "# installed by task-cli gate --install-hook";
+
+    let hooks = std::process::Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+        .ok_or_else(|| CliError::Hook("not inside a git repository".to_owned()))?;
+    let path = hooks.join("pre-push");
+
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        if !existing.contains(MARKER) {
+            return Err(CliError::Hook(format!(
+                "{} already exists; add `task-cli gate` to it by hand",
+                path.display()
+            )));
+        }
+    }
+
+    let program = std::env::current_exe()
+        .map(|exe| exe.display().to_string())
+        .unwrap_or_else(|_| "task-cli".to_owned());
+    let mut command = vec![shell_quote(&program), "gate".to_owned()];
+    for option in ["file", "tag", "where"] {
+        if let Some(value) = args.value(option) {
+            command.push(format!("--{option}"));
+            command.push(shell_quote(value));
+        }
+    }
+
+    let script = format!("#!/bin/sh\n{MARKER}\nexec {}\n", command.join(" "));
+    let write = || {
+        std::fs::create_dir_all(&hooks)?;
+        std::fs::write(&path, script)?;
+        #[cfg(unix)]
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755))?;
+        Ok::<_, std::io::Error>(())
+    };
+    write().map_err(|error| CliError::Hook(format!("cannot write {}: {error}", path.display())))?;
+
+    Ok(path)
+}
+
+fn confirmation(args: &Args) -> Confirm {
+    if args.flag("yes") {
+        Confirm::Given
+    } else {
+        Confirm::Ask
+    }
+}
+
+/// Task named by the first argument of a command that changes it: an id, an
+/// id prefix or part of its description.
+fn task_arg(store: &Store, args: &Args) -> Result<Ulid, CliError> {
+    let query = args.positional(1).ok_or_else(|| CliError::missing("id"))?;
+    store.resolve(query, confirmation(args))
+}
+
+/// Tasks matching a status, `--tag`, `--where`, the `--due`, `--created`
+/// and `--updated` phrases and `--location`, in store order.
+fn selected(
+    store: &Store,
+    args: &Args,
+    config: &Config,
+    status: Option<&str>,
+) -> Result<Vec<Task>, CliError> {
+    let filter = args
+        .value("where")
+        .map(query::parse)
+        .transpose()
+        .map_err(CliError::Query)?;
+
+    let mut tasks = if let Some(status) = status {
+        let status = Status::from_str(status)?;
+        filter_by_status(&store.tasks, &status)
+    } else {
+        store.tasks.clone()
+    };
+
+    if let Some(tag) = args.value("tag") {
+        tasks.retain(|task| task.has_tag(tag));
+    }
+    if let Some(filter) = filter {
+        tasks.retain(|task| filter.matches(task));
+    }
+    if let Some(phrase) = args.value("due") {
+        let span = phrases::parse_span(phrase, config)?;
+        tasks.retain(|task| task.due.is_some_and(|due| span.contains(due)));
+    }
+    if let Some(phrase) = args.value("created") {
+        let span = phrases::parse_span(phrase, config)?;
+        tasks.retain(|task| span.contains(task.created_at));
+    }
+    if let Some(phrase) = args.value("updated") {
+        let span = phrases::parse_span(phrase, config)?;
+        tasks.retain(|task| span.contains(task.updated_at));
+    }
+    if let Some(place) = args.value("location") {
+        let filter = location::Filter::new(place, args.value("radius"));
+        tasks.retain(|task| filter.matches(task.location.as_deref()));
+    }
+
+    Ok(tasks)
+}
+
+/// A field value as JSON, cut short so blame lines stay on one line.
+fn blame_value(value: &serde_json::Value) -> String {
+    if value.is_null() {
+        return "unset".to_owned();
+    }
+    let text = value.to_string();
+    match text.char_indices().nth(32) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+fn blame(painter: &Painter, task: &Task, events: &[history::Event]) {
+    let last = history::last_changes(events, task.id);
+    let authors = events
+        .iter()
+        .filter(|event| event.task == task.id)
+        .filter_map(|event| event.by.as_deref())
+        .collect::<HashSet<_>>();
+
+    let mut fields = match serde_json::to_value(task).expect("could not convert to json") {
+        serde_json::Value::Object(fields) => fields,
+        _ => unreachable!("tasks serialize to objects"),
+    };
+    // Fields cleared since are still worth showing, as unset.
+    for name in last.keys() {
+        fields
+            .entry(name.to_string())
+            .or_insert(serde_json::Value::Null);
+    }
+
+    // The id and creation time never change, and every change touches
+    // updated_at and the revision.
+    let names = fields
+        .keys()
+        .filter(|name| {
+            !matches!(
+                name.as_str(),
+                "id" | "created_at" | "updated_at" | "revision" | "rollup"
+            )
+        })
+        .collect::<Vec<_>>();
+    let name_width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+    let values = names
+        .iter()
+        .map(|name| blame_value(&fields[name.as_str()]))
+        .collect::<Vec<_>>();
+    let value_width = values
+        .iter()
+        .map(|value| value.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (name, value) in names.iter().zip(&values) {
+        let origin = match last.get(name.as_str()) {
+            None => "before the event log".to_owned(),
+            Some((event, change)) => {
+                let mut origin = match event.kind {
+                    history::EventKind::Created => {
+                        format!("set on creation {}", format_local(&event.at))
+                    }
+                    _ => format!(
+                        "changed {} from {}",
+                        format_local(&event.at),
+                        blame_value(&change.from)
+                    ),
+                };
+                if let (true, Some(by)) = (authors.len() > 1, &event.by) {
+                    origin.push_str(&format!(" by {by}"));
+                }
+                origin
+            }
+        };
+        let padding = value_width - value.chars().count();
+        println!(
+            "{}",
+            painter.fit(&format!(
+                "{name:name_width$}  {value}{:padding$}  {}",
+                "",
+                painter.paint("dim", &origin)
+            ))
+        );
+    }
+}
+
+/// The store as it was at the end of the `--as-of` phrase, if one is given.
+fn as_of(store: &Store, args: &Args, config: &Config) -> Result<Option<Store>, CliError> {
+    let Some(phrase) = args.value("as-of") else {
+        return Ok(None);
+    };
+    let at = phrases::parse_span(phrase, config)?
+        .end
+        .ok_or_else(|| CliError::InvalidPhrase(phrase.to_owned()))?;
+
+    store.as_of(at).map(Some)
+}
+
+/// Runs a single command against the loaded store. `args.positional(0)` is
+/// the command name itself.
+pub fn execute(
+    cmd: Commands,
+    args: &Args,
+    config: &Config,
+    store: &mut Store,
+) -> Result<(), CliError> {
+    let painter = Painter::new(args, config)?;
+    let blob_dir = store.blob_dir();
+
+    match cmd {
+        Commands::Add => {
+            let mut new_tasks = if let Some(name) = args.value("template") {
+                let templates = template::load();
+                let template = templates
+                    .get(name)
+                    .ok_or_else(|| CliError::Template(format!("no template named `{name}`")))?;
+
+                let mut vars = args
+                    .value("var")
+                    .map(template::parse_vars)
+                    .transpose()?
+                    .unwrap_or_default();
+                if let Some(text) = args.positional(1) {
+                    vars.insert("text".to_owned(), text.to_owned());
+                }
+
+                template.instantiate(&vars)?
+            } else {
+                let description = args
+                    .positional(1)
+                    .ok_or_else(|| CliError::missing("description"))?;
+                vec![Task::new(description.to_owned())]
+            };
+
+            let task = &mut new_tasks[0];
+            if let Some(due) = args.value("due") {
+                task.due = Some(parse_datetime(due)?);
+            }
+            task.remind_before_secs = remind_offset(args)?;
+            if let Some(priority) = args.value("priority") {
+                task.priority = Some(Priority::from_str(priority)?);
+            }
+            for tag in args.value("tag").map(parse_tags).unwrap_or_default() {
+                if !task.has_tag(&tag) {
+                    task.tags.push(tag);
+                }
+            }
+            if let Some(parent) = args.value("parent") {
+                task.parent = Some(store.resolve(parent, confirmation(args))?);
+            }
+            if let Some(source) = args.value("source") {
+                task.source = Some(Source::from_str(source)?);
+            }
+            task.location = args.value("location").map(location::parse);
+
+            let ids = new_tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+            store.tasks.extend(new_tasks);
+            for id in ids {
+                inherit(&mut store.tasks, id);
+            }
+        }
+
+        Commands::List => {
+            let past = as_of(store, args, config)?;
+            let store = past.as_ref().unwrap_or(store);
+            let status = compat::option_or_positional(
+                args,
+                "status",
+                1,
+                &compat::LIST_STATUS,
+                config.old_syntax,
+            )?;
+            let mut tasks = selected(store, args, config, status)?;
+            if let Some(key) = args.value("sort") {
+                sort_tasks(&mut tasks, SortKey::from_str(key)?);
+            }
+
+            match args.value("format").unwrap_or("plain") {
+                "json" => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&tasks).expect("could not convert to json")
+                ),
+                "plain" if args.flag("flat") => {
+                    for task in tasks {
+                        println!("{}", list_line(&painter, &task));
+                    }
+                }
+                "plain" => print_sections(&painter, &tasks, &store.tasks),
+                format => {
+                    return Err(CliError::invalid_args(
+                        format,
+                        format!("invalid format `{format}`, expected plain or json"),
+                    ))
+                }
+            }
+        }
+
+        Commands::Mark => {
+            let id = task_arg(store, args)?;
+            let status = compat::option_or_positional(
+                args,
+                "status",
+                2,
+                &compat::MARK_STATUS,
+                config.old_syntax,
+            )?;
+            let status = Status::from_str(status.ok_or_else(|| CliError::missing("--status"))?)?;
+
+            if let Some(task) = store.find_mut(id) {
+                task.set_status(status);
+            }
+        }
+
+        Commands::Done => {
+            let words = (1..).map_while(|index| args.positional(index));
+            let query = words.collect::<Vec<_>>().join(" ");
+            let id = store.resolve(&query, confirmation(args))?;
+
+            if let Some(task) = store.find_mut(id) {
+                task.set_status(Status::Done);
+            }
+        }
+
+        Commands::Delete => {
+            let query = args.positional(1).ok_or_else(|| CliError::missing("id"))?;
+
+            if args.flag("purge") {
+                let id = store
+                    .resolve_trashed(query)
+                    .or_else(|_| store.resolve(query, confirmation(args)))?;
+                let task = store
+                    .tasks
+                    .iter()
+                    .chain(&store.trash)
+                    .find(|task| task.id == id);
+                if let Some(task) = task.filter(|task| task.hold.is_some()) {
+                    return Err(CliError::Held(task.description.clone()));
+                }
+                store.tasks.retain(|task| task.id != id);
+                store.trash.retain(|task| task.id != id);
+            } else {
+                let id = store.resolve(query, confirmation(args))?;
+                store.trash(id);
+            }
+        }
+
+        Commands::Update => {
+            let id = task_arg(store, args)?;
+            let new_description = args
+                .positional(2)
+                .ok_or_else(|| CliError::missing("description"))?;
+
+            if let Some(task) = store.find_mut(id) {
+                task.set_description(new_description.to_owned());
+            }
+        }
+
+        Commands::Due => {
+            let id = task_arg(store, args)?;
+            let due = parse_datetime(
+                args.positional(2)
+                    .ok_or_else(|| CliError::missing("due date"))?,
+            )?;
+
+            if let Some(task) = store.find_mut(id) {
+                task.due = Some(due);
+                if let Some(offset) = remind_offset(args)? {
+                    task.remind_before_secs = Some(offset);
+                }
+                task.updated_at = Utc::now();
+            }
+        }
+
+        Commands::Remind => {
+            let within = parse_duration(args.value("within").unwrap_or("1h"))?;
+            let now = Utc::now();
+            if let Some(pause) = pause::active(now) {
+                if !args.flag("notify") {
+                    println!(
+                        "reminders are paused until {}; `task-cli pause none` resumes them",
+                        format_local(&pause.until)
+                    );
+                }
+                return Ok(());
+            }
+
+            for task in remind::due_reminders(&store.tasks, now, within) {
+                if !(args.flag("notify") && remind::notify(task, now)) {
+                    println!("{}", remind::summary_line(task, now));
+                }
+            }
+            for task in remind::due_follow_ups(&store.tasks, now, within) {
+                if !(args.flag("notify") && notify_follow_up(task)) {
+                    println!("{}", remind::follow_up_line(task, now));
+                }
+            }
+        }
+
+        Commands::Note => {
+            let id = task_arg(store, args)?;
+            let notes = match args.positional(2) {
+                Some(notes) => notes.to_owned(),
+                None => {
+                    let mut notes = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut notes)
+                        .expect("could not read notes from stdin");
+                    notes
+                }
+            };
+
+            if let Some(task) = store.find_mut(id) {
+                task.set_notes(Some(notes).filter(|notes| !notes.is_empty()));
+                task.updated_at = Utc::now();
+            }
+        }
+
+        Commands::Show => {
+            let past = as_of(store, args, config)?;
+            let store = past.as_ref().unwrap_or(store);
+            let query = args.positional(1).ok_or_else(|| CliError::missing("id"))?;
+            let id = store.resolve(query, Confirm::NotNeeded)?;
+            let task = blob::inline(store.find(id).expect("resolved ids exist"), &blob_dir);
+
+            print_task(&painter, store, &task);
+        }
+
+        Commands::Export => {
+            let tasks = store
+                .tasks
+                .iter()
+                .map(|task| blob::inline(task, &blob_dir))
+                .collect::<Vec<_>>();
+
+            match args.value("format").unwrap_or("json") {
+                "json" => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&tasks).expect("could not convert to json")
+                ),
+                "ics" => print!("{}", ics::export(&tasks)),
+                format => {
+                    return Err(CliError::invalid_args(
+                        format,
+                        format!("invalid format `{format}`, expected json or ics"),
+                    ))
+                }
+            }
+        }
+
+        Commands::Schedule => {
+            let id = task_arg(store, args)?;
+            let words = (2..).map_while(|index| args.positional(index));
+            let when = words.collect::<Vec<_>>().join(" ");
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            if when == "none" {
+                task.blocks.clear();
+            } else {
+                let block = phrases::parse_block(&when, config)?;
+                task.blocks.push(block);
+                task.blocks.sort_by_key(|block| block.start);
+                println!(
+                    "scheduled {} for {} to {}",
+                    task.description,
+                    format_local(&block.start),
+                    block.end.with_timezone(&Local).format("%H:%M")
+                );
+            }
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Plan => match args.positional(1).unwrap_or("timeline") {
+            "timeline" => {
+                let day = args.value("date").unwrap_or("today");
+                let span = phrases::parse_span(day, config)?;
+                let (Some(start), Some(end)) = (span.start, span.end) else {
+                    return Err(CliError::InvalidPhrase(day.to_owned()));
+                };
+
+                let mut blocks = store
+                    .tasks
+                    .iter()
+                    .flat_map(|task| task.blocks.iter().map(move |block| (block, task)))
+                    .filter(|(block, _)| block.start < end && block.end > start)
+                    .collect::<Vec<_>>();
+                blocks.sort_by_key(|(block, _)| block.start);
+
+                let locale = Locale::new(config);
+                let date = start.with_timezone(&Local).date_naive();
+                println!(
+                    "{}",
+                    painter.paint(
+                        "bold",
+                        &format!("{} {}", date.format("%a"), locale.format_date(date))
+                    )
+                );
+                if blocks.is_empty() {
+                    println!("nothing scheduled");
+                }
+
+                let clock = |time: &DateTime<Utc>| time.with_timezone(&Local).format("%H:%M");
+                let mut free_from: Option<DateTime<Utc>> = None;
+                for (block, task) in blocks {
+                    if let Some(free) = free_from.filter(|free| *free < block.start) {
+                        println!(
+                            "{}",
+                            painter.paint(
+                                "dim",
+                                &format!("{}-{}  free", clock(&free), clock(&block.start))
+                            )
+                        );
+                    }
+                    let length = format_duration(block.end - block.start);
+                    println!(
+                        "{}-{}  {}  {}",
+                        clock(&block.start),
+                        clock(&block.end),
+                        painter.paint("dim", &format!("{length:>5}")),
+                        list_line(&painter, task)
+                    );
+                    free_from = Some(free_from.map_or(block.end, |free| free.max(block.end)));
+                }
+            }
+            view => {
+                return Err(CliError::invalid_args(
+                    view,
+                    format!("unknown plan view `{view}`, expected timeline"),
+                ))
+            }
+        },
+
+        Commands::Tag => {
+            let id = task_arg(store, args)?;
+            let task = store.find_mut(id).expect("resolved ids exist");
+
+            for tag in (2..).map_while(|index| args.positional(index)) {
+                if let Some(tag) = tag.strip_prefix('-') {
+                    task.remove_tag(tag);
+                } else {
+                    task.add_tag(tag);
+                }
+            }
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Priority => {
+            let id = task_arg(store, args)?;
+            let priority = match args
+                .positional(2)
+                .ok_or_else(|| CliError::missing("priority"))?
+            {
+                "none" => None,
+                priority => Some(Priority::from_str(priority)?),
+            };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.set_priority(priority);
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Block => {
+            let id = task_arg(store, args)?;
+            let mut added = Vec::new();
+            let mut removed = Vec::new();
+            for query in (2..).map_while(|index| args.positional(index)) {
+                if let Some(query) = query.strip_prefix('-') {
+                    removed.push(store.resolve(query, confirmation(args))?);
+                } else {
+                    let blocker = store.resolve(query, confirmation(args))?;
+                    if blocker == id {
+                        return Err(CliError::invalid_args(
+                            query,
+                            format!("`{query}` cannot block itself"),
+                        ));
+                    }
+                    added.push(blocker);
+                }
+            }
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.blocked_by.retain(|blocker| !removed.contains(blocker));
+            for blocker in added {
+                if !task.blocked_by.contains(&blocker) {
+                    task.blocked_by.push(blocker);
+                }
+            }
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Delegate => {
+            let id = task_arg(store, args)?;
+            let delegated = match (args.positional(2), args.value("to")) {
+                (Some("none"), _) => None,
+                (_, Some(to)) => Some(Delegation {
+                    to: to.to_owned(),
+                    at: Utc::now(),
+                    follow_up: args
+                        .value("follow-up")
+                        .map(|after| {
+                            parse_duration(after)
+                                .map(|after| Utc::now() + after)
+                                .or_else(|_| parse_datetime(after))
+                                .map_err(|_| {
+                                    CliError::invalid_args(
+                                        after,
+                                        format!(
+                                            "invalid follow-up `{after}`, expected a duration \
+                                             or a date"
+                                        ),
+                                    )
+                                })
+                        })
+                        .transpose()?,
+                }),
+                _ => {
+                    return Err(CliError::invalid_args(
+                        "--to",
+                        "missing --to <name>, or `none` to take the task back",
+                    ))
+                }
+            };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.delegated = delegated;
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Hold => {
+            if args.positional(1).is_none() {
+                for task in store.tasks.iter().filter(|task| task.hold.is_some()) {
+                    println!("{}", list_line(&painter, task));
+                }
+                return Ok(());
+            }
+
+            let id = task_arg(store, args)?;
+            let task = store.find_mut(id).expect("resolved ids exist");
+            match args.positional(2) {
+                Some("none") => task.hold = None,
+                Some(other) => {
+                    return Err(CliError::invalid_args(
+                        other,
+                        format!("unexpected `{other}`, expected --reason <text> or none"),
+                    ))
+                }
+                None => {
+                    task.hold = Some(Hold {
+                        at: task.hold.as_ref().map_or_else(Utc::now, |hold| hold.at),
+                        reason: args.value("reason").map(str::to_owned),
+                    })
+                }
+            }
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Pause => {
+            let now = Utc::now();
+            match (args.positional(1), args.value("until")) {
+                (Some("none"), _) => match pause::active(now) {
+                    Some(pause) => print!("{}", pause.resume(&store.tasks, now)?),
+                    None => println!("not paused"),
+                },
+                (Some(other), _) => {
+                    return Err(CliError::invalid_args(
+                        other,
+                        format!("unexpected `{other}`, expected --until <date> or none"),
+                    ))
+                }
+                (None, Some(text)) => {
+                    let until = parse_datetime(text)?;
+                    if until <= now {
+                        return Err(CliError::invalid_args(
+                            text,
+                            "--until must be in the future",
+                        ));
+                    }
+                    // Moving the end of a pause keeps its start.
+                    let since = pause::active(now).map_or(now, |pause| pause.since);
+                    Pause {
+                        since,
+                        until,
+                        resumed: None,
+                    }
+                    .save()?;
+                    println!("paused until {}", format_local(&until));
+                }
+                (None, None) => match pause::active(now) {
+                    Some(pause) => println!(
+                        "paused since {} until {}",
+                        format_local(&pause.since),
+                        format_local(&pause.until)
+                    ),
+                    None => println!("not paused"),
+                },
+            }
+        }
+
+        Commands::Then => {
+            let id = task_arg(store, args)?;
+            let step =
+                match (args.positional(2), args.value("template")) {
+                    (Some("none"), _) => None,
+                    (_, Some(name)) => Some(template::load().remove(name).ok_or_else(|| {
+                        CliError::Template(format!("no template named `{name}`"))
+                    })?),
+                    (description, None) => Some(Template {
+                        description: description
+                            .ok_or_else(|| CliError::missing("follow-up description"))?
+                            .to_owned(),
+                        priority: args.value("priority").map(Priority::from_str).transpose()?,
+                        tags: args.value("tag").map(parse_tags).unwrap_or_default(),
+                        due_after: args
+                            .value("due")
+                            .map(|due| {
+                                let due = due.trim_start_matches('+');
+                                parse_duration(due).map(|_| due.to_owned())
+                            })
+                            .transpose()?,
+                        subtasks: Vec::new(),
+                        then: None,
+                    }),
+                };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            if let Some(step) = &step {
+                // Placeholders other than {task} and {date} could never be filled.
+                let vars = BTreeMap::from([("task".to_owned(), task.description.clone())]);
+                step.instantiate(&vars)?;
+            }
+            match (&mut task.on_done, step) {
+                (Some(chain), Some(step)) => chain.append(step),
+                (on_done, step) => *on_done = step,
+            }
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Next => {
+            let count = args.value("count").map_or(Ok(5), |count| {
+                count
+                    .parse()
+                    .map_err(|_| CliError::invalid_args(count, format!("invalid count `{count}`")))
+            })?;
+            let now = Utc::now();
+            let mut tasks = selected(store, args, config, None)?
+                .into_iter()
+                .filter(|task| section(task, &store.tasks, now) == Section::Active)
+                .collect::<Vec<_>>();
+            tasks.sort_by_key(urgency);
+            for task in tasks.iter().take(count) {
+                println!("{}", list_line(&painter, task));
+            }
+        }
+
+        Commands::Dashboard => {
+            let now = Utc::now();
+            let parts = dashboard::build(&store.tasks, &config.dashboard, now)?;
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!(
+                    "{}",
+                    painter.paint("bold", &format!("{} ({})", part.title(), part.count()))
+                );
+                if part.entries.is_empty() {
+                    println!("{}", painter.paint("dim", "nothing"));
+                }
+                for entry in &part.entries {
+                    match entry {
+                        Entry::Task(task) => println!("{}", list_line(&painter, task)),
+                        Entry::Block(block, task) => {
+                            let clock =
+                                |time: &DateTime<Utc>| time.with_timezone(&Local).format("%H:%M");
+                            println!(
+                                "{}-{}  {}",
+                                clock(&block.start),
+                                clock(&block.end),
+                                list_line(&painter, task)
+                            );
+                        }
+                        Entry::Review(review) => println!(
+                            "{}",
+                            painter.fit(&format!(
+                                "{} {}",
+                                painter.link(
+                                    &review.url,
+                                    &painter.id(&format!(
+                                        "{}#{}",
+                                        review.repository.name_with_owner, review.number
+                                    ))
+                                ),
+                                review.title
+                            ))
+                        ),
+                        Entry::Event(summary, due) => println!(
+                            "{}",
+                            painter.fit(&format!(
+                                "{} {}",
+                                painter.id(&format_local(due)),
+                                painter.urls(summary)
+                            ))
+                        ),
+                        Entry::Note(note) => println!("{}", painter.paint("dim", note)),
+                    }
+                }
+            }
+        }
+
+        Commands::Wait => {
+            let id = task_arg(store, args)?;
+            let until = match args
+                .positional(2)
+                .ok_or_else(|| CliError::missing("date"))?
+            {
+                "none" => None,
+                date => Some(parse_datetime(date)?),
+            };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.wait_until = until;
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Locate => {
+            let id = task_arg(store, args)?;
+            let words = (2..).map_while(|index| args.positional(index));
+            let place = match words.collect::<Vec<_>>().join(" ").as_str() {
+                "none" => None,
+                place => Some(location::parse(place)),
+            };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.location = place;
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Jump => {
+            let id = task_arg(store, args)?;
+            let task = store.find(id).expect("resolved ids exist");
+            let source = task.source.as_ref().ok_or_else(|| {
+                CliError::Editor(format!("`{}` has no source location", task.description))
+            })?;
+
+            edit::open_editor_at(&source.file, Some(source.line))?;
+        }
+
+        Commands::Scan => {
+            let path = args.positional(1).unwrap_or(".");
+            let keywords = scan::keywords(args.value("pattern").unwrap_or("TODO|FIXME"));
+            if keywords.is_empty() {
+                return Err(CliError::invalid_args(
+                    args.value("pattern").unwrap_or_default(),
+                    "--pattern has no keywords",
+                ));
+            }
+
+            let root = scan::root(path).map_err(|error| CliError::Read {
+                path: path.into(),
+                error,
+            })?;
+            let comments = scan::scan(&root, &keywords).map_err(|error| CliError::Read {
+                path: root.clone(),
+                error,
+            })?;
+
+            let summary = scan::sync(store, &root, comments);
+            if summary.is_empty() {
+                println!("no changes");
+            }
+            for line in summary {
+                println!("{line}");
+            }
+        }
+
+        Commands::Gate => {
+            if args.flag("install-hook") {
+                let path = install_pre_push_hook(args)?;
+                println!("installed {}", path.display());
+                return Ok(());
+            }
+
+            let mut blocking = selected(store, args, config, None)?;
+            blocking.retain(|task| task.status != Status::Done);
+            for task in &blocking {
+                println!("{}", list_line(&painter, task));
+            }
+            if !blocking.is_empty() {
+                return Err(CliError::Gated(blocking.len()));
+            }
+        }
+
+        Commands::Lint => {
+            let tasks = selected(store, args, config, None)?;
+            let events = history::load(&history::history_path(&store.path))?;
+            let problems = lint::check(&tasks, &events, &config.lint, Utc::now());
+            let blob_dir = store.blob_dir();
+
+            let (mut fixed, mut left, mut fixable) = (0, 0, 0);
+            let mut shown = None;
+            for problem in &problems {
+                if shown != Some(problem.task) {
+                    let task = tasks.iter().find(|task| task.id == problem.task);
+                    println!(
+                        "{}",
+                        list_line(&painter, task.expect("checked tasks exist"))
+                    );
+                    shown = Some(problem.task);
+                }
+
+                let done = match store.find_mut(problem.task) {
+                    Some(task) if args.flag("fix") && problem.fixable => {
+                        // On a copy, so a fix that does nothing leaves the
+                        // task as it was.
+                        let mut copy = blob::inline(task, &blob_dir);
+                        let done = lint::fix(&mut copy, problem, &config.lint);
+                        if done.is_some() {
+                            copy.updated_at = Utc::now();
+                            *task = copy;
+                        }
+                        done
+                    }
+                    _ => None,
+                };
+                match done {
+                    Some(done) => {
+                        fixed += 1;
+                        println!("    {}: {}; {done}", problem.rule, problem.message);
+                    }
+                    None => {
+                        left += 1;
+                        fixable += usize::from(problem.fixable);
+                        println!("    {}: {}", problem.rule, problem.message);
+                    }
+                }
+            }
+
+            // Fixes are saved even when problems remain to fix by hand.
+            if args.flag("fix") {
+                println!("fixed {fixed}, {left} left to fix by hand");
+            } else if left > 0 {
+                return Err(CliError::Linted {
+                    problems: left,
+                    fixable,
+                });
+            }
+        }
+
+        Commands::Calendar => {
+            let default_weeks = if args.positional(0) == Some("week") {
+                1
+            } else {
+                4
+            };
+            let weeks = args
+                .value("weeks")
+                .map(|weeks| {
+                    weeks.parse::<i64>().map_err(|_| {
+                        CliError::invalid_args(weeks, format!("invalid week count `{weeks}`"))
+                    })
+                })
+                .transpose()?
+                .unwrap_or(default_weeks);
+            let locale = Locale::new(config);
+
+            let mut tasks = selected(store, args, config, None)?;
+            tasks.retain(|task| task.status != Status::Done && task.due.is_some());
+            sort_tasks(&mut tasks, SortKey::Due);
+
+            let today = Local::now().date_naive();
+            let first_week = locale.week_of(today);
+            let end = first_week + Duration::weeks(weeks);
+            let local_due = |task: &Task| {
+                task.due
+                    .expect("only tasks with a due date")
+                    .with_timezone(&Local)
+                    .date_naive()
+            };
+
+            let overdue = tasks
+                .iter()
+                .filter(|task| local_due(task) < first_week)
+                .collect::<Vec<_>>();
+            if !overdue.is_empty() {
+                println!(
+                    "{}",
+                    painter.overdue(&format!("Earlier ({})", overdue.len()))
+                );
+                for task in overdue {
+                    println!("    {}", list_line(&painter, task));
+                }
+            }
+
+            for week in 0..weeks {
+                let start = first_week + Duration::weeks(week);
+                let in_week = tasks
+                    .iter()
+                    .filter(|task| locale.week_of(local_due(task)) == start)
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}",
+                    painter.paint(
+                        "bold",
+                        &format!("Week of {} ({})", locale.format_date(start), in_week.len())
+                    )
+                );
+
+                for day in (0..7).map(|offset| start + Duration::days(offset)) {
+                    let on_day = in_week
+                        .iter()
+                        .filter(|task| local_due(task) == day)
+                        .collect::<Vec<_>>();
+                    if on_day.is_empty() {
+                        continue;
+                    }
+
+                    let mut heading = format!("{} {}", day.format("%a"), locale.format_date(day));
+                    if day == today {
+                        heading.push_str(" (today)");
+                    }
+                    println!("    {heading}");
+                    for task in on_day {
+                        println!("        {}", list_line(&painter, task));
+                    }
+                }
+            }
+
+            let later = tasks.iter().filter(|task| local_due(task) >= end).count();
+            if later > 0 {
+                println!("{later} more due later");
+            }
+        }
+
+        Commands::Import => {
+            let (report_path, mut report, outcome) = match args.value("resume-report") {
+                Some(path) => {
+                    let path = PathBuf::from(path);
+                    let mut report = import::Report::load(&path)?;
+                    let rejected = std::mem::take(&mut report.rejected);
+                    let outcome = match report.format.as_str() {
+                        "json" => import_tasks(
+                            store,
+                            rejected
+                                .into_iter()
+                                .map(|rejected| (rejected.at, rejected.record))
+                                .collect(),
+                        ),
+                        "ics" => {
+                            let components = rejected
+                                .into_iter()
+                                .map(|rejected| {
+                                    let start = rejected
+                                        .at
+                                        .strip_prefix("line ")
+                                        .and_then(|line| line.parse().ok())
+                                        .unwrap_or(1);
+                                    let lines =
+                                        serde_json::from_value(rejected.record).map_err(|err| {
+                                            CliError::Import(format!(
+                                                "{}: expected the lines of the item: {err}",
+                                                rejected.at
+                                            ))
+                                        })?;
+                                    Ok((start, lines))
+                                })
+                                .collect::<Result<_, CliError>>()?;
+                            import_ics(store, components, report.filter.as_deref(), &report.tags)
+                        }
+                        format => {
+                            return Err(CliError::Import(format!(
+                                "unknown format `{format}` in {}",
+                                path.display()
+                            )))
+                        }
+                    };
+                    (path, report, outcome)
+                }
+                None => {
+                    let kind = args
+                        .positional(1)
+                        .ok_or_else(|| CliError::missing("import format"))?;
+                    if kind != "ics" && kind != "json" {
+                        return Err(CliError::Import(format!(
+                            "unknown format `{kind}`, expected ics or json"
+                        )));
+                    }
+                    let path = args
+                        .positional(2)
+                        .ok_or_else(|| CliError::missing("file"))?;
+                    let text = std::fs::read_to_string(path).map_err(|error| CliError::Read {
+                        path: path.into(),
+                        error,
+                    })?;
+
+                    let report = import::Report {
+                        format: kind.to_owned(),
+                        source: PathBuf::from(path),
+                        created_at: Utc::now(),
+                        filter: args.value("filter").map(str::to_owned),
+                        tags: args.value("tag").map(parse_tags).unwrap_or_default(),
+                        rejected: Vec::new(),
+                    };
+                    let outcome = if kind == "json" {
+                        import_json(store, &text)?
+                    } else {
+                        let components = ics::components(&text)?;
+                        import_ics(store, components, report.filter.as_deref(), &report.tags)
+                    };
+                    (import::Report::path(Path::new(path)), report, outcome)
+                }
+            };
+
+            println!(
+                "imported {} new and {} updated tasks",
+                outcome.added, outcome.updated
+            );
+            if outcome.stale > 0 {
+                println!("kept {} tasks changed here since the export", outcome.stale);
+            }
+
+            report.rejected = outcome.rejected;
+            report.save(&report_path)?;
+            if !report.rejected.is_empty() {
+                println!(
+                    "rejected {} records; fix them in {}, then run \
+                     `task-cli import --resume-report {}`",
+                    report.rejected.len(),
+                    report_path.display(),
+                    shell_quote(&report_path.to_string_lossy())
+                );
+                for rejected in &report.rejected {
+                    for problem in &rejected.problems {
+                        let fix = problem
+                            .fix
+                            .as_ref()
+                            .map(|fix| format!("; {fix}"))
+                            .unwrap_or_default();
+                        println!(
+                            "  {}: {}: {}{fix}",
+                            rejected.at, problem.field, problem.reason
+                        );
+                    }
+                }
+            }
+        }
+
+        Commands::Schema => {
+            let name = args.positional(1).unwrap_or("task");
+            let schema = schema::named(name).ok_or_else(|| {
+                CliError::invalid_args(
+                    name,
+                    format!("unknown schema `{name}`, expected task or event"),
+                )
+            })?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&schema).expect("could not convert to json")
+            );
+        }
+
+        Commands::Defaults => {
+            let id = task_arg(store, args)?;
+            let task = store.find_mut(id).expect("resolved ids exist");
+            let old = task.defaults.clone();
+
+            match args.value("priority") {
+                Some("none") => task.defaults.priority = None,
+                Some(priority) => {
+                    task.defaults.priority = Some(Priority::from_str(priority)?);
+                }
+                None => {}
+            }
+            match args.value("tag") {
+                Some("none") => task.defaults.tags.clear(),
+                Some(tags) => task.defaults.tags = parse_tags(tags),
+                None => {}
+            }
+
+            if task.defaults == old {
+                if task.defaults.is_empty() {
+                    println!("no defaults");
+                } else {
+                    println!("{}", describe_defaults(&painter, &task.defaults));
+                }
+            } else {
+                task.updated_at = Utc::now();
+                pass_down_defaults(&mut store.tasks, id, &old);
+            }
+        }
+
+        Commands::Estimate => {
+            let id = task_arg(store, args)?;
+            let estimate = match args
+                .positional(2)
+                .ok_or_else(|| CliError::missing("estimate"))?
+            {
+                "none" => None,
+                estimate => Some(parse_duration(estimate)?.num_seconds()),
+            };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.estimate_secs = estimate;
+            task.updated_at = Utc::now();
+        }
+
+        Commands::Track => {
+            let id = task_arg(store, args)?;
+            let spent = args
+                .positional(2)
+                .ok_or_else(|| CliError::missing("duration"))?;
+            let secs = match spent.strip_prefix('-') {
+                Some(spent) => -parse_duration(spent)?.num_seconds(),
+                None => parse_duration(spent)?.num_seconds(),
+            };
+
+            let task = store.find_mut(id).expect("resolved ids exist");
+            task.tracked_secs = (task.tracked_secs + secs).max(0);
+            task.updated_at = Utc::now();
+            println!(
+                "{} tracked on {}",
+                format_duration(Duration::seconds(task.tracked_secs)),
+                task.description
+            );
+        }
+
+        Commands::Blame => {
+            let id = task_arg(store, args)?;
+            // The log holds full texts, not their previews.
+            let task = blob::inline(store.find(id).expect("resolved ids exist"), &blob_dir);
+            let events = history::load(&history::history_path(&store.path))?;
+            blame(&painter, &task, &events);
+        }
+
+        Commands::Stats => match args.positional(1).unwrap_or("rhythm") {
+            "rhythm" => {
+                let tasks = selected(store, args, config, None)?;
+                let events = history::load(&history::history_path(&store.path))?;
+                // `--since 12w` means twelve weeks ago.
+                let since = args
+                    .value("since")
+                    .map(|since| {
+                        parse_duration(since)
+                            .map(|ago| Utc::now() - ago)
+                            .or_else(|_| parse_datetime(since))
+                    })
+                    .transpose()?;
+                let rhythm = stats::rhythm(&tasks, &events, since);
+                print!("{}", stats::render(&rhythm, &Locale::new(config)));
+            }
+            other => {
+                return Err(CliError::invalid_args(
+                    other,
+                    format!("unknown stats command `{other}`, expected rhythm"),
+                ))
+            }
+        },
+
+        Commands::Backup if args.positional(1) == Some("show") => {
+            let backup = open_backup(args.positional(2))?;
+            let Some(query) = args.value("id") else {
+                for task in &backup.tasks {
+                    println!("{}", list_line(&painter, task));
+                }
+                for task in &backup.trash {
+                    println!("{} (in the trash)", list_line(&painter, task));
+                }
+                return Ok(());
+            };
+
+            let id = backup
+                .resolve_id(query)
+                .or_else(|_| backup.resolve_trashed(query))?;
+            match backup.find(id) {
+                Some(task) => print_task(&painter, &backup, task),
+                None => {
+                    let task = backup.trash.iter().find(|task| task.id == id);
+                    print_task(&painter, &backup, task.expect("resolved ids exist"));
+                    println!("(in the trash)");
+                }
+            }
+        }
+
+        Commands::Backup if args.positional(1) == Some("restore-task") => {
+            let backup = open_backup(args.positional(2))?;
+            let query = args.positional(3).ok_or_else(|| CliError::missing("id"))?;
+            let id = backup
+                .resolve_id(query)
+                .or_else(|_| backup.resolve_trashed(query))?;
+            let task = backup
+                .tasks
+                .iter()
+                .chain(&backup.trash)
+                .find(|task| task.id == id)
+                .expect("resolved ids exist")
+                .clone();
+
+            let now = if store.find(id).is_some() {
+                "replacing the current copy"
+            } else if store.trash.iter().any(|task| task.id == id) {
+                "out of the trash"
+            } else {
+                "after it was purged"
+            };
+            println!(
+                "restored `{}` from {}, {now}",
+                task.description,
+                backup.path.display()
+            );
+            store.put_back(task);
+        }
+
+        Commands::Backup => {
+            let target = args
+                .positional(1)
+                .ok_or_else(|| CliError::missing("backup path or url"))?;
+            let target = match target.ends_with('/') {
+                true => format!("{target}tasks-{}.json", Utc::now().format("%Y%m%dT%H%M%SZ")),
+                false => target.to_owned(),
+            };
+            let target = PathBuf::from(target);
+            let snapshot = store.snapshot();
+
+            match Remote::parse(&target) {
+                Some(remote) => remote.upload(&snapshot)?,
+                None => std::fs::write(&target, &snapshot).map_err(|error| CliError::Write {
+                    path: target.clone(),
+                    error,
+                })?,
+            }
+            println!(
+                "backed up {} tasks to {}",
+                store.tasks.len() + store.trash.len(),
+                target.display()
+            );
+        }
+
+        Commands::Runbook => {
+            let events = || history::load(&history::history_path(&store.path));
+            match args
+                .positional(1)
+                .ok_or_else(|| CliError::missing("runbook command"))?
+            {
+                "start" => {
+                    let path = PathBuf::from(
+                        args.positional(2)
+                            .ok_or_else(|| CliError::missing("runbook file"))?,
+                    );
+                    let name = path
+                        .file_stem()
+                        .map_or("runbook".into(), |stem| stem.to_string_lossy());
+                    let vars = args
+                        .value("var")
+                        .map(template::parse_vars)
+                        .transpose()?
+                        .unwrap_or_default();
+                    let new_tasks = runbook::load(&path)?.start(&name, &vars)?;
+
+                    let ids = new_tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+                    store.tasks.extend(new_tasks);
+                    for id in &ids {
+                        inherit(&mut store.tasks, *id);
+                    }
+                    store.assign_numbers();
+
+                    let run = store.find(ids[0]).expect("just added");
+                    println!("started {}", list_line(&painter, run));
+                    for step in runbook::steps(run, &store.tasks) {
+                        println!(
+                            "{}",
+                            painter.fit(&format!(
+                                "    {}",
+                                runbook_step(&painter, step, &Default::default())
+                            ))
+                        );
+                    }
+                }
+                "ack" => {
+                    let id = store.resolve(
+                        args.positional(2)
+                            .ok_or_else(|| CliError::missing("step"))?,
+                        confirmation(args),
+                    )?;
+                    if let Some(task) = store.find_mut(id) {
+                        task.set_status(Status::InProgress);
+                        if task.owner.is_none() {
+                            task.owner = history::author();
+                        }
+                    }
+                }
+                "status" => {
+                    let events = events()?;
+                    let Some(query) = args.positional(2) else {
+                        let runs = store
+                            .tasks
+                            .iter()
+                            .filter(|task| task.runbook.is_some() && task.status != Status::Done);
+                        for run in runs {
+                            println!("{}", list_line(&painter, run));
+                        }
+                        return Ok(());
+                    };
+
+                    let run = runbook_run(store, query, args)?;
+                    let steps = runbook::steps(run, &store.tasks);
+                    let done = steps
+                        .iter()
+                        .filter(|step| step.status == Status::Done)
+                        .count();
+                    let started = format_duration(Utc::now() - run.created_at);
+                    println!(
+                        "{}",
+                        painter.fit(&format!(
+                            "{}: started {started} ago, {done} of {} steps done",
+                            run.description,
+                            steps.len()
+                        ))
+                    );
+                    for step in steps {
+                        let progress = runbook::progress(step, &events);
+                        println!(
+                            "{}",
+                            painter
+                                .fit(&format!("    {}", runbook_step(&painter, step, &progress)))
+                        );
+                    }
+                }
+                "timeline" => {
+                    let run = runbook_run(
+                        store,
+                        args.positional(2).ok_or_else(|| CliError::missing("run"))?,
+                        args,
+                    )?;
+                    let entries = runbook::timeline(run, &store.tasks, &events()?, Utc::now());
+                    match args.value("format").unwrap_or("markdown") {
+                        "markdown" => print!("{}", runbook::markdown(run, &entries)),
+                        "json" => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&entries)
+                                .expect("could not convert to json")
+                        ),
+                        format => {
+                            return Err(CliError::invalid_args(
+                                format,
+                                format!("invalid format `{format}`, expected markdown or json"),
+                            ))
+                        }
+                    }
+                }
+                other => {
+                    return Err(CliError::invalid_args(
+                        other,
+                        format!(
+                            "unknown runbook command `{other}`, expected start, ack, status or \
+                             timeline"
+                        ),
+                    ))
+                }
+            }
+        }
+
+        Commands::Meeting => {
+            let path = meeting::path(&store.path);
+            let current = meeting::load(&path)?;
+            let words = || {
+                let words = (2..).map_while(|index| args.positional(index));
+                words.collect::<Vec<_>>().join(" ")
+            };
+            let subcommand = args.positional(1).unwrap_or("status");
+            if subcommand == "start" {
+                if let Some(current) = current {
+                    return Err(CliError::Meeting(format!(
+                        "`{}` is still in progress; `meeting end` or `meeting cancel` it first",
+                        current.title
+                    )));
+                }
+                let title = words();
+                if title.is_empty() {
+                    return Err(CliError::missing("meeting title"));
+                }
+                let mut meeting = Meeting::new(title);
+                meeting.agenda = args
+                    .value("agenda")
+                    .map(|agenda| {
+                        agenda
+                            .split(',')
+                            .map(|topic| topic.trim().to_owned())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                meeting.attendees = args.value("attendees").map(parse_tags).unwrap_or_default();
+                meeting::save(&path, &meeting)?;
+                println!("started {}", meeting.title);
+                return Ok(());
+            }
+
+            let Some(mut meeting) = current else {
+                return Err(CliError::Meeting(
+                    "no meeting in progress; start one with `meeting start <title>`".to_owned(),
+                ));
+            };
+            match subcommand {
+                "item" => {
+                    let mut item = meeting::parse_item(&words(), config)?;
+                    if let Some(owner) = args.value("to") {
+                        item.owner = Some(owner.to_owned());
+                    }
+                    if let Some(due) = args.value("due") {
+                        item.due = Some(phrases::parse_deadline(due, config)?);
+                    }
+                    meeting.items.push(item);
+                    meeting::save(&path, &meeting)?;
+                }
+                "note" | "agenda" => {
+                    let text = words();
+                    if text.is_empty() {
+                        return Err(CliError::missing(&format!("{subcommand} text")));
+                    }
+                    if subcommand == "note" {
+                        meeting.notes.push(meeting::Note {
+                            at: Utc::now(),
+                            text,
+                        });
+                    } else {
+                        meeting.agenda.push(text);
+                    }
+                    meeting::save(&path, &meeting)?;
+                }
+                "status" => {
+                    println!(
+                        "{}: started {} ago, {} action items, {} notes",
+                        meeting.title,
+                        format_duration(Utc::now() - meeting.started_at),
+                        meeting.items.len(),
+                        meeting.notes.len()
+                    );
+                    for item in &meeting.items {
+                        let mut line = format!("    - {}", item.description);
+                        if let Some(owner) = &item.owner {
+                            line.push_str(&format!(" @{owner}"));
+                        }
+                        if let Some(due) = item.due {
+                            line.push_str(&format!(" (due {})", format_local(&due)));
+                        }
+                        println!("{}", painter.fit(&line));
+                    }
+                }
+                "cancel" => meeting::clear(&path)?,
+                "end" => {
+                    let ended_at = Utc::now();
+                    let mut new_tasks = meeting.tasks();
+                    for task in &mut new_tasks {
+                        for tag in args.value("tag").map(parse_tags).unwrap_or_default() {
+                            task.add_tag(&tag);
+                        }
+                    }
+                    let ids = new_tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+                    store.tasks.extend(new_tasks);
+                    store.assign_numbers();
+
+                    let filed = ids
+                        .iter()
+                        .filter_map(|id| store.find(*id))
+                        .collect::<Vec<_>>();
+                    let minutes = meeting.minutes(ended_at, &filed);
+                    match args.value("output") {
+                        Some(output) => {
+                            std::fs::write(output, &minutes).map_err(|error| CliError::Write {
+                                path: PathBuf::from(output),
+                                error,
+                            })?;
+                            println!("filed {} action items; minutes in {output}", filed.len());
+                        }
+                        None => print!("{minutes}"),
+                    }
+                    meeting::clear(&path)?;
+                }
+                other => {
+                    return Err(CliError::invalid_args(
+                        other,
+                        format!(
+                            "unknown meeting command `{other}`, expected start, item, note, \
+                             agenda, status, cancel or end"
+                        ),
+                    ))
+                }
+            }
+        }
+
+        Commands::Aging => {
+            let policy = &config.aging;
+            let now = Utc::now();
+
+            match args.positional(1).unwrap_or("list") {
+                "list" => {
+                    let Some(months) = policy.months else {
+                        println!("aging is off; set `months` in the [aging] table to turn it on");
+                        return Ok(());
+                    };
+                    let action = match policy.action {
+                        aging::Action::Demote => "demoted",
+                        aging::Action::Someday => "moved to someday",
+                    };
+                    println!("open tasks idle for {months} months are {action}");
+
+                    for task in store
+                        .tasks
+                        .iter()
+                        .filter(|task| aging::undoable(task, policy, now))
+                    {
+                        let aged = task.aged.as_ref().expect("undoable tasks have aged");
+                        println!(
+                            "{} [undo until {}]",
+                            list_line(&painter, task),
+                            format_local(&policy.undo_until(aged))
+                        );
+                    }
+                    let due = aging::due_to_age(&store.tasks, policy, pause::load().as_ref(), now);
+                    if !due.is_empty() {
+                        println!("{} more will age with the next change:", due.len());
+                        for id in due {
+                            let task = store.find(id).expect("ids come from the store");
+                            println!("    {}", list_line(&painter, task));
+                        }
+                    }
+                }
+                "undo" => {
+                    let ids = match args.positional(2) {
+                        Some(query) => vec![store.resolve(query, confirmation(args))?],
+                        None => store.tasks.iter().map(|task| task.id).collect(),
+                    };
+                    let mut undone = 0;
+                    for id in ids {
+                        let task = store.find_mut(id).expect("ids come from the store");
+                        if aging::undoable(task, policy, now) {
+                            aging::undo(task, now);
+                            undone += 1;
+                        }
+                    }
+                    println!("undid aging of {undone} tasks");
+                }
+                other => {
+                    return Err(CliError::invalid_args(
+                        other,
+                        format!("unknown aging command `{other}`, expected list or undo"),
+                    ))
+                }
+            }
+        }
+
+        Commands::Rules => {
+            let rules = rules::load()?;
+
+            match args.positional(1).unwrap_or("list") {
+                "list" => {
+                    if rules.is_empty() {
+                        println!("no rules in {}", rules::path().display());
+                    }
+                    for rule in &rules {
+                        println!("{}", rule.summary());
+                    }
+                }
+                "test" => {
+                    if let Some(name) = args.positional(2) {
+                        let nested = args.shifted(2);
+                        match Commands::from_str(name)? {
+                            Commands::Daemon
+                            | Commands::Interactive
+                            | Commands::Serve
+                            | Commands::Tour
+                            | Commands::Demo
+                            | Commands::Generate
+                            | Commands::ShellInit
+                            | Commands::Context
+                            | Commands::Greet
+                            | Commands::Sync
+                            | Commands::SelfUpdate
+                            | Commands::Rules => {
+                                return Err(CliError::invalid_args(
+                                    name,
+                                    format!("`{name}` cannot be tried out with rules test"),
+                                ))
+                            }
+                            cmd => execute(cmd, &nested, config, store)?,
+                        }
+                    }
+
+                    let outcome = rules::apply(store, &rules, Utc::now());
+                    if outcome.fired.is_empty() {
+                        println!("no rules would run");
+                    }
+                    for firing in &outcome.fired {
+                        let task = store.find(firing.task).expect("rules only run on tasks");
+                        println!(
+                            "rule `{}` would run on {}",
+                            firing.rule,
+                            list_line(&painter, task)
+                        );
+                        for action in &firing.actions {
+                            println!("    {action}");
+                        }
+                    }
+                    if outcome.exhausted {
+                        println!(
+                            "rules would still be changing tasks after {} rounds",
+                            rules::MAX_PASSES
+                        );
+                    }
+                }
+                other => {
+                    return Err(CliError::invalid_args(
+                        other,
+                        format!("unknown rules command `{other}`, expected list or test"),
+                    ))
+                }
+            }
+        }
+
+        Commands::Capture => {
+            let text = match (args.positional(1), args.value("from-file")) {
+                (Some(text), _) => text.to_owned(),
+                (None, Some(path)) => {
+                    std::fs::read_to_string(path).map_err(|error| CliError::Read {
+                        path: path.into(),
+                        error,
+                    })?
+                }
+                (None, None) => {
+                    let mut text = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut text)
+                        .map_err(|error| CliError::Read {
+                            path: "<stdin>".into(),
+                            error,
+                        })?;
+                    text
+                }
+            };
+
+            let entries = if args.flag("json") {
+                capture::from_json(&text)?
+            } else if args.flag("split-lines") {
+                capture::from_lines(&text)
+            } else {
+                capture::from_text(&text)
+            };
+
+            // Check the whole batch before adding anything.
+            let mut results = Vec::new();
+            let mut new_tasks = Vec::new();
+            for (index, entry) in entries.iter().enumerate() {
+                let import_id = entry.import_id();
+                let known = store
+                    .tasks
+                    .iter()
+                    .chain(&store.trash)
+                    .chain(&new_tasks)
+                    .find(|task| import_id.is_some() && task.import_id == import_id);
+                if let Some(task) = known {
+                    results.push((task.id, entry.id.clone(), "duplicate"));
+                    continue;
+                }
+
+                let task = entry.to_task().map_err(|message| {
+                    CliError::Import(format!("entry {}: {message}", index + 1))
+                })?;
+                results.push((task.id, entry.id.clone(), "captured"));
+                new_tasks.push(task);
+            }
+
+            let count = new_tasks.len();
+            store.tasks.extend(new_tasks);
+            store.assign_numbers();
+
+            if args.flag("json") {
+                let results = results
+                    .iter()
+                    .map(|(id, key, result)| {
+                        let task = store
+                            .find(*id)
+                            .or_else(|| store.trash.iter().find(|task| task.id == *id));
+                        serde_json::json!({
+                            "result": result,
+                            "key": key,
+                            "id": id.to_string(),
+                            "number": task.and_then(|task| task.number),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&results).expect("could not convert to json")
+                );
+            } else {
+                for (id, _, result) in &results {
+                    if let Some(task) = store.find(*id).filter(|_| *result == "captured") {
+                        println!("+ {}", list_line(&painter, task));
+                    }
+                }
+                let skipped = results.len() - count;
+                if skipped > 0 {
+                    println!("{skipped} already captured");
+                }
+            }
+        }
+
+        Commands::QuickAdd => {
+            let text = args
+                .positional(1)
+                .ok_or_else(|| CliError::missing("text"))?;
+            let mut new_tasks = Vec::new();
+            for entry in capture::from_text(text) {
+                new_tasks.push(entry.to_task().map_err(CliError::Import)?);
+            }
+            let ids = new_tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+            store.tasks.extend(new_tasks);
+            store.assign_numbers();
+
+            for id in ids {
+                let task = store.find(id).expect("just added");
+                println!("+ {}", list_line(&painter, task));
+                // Started from a hotkey, nobody sees the output.
+                if !std::io::stdout().is_terminal() {
+                    remind::notify_message("Added to the inbox", &task.description);
+                }
+            }
+        }
+
+        Commands::Template => match args.positional(1).unwrap_or("list") {
+            "list" => {
+                for (name, template) in template::load() {
+                    let mut details = Vec::new();
+                    if let Some(priority) = template.priority {
+                        details.push(painter.priority(priority));
+                    }
+                    if let Some(offset) = &template.due_after {
+                        details.push(format!("due +{offset}"));
+                    }
+                    if !template.subtasks.is_empty() {
+                        details.push(format!("{} subtasks", template.subtasks.len()));
+                    }
+                    let tags = template.tags.iter().map(|tag| painter.tag(tag));
+
+                    let mut line = format!("{name}: {}", template.description);
+                    if !details.is_empty() {
+                        line.push_str(&format!(" ({})", details.join(", ")));
+                    }
+                    for tag in tags {
+                        line.push(' ');
+                        line.push_str(&tag);
+                    }
+                    println!("{line}");
+                }
+            }
+            "save" => {
+                let name = args
+                    .positional(2)
+                    .ok_or_else(|| CliError::missing("template name"))?;
+                let query = args.positional(3).ok_or_else(|| CliError::missing("id"))?;
+                let id = store.resolve(query, Confirm::NotNeeded)?;
+
+                let task = store.find(id).expect("resolved ids exist");
+                let subtasks = store
+                    .tasks
+                    .iter()
+                    .filter(|subtask| subtask.parent == Some(id))
+                    .collect::<Vec<_>>();
+
+                let mut template = Template::from_task(task, &subtasks);
+                if let Some(description) = args.value("description") {
+                    template.description = description.to_owned();
+                }
+
+                let mut templates = template::load();
+                templates.insert(name.to_owned(), template);
+                template::save(&templates)?;
+            }
+            "delete" => {
+                let name = args
+                    .positional(2)
+                    .ok_or_else(|| CliError::missing("template name"))?;
+                let mut templates = template::load();
+                if templates.remove(name).is_none() {
+                    return Err(CliError::Template(format!("no template named `{name}`")));
+                }
+                template::save(&templates)?;
+            }
+            other => {
+                return Err(CliError::invalid_args(
+                    other,
+                    format!("unknown template command `{other}`, expected list, save or delete"),
+                ))
+            }
+        },
+
+        Commands::Edit => {
+            let tasks = selected(store, args, config, args.value("status"))?;
+            if tasks.is_empty() {
+                println!("no tasks to edit");
+                return Ok(());
+            }
+
+            let Some(_editing) = hold_for_editing(store, &tasks) else {
+                return Ok(());
+            };
+            let selection = tasks.iter().collect::<Vec<_>>();
+            let mut scratch =
+                Scratch::create("edit.toml", &edit::render(&selection)).map_err(|error| {
+                    CliError::Write {
+                        path: std::env::temp_dir(),
+                        error,
+                    }
+                })?;
+            let edits = edit_until_valid(&scratch.path, |buffer| edit::parse(buffer, &selection))
+                .inspect_err(|_| scratch.keep())?;
+
+            // The store was left unlocked while the editor was open; what
+            // was saved meanwhile is merged in before the edits count.
+            store.lock()?;
+            let summary = edit::apply(store, edits);
+            if let Err(err) = store.merge_saved() {
+                eprintln!("your changes are kept in {}", scratch.path.display());
+                scratch.keep();
+                return Err(err);
+            }
+
+            if summary.is_empty() {
+                println!("no changes");
+            }
+            for line in summary {
+                println!("{line}");
+            }
+        }
+
+        Commands::Rebase => {
+            let words = (1..).map_while(|index| args.positional(index));
+            let filter = match words.collect::<Vec<_>>().join(" ") {
+                words if words.is_empty() => None,
+                words => Some(query::parse(&words).map_err(CliError::Query)?),
+            };
+            let mut tasks = selected(store, args, config, None)?;
+            tasks.retain(|task| {
+                task.status != Status::Done
+                    && filter.as_ref().is_none_or(|filter| filter.matches(task))
+            });
+            if tasks.is_empty() {
+                println!("no tasks to rebase");
+                return Ok(());
+            }
+            let snooze_until = parse_datetime(args.value("snooze").unwrap_or("tomorrow"))?;
+
+            let Some(_editing) = hold_for_editing(store, &tasks) else {
+                return Ok(());
+            };
+            let selection = tasks.iter().collect::<Vec<_>>();
+            let mut scratch =
+                Scratch::create("rebase.txt", &rebase::render(&selection)).map_err(|error| {
+                    CliError::Write {
+                        path: std::env::temp_dir(),
+                        error,
+                    }
+                })?;
+            let directives = edit_until_valid(&scratch.path, |buffer| {
+                rebase::parse(buffer, &selection, snooze_until)
+            })
+            .inspect_err(|_| scratch.keep())?;
+
+            // As with `edit`, what was saved while the editor was open is
+            // merged in.
+            store.lock()?;
+            let rendered = tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+            let summary = rebase::apply(store, &rendered, directives);
+            if let Err(err) = store.merge_saved() {
+                eprintln!("your changes are kept in {}", scratch.path.display());
+                scratch.keep();
+                return Err(err);
+            }
+
+            if summary.is_empty() {
+                println!("no changes");
+            }
+            for line in summary {
+                println!("{line}");
+            }
+        }
+
+        Commands::Usage => {
+            if args.flag("clear") {
+                usage::clear()?;
+                return Ok(());
+            }
+
+            let entries = usage::load();
+            if entries.is_empty() {
+                if config.usage {
+                    println!("no commands logged yet");
+                } else {
+                    println!(
+                        "usage logging is off; set `usage = true` in {}",
+                        Config::path().display()
+                    );
+                }
+                return Ok(());
+            }
+
+            let top = args
+                .value("top")
+                .map(|top| {
+                    top.parse().map_err(|_| {
+                        CliError::invalid_args(top, format!("invalid --top count `{top}`"))
+                    })
+                })
+                .transpose()?
+                .unwrap_or(10);
+            usage::report(&entries, top);
+        }
+
+        Commands::Trash => match args.positional(1).unwrap_or("list") {
+            "list" => {
+                for task in &store.trash {
+                    let deleted_at = task.deleted_at.expect("trashed tasks have a deletion time");
+                    println!(
+                        "{}. {} ({}, deleted {})",
+                        painter.id(&task.id.to_string()),
+                        task.description,
+                        painter.status(&task.status),
+                        format_local(&deleted_at)
+                    );
+                }
+            }
+            "restore" => {
+                let id = store
+                    .resolve_trashed(args.positional(2).ok_or_else(|| CliError::missing("id"))?)?;
+                store.restore(id);
+            }
+            "empty" => {
+                let cutoff = args
+                    .value("older-than")
+                    .map(|age| Ok::<_, CliError>(Utc::now() - parse_duration(age)?))
+                    .transpose()?;
+
+                let before = store.trash.len();
+                store.trash.retain(|task| {
+                    task.hold.is_some()
+                        || cutoff
+                            .is_some_and(|cutoff| task.deleted_at.is_some_and(|at| at > cutoff))
+                });
+                let held = store
+                    .trash
+                    .iter()
+                    .filter(|task| task.hold.is_some())
+                    .count();
+                if held > 0 {
+                    println!(
+                        "emptied {} tasks from the trash; kept {held} on hold",
+                        before - store.trash.len()
+                    );
+                }
+            }
+            other => {
+                return Err(CliError::invalid_args(
+                    other,
+                    format!("unknown trash command `{other}`, expected list, restore or empty"),
+                ))
+            }
+        },
+
+        Commands::Daemon
+        | Commands::Interactive
+        | Commands::Serve
+        | Commands::Tour
+        | Commands::Demo
+        | Commands::Generate => {
+            unreachable!("long-running commands are started from main")
+        }
+        Commands::ShellInit | Commands::Context | Commands::Greet => {
+            unreachable!("shell commands are run from main")
+        }
+        Commands::Sync => unreachable!("sync status is run from main, without pulling"),
+        Commands::SelfUpdate => unreachable!("self-update is run from main, without a store"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Forms of the command line that were replaced but still work, so scripts
+//! written against them keep running while they are moved over. Each use
+//! prints a warning naming the form that replaced it, once per run; the
+//! `old-syntax` setting chooses what happens instead:
+//!
+//! ```toml
+//! old-syntax = "warn"   # the default; "allow" stays quiet, "deny" refuses
+//! ```
+
+use serde::Deserialize;
+use std::sync::Mutex;
+
+use crate::{args::Args, CliError};
+
+/// What to do when a replaced form is used.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    #[default]
+    Warn,
+    Allow,
+    Deny,
+}
+
+/// A replaced form of a command and what replaced it.
+pub struct Deprecation {
+    pub old: &'static str,
+    pub new: &'static str,
+}
+
+pub const LIST_STATUS: Deprecation = Deprecation {
+    old: "list <status>",
+    new: "list --status <status>",
+};
+
+pub const MARK_STATUS: Deprecation = Deprecation {
+    old: "mark <id> <status>",
+    new: "mark <id> --status <status>",
+};
+
+/// Deprecations already warned about in this run.
+static WARNED: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+/// Notes a use of `deprecation`: warns on the first one, or refuses it when
+/// `mode` is [`Mode::Deny`].
+pub fn used(deprecation: &Deprecation, mode: Mode) -> Result<(), CliError> {
+    match mode {
+        Mode::Allow => Ok(()),
+        Mode::Deny => Err(CliError::Deprecated {
+            old: deprecation.old.to_owned(),
+            new: deprecation.new.to_owned(),
+        }),
+        Mode::Warn => {
+            let mut warned = WARNED.lock().unwrap_or_else(|err| err.into_inner());
+            if !warned.contains(&deprecation.old) {
+                warned.push(deprecation.old);
+                eprintln!(
+                    "warning: `{}` is deprecated; use `{}` (set `old-syntax = \"allow\"` \
+                     in the config file to silence this)",
+                    deprecation.old, deprecation.new
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The value of `--name`, or else the positional argument at `index` in
+/// the replaced form `deprecation`.
+pub fn option_or_positional<'a>(
+    args: &'a Args,
+    name: &str,
+    index: usize,
+    deprecation: &Deprecation,
+    mode: Mode,
+) -> Result<Option<&'a str>, CliError> {
+    match (args.value(name), args.positional(index)) {
+        (Some(value), _) => Ok(Some(value)),
+        (None, Some(value)) => {
+            used(deprecation, mode)?;
+            Ok(Some(value))
+        }
+        (None, None) => Ok(None),
+    }
+}
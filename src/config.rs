@@ -0,0 +1,173 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{
+    aging, compat, dashboard, lint, quota, retention, server, toml, vocabulary::Vocabulary,
+};
+
+/// When to color output or emit hyperlinks.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Styles such as `"bold green"` or `"dim"` for each part of the output.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Theme {
+    pub id: String,
+    pub todo: String,
+    pub in_progress: String,
+    pub done: String,
+    pub overdue: String,
+    pub low: String,
+    pub medium: String,
+    pub high: String,
+    pub urgent: String,
+    pub tag: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            id: "dim".to_owned(),
+            todo: "none".to_owned(),
+            in_progress: "yellow".to_owned(),
+            done: "green".to_owned(),
+            overdue: "red".to_owned(),
+            low: "dim".to_owned(),
+            medium: "none".to_owned(),
+            high: "bold".to_owned(),
+            urgent: "bold red".to_owned(),
+            tag: "cyan".to_owned(),
+        }
+    }
+}
+
+/// A rendering profile for constrained displays. Unset fields keep the
+/// built-in profile's value, or the normal behavior for custom profiles.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Profile {
+    /// Wrap output at this many columns.
+    pub width: Option<usize>,
+    /// Turn off color and hyperlinks regardless of `color` and `hyperlinks`.
+    pub monochrome: Option<bool>,
+    /// Replace symbols such as `✓` and `…` with plain ASCII.
+    pub ascii: Option<bool>,
+}
+
+impl Profile {
+    /// The built-in `narrow` profile, for serial consoles and e-ink screens.
+    pub fn narrow() -> Self {
+        Self {
+            width: Some(40),
+            monochrome: Some(true),
+            ascii: Some(true),
+        }
+    }
+
+    /// `self` with its unset fields taken from `base`.
+    fn or(self, base: Self) -> Self {
+        Self {
+            width: self.width.or(base.width),
+            monochrome: self.monochrome.or(base.monochrome),
+            ascii: self.ascii.or(base.ascii),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub color: ColorMode,
+    /// Whether to emit terminal hyperlinks; `auto` uses them on terminals.
+    pub hyperlinks: ColorMode,
+    /// Base URL of a web UI served by `task-cli serve`; task ids link to
+    /// `<web-url>/tasks/<id>` instead of `task-cli://<id>`.
+    pub web_url: Option<String>,
+    pub theme: Theme,
+    /// Recurring times such as `standup = "09:30"` or `review = "fri 15:00"`
+    /// that filter phrases can refer to.
+    pub named_times: BTreeMap<String, String>,
+    /// Keep a local log of the commands run, shown by `task-cli usage`.
+    pub usage: bool,
+    /// Locale such as `en_US` deciding the first day of the week and the
+    /// date format; defaults to `LC_ALL`, `LC_TIME` or `LANG`.
+    pub locale: Option<String>,
+    /// First day of the week, e.g. `sunday`; overrides the locale.
+    pub week_start: Option<String>,
+    /// `strftime` format for dates, e.g. `%d/%m/%Y`; overrides the locale.
+    pub date_format: Option<String>,
+    /// Rendering profile used when `--profile` is not given.
+    pub profile: Option<String>,
+    /// Custom rendering profiles, or overrides for the built-in `narrow`.
+    pub profiles: BTreeMap<String, Profile>,
+    /// Demote or shelve tasks that have been idle for months.
+    pub aging: aging::Policy,
+    /// Other names for statuses and priorities, shown in output and
+    /// accepted as input.
+    pub vocabulary: Vocabulary,
+    /// How long done and trashed tasks are kept before they are purged.
+    pub retention: retention::Policy,
+    /// Rules `task-cli lint` checks, and their thresholds.
+    pub lint: lint::Settings,
+    /// Counts, such as of overdue tasks, to nag about once exceeded.
+    pub quotas: quota::Quotas,
+    /// Sections of `task-cli dashboard` and the sources feeding each one.
+    pub dashboard: dashboard::Settings,
+    /// Web pages allowed to call `task-cli serve`.
+    pub serve: server::Settings,
+    /// Whether replaced forms of commands, such as `list done`, warn, work
+    /// quietly or are refused.
+    pub old_syntax: compat::Mode,
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir().unwrap().join("task-cli")
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        config_dir().join("config.toml")
+    }
+
+    /// Reads the configuration file, falling back to defaults when it does
+    /// not exist. Its vocabulary is installed for the whole process, so
+    /// statuses and priorities read and print with it everywhere.
+    pub fn load() -> Self {
+        let config = match std::fs::read_to_string(Self::path()) {
+            Ok(contents) => {
+                let value = toml::parse(&contents)
+                    .unwrap_or_else(|err| panic!("invalid config file: {err}"));
+                serde_json::from_value::<Self>(value)
+                    .unwrap_or_else(|err| panic!("invalid config file: {err}"))
+            }
+            Err(_) => Self::default(),
+        };
+
+        if let Err(err) = config.vocabulary.check().and_then(|()| config.lint.check()) {
+            panic!("invalid config file: {err}");
+        }
+        config.vocabulary.clone().install();
+        config
+    }
+
+    /// The rendering profile called `name`; `default` means none at all.
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        let builtin = match name {
+            "default" => return Some(Profile::default()),
+            "narrow" => Some(Profile::narrow()),
+            _ => None,
+        };
+
+        match (self.profiles.get(name).cloned(), builtin) {
+            (Some(profile), Some(builtin)) => Some(profile.or(builtin)),
+            (profile, builtin) => profile.or(builtin),
+        }
+    }
+}
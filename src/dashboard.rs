@@ -0,0 +1,305 @@
+//! `dashboard`: one morning view of what needs attention, gathered from the
+//! store, calendar files and GitHub. Which sections appear, in which order,
+//! and which sources feed each one is set in the `[dashboard]` table:
+//!
+//! ```toml
+//! [dashboard]
+//! sections = ["overdue", "today", "reviews", "deadlines", "next"]
+//!
+//! [dashboard.deadlines]
+//! sources = ["tasks", "calendar"]
+//! calendars = ["~/calendars/work.ics"]
+//! days = 7
+//! ```
+//!
+//! Sections and their sources: `overdue` (tasks), `today` (blocks, due),
+//! `reviews` (github), `deadlines` (tasks, calendar) and `next` (tasks).
+
+use chrono::{DateTime, Duration, Local, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, process::Command};
+
+use crate::{
+    ics,
+    task::{section, urgency, Section, Status, Task, TimeBlock},
+    time::local_to_utc,
+    CliError,
+};
+
+const SECTIONS: [&str; 5] = ["overdue", "today", "reviews", "deadlines", "next"];
+
+/// Lists GitHub pull requests waiting on the user's review as JSON.
+const GH_REVIEWS: &str = "gh search prs --review-requested=@me --state=open \
+                          --json number,title,url,repository,updatedAt";
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub sections: Vec<String>,
+    /// Options of each section, by name.
+    #[serde(flatten)]
+    pub options: BTreeMap<String, Options>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sections: SECTIONS.map(str::to_owned).to_vec(),
+            options: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Sources feeding the section; all it knows when unset.
+    pub sources: Option<Vec<String>>,
+    /// How many days ahead `deadlines` looks (7 unless set).
+    pub days: Option<i64>,
+    /// Most entries shown, e.g. for `next` (5 unless set).
+    pub limit: Option<usize>,
+    /// iCalendar files whose events and to-dos count as deadlines.
+    pub calendars: Vec<String>,
+    /// Command printing the review requests as `gh search prs --json` does.
+    pub command: Option<String>,
+}
+
+impl Options {
+    fn uses(&self, source: &str) -> bool {
+        self.sources
+            .as_ref()
+            .is_none_or(|sources| sources.iter().any(|name| name == source))
+    }
+}
+
+/// A pull request waiting on a review.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Review {
+    pub number: u64,
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub repository: Repository,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct Repository {
+    #[serde(default, rename = "nameWithOwner")]
+    pub name_with_owner: String,
+}
+
+pub enum Entry<'t> {
+    Task(&'t Task),
+    Block(&'t TimeBlock, &'t Task),
+    Review(Review),
+    /// A calendar item not imported as a task yet, with its date.
+    Event(String, DateTime<Utc>),
+    /// Something to say about the section, such as a source that failed.
+    Note(String),
+}
+
+pub struct Part<'t> {
+    pub name: String,
+    pub entries: Vec<Entry<'t>>,
+}
+
+impl Part<'_> {
+    pub fn title(&self) -> &str {
+        match self.name.as_str() {
+            "overdue" => "Overdue",
+            "today" => "Today",
+            "reviews" => "Review requests",
+            "deadlines" => "Coming up",
+            "next" => "Next",
+            name => name,
+        }
+    }
+
+    /// Entries counted in the heading; notes are not.
+    pub fn count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| !matches!(entry, Entry::Note(_)))
+            .count()
+    }
+}
+
+fn end_of_today() -> Result<DateTime<Utc>, CliError> {
+    local_to_utc((Local::now().date_naive() + Duration::days(1)).and_time(NaiveTime::MIN))
+}
+
+fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The sections of the dashboard, in the configured order.
+pub fn build<'t>(
+    tasks: &'t [Task],
+    settings: &Settings,
+    now: DateTime<Utc>,
+) -> Result<Vec<Part<'t>>, CliError> {
+    let tonight = end_of_today()?;
+    let open = || tasks.iter().filter(|task| task.status != Status::Done);
+    let by_urgency = |mut tasks: Vec<&'t Task>| {
+        tasks.sort_by_key(|task| urgency(task));
+        tasks
+    };
+
+    let mut parts = Vec::new();
+    for name in &settings.sections {
+        let options = settings.options.get(name).cloned().unwrap_or_default();
+        let mut entries = Vec::new();
+        match name.as_str() {
+            "overdue" => {
+                let overdue = open()
+                    .filter(|task| task.due.is_some_and(|due| due < now))
+                    .collect();
+                entries.extend(by_urgency(overdue).into_iter().map(Entry::Task));
+            }
+            "today" => {
+                if options.uses("blocks") {
+                    let mut blocks = tasks
+                        .iter()
+                        .flat_map(|task| task.blocks.iter().map(move |block| (block, task)))
+                        .filter(|(block, _)| block.end > now && block.start < tonight)
+                        .collect::<Vec<_>>();
+                    blocks.sort_by_key(|(block, _)| block.start);
+                    entries.extend(
+                        blocks
+                            .into_iter()
+                            .map(|(block, task)| Entry::Block(block, task)),
+                    );
+                }
+                if options.uses("due") {
+                    let due = open()
+                        .filter(|task| task.due.is_some_and(|due| due >= now && due < tonight))
+                        .collect();
+                    entries.extend(by_urgency(due).into_iter().map(Entry::Task));
+                }
+            }
+            "reviews" => {
+                if options.uses("github") {
+                    let command = options.command.as_deref().unwrap_or(GH_REVIEWS);
+                    match reviews(command) {
+                        Ok(reviews) => entries.extend(reviews.into_iter().map(Entry::Review)),
+                        Err(message) => match cached_reviews() {
+                            Some((reviews, at)) => {
+                                entries.push(Entry::Note(format!(
+                                    "{message}; showing the requests from {}",
+                                    crate::time::format_local(&at)
+                                )));
+                                entries.extend(reviews.into_iter().map(Entry::Review));
+                            }
+                            None => entries.push(Entry::Note(message)),
+                        },
+                    }
+                }
+            }
+            "deadlines" => {
+                let until = tonight + Duration::days(options.days.unwrap_or(7));
+                let mut dated = Vec::new();
+                if options.uses("tasks") {
+                    dated.extend(
+                        open()
+                            .filter(|task| {
+                                task.due.is_some_and(|due| due >= tonight && due < until)
+                            })
+                            .map(|task| (task.due.expect("filtered on due"), Entry::Task(task))),
+                    );
+                }
+                if options.uses("calendar") {
+                    for calendar in &options.calendars {
+                        let path = expand(calendar);
+                        let text = std::fs::read_to_string(&path)
+                            .map_err(|error| CliError::Read { path, error })?;
+                        for item in ics::parse(&text)? {
+                            let imported = item.uid.as_ref().is_some_and(|uid| {
+                                let import_id = format!("ics:{uid}");
+                                tasks
+                                    .iter()
+                                    .any(|task| task.import_id.as_ref() == Some(&import_id))
+                            });
+                            match item.due {
+                                Some(due)
+                                    if !imported
+                                        && !item.completed
+                                        && due >= now
+                                        && due < until =>
+                                {
+                                    dated.push((due, Entry::Event(item.summary, due)));
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                dated.sort_by_key(|(due, _)| *due);
+                entries.extend(dated.into_iter().map(|(_, entry)| entry));
+            }
+            "next" => {
+                let active = open()
+                    .filter(|task| section(task, tasks, now) == Section::Active)
+                    .filter(|task| task.due.is_none_or(|due| due >= tonight))
+                    .collect();
+                let limit = options.limit.unwrap_or(5);
+                entries.extend(by_urgency(active).into_iter().take(limit).map(Entry::Task));
+            }
+            _ => panic!(
+                "unknown dashboard section `{name}`, expected one of {}",
+                SECTIONS.join(", ")
+            ),
+        }
+        if let Some(limit) = options.limit {
+            entries.truncate(limit);
+        }
+        parts.push(Part {
+            name: name.clone(),
+            entries,
+        });
+    }
+
+    Ok(parts)
+}
+
+fn reviews_cache() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap()
+        .join("task-cli/github-reviews.json")
+}
+
+/// Runs `command` for the review requests, keeping the answer for when
+/// GitHub cannot be reached.
+fn reviews(command: &str) -> Result<Vec<Review>, String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|err| format!("cannot run `{command}`: {err}"))?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        let error = error.lines().next().unwrap_or("failed").trim().to_owned();
+        return Err(format!("cannot fetch review requests: {error}"));
+    }
+
+    let reviews = serde_json::from_slice::<Vec<Review>>(&output.stdout)
+        .map_err(|err| format!("cannot read review requests: {err}"))?;
+    let cache = reviews_cache();
+    if let Some(dir) = cache.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(&cache, &output.stdout);
+    Ok(reviews)
+}
+
+fn cached_reviews() -> Option<(Vec<Review>, DateTime<Utc>)> {
+    let cache = reviews_cache();
+    let at = std::fs::metadata(&cache)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    let reviews = serde_json::from_slice(&std::fs::read(&cache).ok()?).ok()?;
+    Some((reviews, at.into()))
+}
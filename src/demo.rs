@@ -0,0 +1,261 @@
+//! `task-cli demo`: an interactive session on a temporary store filled with
+//! generated tasks, for trying out views without touching real data, and
+//! `task-cli generate`, which writes the same kind of tasks into a store for
+//! benchmarks and for reproducing bugs that need many tasks.
+
+use chrono::{DateTime, Duration, NaiveTime, SecondsFormat, Utc};
+use ulid::Ulid;
+
+use crate::{
+    args::Args,
+    lock::StoreLock,
+    remote, repl,
+    store::{self, Store},
+    task::{Priority, Status, Task},
+    time::parse_datetime,
+    CliError,
+};
+
+const PROJECTS: &[(&str, &[&str])] = &[
+    (
+        "work",
+        &[
+            "Review pull request for the billing service",
+            "Write quarterly report",
+            "Prepare slides for the team sync",
+            "Fix flaky login test",
+            "Reply to the vendor about the contract",
+            "Update onboarding docs",
+            "Plan the database migration",
+        ],
+    ),
+    (
+        "home",
+        &[
+            "Buy groceries",
+            "Call the plumber about the sink",
+            "Renew car insurance",
+            "Clean out the garage",
+            "Pay the electricity bill",
+            "Book a dentist appointment",
+        ],
+    ),
+    (
+        "learning",
+        &[
+            "Finish the Rust ownership chapter",
+            "Build the roadmap.sh task tracker project",
+            "Watch the talk on async runtimes",
+            "Practice SQL joins",
+            "Read about HTTP caching",
+        ],
+    ),
+    (
+        "health",
+        &[
+            "Go for a 5k run",
+            "Schedule annual checkup",
+            "Meal prep for the week",
+        ],
+    ),
+];
+
+const SUBTASKS: &[&str] = &["Draft", "Get feedback", "Finalize"];
+
+/// Small xorshift generator so the same seed gives the same tasks.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    /// An id for a task created at `at`, so ids sort like the tasks.
+    fn id(&mut self, at: DateTime<Utc>) -> Ulid {
+        let random = u128::from(self.next()) << 16 | u128::from(self.next() & 0xffff);
+        Ulid::from_parts(at.timestamp_millis() as u64, random)
+    }
+}
+
+/// Generates `count` tasks created over the six weeks before `now`, with a
+/// mix of statuses, priorities, tags, due dates, blockers and subtasks. The
+/// same seed and `now` always give the same tasks.
+pub fn generate(count: usize, seed: u64, now: DateTime<Utc>) -> Vec<Task> {
+    let mut rng = Rng(seed.max(1));
+    let mut tasks = Vec::new();
+    let mut open = Vec::new();
+
+    while tasks.len() < count {
+        let (project, descriptions) = rng.pick(PROJECTS);
+        let created_at = now - Duration::minutes(rng.below(60 * 24 * 45) as i64);
+
+        let mut task = Task::new(rng.pick(descriptions).to_string());
+        task.id = rng.id(created_at);
+        task.created_at = created_at;
+        task.updated_at = created_at + Duration::minutes(rng.below(60 * 24 * 3) as i64);
+        task.updated_at = task.updated_at.min(now);
+        task.status = match rng.below(10) {
+            0..=4 => Status::ToDo,
+            5..=6 => Status::InProgress,
+            _ => Status::Done,
+        };
+        task.priority = match rng.below(10) {
+            0..=3 => None,
+            4..=5 => Some(Priority::Low),
+            6..=7 => Some(Priority::Medium),
+            8 => Some(Priority::High),
+            _ => Some(Priority::Urgent),
+        };
+        task.tags.push(project.to_string());
+        if rng.chance(20) {
+            task.tags.push("errand".to_owned());
+        }
+        if rng.chance(50) {
+            // From a few days overdue to a couple of weeks out.
+            let offset = rng.below(60 * 24 * 18) as i64 - 60 * 24 * 4;
+            task.due = Some(now + Duration::minutes(offset));
+            if rng.chance(30) {
+                task.remind_before_secs = Some(60 * 60);
+            }
+        }
+        if rng.chance(15) {
+            task.set_notes(Some(format!("Context for `{}`.", task.description)));
+        }
+        if task.status != Status::Done && !open.is_empty() && rng.chance(10) {
+            task.blocked_by.push(*rng.pick(&open));
+        }
+
+        let subtasks = if rng.chance(15) { SUBTASKS } else { &[] };
+        let parent = task.id;
+        let status = task.status.clone();
+        if status != Status::Done {
+            open.push(parent);
+        }
+        tasks.push(task);
+
+        for (index, step) in subtasks.iter().enumerate() {
+            // Broken down right after the parent was added.
+            let created_at = created_at + Duration::minutes(index as i64 + 1);
+            let mut subtask = Task::new(step.to_string());
+            subtask.id = rng.id(created_at);
+            subtask.created_at = created_at;
+            subtask.updated_at = created_at;
+            subtask.parent = Some(parent);
+            subtask.status = match status {
+                Status::Done => Status::Done,
+                _ if index == 0 => Status::Done,
+                _ => Status::ToDo,
+            };
+            tasks.push(subtask);
+        }
+    }
+
+    // Oldest first, like a store that grew over time; subtasks stay after
+    // their parent.
+    tasks.truncate(count);
+    tasks.sort_by_key(|task| task.created_at);
+    tasks
+}
+
+fn count_and_seed(args: &Args, count: usize) -> (usize, Option<u64>) {
+    let count = args
+        .value("count")
+        .map(|count| count.parse().expect("invalid task count"))
+        .unwrap_or(count);
+    let seed = args
+        .value("seed")
+        .map(|seed| seed.parse().expect("invalid seed"));
+    (count, seed)
+}
+
+/// `task-cli generate`: fills an empty store with generated tasks. Dates are
+/// relative to `--at`, the start of today (UTC) unless given, so the same
+/// command gives the same store all day and `--at` reproduces it later.
+pub fn run_generate(args: &Args) -> Result<(), CliError> {
+    let (count, seed) = count_and_seed(args, 1000);
+    let seed = seed.unwrap_or(1);
+    let at = match args.value("at") {
+        Some(at) => parse_datetime(at).expect("invalid --at time"),
+        None => Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc(),
+    };
+
+    let path = store::tasks_path(args);
+    remote::local_only(&path, "generate");
+    store::check_writable(&path)?;
+    let _lock = StoreLock::acquire(&path)?;
+
+    let mut store = Store::open(path);
+    if !store.tasks.is_empty() || !store.trash.is_empty() {
+        panic!(
+            "{} already has tasks; generate into a new store, e.g. with --file fixture.json",
+            store.path.display()
+        );
+    }
+    store.tasks = generate(count, seed, at);
+    store.save()?;
+
+    println!(
+        "generated {count} tasks in {} (--seed {seed} --at {})",
+        store.path.display(),
+        at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+    Ok(())
+}
+
+pub fn run(args: &Args) -> Result<(), CliError> {
+    let (count, seed) = count_and_seed(args, 25);
+    let seed = seed.unwrap_or_else(|| Utc::now().timestamp_millis() as u64);
+
+    let dir = std::env::temp_dir().join(format!("task-cli-demo-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(|error| CliError::Write {
+        path: dir.clone(),
+        error,
+    })?;
+    let path = dir.join("tasks.json");
+
+    let mut store = Store::new(path.clone(), generate(count, seed, Utc::now()));
+    store.save()?;
+
+    println!(
+        "demo store with {} tasks at {} (seed {seed})",
+        store.tasks.len(),
+        path.display()
+    );
+    if args.flag("keep") {
+        println!(
+            "it is kept afterwards; use it with --file {}",
+            path.display()
+        );
+    }
+
+    let mut words = vec![
+        "interactive".to_owned(),
+        "--file".to_owned(),
+        path.display().to_string(),
+    ];
+    if args.flag("no-color") {
+        words.push("--no-color".to_owned());
+    }
+    repl::run(&Args::parse(words));
+
+    if !args.flag("keep") {
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    Ok(())
+}
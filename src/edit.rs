@@ -0,0 +1,352 @@
+//! `edit`: round-trips tasks through a TOML buffer in `$EDITOR`.
+
+use chrono::Utc;
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fs::{DirBuilder, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+use ulid::Ulid;
+
+use crate::{
+    store::Store,
+    task::{Priority, Status, Task},
+    time::{format_local, parse_datetime},
+    CliError,
+};
+
+const HEADER: &str = "\
+# Edit the tasks below, then save and quit.
+# Set `delete = true` to move a task to the trash; removing a [[task]] block
+# is refused. Add a block without an `id` to create a task. Leave `due` or
+# `priority` empty to clear them.
+";
+
+fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn render(tasks: &[&Task]) -> String {
+    let mut out = HEADER.to_owned();
+
+    for task in tasks {
+        out.push_str("\n[[task]]\n");
+        out.push_str(&format!("id = {}\n", quote(&task.id.to_string())));
+        if task.description_blob.is_some() {
+            out.push_str("# the description is stored separately and cannot be edited here\n");
+        } else {
+            out.push_str(&format!("description = {}\n", quote(&task.description)));
+        }
+        out.push_str(&format!("status = {}\n", quote(&task.status.to_string())));
+        let priority = task.priority.map(|p| p.to_string()).unwrap_or_default();
+        out.push_str(&format!("priority = {}\n", quote(&priority)));
+        let tags = task.tags.iter().map(|tag| quote(tag)).collect::<Vec<_>>();
+        out.push_str(&format!("tags = [{}]\n", tags.join(", ")));
+        let due = task.due.map(|due| format_local(&due)).unwrap_or_default();
+        out.push_str(&format!("due = {}\n", quote(&due)));
+    }
+
+    out
+}
+
+#[derive(Deserialize)]
+struct Buffer {
+    #[serde(default)]
+    task: Vec<RawTask>,
+}
+
+#[derive(Deserialize)]
+struct RawTask {
+    id: Option<String>,
+    description: Option<String>,
+    status: Option<String>,
+    priority: Option<String>,
+    tags: Option<Vec<String>>,
+    due: Option<String>,
+    #[serde(default)]
+    delete: bool,
+}
+
+/// A validated `[[task]]` block. `None` fields were left out and keep their
+/// current value.
+pub struct Edited {
+    id: Option<Ulid>,
+    description: Option<String>,
+    status: Option<Status>,
+    priority: Option<Option<Priority>>,
+    tags: Option<Vec<String>>,
+    due: Option<Option<chrono::DateTime<Utc>>>,
+    delete: bool,
+}
+
+fn invalid(message: String) -> CliError {
+    CliError::InvalidEdit(message)
+}
+
+/// Parses the edited buffer; every task in `selected` has to come back,
+/// either unchanged, edited or marked for deletion.
+pub fn parse(buffer: &str, selected: &[&Task]) -> Result<Vec<Edited>, CliError> {
+    let value = crate::toml::parse(buffer).map_err(|err| invalid(err.to_string()))?;
+    let buffer = serde_json::from_value::<Buffer>(value).map_err(|err| invalid(err.to_string()))?;
+
+    let known = selected.iter().map(|task| task.id).collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    let mut edited = Vec::new();
+
+    for (index, raw) in buffer.task.into_iter().enumerate() {
+        let block = index + 1;
+
+        let id = raw
+            .id
+            .map(|id| {
+                let id = Ulid::from_string(&id)
+                    .map_err(|_| invalid(format!("task {block}: invalid id `{id}`")))?;
+                if !known.contains(&id) {
+                    return Err(invalid(format!("task {block}: {id} was not being edited")));
+                }
+                if !seen.insert(id) {
+                    return Err(invalid(format!("task {block}: {id} appears twice")));
+                }
+                Ok(id)
+            })
+            .transpose()?;
+
+        if id.is_none() && raw.description.as_deref().is_none_or(str::is_empty) {
+            return Err(invalid(format!(
+                "task {block}: new tasks need a description"
+            )));
+        }
+
+        let status = raw
+            .status
+            .map(|status| {
+                Status::from_str(&status)
+                    .map_err(|_| invalid(format!("task {block}: invalid status `{status}`")))
+            })
+            .transpose()?;
+        let priority = raw
+            .priority
+            .map(|priority| match priority.as_str() {
+                "" => Ok(None),
+                _ => Priority::from_str(&priority)
+                    .map(Some)
+                    .map_err(|_| invalid(format!("task {block}: invalid priority `{priority}`"))),
+            })
+            .transpose()?;
+        let due = raw
+            .due
+            .map(|due| match due.as_str() {
+                "" => Ok(None),
+                _ => parse_datetime(&due)
+                    .map(Some)
+                    .map_err(|_| invalid(format!("task {block}: invalid due date `{due}`"))),
+            })
+            .transpose()?;
+
+        edited.push(Edited {
+            id,
+            description: raw.description,
+            status,
+            priority,
+            tags: raw.tags,
+            due,
+            delete: raw.delete,
+        });
+    }
+
+    if let Some(missing) = selected.iter().find(|task| !seen.contains(&task.id)) {
+        return Err(invalid(format!(
+            "{} ({}) is missing; set `delete = true` on it to delete it",
+            missing.id, missing.description
+        )));
+    }
+
+    Ok(edited)
+}
+
+/// Applies the edits and describes each change, one line per task.
+pub fn apply(store: &mut Store, edits: Vec<Edited>) -> Vec<String> {
+    let mut summary = Vec::new();
+    let now = Utc::now();
+
+    for edit in edits {
+        let Some(id) = edit.id else {
+            let mut task = Task::new(edit.description.unwrap_or_default());
+            task.status = edit.status.unwrap_or(Status::ToDo);
+            task.priority = edit.priority.flatten();
+            task.tags = edit.tags.unwrap_or_default();
+            task.due = edit.due.flatten();
+
+            summary.push(format!("+ {}. {}", task.id, task.description));
+            store.tasks.push(task);
+            continue;
+        };
+
+        if edit.delete {
+            if let Some(task) = store.trash(id) {
+                summary.push(format!("- {}. {}", task.id, task.description));
+            }
+            continue;
+        }
+
+        let task = store.find_mut(id).expect("edited ids exist");
+        let mut changes = Vec::new();
+
+        if let Some(description) = edit.description.filter(|d| *d != task.description) {
+            changes.push(format!(
+                "description {:?} -> {description:?}",
+                task.description
+            ));
+            task.set_description(description);
+        }
+        if let Some(status) = edit.status.filter(|s| *s != task.status) {
+            changes.push(format!("status {} -> {status}", task.status));
+            task.set_status(status);
+        }
+        if let Some(priority) = edit.priority.filter(|p| *p != task.priority) {
+            let show = |p: Option<Priority>| p.map_or("none".to_owned(), |p| p.to_string());
+            changes.push(format!(
+                "priority {} -> {}",
+                show(task.priority),
+                show(priority)
+            ));
+            task.set_priority(priority);
+        }
+        if let Some(tags) = edit.tags.filter(|t| *t != task.tags) {
+            changes.push(format!(
+                "tags [{}] -> [{}]",
+                task.tags.join(", "),
+                tags.join(", ")
+            ));
+            task.inherited
+                .tags
+                .retain(|inherited| tags.iter().any(|tag| tag.eq_ignore_ascii_case(inherited)));
+            task.tags = tags;
+        }
+        // Due dates are shown to the minute, so only compare at that precision.
+        let minute = |due: Option<chrono::DateTime<Utc>>| due.map(|due| format_local(&due));
+        if let Some(due) = edit.due.filter(|d| minute(*d) != minute(task.due)) {
+            let show = |due: Option<String>| due.unwrap_or_else(|| "none".to_owned());
+            changes.push(format!(
+                "due {} -> {}",
+                show(minute(task.due)),
+                show(minute(due))
+            ));
+            task.due = due;
+        }
+
+        if !changes.is_empty() {
+            task.updated_at = now;
+            summary.push(format!("~ {}. {}", task.id, changes.join(", ")));
+        }
+    }
+
+    summary
+}
+
+/// Opens `$VISUAL`/`$EDITOR` (falling back to `vi`) on `path`.
+pub fn open_editor(path: &Path) -> Result<(), CliError> {
+    open_editor_at(path, None)
+}
+
+/// Like [`open_editor`], with the cursor on `line` for the editors that
+/// support it.
+pub fn open_editor_at(path: &Path, line: Option<u32>) -> Result<(), CliError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let mut command = Command::new(program);
+    command.args(words);
+    let name = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
+    match (name, line) {
+        (_, None) => command.arg(path),
+        ("code" | "code-insiders" | "codium", Some(line)) => command
+            .arg("--goto")
+            .arg(format!("{}:{line}", path.display())),
+        ("subl" | "zed" | "hx" | "helix", Some(line)) => {
+            command.arg(format!("{}:{line}", path.display()))
+        }
+        // vi, vim, nvim, nano, emacs, micro and most others.
+        (_, Some(line)) => command.arg(format!("+{line}")).arg(path),
+    };
+
+    let status = command
+        .status()
+        .map_err(|err| CliError::Editor(format!("could not start editor `{editor}`: {err}")))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CliError::Editor(format!(
+            "editor `{editor}` exited with {status}"
+        )))
+    }
+}
+
+/// A file for the editor, alone in a new directory only this user can
+/// enter, so that other users can neither read it nor plant a link in its
+/// place. The directory goes when this is dropped, unless it is kept.
+pub struct Scratch {
+    dir: PathBuf,
+    pub path: PathBuf,
+    keep: bool,
+}
+
+impl Scratch {
+    pub fn create(name: &str, contents: &str) -> io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("task-cli-{}", Ulid::new()));
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&dir)?;
+
+        let scratch = Self {
+            path: dir.join(name),
+            dir,
+            keep: false,
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&scratch.path)?
+            .write_all(contents.as_bytes())?;
+        Ok(scratch)
+    }
+
+    /// Leaves the file behind, for changes that could not be applied.
+    pub fn keep(&mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/// Scores how well `query` matches `text`, or `None` when the query's
+/// characters do not all appear in order. Substring matches, consecutive
+/// runs and matches at word starts score higher; longer texts score lower.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let query = query.trim().to_lowercase();
+    let text = text.to_lowercase();
+
+    if query.is_empty() {
+        return None;
+    }
+
+    let length_penalty = text.chars().count() as i64;
+    if let Some(position) = text.find(&query) {
+        let at_word_start = position == 0 || text[..position].ends_with(char::is_whitespace);
+        let bonus = if at_word_start { 50 } else { 0 };
+        return Some(1_000 + bonus - length_penalty);
+    }
+
+    let mut score = 0;
+    let mut run = 0;
+    let mut previous = ' ';
+    let mut wanted = query.chars().filter(|c| !c.is_whitespace()).peekable();
+
+    for c in text.chars() {
+        match wanted.peek() {
+            Some(&w) if w == c => {
+                wanted.next();
+                run += 1;
+                score += 10 * run;
+                if previous.is_whitespace() {
+                    score += 20;
+                }
+            }
+            Some(_) => run = 0,
+            None => break,
+        }
+        previous = c;
+    }
+
+    wanted.peek().is_none().then_some(score - length_penalty)
+}
+
+/// The candidate closest to a mistyped `word`, if any is within two edits.
+pub fn closest<'c>(word: &str, candidates: &[&'c str]) -> Option<&'c str> {
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
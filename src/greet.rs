@@ -0,0 +1,191 @@
+//! `greet`: one line for shell rc files about what needs attention, such
+//! as `task-cli: 2 overdue, 1 due today, 14 in the inbox`, and nothing at
+//! all when nothing does.
+//!
+//! Opening a shell must not wait on it, so it reads a small summary cached
+//! next to the store (`.tasks.greet` for `.tasks.json`) rather than the
+//! store itself. The summary is rebuilt when the store has changed since,
+//! and saves keep it up to date once it exists. A greeting that would take
+//! longer than [`BUDGET`] is left out; the next shell has the summary.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    args::Args,
+    capture::INBOX_TAG,
+    config::Config,
+    pause,
+    remote::Remote,
+    store,
+    task::{Status, Task},
+    CliError,
+};
+
+pub const BUDGET: Duration = Duration::from_millis(20);
+
+/// What the greeting needs to know about the open tasks.
+#[derive(Serialize, Deserialize)]
+pub struct Summary {
+    /// Modification time and length of the store it was read from.
+    stamp: (SystemTime, u64),
+    due: Vec<DateTime<Utc>>,
+    inbox: usize,
+}
+
+/// The fields of a task the summary is made from; the rest of the store is
+/// not read.
+#[derive(Deserialize)]
+struct Open {
+    status: Status,
+    #[serde(default)]
+    due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoreFile {
+    Current { tasks: Vec<Open> },
+    Legacy(Vec<Open>),
+}
+
+pub fn summary_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("greet")
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl Summary {
+    fn new(
+        stamp: (SystemTime, u64),
+        open: impl Iterator<Item = (Option<DateTime<Utc>>, bool)>,
+    ) -> Self {
+        let mut summary = Self {
+            stamp,
+            due: Vec::new(),
+            inbox: 0,
+        };
+        for (due, inbox) in open {
+            summary.due.extend(due);
+            summary.inbox += usize::from(inbox);
+        }
+        summary
+    }
+
+    /// The summary of the store at `store_path`, from the cache when it is
+    /// up to date.
+    fn load(store_path: &Path) -> Option<Self> {
+        let stamp = stamp(store_path)?;
+        let cache = summary_path(store_path);
+        if let Some(summary) = std::fs::read(&cache)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok())
+            .filter(|summary| summary.stamp == stamp)
+        {
+            return Some(summary);
+        }
+
+        let bytes = std::fs::read(store_path).ok()?;
+        let tasks = match serde_json::from_slice(&bytes).ok()? {
+            StoreFile::Current { tasks } | StoreFile::Legacy(tasks) => tasks,
+        };
+        let open = tasks
+            .into_iter()
+            .filter(|task| task.status != Status::Done)
+            .map(|task| {
+                let inbox = task.tags.iter().any(|t| t.eq_ignore_ascii_case(INBOX_TAG));
+                (task.due, inbox)
+            });
+        let summary = Self::new(stamp, open);
+        summary.write(&cache);
+        Some(summary)
+    }
+
+    /// Best effort: without a cache the next greeting reads the store.
+    fn write(&self, path: &Path) {
+        let _ = std::fs::write(
+            path,
+            serde_json::to_vec(self).expect("could not convert to json"),
+        );
+    }
+
+    /// Brings the cached summary of the store at `store_path` up to date
+    /// after a save, when there is one.
+    pub fn refresh(store_path: &Path, tasks: &[Task]) {
+        let cache = summary_path(store_path);
+        let Some(stamp) = stamp(store_path).filter(|_| cache.exists()) else {
+            return;
+        };
+        let open = tasks
+            .iter()
+            .filter(|task| task.status != Status::Done)
+            .map(|task| (task.due, task.has_tag(INBOX_TAG)));
+        Self::new(stamp, open).write(&cache);
+    }
+
+    /// What needs attention at `now`, or `None` when nothing does.
+    fn greeting(&self, inbox_limit: Option<usize>, now: DateTime<Utc>) -> Option<String> {
+        let today = now.with_timezone(&Local).date_naive();
+        let overdue = self.due.iter().filter(|due| **due < now).count();
+        let due_today = self
+            .due
+            .iter()
+            .filter(|due| **due >= now && due.with_timezone(&Local).date_naive() == today)
+            .count();
+
+        let mut parts = Vec::new();
+        if overdue > 0 {
+            parts.push(format!("{overdue} overdue"));
+        }
+        if due_today > 0 {
+            parts.push(format!("{due_today} due today"));
+        }
+        if inbox_limit.is_some_and(|limit| self.inbox > limit) {
+            parts.push(format!("{} in the inbox", self.inbox));
+        }
+
+        (!parts.is_empty()).then(|| format!("task-cli: {}", parts.join(", ")))
+    }
+}
+
+/// Prints the greeting, if any. With `--quiet` nothing is printed and the
+/// exit status tells instead, as with `grep -q`: 0 when something needs
+/// attention, 1 when nothing does.
+pub fn run(args: &Args) -> Result<(), CliError> {
+    let start = Instant::now();
+
+    // Remote stores are greeted from their local copy, never fetched.
+    let location = store::tasks_path(args);
+    let path = match Remote::parse(&location) {
+        Some(remote) => remote.cache_path()?,
+        None => location,
+    };
+    let inbox_limit = match args.value("inbox") {
+        Some(limit) => Some(limit.parse().expect("invalid --inbox limit")),
+        None => Config::load().quotas.inbox,
+    };
+
+    // Nothing needs attention during a pause.
+    let now = Utc::now();
+    let greeting = Summary::load(&path)
+        .filter(|_| pause::active(now).is_none())
+        .and_then(|summary| summary.greeting(inbox_limit, now))
+        .filter(|_| start.elapsed() <= BUDGET);
+
+    match greeting {
+        Some(_) if args.flag("quiet") => {}
+        Some(greeting) => println!("{greeting}"),
+        None if args.flag("quiet") => std::process::exit(1),
+        None => {}
+    }
+    Ok(())
+}
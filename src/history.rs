@@ -0,0 +1,458 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ulid::Ulid;
+
+use crate::{schema, task::Task, CliError};
+
+/// The event log sits next to the store: `tasks.json` logs to
+/// `tasks.events.jsonl`.
+pub fn history_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("events.jsonl")
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    Created,
+    Updated,
+    /// Moved to the trash.
+    Deleted,
+    /// Moved back out of the trash.
+    Restored,
+    /// Removed for good.
+    Purged,
+}
+
+/// A field's value before and after a change; `null` when it was unset.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Change {
+    pub from: Value,
+    pub to: Value,
+}
+
+/// One change to one task, as appended to the event log on every save.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Event {
+    /// Schema version the event was written with.
+    pub schema: u32,
+    pub at: DateTime<Utc>,
+    pub task: Ulid,
+    pub kind: EventKind,
+    /// Who made the change: `TASK_CLI_USER`, or else the login name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by: Option<String>,
+    /// Fields that changed, by their name in the task JSON. Created tasks
+    /// list every field they were created with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub changes: BTreeMap<String, Change>,
+}
+
+fn fields(task: &Task) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(task).expect("could not convert to json") {
+        Value::Object(fields) => fields,
+        _ => unreachable!("tasks serialize to objects"),
+    }
+}
+
+fn changes(before: Option<&Task>, after: Option<&Task>) -> BTreeMap<String, Change> {
+    let before = before.map(fields).unwrap_or_default();
+    let after = after.map(fields).unwrap_or_default();
+
+    // Rollups are derived from other tasks, whose own events record why
+    // they changed, and every change bumps the revision.
+    before
+        .keys()
+        .chain(after.keys())
+        .filter(|name| !matches!(name.as_str(), "rollup" | "revision"))
+        .filter_map(|name| {
+            let from = before.get(name).cloned().unwrap_or(Value::Null);
+            let to = after.get(name).cloned().unwrap_or(Value::Null);
+            (from != to).then(|| (name.clone(), Change { from, to }))
+        })
+        .collect()
+}
+
+/// Where a task is kept: `false` for the list, `true` for the trash.
+type Placed<'t> = HashMap<Ulid, (&'t Task, bool)>;
+
+fn placed<'t>(tasks: &'t [Task], trash: &'t [Task]) -> Placed<'t> {
+    tasks
+        .iter()
+        .map(|task| (task.id, (task, false)))
+        .chain(trash.iter().map(|task| (task.id, (task, true))))
+        .collect()
+}
+
+/// Who is running the command: `TASK_CLI_USER`, or else the login name.
+pub(crate) fn author() -> Option<String> {
+    ["TASK_CLI_USER", "USER", "USERNAME"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|name| !name.is_empty())
+}
+
+/// The events that turn the `before` tasks and trash into the `after` ones.
+/// Changes are logged with the full texts that `inline` reads back from the
+/// blobs, not their previews, so that every edit is logged and the log can
+/// bring back what was there.
+pub fn diff(
+    before: (&[Task], &[Task]),
+    after: (&[Task], &[Task]),
+    at: DateTime<Utc>,
+    inline: impl Fn(&Task) -> Task,
+) -> Vec<Event> {
+    let old = placed(before.0, before.1);
+    let new = placed(after.0, after.1);
+    let by = author();
+    let event = |task, kind, changes| Event {
+        schema: schema::VERSION,
+        at,
+        task,
+        kind,
+        by: by.clone(),
+        changes,
+    };
+
+    let mut events = Vec::new();
+    for task in after.0.iter().chain(after.1) {
+        let (_, trashed) = new[&task.id];
+        let kind = match old.get(&task.id) {
+            None => EventKind::Created,
+            Some((_, false)) if trashed => EventKind::Deleted,
+            Some((_, true)) if !trashed => EventKind::Restored,
+            Some(_) => EventKind::Updated,
+        };
+        let before = old.get(&task.id).map(|(task, _)| *task);
+        // Blobs are named after their texts, so tasks that compare equal
+        // need neither reading back nor turning into JSON.
+        let changes = if before == Some(task) {
+            BTreeMap::new()
+        } else {
+            changes(before.map(&inline).as_ref(), Some(&inline(task)))
+        };
+        if kind != EventKind::Updated || !changes.is_empty() {
+            events.push(event(task.id, kind, changes));
+        }
+    }
+    for task in before.0.iter().chain(before.1) {
+        if !new.contains_key(&task.id) {
+            events.push(event(task.id, EventKind::Purged, BTreeMap::new()));
+        }
+    }
+
+    events
+}
+
+pub fn append(path: &Path, events: &[Event]) -> Result<(), CliError> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = String::new();
+    for event in events {
+        lines.push_str(&serde_json::to_string(event).expect("could not convert to json"));
+        lines.push('\n');
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| {
+            // A save killed while appending leaves a torn last line, which
+            // the new events must not run on from.
+            let mut last = [b'\n'];
+            if file.metadata()?.len() > 0 {
+                file.seek(SeekFrom::End(-1))?;
+                file.read_exact(&mut last)?;
+            }
+            if last[0] != b'\n' {
+                lines.insert(0, '\n');
+            }
+            file.write_all(lines.as_bytes())
+        })
+        .map_err(|error| CliError::Write {
+            path: path.to_owned(),
+            error,
+        })
+}
+
+/// Every event in the log, oldest first; a missing log has none.
+pub fn load(path: &Path) -> Result<Vec<Event>, CliError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(CliError::Read {
+                path: path.to_owned(),
+                error,
+            })
+        }
+    };
+
+    // Lines that cannot be read, such as the torn end of an interrupted
+    // save, are skipped rather than keeping the rest of the log from use.
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(index, line)| {
+            serde_json::from_str(line)
+                .inspect_err(|err| {
+                    eprintln!(
+                        "warning: skipped line {} of {}: {err}",
+                        index + 1,
+                        path.display()
+                    );
+                })
+                .ok()
+        })
+        .collect())
+}
+
+/// Clears what the log says about the tasks `ids`, keeping only when they
+/// were created, changed and purged, so that nothing of a task purged for
+/// good can be read back. The log is rewritten through a sibling file;
+/// lines that cannot be read are kept as they are.
+pub fn redact(path: &Path, ids: &HashSet<Ulid>) -> Result<(), CliError> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => {
+            return Err(CliError::Read {
+                path: path.to_owned(),
+                error,
+            })
+        }
+    };
+
+    let mut redacted = false;
+    let mut lines = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        match serde_json::from_str::<Event>(line) {
+            Ok(mut event) if ids.contains(&event.task) && !event.changes.is_empty() => {
+                event.changes.clear();
+                lines.push_str(&serde_json::to_string(&event).expect("could not convert to json"));
+                redacted = true;
+            }
+            _ => lines.push_str(line),
+        }
+        lines.push('\n');
+    }
+    if !redacted {
+        return Ok(());
+    }
+
+    let temp = path.with_extension(format!("jsonl.tmp-{}", std::process::id()));
+    std::fs::write(&temp, lines)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .map_err(|error| {
+            let _ = std::fs::remove_file(&temp);
+            CliError::Write {
+                path: path.to_owned(),
+                error,
+            }
+        })
+}
+
+type Fields = serde_json::Map<String, Value>;
+
+fn set_fields(fields: &mut Fields, changes: &BTreeMap<String, Change>, forward: bool) {
+    for (name, change) in changes {
+        match if forward { &change.to } else { &change.from } {
+            Value::Null => fields.remove(name),
+            value => fields.insert(name.clone(), value.clone()),
+        };
+    }
+}
+
+/// A purged task as it was just before `events[purge]` removed it, built up
+/// from its earlier events. Tasks created before the log began are lost.
+fn before_purge(events: &[Event], purge: usize) -> Option<(Fields, bool)> {
+    let id = events[purge].task;
+    let mut state = None::<(Fields, bool)>;
+
+    for event in events[..purge].iter().filter(|event| event.task == id) {
+        match event.kind {
+            EventKind::Created => {
+                let mut fields = Fields::new();
+                set_fields(&mut fields, &event.changes, true);
+                state = Some((fields, false));
+            }
+            EventKind::Purged => state = None,
+            kind => {
+                if let Some((fields, trashed)) = &mut state {
+                    set_fields(fields, &event.changes, true);
+                    *trashed = match kind {
+                        EventKind::Deleted => true,
+                        EventKind::Restored => false,
+                        _ => *trashed,
+                    };
+                }
+            }
+        }
+    }
+
+    state
+}
+
+/// The tasks and trash as they were at `at`, found by undoing the `events`
+/// made since then, newest first, starting from the current `tasks` and
+/// `trash`. Both come back in creation order.
+pub fn rewind(
+    tasks: &[Task],
+    trash: &[Task],
+    events: &[Event],
+    at: DateTime<Utc>,
+) -> (Vec<Task>, Vec<Task>) {
+    let mut state = placed(tasks, trash)
+        .into_iter()
+        .map(|(id, (task, trashed))| (id, (fields(task), trashed)))
+        .collect::<HashMap<_, _>>();
+
+    for (index, event) in events.iter().enumerate().rev() {
+        if event.at <= at {
+            break;
+        }
+
+        match event.kind {
+            EventKind::Created => {
+                state.remove(&event.task);
+            }
+            EventKind::Purged => {
+                if let Some(task) = before_purge(events, index) {
+                    state.insert(event.task, task);
+                }
+            }
+            kind => {
+                if let Some((fields, trashed)) = state.get_mut(&event.task) {
+                    set_fields(fields, &event.changes, false);
+                    *trashed = match kind {
+                        EventKind::Deleted => false,
+                        EventKind::Restored => true,
+                        _ => *trashed,
+                    };
+                }
+            }
+        }
+    }
+
+    let (mut tasks, mut trash) = (Vec::new(), Vec::new());
+    for (fields, trashed) in state.into_values() {
+        // Tasks that predate the log and only have some of their fields
+        // from later events cannot be brought back whole.
+        let Ok(task) = serde_json::from_value::<Task>(Value::Object(fields)) else {
+            continue;
+        };
+        if trashed {
+            trash.push(task);
+        } else {
+            tasks.push(task);
+        }
+    }
+    tasks.sort_by_key(|task| (task.created_at, task.id));
+    trash.sort_by_key(|task| (task.created_at, task.id));
+
+    (tasks, trash)
+}
+
+/// For each field of task `id`, the latest event that changed it and how.
+pub fn last_changes(events: &[Event], id: Ulid) -> BTreeMap<&str, (&Event, &Change)> {
+    let mut last = BTreeMap::new();
+    for event in events.iter().filter(|event| event.task == id) {
+        if event.kind == EventKind::Purged {
+            last.clear();
+        }
+        for (name, change) in &event.changes {
+            last.insert(name.as_str(), (event, change));
+        }
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_torn_last_line_is_skipped_and_not_appended_to() {
+        let dir = std::env::temp_dir().join(format!("task-cli-history-{}", Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tasks.events.jsonl");
+        let task = Task::new("file the taxes".to_owned());
+
+        let created = diff(
+            (&[], &[]),
+            (std::slice::from_ref(&task), &[]),
+            Utc::now(),
+            Task::clone,
+        );
+        append(&path, &created).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"schema":1,"at":"2026"#).unwrap();
+        assert_eq!(load(&path).unwrap().len(), 1);
+
+        let purged = diff(
+            (std::slice::from_ref(&task), &[]),
+            (&[], &[]),
+            Utc::now(),
+            Task::clone,
+        );
+        append(&path, &purged).unwrap();
+        let kinds = load(&path)
+            .unwrap()
+            .iter()
+            .map(|event| event.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [EventKind::Created, EventKind::Purged]);
+    }
+
+    #[test]
+    fn redacted_tasks_cannot_be_rewound() {
+        let dir = std::env::temp_dir().join(format!("task-cli-history-{}", Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tasks.events.jsonl");
+        let secret = Task::new("call the lawyer about the divorce".to_owned());
+        let kept = Task::new("water the plants".to_owned());
+        let created = Utc::now();
+        let purged = created + chrono::Duration::hours(1);
+
+        let tasks = [secret.clone(), kept.clone()];
+        append(
+            &path,
+            &diff((&[], &[]), (&tasks, &[]), created, Task::clone),
+        )
+        .unwrap();
+        let remaining = std::slice::from_ref(&kept);
+        append(
+            &path,
+            &diff((&tasks, &[]), (remaining, &[]), purged, Task::clone),
+        )
+        .unwrap();
+        let between = created + chrono::Duration::minutes(1);
+        let (tasks, _) = rewind(remaining, &[], &load(&path).unwrap(), between);
+        assert_eq!(tasks.len(), 2);
+        redact(&path, &HashSet::from([secret.id])).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("divorce"));
+        assert!(text.contains("water the plants"));
+        let events = load(&path).unwrap();
+        assert_eq!(events.len(), 3);
+        let (tasks, _) = rewind(remaining, &[], &events, between);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, kept.id);
+    }
+}
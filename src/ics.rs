@@ -0,0 +1,333 @@
+//! Reading iCalendar (`.ics`) files: events and to-dos with the fields
+//! needed to turn them into tasks.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use ulid::Ulid;
+
+use crate::{
+    location::Geo,
+    task::{Status, Task, TimeBlock},
+    time::local_to_utc,
+    CliError,
+};
+
+/// Suffix of the UIDs of exported items, so importing them again updates
+/// the tasks they came from.
+const UID_DOMAIN: &str = "@task-cli";
+
+/// A `VEVENT` or `VTODO`.
+#[derive(Debug, Default)]
+pub struct Item {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+    pub location: Option<String>,
+    /// `DUE` of a to-do, or `DTSTART` of an event.
+    pub due: Option<DateTime<Utc>>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+    pub completed: bool,
+}
+
+/// A property whose value could not be read, such as a malformed date.
+#[derive(Debug)]
+pub struct Invalid {
+    /// Line of the property, counting from 1.
+    pub line: usize,
+    pub property: String,
+    pub value: String,
+    pub message: String,
+}
+
+/// Physical lines with folded continuations joined back together.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+pub fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(other) => text.push(other),
+                None => {}
+            },
+            (c, false) => text.push(c),
+        }
+    }
+    text
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Parses `20240820`, `20240820T140000` (local time, as time zones other
+/// than UTC are not resolved) or `20240820T140000Z`. Dates without a time
+/// mean the end of that day, like elsewhere.
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, CliError> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map_err(|_| invalid(format!("invalid time `{value}`")))?;
+        return Ok(Utc.from_utc_datetime(&naive));
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return local_to_utc(naive);
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d")
+        .map_err(|_| invalid(format!("invalid date `{value}`")))?;
+    local_to_utc(date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap()))
+}
+
+/// `Utc` time in the basic format used by iCalendar.
+pub fn format_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn invalid(message: String) -> CliError {
+    CliError::Import(message)
+}
+
+/// Splits `NAME;PARAM=x:value` into the name and the value.
+fn property(line: &str) -> Option<(String, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let name = key.split(';').next()?.to_ascii_uppercase();
+    Some((name, value))
+}
+
+/// Folds a content line to at most 75 bytes per line, as required.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+/// Writes a calendar of `components`, each a name such as `VEVENT` with its
+/// already formatted properties.
+pub fn write(components: &[(&str, Vec<(&str, String)>)]) -> String {
+    let mut out = String::new();
+    let mut line = |text: String| {
+        out.push_str(&fold(&text));
+        out.push_str("\r\n");
+    };
+
+    line("BEGIN:VCALENDAR".to_owned());
+    line("VERSION:2.0".to_owned());
+    line("PRODID:-//task-cli//EN".to_owned());
+    for (name, properties) in components {
+        line(format!("BEGIN:{name}"));
+        for (key, value) in properties {
+            line(format!("{key}:{value}"));
+        }
+        line(format!("END:{name}"));
+    }
+    line("END:VCALENDAR".to_owned());
+
+    out
+}
+
+/// The `VEVENT`s and `VTODO`s of a calendar as their content lines, from
+/// `BEGIN` to `END`, each with the number of the line it starts on.
+pub fn components(text: &str) -> Result<Vec<(usize, Vec<String>)>, CliError> {
+    let mut components = Vec::new();
+    let mut current: Option<(usize, Vec<String>)> = None;
+
+    for (index, line) in unfold(text).into_iter().enumerate() {
+        let (begins, ends) = match property(&line) {
+            Some((name, "VEVENT" | "VTODO")) => (
+                name == "BEGIN" && current.is_none(),
+                name == "END" && current.is_some(),
+            ),
+            _ => (false, false),
+        };
+        if begins {
+            current = Some((index + 1, Vec::new()));
+        }
+        if let Some((_, lines)) = current.as_mut() {
+            lines.push(line);
+        }
+        if ends {
+            components.extend(current.take());
+        }
+    }
+
+    if current.is_some() {
+        return Err(invalid("unterminated VEVENT or VTODO".to_owned()));
+    }
+
+    Ok(components)
+}
+
+/// Reads a component found by [`components`] starting on line `start`,
+/// collecting the properties that could not be read instead of stopping at
+/// the first.
+pub fn read(start: usize, lines: &[String]) -> (Item, Vec<Invalid>) {
+    let mut item = Item::default();
+    let mut problems = Vec::new();
+
+    for (offset, line) in lines.iter().enumerate() {
+        let Some((name, value)) = property(line) else {
+            continue;
+        };
+        let time = match name.as_str() {
+            "DUE" | "DTSTART" | "DTEND" => match parse_time(value) {
+                Ok(time) => Some(time),
+                Err(err) => {
+                    problems.push(Invalid {
+                        line: start + offset,
+                        property: name,
+                        value: value.to_owned(),
+                        message: match err {
+                            CliError::Import(message) => message,
+                            err => err.to_string(),
+                        },
+                    });
+                    continue;
+                }
+            },
+            _ => None,
+        };
+
+        match (name.as_str(), time) {
+            ("UID", _) => item.uid = Some(value.to_owned()),
+            ("SUMMARY", _) => item.summary = unescape(value),
+            ("DESCRIPTION", _) => item.description = Some(unescape(value)),
+            ("LOCATION", _) => item.location = Some(unescape(value)),
+            ("CATEGORIES", _) => item.categories.extend(
+                value
+                    .split(',')
+                    .map(|category| unescape(category).trim().to_owned())
+                    .filter(|category| !category.is_empty()),
+            ),
+            ("DUE", Some(due)) => item.due = Some(due),
+            ("DTSTART", Some(start)) => {
+                item.start = Some(start);
+                item.due.get_or_insert(start);
+            }
+            ("DTEND", Some(end)) => item.end = Some(end),
+            ("STATUS", _) => {
+                item.completed = value.eq_ignore_ascii_case("COMPLETED");
+                item.status = Some(value.to_owned());
+            }
+            _ => {}
+        }
+    }
+
+    (item, problems)
+}
+
+pub fn parse(text: &str) -> Result<Vec<Item>, CliError> {
+    components(text)?
+        .into_iter()
+        .map(|(start, lines)| match read(start, &lines) {
+            (item, problems) if problems.is_empty() => Ok(item),
+            (_, problems) => Err(invalid(format!(
+                "line {}: {}",
+                problems[0].line, problems[0].message
+            ))),
+        })
+        .collect()
+}
+
+/// What an exported UID refers to.
+pub enum Exported {
+    /// The to-do for a task's due date.
+    Due(Ulid),
+    /// A time block of a task, by its start when exported.
+    Block(Ulid, i64),
+}
+
+pub fn parse_uid(uid: &str) -> Option<Exported> {
+    let local = uid.strip_suffix(UID_DOMAIN)?;
+    match local.split_once('-') {
+        Some((id, start)) => Some(Exported::Block(
+            Ulid::from_string(id).ok()?,
+            start.parse().ok()?,
+        )),
+        None => Some(Exported::Due(Ulid::from_string(local).ok()?)),
+    }
+}
+
+fn block_uid(task: &Task, block: &TimeBlock) -> String {
+    format!("{}-{}{UID_DOMAIN}", task.id, block.start.timestamp())
+}
+
+/// `LOCATION` and, for geo URIs, `GEO` of a task.
+fn location(task: &Task) -> Vec<(&'static str, String)> {
+    let Some(place) = &task.location else {
+        return Vec::new();
+    };
+    let mut properties = vec![("LOCATION", escape(place))];
+    if let Some(geo) = Geo::parse(place) {
+        properties.push(("GEO", format!("{};{}", geo.lat, geo.lon)));
+    }
+    properties
+}
+
+/// A calendar with a to-do for every task with a due date and an event for
+/// every time block.
+pub fn export(tasks: &[Task]) -> String {
+    let now = format_time(&Utc::now());
+    let mut components = Vec::new();
+
+    for task in tasks {
+        if let Some(due) = task.due {
+            let status = match task.status {
+                Status::Done => "COMPLETED",
+                Status::InProgress => "IN-PROCESS",
+                Status::ToDo => "NEEDS-ACTION",
+            };
+            let mut properties = vec![
+                ("UID", format!("{}{UID_DOMAIN}", task.id)),
+                ("DTSTAMP", now.clone()),
+                ("SUMMARY", escape(&task.description)),
+                ("DUE", format_time(&due)),
+                ("STATUS", status.to_owned()),
+            ];
+            if !task.tags.is_empty() {
+                let tags = task.tags.iter().map(|tag| escape(tag)).collect::<Vec<_>>();
+                properties.push(("CATEGORIES", tags.join(",")));
+            }
+            properties.extend(location(task));
+            components.push(("VTODO", properties));
+        }
+
+        for block in &task.blocks {
+            let mut properties = vec![
+                ("UID", block_uid(task, block)),
+                ("DTSTAMP", now.clone()),
+                ("SUMMARY", escape(&task.description)),
+                ("DTSTART", format_time(&block.start)),
+                ("DTEND", format_time(&block.end)),
+            ];
+            properties.extend(location(task));
+            components.push(("VEVENT", properties));
+        }
+    }
+
+    write(&components)
+}
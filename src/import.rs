@@ -0,0 +1,309 @@
+//! Reports of the records `import` could not take. Each rejected record is
+//! kept with the reasons and, where one can be guessed, a suggested fix:
+//!
+//! ```json
+//! {
+//!   "format": "json",
+//!   "source": "tasks.json",
+//!   "rejected": [
+//!     {
+//!       "at": "/3",
+//!       "problems": [
+//!         { "field": "status", "reason": "unknown status \"doing\"", "fix": "use \"in-progress\"" }
+//!       ],
+//!       "record": { "description": "Write the report", "status": "doing", ... }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The report is written next to the imported file. Fix the records in it,
+//! then `task-cli import --resume-report <report>` imports only those; what
+//! is still rejected stays in the report, which is removed once it is empty.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use ulid::Ulid;
+
+use crate::{fuzzy, ics, schema, time::local_to_utc, CliError};
+
+/// Longest description accepted, in characters: descriptions are one-line
+/// titles, and longer texts belong in the notes.
+pub const MAX_DESCRIPTION: usize = 500;
+
+const STATUSES: [(&str, &[&str]); 3] = [
+    ("to-do", &["todo", "open", "pending", "new", "backlog"]),
+    ("in-progress", &["doing", "started", "active", "wip"]),
+    ("done", &["complete", "completed", "finished", "closed"]),
+];
+
+const PRIORITIES: [(&str, &[&str]); 4] = [
+    ("low", &["lowest", "minor", "trivial"]),
+    ("medium", &["med", "normal", "moderate", "default"]),
+    ("high", &["important", "major"]),
+    ("urgent", &["critical", "highest", "blocker"]),
+];
+
+/// `STATUS` values of iCalendar events and to-dos.
+const ICS_STATUSES: [(&str, &[&str]); 6] = [
+    ("needs-action", &["todo", "to-do", "open", "pending"]),
+    ("in-process", &["in-progress", "doing", "started"]),
+    ("completed", &["done", "complete", "finished", "closed"]),
+    ("cancelled", &["canceled"]),
+    ("tentative", &[]),
+    ("confirmed", &[]),
+];
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Problem {
+    pub field: String,
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Rejected {
+    /// Where the record was: a JSON pointer into the file, or `line <n>`.
+    pub at: String,
+    pub problems: Vec<Problem>,
+    /// The record as read: a task object, or the content lines of a
+    /// calendar component.
+    pub record: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Report {
+    /// `json` or `ics`.
+    pub format: String,
+    pub source: PathBuf,
+    pub created_at: DateTime<Utc>,
+    /// `--filter` and `--tag` of the import, applied again on resuming.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub rejected: Vec<Rejected>,
+}
+
+/// What an import did.
+#[derive(Default)]
+pub struct Outcome {
+    pub added: usize,
+    pub updated: usize,
+    /// Copies of tasks that changed here since they were exported.
+    pub stale: usize,
+    pub rejected: Vec<Rejected>,
+}
+
+impl Report {
+    /// Where the report on importing `source` goes.
+    pub fn path(source: &Path) -> PathBuf {
+        let mut name = source.as_os_str().to_owned();
+        name.push(".report.json");
+        PathBuf::from(name)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let text = std::fs::read_to_string(path).map_err(|error| CliError::Read {
+            path: path.to_owned(),
+            error,
+        })?;
+        serde_json::from_str(&text).map_err(|err| {
+            CliError::Import(format!("{} is not an import report: {err}", path.display()))
+        })
+    }
+
+    /// Writes the report to `path`, or removes it when nothing was rejected.
+    pub fn save(&self, path: &Path) -> Result<(), CliError> {
+        let result = if self.rejected.is_empty() {
+            match std::fs::remove_file(path) {
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            }
+        } else {
+            let text = serde_json::to_string_pretty(self).expect("could not convert to json");
+            std::fs::write(path, text + "\n")
+        };
+        result.map_err(|error| CliError::Write {
+            path: path.to_owned(),
+            error,
+        })
+    }
+}
+
+/// The canonical name `value` most likely means: one of `names` or their
+/// synonyms, ignoring case, spaces and underscores, or else the name within
+/// two edits of it.
+fn closest_name<'n>(value: &str, names: &[(&'n str, &[&str])]) -> Option<&'n str> {
+    let value = value.trim().to_lowercase().replace([' ', '_'], "-");
+    names
+        .iter()
+        .find(|(name, synonyms)| *name == value || synonyms.contains(&value.as_str()))
+        .map(|(name, _)| *name)
+        .or_else(|| {
+            let candidates = names.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            fuzzy::closest(&value, &candidates)
+        })
+}
+
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_opt(23, 59, 59).expect("valid time")
+}
+
+/// Reads a date written in a format other than the expected one, such as
+/// `2024-08-20`, `2024/08/20 14:00` or iCalendar's `20240820T140000`. Dates
+/// without a time mean the end of that day, in local time.
+fn guess_time(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    if let Ok(time) = ics::parse_time(text) {
+        return Some(time);
+    }
+
+    let times = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y/%m/%d %H:%M",
+    ];
+    let dates = ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y"];
+    let naive = times
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            dates
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+                .map(|date| date.and_time(end_of_day()))
+        })?;
+    local_to_utc(naive).ok()
+}
+
+/// The problem with `value` at `pointer` in a task, from a schema error.
+fn task_problem(task: &Value, pointer: &str, reason: &str) -> Problem {
+    let value = task.pointer(pointer).unwrap_or(&Value::Null);
+    let field = match pointer.trim_start_matches('/') {
+        "" => "task".to_owned(),
+        field => field.to_owned(),
+    };
+    let name = field.split('/').next().unwrap_or_default();
+
+    if let Some(missing) = reason
+        .strip_prefix("missing required field `")
+        .and_then(|rest| rest.strip_suffix('`'))
+    {
+        let fix = match missing {
+            "id" => Some(Value::from(Ulid::new().to_string())),
+            "status" => Some(Value::from("to-do")),
+            "created_at" | "updated_at" => Some(Value::from(
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            )),
+            _ => None,
+        };
+        return Problem {
+            field: missing.to_owned(),
+            reason: reason.to_owned(),
+            fix: fix.map(|fix| format!("add \"{missing}\": {fix}")),
+        };
+    }
+
+    let (reason, fix) = if reason == "expected a date-time" {
+        let fix = value.as_str().and_then(guess_time).map(|time| {
+            format!(
+                "use \"{}\"",
+                time.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )
+        });
+        (format!("{value} is not an RFC 3339 date and time"), fix)
+    } else if reason.starts_with("expected one of") {
+        let names = match name {
+            "status" => &STATUSES[..],
+            "priority" => &PRIORITIES[..],
+            _ => &[],
+        };
+        let fix = value
+            .as_str()
+            .and_then(|value| closest_name(value, names))
+            .map(|name| format!("use \"{name}\""));
+        (format!("unknown {name} {value}; {reason}"), fix)
+    } else {
+        (reason.to_owned(), None)
+    };
+
+    Problem { field, reason, fix }
+}
+
+fn too_long(field: &str, text: &str, rest: &str) -> Option<Problem> {
+    let length = text.chars().count();
+    (length > MAX_DESCRIPTION).then(|| Problem {
+        field: field.to_owned(),
+        reason: format!("is {length} characters long, more than {MAX_DESCRIPTION}"),
+        fix: Some(format!(
+            "keep at most {MAX_DESCRIPTION} characters and move the rest into {rest}"
+        )),
+    })
+}
+
+/// The problems of a task read from a JSON import, checked against
+/// `task_schema`.
+pub fn check_task(task_schema: &Value, task: &Value) -> Vec<Problem> {
+    let mut problems = schema::validate(task_schema, task)
+        .iter()
+        .map(|error| {
+            let (pointer, reason) = error.split_once(": ").unwrap_or(("/", error));
+            task_problem(task, pointer.trim_end_matches('/'), reason)
+        })
+        .collect::<Vec<_>>();
+    if let Some(description) = task["description"].as_str() {
+        problems.extend(too_long("description", description, "\"notes\""));
+    }
+    problems
+}
+
+/// The problems of a calendar item: the properties [`ics::read`] could not
+/// read, an unknown `STATUS` and an over-long `SUMMARY`.
+pub fn check_item(item: &ics::Item, invalid: &[ics::Invalid]) -> Vec<Problem> {
+    let mut problems = invalid
+        .iter()
+        .map(|invalid| Problem {
+            field: invalid.property.clone(),
+            reason: invalid.message.clone(),
+            fix: guess_time(&invalid.value).map(|time| {
+                // The end of a day is what iCalendar's plain dates mean.
+                let local = time.with_timezone(&Local);
+                let value = if local.time() == end_of_day() {
+                    local.format("%Y%m%d").to_string()
+                } else {
+                    ics::format_time(&time)
+                };
+                format!("use {}:{value}", invalid.property)
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(status) = &item.status {
+        let known = ICS_STATUSES
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(status));
+        if !known {
+            let names = ICS_STATUSES.map(|(name, _)| name.to_uppercase());
+            problems.push(Problem {
+                field: "STATUS".to_owned(),
+                reason: format!(
+                    "unknown status `{status}`; expected one of {}",
+                    names.join(", ")
+                ),
+                fix: closest_name(status, &ICS_STATUSES)
+                    .map(|name| format!("use STATUS:{}", name.to_uppercase())),
+            });
+        }
+    }
+    problems.extend(too_long("SUMMARY", &item.summary, "DESCRIPTION"));
+    problems
+}
@@ -0,0 +1,333 @@
+pub mod aging;
+pub mod args;
+pub mod blob;
+pub mod capture;
+pub mod color;
+pub mod commands;
+pub mod compat;
+pub mod config;
+pub mod dashboard;
+pub mod demo;
+pub mod edit;
+pub mod fuzzy;
+pub mod greet;
+pub mod history;
+pub mod ics;
+pub mod import;
+pub mod line_editor;
+pub mod lint;
+pub mod locale;
+pub mod location;
+pub mod lock;
+pub mod meeting;
+pub mod merge;
+#[cfg(feature = "object-storage")]
+pub mod object;
+pub mod pause;
+pub mod phrases;
+pub mod picker;
+pub mod query;
+pub mod quick_add;
+pub mod quota;
+pub mod rebase;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod release;
+pub mod remind;
+pub mod remote;
+pub mod repl;
+pub mod retention;
+pub mod rules;
+pub mod runbook;
+pub mod scan;
+pub mod schema;
+pub mod server;
+pub mod shell;
+pub mod signal;
+pub mod stats;
+pub mod store;
+pub mod sync;
+pub mod task;
+pub mod template;
+pub mod time;
+pub mod toml;
+pub mod tour;
+pub mod usage;
+pub mod vocabulary;
+
+use std::{fmt::Display, path::PathBuf};
+
+#[derive(Debug)]
+pub enum CliError {
+    InvalidCommand,
+    /// An argument is missing or cannot be used; `argument` is the value
+    /// given, or the name of the missing one.
+    InvalidArgs {
+        argument: String,
+        message: String,
+    },
+    Write {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Read {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Locked {
+        path: PathBuf,
+        pid: i32,
+    },
+    Query(query::QueryError),
+    Template(String),
+    InvalidEdit(String),
+    Editor(String),
+    Hook(String),
+    Import(String),
+    NoMatch(String),
+    InvalidPhrase(String),
+    UnknownProfile(String),
+    Rule(String),
+    Remote(String),
+    Runbook(String),
+    Meeting(String),
+    Prompt(String),
+    /// Checking for, fetching or installing a release failed.
+    Release(String),
+    /// Tasks changed by someone else while they were being edited.
+    Conflict(String),
+    /// A task on hold cannot be purged; holds the description.
+    Held(String),
+    /// `gate` found open tasks matching its filter.
+    Gated(usize),
+    /// `lint` found problems, of which `fixable` can be fixed with `--fix`.
+    Linted {
+        problems: usize,
+        fixable: usize,
+    },
+    /// A replaced form of a command, refused by `old-syntax = "deny"`.
+    Deprecated {
+        old: String,
+        new: String,
+    },
+    Unconfirmed {
+        query: String,
+        candidate: String,
+    },
+    Ambiguous {
+        query: String,
+        candidates: Vec<String>,
+    },
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCommand => write!(f, "invalid command"),
+            Self::InvalidArgs { message, .. } => write!(f, "{message}"),
+            Self::Write { path, error } => write!(
+                f,
+                "cannot save tasks to {}: {error}\n\
+                 hint: pass --file <path> or set TASK_CLI_FILE to use a writable location",
+                path.display()
+            ),
+            Self::Read { path, error } => write!(f, "cannot read {}: {error}", path.display()),
+            Self::Locked { path, pid } => write!(
+                f,
+                "the store is locked by process {pid} ({})",
+                path.display()
+            ),
+            Self::Query(err) => write!(f, "{err}"),
+            Self::Template(message) => write!(f, "{message}"),
+            Self::InvalidEdit(message) => write!(f, "invalid edit: {message}"),
+            Self::Editor(message) => write!(f, "{message}"),
+            Self::Hook(message) => write!(f, "cannot install hook: {message}"),
+            Self::Import(message) => write!(f, "cannot import: {message}"),
+            Self::NoMatch(query) => write!(f, "no task matches `{query}`"),
+            Self::InvalidPhrase(phrase) => write!(f, "cannot understand time phrase: {phrase}"),
+            Self::UnknownProfile(name) => write!(f, "no rendering profile named `{name}`"),
+            Self::Rule(message) => write!(f, "{message}"),
+            Self::Remote(message) => write!(f, "remote store: {message}"),
+            Self::Runbook(message) => write!(f, "{message}"),
+            Self::Meeting(message) => write!(f, "{message}"),
+            Self::Prompt(message) => write!(f, "cannot prompt: {message}"),
+            Self::Release(message) => write!(f, "{message}"),
+            Self::Conflict(message) => write!(f, "{message}"),
+            Self::Held(description) => write!(f, "`{description}` is on hold"),
+            Self::Deprecated { old, new } => {
+                write!(f, "`{old}` is no longer accepted; use `{new}`")
+            }
+            Self::Gated(count) => write!(
+                f,
+                "{count} blocking task{} still open",
+                if *count == 1 { " is" } else { "s are" }
+            ),
+            Self::Linted { problems, fixable } => {
+                write!(
+                    f,
+                    "{problems} lint problem{} found",
+                    if *problems == 1 { "" } else { "s" }
+                )?;
+                if *fixable > 0 {
+                    write!(f, "\nhint: `task-cli lint --fix` fixes {fixable} of them")?;
+                }
+                Ok(())
+            }
+            Self::Unconfirmed { query, candidate } => write!(
+                f,
+                "`{query}` is not an id; its closest match is\n    {candidate}\n\
+                 pass --yes to use it or give the id instead"
+            ),
+            Self::Ambiguous { query, candidates } => {
+                write!(f, "`{query}` matches several tasks:")?;
+                for candidate in candidates {
+                    write!(f, "\n    {candidate}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CliError {
+    /// [`Self::InvalidArgs`] about `argument`.
+    pub fn invalid_args(argument: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::InvalidArgs {
+            argument: argument.into(),
+            message: message.into(),
+        }
+    }
+
+    /// [`Self::InvalidArgs`] for an argument that was not given.
+    pub fn missing(argument: &str) -> Self {
+        Self::invalid_args(argument, format!("missing {argument}"))
+    }
+
+    /// Stable name of the kind of error, for `--format json`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidCommand => "invalid-command",
+            Self::InvalidArgs { .. } => "invalid-args",
+            Self::Write { .. } => "write-failed",
+            Self::Read { .. } => "read-failed",
+            Self::Locked { .. } => "locked",
+            Self::Query(_) => "invalid-filter",
+            Self::Template(_) => "template",
+            Self::InvalidEdit(_) => "invalid-edit",
+            Self::Editor(_) => "editor",
+            Self::Hook(_) => "hook",
+            Self::Import(_) => "import",
+            Self::NoMatch(_) => "no-match",
+            Self::InvalidPhrase(_) => "invalid-phrase",
+            Self::UnknownProfile(_) => "unknown-profile",
+            Self::Rule(_) => "rule",
+            Self::Remote(_) => "remote",
+            Self::Runbook(_) => "runbook",
+            Self::Meeting(_) => "meeting",
+            Self::Prompt(_) => "prompt",
+            Self::Release(_) => "release",
+            Self::Conflict(_) => "conflict",
+            Self::Held(_) => "held",
+            Self::Gated(_) => "gated",
+            Self::Linted { .. } => "linted",
+            Self::Deprecated { .. } => "deprecated",
+            Self::Unconfirmed { .. } => "unconfirmed",
+            Self::Ambiguous { .. } => "ambiguous",
+        }
+    }
+
+    /// What went wrong, without the advice [`Self::suggestion`] gives.
+    pub fn message(&self) -> String {
+        match self {
+            Self::Write { path, error } => {
+                format!("cannot save tasks to {}: {error}", path.display())
+            }
+            Self::Unconfirmed { query, candidate } => {
+                format!("`{query}` is not an id; its closest match is {candidate}")
+            }
+            Self::Ambiguous { query, .. } => format!("`{query}` matches several tasks"),
+            Self::Linted { .. } => self
+                .to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// The argument, path or phrase the error is about, when there is one.
+    pub fn argument(&self) -> Option<String> {
+        match self {
+            Self::Write { path, .. } | Self::Read { path, .. } | Self::Locked { path, .. } => {
+                Some(path.display().to_string())
+            }
+            Self::NoMatch(query)
+            | Self::InvalidPhrase(query)
+            | Self::UnknownProfile(query)
+            | Self::InvalidArgs {
+                argument: query, ..
+            }
+            | Self::Unconfirmed { query, .. }
+            | Self::Ambiguous { query, .. } => Some(query.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<String> {
+        match self {
+            Self::Write { .. } => Some(
+                "pass --file <path> or set TASK_CLI_FILE to use a writable location".to_owned(),
+            ),
+            Self::Locked { pid, .. } => Some(format!("try again once process {pid} is done")),
+            Self::InvalidArgs { .. } => Some("`task-cli help` describes the arguments".to_owned()),
+            Self::NoMatch(_) => Some("`task-cli list` shows the tasks and their ids".to_owned()),
+            Self::UnknownProfile(_) => {
+                Some("use `default`, `narrow` or a profile from the config file".to_owned())
+            }
+            Self::Unconfirmed { candidate, .. } => Some(format!(
+                "pass --yes to use {candidate} or give the id instead"
+            )),
+            Self::Ambiguous { candidates, .. } => {
+                Some(format!("give the id of one of: {}", candidates.join("; ")))
+            }
+            Self::Held(_) => {
+                Some("release the hold with `task-cli hold <id> none` first".to_owned())
+            }
+            Self::Linted { fixable, .. } if *fixable > 0 => {
+                Some(format!("`task-cli lint --fix` fixes {fixable} of them"))
+            }
+            Self::Deprecated { new, .. } => Some(format!(
+                "use `{new}`, or set `old-syntax = \"warn\"` in the config file"
+            )),
+            _ => None,
+        }
+    }
+
+    /// The error as written to stderr with `--format json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        error_json(
+            self.code(),
+            &self.message(),
+            self.argument(),
+            self.suggestion(),
+        )
+    }
+}
+
+/// `{"error": {...}}` with the fields every JSON error has, unset ones null.
+pub fn error_json(
+    code: &str,
+    message: &str,
+    argument: Option<String>,
+    suggestion: Option<String>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "error": {
+            "code": code,
+            "message": message,
+            "argument": argument,
+            "suggestion": suggestion,
+        }
+    })
+}
@@ -0,0 +1,256 @@
+//! Minimal line editor for the interactive prompt: cursor movement, history
+//! and tab completion on a raw-mode terminal, plain `read_line` otherwise.
+
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+
+use crate::signal;
+
+pub enum ReadResult {
+    Line(String),
+    /// Ctrl-C on the current line.
+    Interrupted,
+    /// Ctrl-D on an empty line or end of input.
+    Eof,
+}
+
+/// Puts the terminal into raw mode for as long as the guard lives.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let fd = libc::STDIN_FILENO;
+        let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = original;
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+
+        signal::register_terminal(Some(&original));
+        if unsafe { libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) } != 0 {
+            signal::register_terminal(None);
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original) };
+        signal::register_terminal(None);
+    }
+}
+
+pub struct LineEditor {
+    history: Vec<String>,
+}
+
+impl LineEditor {
+    pub fn new(history: Vec<String>) -> Self {
+        Self { history }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// `complete` receives the text before the cursor and returns candidates
+    /// for the word being typed.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: impl Fn(&str) -> Vec<String>,
+    ) -> io::Result<ReadResult> {
+        let result = if io::stdin().is_terminal() {
+            self.read_raw(prompt, complete)?
+        } else {
+            print!("{prompt}");
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                ReadResult::Eof
+            } else {
+                ReadResult::Line(line.trim_end_matches(['\r', '\n']).to_owned())
+            }
+        };
+
+        if let ReadResult::Line(line) = &result {
+            if !line.trim().is_empty() && self.history.last() != Some(line) {
+                self.history.push(line.clone());
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn read_raw(
+        &mut self,
+        prompt: &str,
+        complete: impl Fn(&str) -> Vec<String>,
+    ) -> io::Result<ReadResult> {
+        let _raw = RawMode::enable()?;
+        let mut stdin = io::stdin().lock();
+        let mut stdout = io::stdout().lock();
+
+        let mut line = Vec::<char>::new();
+        let mut cursor = 0;
+        let mut history_index = self.history.len();
+        let mut last_was_tab = false;
+
+        let redraw = |out: &mut io::StdoutLock, line: &[char], cursor: usize| -> io::Result<()> {
+            let text = line.iter().collect::<String>();
+            write!(out, "\r\x1b[K{prompt}{text}")?;
+            if cursor < line.len() {
+                write!(out, "\x1b[{}D", line.len() - cursor)?;
+            }
+            out.flush()
+        };
+
+        redraw(&mut stdout, &line, cursor)?;
+
+        loop {
+            let mut byte = [0u8; 1];
+            if stdin.read(&mut byte)? == 0 {
+                return Ok(ReadResult::Eof);
+            }
+
+            let is_tab = byte[0] == b'\t';
+
+            match byte[0] {
+                b'\r' | b'\n' => {
+                    write!(stdout, "\r\n")?;
+                    stdout.flush()?;
+                    return Ok(ReadResult::Line(line.into_iter().collect()));
+                }
+                0x03 => {
+                    write!(stdout, "^C\r\n")?;
+                    stdout.flush()?;
+                    return Ok(ReadResult::Interrupted);
+                }
+                0x04 if line.is_empty() => {
+                    write!(stdout, "\r\n")?;
+                    stdout.flush()?;
+                    return Ok(ReadResult::Eof);
+                }
+                0x04 if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                0x7f | 0x08 if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                0x01 => cursor = 0,
+                0x05 => cursor = line.len(),
+                0x15 => {
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                b'\t' => {
+                    let before = line[..cursor].iter().collect::<String>();
+                    let candidates = complete(&before);
+                    let word_len = before
+                        .rsplit(char::is_whitespace)
+                        .next()
+                        .map_or(0, |word| word.chars().count());
+
+                    if let Some(prefix) = common_prefix(&candidates) {
+                        let mut insert = prefix.chars().skip(word_len).collect::<Vec<_>>();
+                        if candidates.len() == 1 {
+                            insert.push(' ');
+                        }
+                        let inserted = insert.len();
+                        line.splice(cursor..cursor, insert);
+                        cursor += inserted;
+
+                        if inserted == 0 && last_was_tab && candidates.len() > 1 {
+                            write!(stdout, "\r\n{}\r\n", candidates.join("  "))?;
+                        }
+                    }
+                }
+                0x1b => {
+                    let mut seq = [0u8; 2];
+                    stdin.read_exact(&mut seq)?;
+
+                    match &seq {
+                        b"[D" if cursor > 0 => cursor -= 1,
+                        b"[C" if cursor < line.len() => cursor += 1,
+                        b"[H" => cursor = 0,
+                        b"[F" => cursor = line.len(),
+                        b"[A" if history_index > 0 => {
+                            history_index -= 1;
+                            line = self.history[history_index].chars().collect();
+                            cursor = line.len();
+                        }
+                        b"[B" if history_index < self.history.len() => {
+                            history_index += 1;
+                            line = self
+                                .history
+                                .get(history_index)
+                                .map(|entry| entry.chars().collect())
+                                .unwrap_or_default();
+                            cursor = line.len();
+                        }
+                        b"[3" => {
+                            let mut tilde = [0u8; 1];
+                            stdin.read_exact(&mut tilde)?;
+                            if cursor < line.len() {
+                                line.remove(cursor);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                byte if byte >= 0x20 => {
+                    let mut buf = vec![byte];
+                    let extra = match byte {
+                        0xc0..=0xdf => 1,
+                        0xe0..=0xef => 2,
+                        0xf0..=0xf7 => 3,
+                        _ => 0,
+                    };
+                    for _ in 0..extra {
+                        let mut next = [0u8; 1];
+                        stdin.read_exact(&mut next)?;
+                        buf.push(next[0]);
+                    }
+
+                    for c in String::from_utf8_lossy(&buf).chars() {
+                        line.insert(cursor, c);
+                        cursor += 1;
+                    }
+                }
+                _ => {}
+            }
+
+            last_was_tab = is_tab;
+            redraw(&mut stdout, &line, cursor)?;
+        }
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> Option<String> {
+    let first = candidates.first()?;
+    let mut prefix = first.clone();
+
+    for candidate in &candidates[1..] {
+        let len = prefix
+            .chars()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        prefix.truncate(len);
+    }
+
+    Some(prefix)
+}
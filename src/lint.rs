@@ -0,0 +1,203 @@
+//! `lint`: points out tasks that are likely to get lost or forgotten, such
+//! as urgent ones without a due date. Rules can be turned off and their
+//! thresholds changed in the `[lint]` table of the config file:
+//!
+//! ```toml
+//! [lint]
+//! disable = ["never-started"]
+//! untagged-days = 14
+//! max-description = 80
+//! ```
+//!
+//! `lint --fix` fixes what can be fixed without asking: long descriptions
+//! move their overflow into the notes, and old untagged tasks go to the
+//! inbox for triage.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::HashSet;
+use ulid::Ulid;
+
+use crate::{
+    capture::INBOX_TAG,
+    history::{Event, EventKind},
+    task::{Priority, Status, Task},
+};
+
+/// Every rule, by name.
+pub const RULES: [&str; 4] = [
+    "no-due-date",
+    "untagged",
+    "long-description",
+    "never-started",
+];
+
+/// The `[lint]` table of the config file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    /// Rules not to check, by name.
+    pub disable: Vec<String>,
+    /// Days an open task may go without tags.
+    pub untagged_days: u32,
+    /// Characters a description may have before the rest belongs in notes.
+    pub max_description: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            disable: Vec::new(),
+            untagged_days: 7,
+            max_description: 120,
+        }
+    }
+}
+
+impl Settings {
+    /// Checks that every disabled rule exists.
+    pub fn check(&self) -> Result<(), String> {
+        match self
+            .disable
+            .iter()
+            .find(|name| !RULES.contains(&name.as_str()))
+        {
+            Some(unknown) => Err(format!(
+                "unknown rule `{unknown}` in [lint], expected one of {}",
+                RULES.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn enabled(&self, rule: &str) -> bool {
+        !self.disable.iter().any(|name| name == rule)
+    }
+}
+
+/// One rule broken by one task.
+pub struct Problem {
+    pub task: Ulid,
+    pub rule: &'static str,
+    pub message: String,
+    /// Whether `lint --fix` can fix it.
+    pub fixable: bool,
+}
+
+/// Tasks whose whole life is in `events`, and of those the ones that
+/// were ever in progress. Tasks created done, as by imports, were
+/// finished elsewhere and are not counted.
+fn lives(events: &[Event]) -> (HashSet<Ulid>, HashSet<Ulid>) {
+    let status = |event: &Event| event.changes.get("status").map(|change| change.to.clone());
+    let mut logged = HashSet::new();
+    let mut started = HashSet::new();
+    for event in events {
+        let status = status(event);
+        if event.kind == EventKind::Created && status.as_ref().is_none_or(|to| to != "done") {
+            logged.insert(event.task);
+        }
+        if status.is_some_and(|to| to == "in-progress") {
+            started.insert(event.task);
+        }
+    }
+    (logged, started)
+}
+
+/// The problems of `tasks` at `now`, task by task in their order.
+pub fn check(
+    tasks: &[Task],
+    events: &[Event],
+    settings: &Settings,
+    now: DateTime<Utc>,
+) -> Vec<Problem> {
+    let (logged, started) = lives(events);
+    let mut problems = Vec::new();
+
+    for task in tasks {
+        let open = task.status != Status::Done;
+        let mut report = |rule: &'static str, message: String, fixable: bool| {
+            if settings.enabled(rule) {
+                problems.push(Problem {
+                    task: task.id,
+                    rule,
+                    message,
+                    fixable,
+                });
+            }
+        };
+
+        if let Some(priority) = task.priority.filter(|p| open && *p >= Priority::High) {
+            report(
+                "no-due-date",
+                format!("{priority} priority but no due date"),
+                false,
+            );
+        }
+
+        let untagged_since = now - Duration::days(settings.untagged_days.into());
+        if open && task.tags.is_empty() && task.created_at < untagged_since {
+            let days = (now - task.created_at).num_days();
+            report("untagged", format!("no tags after {days} days"), true);
+        }
+
+        // Descriptions in a blob are thousands of characters long.
+        let length = task.description.chars().count();
+        if task.description_blob.is_some() || length > settings.max_description {
+            let message = match task.description_blob {
+                Some(_) => format!("description over {} characters", settings.max_description),
+                None => format!(
+                    "description of {length} characters, over {}",
+                    settings.max_description
+                ),
+            };
+            report("long-description", message, true);
+        }
+
+        if !open && logged.contains(&task.id) && !started.contains(&task.id) {
+            report(
+                "never-started",
+                "done without ever being in progress".to_owned(),
+                false,
+            );
+        }
+    }
+
+    problems
+}
+
+/// The description cut to `max` characters at a word break, and the rest.
+fn split_description(description: &str, max: usize) -> (String, String) {
+    let cut = description
+        .char_indices()
+        .nth(max)
+        .map_or(description.len(), |(at, _)| at);
+    let cut = match description[..cut].rfind(char::is_whitespace) {
+        Some(space) if space > 0 => space,
+        _ => cut,
+    };
+    let (kept, rest) = description.split_at(cut);
+    (kept.trim_end().to_owned(), rest.trim().to_owned())
+}
+
+/// Fixes `problem` of `task`, whose blobs have been inlined, and says what
+/// was done.
+pub fn fix(task: &mut Task, problem: &Problem, settings: &Settings) -> Option<String> {
+    match problem.rule {
+        "untagged" => {
+            task.add_tag(INBOX_TAG);
+            Some(format!("tagged {INBOX_TAG}"))
+        }
+        // Only a preview is left of a description that cannot be read.
+        "long-description" if task.description_blob.is_none() => {
+            let (kept, rest) = split_description(&task.description, settings.max_description);
+            let notes = match task.notes.take() {
+                Some(notes) => format!("{rest}\n\n{notes}"),
+                None => rest,
+            };
+            task.set_description(kept);
+            task.set_notes(Some(notes));
+            Some("moved the end of the description to the notes".to_owned())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,95 @@
+//! First day of the week and date format, from the config file or the
+//! `LC_ALL`/`LC_TIME`/`LANG` environment.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::config::Config;
+
+/// Regions whose weeks start on Sunday; everyone else starts on Monday.
+const SUNDAY_START: &[&str] = &[
+    "US", "CA", "MX", "BR", "JP", "KR", "TW", "HK", "PH", "IL", "ZA", "SA", "IN",
+];
+
+#[derive(Clone, Debug)]
+pub struct Locale {
+    pub week_start: Weekday,
+    /// `strftime` format for dates without a time.
+    pub date_format: String,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            week_start: Weekday::Mon,
+            date_format: "%Y-%m-%d".to_owned(),
+        }
+    }
+}
+
+/// The region of a locale name such as `en_US.UTF-8` or `de-DE`.
+fn region(name: &str) -> Option<&str> {
+    let name = name.split(['.', '@']).next()?;
+    let (_, region) = name.split_once(['_', '-'])?;
+    Some(region)
+}
+
+fn date_format(region: &str) -> &'static str {
+    match region {
+        "US" | "PH" => "%m/%d/%Y",
+        "DE" | "AT" | "CH" | "RU" | "PL" | "CZ" | "FI" | "NO" | "DK" | "TR" => "%d.%m.%Y",
+        "NL" => "%d-%m-%Y",
+        "GB" | "IE" | "FR" | "ES" | "IT" | "PT" | "BR" | "AU" | "NZ" | "IN" | "MX" | "BE"
+        | "GR" | "IL" => "%d/%m/%Y",
+        "JP" | "CN" | "TW" | "HK" | "KR" | "HU" | "ZA" => "%Y/%m/%d",
+        _ => "%Y-%m-%d",
+    }
+}
+
+/// Parses `monday`, `mon`, `sunday` and so on.
+pub fn parse_weekday(name: &str) -> Option<Weekday> {
+    name.trim().to_lowercase().get(..3)?.parse().ok()
+}
+
+impl Locale {
+    /// Settings from the config file win over those of the locale named
+    /// there or in the environment.
+    pub fn new(config: &Config) -> Self {
+        let name = config.locale.clone().or_else(|| {
+            ["LC_ALL", "LC_TIME", "LANG"]
+                .iter()
+                .filter_map(|var| std::env::var(var).ok())
+                .find(|value| !value.is_empty())
+        });
+
+        let mut locale = Self::default();
+        if let Some(region) = name.as_deref().and_then(region) {
+            let region = region.to_uppercase();
+            if SUNDAY_START.contains(&region.as_str()) {
+                locale.week_start = Weekday::Sun;
+            }
+            locale.date_format = date_format(&region).to_owned();
+        }
+
+        if let Some(day) = &config.week_start {
+            locale.week_start = parse_weekday(day)
+                .unwrap_or_else(|| panic!("invalid week-start `{day}`, expected a weekday"));
+        }
+        if let Some(format) = &config.date_format {
+            locale.date_format = format.clone();
+        }
+
+        locale
+    }
+
+    /// The first day of the week `date` falls in.
+    pub fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        let offset = (date.weekday().num_days_from_monday() + 7
+            - self.week_start.num_days_from_monday())
+            % 7;
+        date - Duration::days(offset as i64)
+    }
+
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+}
@@ -0,0 +1,121 @@
+//! Where a task has to be done: a place name such as `Post office`, or a
+//! geo URI (RFC 5870) such as `geo:48.2082,16.3738` for maps apps to open.
+//! `list --location` finds tasks by place name, or with a geo URI those
+//! within `--radius` of the point.
+
+/// How far from a point `--location geo:…` looks unless `--radius` says.
+pub const DEFAULT_RADIUS_M: f64 = 1_000.0;
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// A point on the earth, in degrees.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Geo {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Geo {
+    /// The point of a `geo:<lat>,<lon>` URI, ignoring an altitude and
+    /// parameters such as `;u=35`.
+    pub fn parse(uri: &str) -> Option<Self> {
+        let rest = uri.trim().strip_prefix("geo:")?;
+        let coordinates = rest.split(';').next()?;
+        let mut parts = coordinates
+            .split(',')
+            .map(|part| part.trim().parse::<f64>());
+        let lat = parts.next()?.ok()?;
+        let lon = parts.next()?.ok()?;
+        ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon))
+            .then_some(Self { lat, lon })
+    }
+
+    /// Great-circle distance in meters.
+    pub fn distance_m(&self, other: &Self) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+}
+
+/// A location given by hand, trimmed; geo URIs have to be readable.
+pub fn parse(location: &str) -> String {
+    let location = location.trim();
+    if location.is_empty() {
+        panic!("missing location");
+    }
+    if location.starts_with("geo:") && Geo::parse(location).is_none() {
+        panic!("invalid geo URI `{location}`, expected geo:<latitude>,<longitude>");
+    }
+    location.to_owned()
+}
+
+/// A distance such as `500m` or `2km`, in meters.
+pub fn parse_radius(text: &str) -> Option<f64> {
+    let (number, factor) = match text.strip_suffix("km") {
+        Some(number) => (number, 1_000.0),
+        None => (text.strip_suffix('m').unwrap_or(text), 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|meters| *meters >= 0.0)
+        .map(|number| number * factor)
+}
+
+/// What `list --location` looks for.
+pub enum Filter {
+    /// Place names containing the text, ignoring case.
+    Name(String),
+    /// Geo URIs within this many meters of the point.
+    Near(Geo, f64),
+}
+
+impl Filter {
+    pub fn new(location: &str, radius: Option<&str>) -> Self {
+        let radius = radius.map(|radius| parse_radius(radius).expect("invalid --radius"));
+        match Geo::parse(location) {
+            Some(point) => Self::Near(point, radius.unwrap_or(DEFAULT_RADIUS_M)),
+            None if radius.is_some() => panic!("--radius needs a geo URI for --location"),
+            None => Self::Name(location.to_lowercase()),
+        }
+    }
+
+    pub fn matches(&self, location: Option<&str>) -> bool {
+        let Some(location) = location else {
+            return false;
+        };
+        match self {
+            Self::Name(name) => location.to_lowercase().contains(name),
+            Self::Near(point, radius) => {
+                Geo::parse(location).is_some_and(|geo| geo.distance_m(point) <= *radius)
+            }
+        }
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// A link that opens the location on a map: geo URIs as they are, and a
+/// map search for place names.
+pub fn link(location: &str) -> String {
+    match Geo::parse(location) {
+        Some(_) => location.trim().to_owned(),
+        None => format!(
+            "https://www.openstreetmap.org/search?query={}",
+            percent_encode(location)
+        ),
+    }
+}
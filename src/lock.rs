@@ -0,0 +1,265 @@
+use chrono::{DateTime, Local, Utc};
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use ulid::Ulid;
+
+use crate::{signal, CliError};
+
+const WAIT: Duration = Duration::from_secs(2);
+
+/// How long a lock file without a readable pid counts as held.
+const GRACE: Duration = Duration::from_secs(10);
+
+/// Advisory lock on the store, held from before it is read until after it is
+/// written. The lock file contains the owner's pid so locks left behind by a
+/// crashed process can be taken over.
+pub struct StoreLock {
+    path: PathBuf,
+}
+
+pub fn lock_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("lock")
+}
+
+fn owner(path: &Path) -> Option<i32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn is_alive(pid: i32) -> bool {
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Whether this process holds the lock on the store at `store_path`.
+pub fn held(store_path: &Path) -> bool {
+    owner(&lock_path(store_path)) == Some(std::process::id() as i32)
+}
+
+/// Creates the lock file at `path` with this process's pid already in it,
+/// unless it exists: the pid goes into a temporary file first, which is then
+/// linked into place, so no one ever sees the lock without its owner.
+fn create(path: &Path) -> std::io::Result<()> {
+    let pid = std::process::id().to_string();
+    let temp = path.with_extension(format!("lock.{pid}"));
+    std::fs::write(&temp, &pid)?;
+    let linked = std::fs::hard_link(&temp, path);
+    let _ = std::fs::remove_file(&temp);
+    match linked {
+        // File systems without hard links.
+        Err(err) if err.kind() != ErrorKind::AlreadyExists => {
+            let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+            file.write_all(pid.as_bytes())
+        }
+        linked => linked,
+    }
+}
+
+/// Moves the stale lock at `path`, read as `seen`, out of the way. When it
+/// changed since, another process has just taken it over, and it is put
+/// back instead of being deleted.
+fn take_over(path: &Path, seen: &[u8]) {
+    let aside = path.with_extension(format!("lock.stale.{}", std::process::id()));
+    if std::fs::rename(path, &aside).is_err() {
+        return;
+    }
+    // A lock that cannot be put back, as a third process locked meanwhile,
+    // is left aside rather than lost.
+    let stale = std::fs::read(&aside).is_ok_and(|now| now == seen);
+    if stale || std::fs::hard_link(&aside, path).is_ok() {
+        let _ = std::fs::remove_file(&aside);
+    }
+}
+
+fn older_than(path: &Path, age: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed > age))
+}
+
+impl StoreLock {
+    pub fn acquire(store_path: &Path) -> Result<Self, CliError> {
+        let path = lock_path(store_path);
+        let started = Instant::now();
+
+        loop {
+            match create(&path) {
+                Ok(()) => {
+                    signal::register_lock_file(Some(&path));
+                    return Ok(Self { path });
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    let Ok(seen) = std::fs::read(&path) else {
+                        // Released meanwhile.
+                        continue;
+                    };
+                    let pid = std::str::from_utf8(&seen)
+                        .ok()
+                        .and_then(|pid| pid.trim().parse::<i32>().ok());
+
+                    match pid {
+                        // The owner died without cleaning up.
+                        Some(pid) if !is_alive(pid) => take_over(&path, &seen),
+                        // Written by hand or by an older version that crashed
+                        // before writing its pid.
+                        None if older_than(&path, GRACE) => take_over(&path, &seen),
+                        Some(pid) if started.elapsed() > WAIT => {
+                            return Err(CliError::Locked { path, pid })
+                        }
+                        _ => std::thread::sleep(Duration::from_millis(50)),
+                    }
+                }
+                Err(error) => {
+                    return Err(CliError::Write {
+                        path: store_path.to_owned(),
+                        error,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        signal::register_lock_file(None);
+    }
+}
+
+/// Advisory locks on single tasks, held while they are open in an editor
+/// without the store being locked, so that other commands can warn before
+/// changing them. Each is a file named after the task in `<store>.editing/`
+/// containing the owner's pid; files of processes that died are ignored.
+pub struct TaskLocks {
+    paths: Vec<PathBuf>,
+}
+
+/// A task being edited by another process.
+pub struct Holder {
+    pub task: Ulid,
+    pub pid: i32,
+    pub since: DateTime<Utc>,
+}
+
+impl std::fmt::Display for Holder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "process {}, since {}",
+            self.pid,
+            self.since.with_timezone(&Local).format("%H:%M")
+        )
+    }
+}
+
+fn editing_dir(store_path: &Path) -> PathBuf {
+    store_path.with_extension("editing")
+}
+
+fn holder(store_path: &Path, task: Ulid) -> Option<Holder> {
+    let path = editing_dir(store_path).join(task.to_string());
+    let pid = owner(&path).filter(|pid| is_alive(*pid))?;
+    let since = std::fs::metadata(&path).and_then(|metadata| metadata.modified());
+    Some(Holder {
+        task,
+        pid,
+        since: since.map_or_else(|_| Utc::now(), DateTime::from),
+    })
+}
+
+/// Which of `tasks` other processes are editing.
+pub fn editing_elsewhere(store_path: &Path, tasks: &[Ulid]) -> Vec<Holder> {
+    if !editing_dir(store_path).is_dir() {
+        return Vec::new();
+    }
+    tasks
+        .iter()
+        .filter_map(|task| holder(store_path, *task))
+        .filter(|holder| holder.pid != std::process::id() as i32)
+        .collect()
+}
+
+impl TaskLocks {
+    /// Locks `tasks` for this process, except those another process is
+    /// editing already, which are returned instead.
+    pub fn acquire(store_path: &Path, tasks: &[Ulid]) -> (Self, Vec<Holder>) {
+        let held = editing_elsewhere(store_path, tasks);
+        let dir = editing_dir(store_path);
+        let _ = std::fs::create_dir_all(&dir);
+
+        let mut locks = Self { paths: Vec::new() };
+        for task in tasks {
+            if held.iter().any(|holder| holder.task == *task) {
+                continue;
+            }
+            let path = dir.join(task.to_string());
+            if std::fs::write(&path, std::process::id().to_string()).is_ok() {
+                locks.paths.push(path);
+            }
+        }
+        (locks, held)
+    }
+}
+
+impl Drop for TaskLocks {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+        if let Some(dir) = self.paths.first().and_then(|path| path.parent()) {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("task-cli-lock-{}", Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    /// The pid of a process that has already exited.
+    fn dead_pid() -> i32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id() as i32;
+        child.wait().unwrap();
+        pid
+    }
+
+    #[test]
+    fn the_lock_holds_the_pid_and_goes_with_the_guard() {
+        let store = store_path("tasks.json");
+        let lock = StoreLock::acquire(&store).unwrap();
+        assert!(held(&store));
+        drop(lock);
+        assert!(!lock_path(&store).exists());
+    }
+
+    #[test]
+    fn locks_of_dead_processes_are_taken_over() {
+        let store = store_path("tasks.json");
+        std::fs::write(lock_path(&store), dead_pid().to_string()).unwrap();
+        let _lock = StoreLock::acquire(&store).unwrap();
+        assert!(held(&store));
+    }
+
+    #[test]
+    fn a_lock_taken_over_meanwhile_is_put_back() {
+        let store = store_path("tasks.json");
+        let path = lock_path(&store);
+        std::fs::write(&path, "1").unwrap();
+        take_over(&path, b"12345");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1");
+
+        take_over(&path, b"1");
+        assert!(!path.exists());
+    }
+}
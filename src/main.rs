@@ -1,174 +1,171 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::{fmt::Display, str::FromStr};
-use ulid::Ulid;
-
-enum Commands {
-    Add,
-    Update,
-    Delete,
-    Mark,
-    List,
-}
+use chrono::Utc;
+use std::str::FromStr;
+use task_cli::{
+    args::Args,
+    commands::{self, Commands},
+    config::Config,
+    demo, error_json, fuzzy, greet,
+    lock::StoreLock,
+    pause, quick_add, quota, release,
+    remote::Remote,
+    repl, rules, server, shell, signal,
+    store::{self, Store},
+    sync, tour, usage, CliError,
+};
+
+fn run(cmd: Commands, args: &Args) -> Result<(), CliError> {
+    let location = store::tasks_path(args);
+    let remote = Remote::parse(&location);
+    let path = match &remote {
+        Some(remote) => remote.cache_path()?,
+        None => location,
+    };
+
+    // Mutations hold the lock from before the store is read until it has
+    // been written back, on the remote host too where it can be locked.
+    // Remote stores are pulled once, so they stay locked throughout.
+    let _lock = if cmd.mutates(args) {
+        store::check_writable(&path)?;
+        if cmd.locks_late() && remote.is_none() {
+            None
+        } else {
+            Some(StoreLock::acquire(&path)?)
+        }
+    } else {
+        None
+    };
+    let _remote_lock = match &remote {
+        Some(remote) if cmd.mutates(args) => remote.lock()?,
+        _ => None,
+    };
+    let pulled = match &remote {
+        Some(remote) => Some(remote.pull(&path)?),
+        None => None,
+    };
+
+    let config = Config::load();
+    let mut store = Store::open(path);
+    if let Some(digest) = pause::resume_if_over(&store.tasks, Utc::now())? {
+        eprint!("{digest}");
+    }
+    commands::execute(cmd, args, &config, &mut store)?;
 
-#[derive(Debug)]
-pub enum CliError {
-    InvalidCommand,
-    InvalidArgs,
-}
+    if cmd.mutates(args) {
+        rules::save(&mut store, &config)?;
+        if let (Some(remote), Some(pulled)) = (&remote, &pulled) {
+            remote.push(&store, pulled)?;
+        }
+    }
 
-impl FromStr for Commands {
-    type Err = CliError;
+    usage::record(args, &config);
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "add" => Ok(Self::Add),
-            "update" => Ok(Self::Update),
-            "delete" => Ok(Self::Delete),
-            "mark" => Ok(Self::Mark),
-            "list" => Ok(Self::List),
-            _ => Err(CliError::InvalidCommand),
-        }
+    if let Some(nag) = quota::nag(&store.tasks, &config.quotas, Utc::now()) {
+        eprintln!("{nag}");
     }
-}
 
-#[derive(Deserialize, Serialize, PartialEq, Eq, Clone)]
-#[serde(rename_all = "kebab-case")]
-enum Status {
-    InProgress,
-    Done,
-    ToDo,
+    Ok(())
 }
 
-impl FromStr for Status {
-    type Err = CliError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "in-progress" => Ok(Self::InProgress),
-            "done" => Ok(Self::Done),
-            "todo" => Ok(Self::ToDo),
-            _ => Err(CliError::InvalidArgs),
+/// Prompts before taking the store lock, so that typing does not hold up
+/// the daemon or other commands.
+fn quick_add(args: &Args) -> Result<(), CliError> {
+    if args.positional(1).is_some() {
+        return run(Commands::QuickAdd, args);
+    }
+    match quick_add::prompt()? {
+        Some(text) => {
+            let mut args = args.shifted(0);
+            args.push_positional(text);
+            run(Commands::QuickAdd, &args)
         }
+        None => Ok(()),
     }
 }
 
-impl Display for Status {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Done => write!(f, "done"),
-            Self::ToDo => write!(f, "todo"),
-            Self::InProgress => write!(f, "in-progress"),
-        }
+/// With `--format json`, errors go to stderr as JSON objects for wrappers to
+/// parse. Commands return bad input as [`CliError::InvalidArgs`]; the hook
+/// covers whatever still panics.
+fn json_errors(args: &Args) -> bool {
+    if args.value("format") != Some("json") {
+        return false;
     }
-}
 
-#[derive(Deserialize, Serialize, Clone)]
-pub struct Task {
-    id: Ulid,
-    description: String,
-    status: Status,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
+    std::panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "command failed".to_owned());
+        let suggestion = "`task-cli help` describes the arguments".to_owned();
+        eprintln!(
+            "{}",
+            error_json("invalid-input", &message, None, Some(suggestion))
+        );
+    }));
+    true
 }
 
-fn print_help() {
-    const HELP_TEXT: &str = r#"Usage: task-cli [command] [args]
-
-Commands:
-    add      Adds a new task
-    update   Update a task
-    delete   Delete a task
-    mark     Change status of a task
-    list     List all tasks"#;
-
-    println!("{HELP_TEXT}");
+fn exit_on_error(result: Result<(), CliError>, json: bool) {
+    if let Err(err) = result {
+        if json {
+            eprintln!("{}", err.to_json());
+        } else {
+            eprintln!("error: {err}");
+        }
+        std::process::exit(1);
+    }
 }
 
 fn main() {
-    let args = std::env::args().collect::<Vec<_>>();
-
-    if args.len() == 1 {
-        print_help();
-    } else {
-        if let Ok(cmd) = Commands::from_str(&args[1]) {
-            let mut tasks =
-                if let Ok(tasks) = std::fs::read(dirs::home_dir().unwrap().join(".tasks.json")) {
-                    serde_json::from_slice::<Vec<Task>>(&tasks).expect("invalid json format")
-                } else {
-                    Vec::new()
-                };
+    let args = Args::parse(std::env::args().skip(1));
+    signal::install();
+    let json = json_errors(&args);
 
+    if let Some(cmd) = args.positional(0) {
+        if let Ok(cmd) = Commands::from_str(cmd) {
             match cmd {
-                Commands::Add => {
-                    let description = args[2].clone();
-
-                    let new_task = Task {
-                        id: Ulid::new(),
-                        description,
-                        status: Status::ToDo,
-                        created_at: Utc::now(),
-                        updated_at: Utc::now(),
-                    };
-
-                    tasks.push(new_task);
-                }
-
-                Commands::List => {
-                    let tasks = if let Some(status) = args.get(2) {
-                        let status = Status::from_str(status).expect("invalid status type");
-                        tasks
-                            .iter()
-                            .filter(|task| task.status == status)
-                            .cloned()
-                            .collect()
-                    } else {
-                        tasks.clone()
+                Commands::Daemon => commands::run_daemon(&args),
+                Commands::List if args.flag("watch") => commands::run_watch(&args),
+                Commands::Interactive => repl::run(&args),
+                Commands::Serve => server::run(&args),
+                Commands::Tour => tour::run(&args),
+                _ => {
+                    let result = match cmd {
+                        Commands::Demo => demo::run(&args),
+                        Commands::Generate => demo::run_generate(&args),
+                        Commands::ShellInit => shell::init(&args),
+                        Commands::Context => shell::context(&args),
+                        Commands::Greet => greet::run(&args),
+                        Commands::Sync => sync::status(&args),
+                        Commands::SelfUpdate => release::self_update(&args),
+                        Commands::QuickAdd => quick_add(&args),
+                        _ => run(cmd, &args),
                     };
-
-                    for task in tasks {
-                        println!("{}. {} ({})", task.id, task.description, task.status)
-                    }
-                }
-
-                Commands::Mark => {
-                    let id = Ulid::from_string(&args[2]).expect("invalid ulid format");
-                    let status = Status::from_str(&args[3]).expect("invalid status kind");
-
-                    for task in tasks.iter_mut() {
-                        if task.id == id {
-                            task.status = status;
-                            break;
-                        }
-                    }
-                }
-
-                Commands::Delete => {
-                    let id = Ulid::from_string(&args[2]).expect("invalid ulid format");
-                    tasks.retain(|task| task.id != id);
-                }
-
-                Commands::Update => {
-                    let id = Ulid::from_string(&args[2]).expect("invalid ulid format");
-                    let new_description = args[3].clone();
-
-                    for task in tasks.iter_mut() {
-                        if task.id == id {
-                            task.description = new_description;
-                            break;
-                        }
-                    }
+                    exit_on_error(result, json);
                 }
             }
-
-            std::fs::write(
-                dirs::home_dir().unwrap().join(".tasks.json"),
-                serde_json::to_vec(&tasks).expect("could not convert to json"),
-            )
-            .expect("could not write to tasks file");
+        } else if json {
+            let suggestion = match fuzzy::closest(cmd, commands::COMMAND_NAMES) {
+                Some(name) => format!("did you mean `{name}`?"),
+                None => "`task-cli help` lists the commands".to_owned(),
+            };
+            let message = format!("unknown command `{cmd}`");
+            let error = error_json(
+                "invalid-command",
+                &message,
+                Some(cmd.to_owned()),
+                Some(suggestion),
+            );
+            eprintln!("{error}");
+            std::process::exit(1);
         } else {
             println!("Invalid command");
-            print_help();
+            commands::print_help();
         }
+    } else if args.flag("version") {
+        exit_on_error(release::print_version(&args), json);
+    } else {
+        commands::print_help();
     }
 }
@@ -0,0 +1,210 @@
+//! `meeting`: collects action items and notes while a meeting runs. The
+//! meeting in progress is kept next to the store (`tasks.json` uses
+//! `tasks.meeting.json`) until `meeting end` files its action items as tasks
+//! and writes the minutes.
+//!
+//! Action items name who they are for as `@name` and when they are due as
+//! `due:<date>`, e.g. `meeting item "Send the slides @sam due:friday"`.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config::Config,
+    phrases::parse_deadline,
+    task::Task,
+    time::{format_duration, format_local},
+    CliError,
+};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Meeting {
+    pub title: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attendees: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agenda: Vec<String>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Item {
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Note {
+    pub at: DateTime<Utc>,
+    pub text: String,
+}
+
+/// Where the meeting in progress on the store at `store_path` is kept.
+pub fn path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("meeting.json")
+}
+
+/// The meeting in progress, if there is one.
+pub fn load(path: &Path) -> Result<Option<Meeting>, CliError> {
+    match std::fs::read(path) {
+        Ok(contents) => Ok(Some(
+            serde_json::from_slice(&contents).expect("invalid meeting file"),
+        )),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(CliError::Read {
+            path: path.to_owned(),
+            error,
+        }),
+    }
+}
+
+pub fn save(path: &Path, meeting: &Meeting) -> Result<(), CliError> {
+    std::fs::write(
+        path,
+        serde_json::to_vec_pretty(meeting).expect("could not convert to json"),
+    )
+    .map_err(|error| CliError::Write {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+/// Forgets the meeting in progress.
+pub fn clear(path: &Path) -> Result<(), CliError> {
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(CliError::Write {
+            path: path.to_owned(),
+            error,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Reads an action item, taking `@name` and `due:<date>` out of `text`.
+pub fn parse_item(text: &str, config: &Config) -> Result<Item, CliError> {
+    let mut item = Item {
+        description: String::new(),
+        owner: None,
+        due: None,
+    };
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        if let Some(owner) = word.strip_prefix('@').filter(|owner| !owner.is_empty()) {
+            item.owner = Some(owner.to_owned());
+        } else if let Some(due) = word.strip_prefix("due:") {
+            // Phrases of several words can be joined with `-` or `_`.
+            item.due = Some(
+                parse_deadline(&due.replace(['-', '_'], " "), config)
+                    .or_else(|_| parse_deadline(due, config))?,
+            );
+        } else {
+            words.push(word);
+        }
+    }
+
+    item.description = words.join(" ");
+    if item.description.is_empty() {
+        panic!("missing action item");
+    }
+    Ok(item)
+}
+
+/// Local time of day, for times within the meeting.
+fn clock(at: &DateTime<Utc>) -> String {
+    at.with_timezone(&Local).format("%H:%M").to_string()
+}
+
+impl Meeting {
+    pub fn new(title: String) -> Self {
+        Self {
+            title,
+            started_at: Utc::now(),
+            attendees: Vec::new(),
+            agenda: Vec::new(),
+            items: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// A task for each action item, noting the meeting it came from.
+    pub fn tasks(&self) -> Vec<Task> {
+        let origin = format!(
+            "From {} on {}.",
+            self.title,
+            self.started_at.format("%Y-%m-%d")
+        );
+        self.items
+            .iter()
+            .map(|item| {
+                let mut task = Task::new(item.description.clone());
+                task.owner = item.owner.clone();
+                task.due = item.due;
+                task.set_notes(Some(origin.clone()));
+                task
+            })
+            .collect()
+    }
+
+    /// The minutes in Markdown, with the action items as the `tasks` they
+    /// were filed as.
+    pub fn minutes(&self, ended_at: DateTime<Utc>, tasks: &[&Task]) -> String {
+        let mut out = format!(
+            "# {}\n\n{} to {} ({}).",
+            self.title,
+            format_local(&self.started_at),
+            clock(&ended_at),
+            format_duration(ended_at - self.started_at)
+        );
+        if !self.attendees.is_empty() {
+            out.push_str(&format!(" Attendees: {}.", self.attendees.join(", ")));
+        }
+        out.push('\n');
+
+        if !self.agenda.is_empty() {
+            out.push_str("\n## Agenda\n\n");
+            for topic in &self.agenda {
+                out.push_str(&format!("- {topic}\n"));
+            }
+        }
+
+        if !self.notes.is_empty() {
+            out.push_str("\n## Notes\n\n");
+            for note in &self.notes {
+                out.push_str(&format!("- {} {}\n", clock(&note.at), note.text));
+            }
+        }
+
+        out.push_str("\n## Action items\n\n");
+        if tasks.is_empty() {
+            out.push_str("None.\n");
+        }
+        for task in tasks {
+            let number = task
+                .number
+                .map(|number| format!("#{number} "))
+                .unwrap_or_default();
+            let mut line = format!("- [ ] {number}{}", task.description);
+            let mut details = Vec::new();
+            if let Some(owner) = &task.owner {
+                details.push(format!("@{owner}"));
+            }
+            if let Some(due) = task.due {
+                details.push(format!("due {}", format_local(&due)));
+            }
+            if !details.is_empty() {
+                line.push_str(&format!(" ({})", details.join(", ")));
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
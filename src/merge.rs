@@ -0,0 +1,325 @@
+//! Three-way merge of the store, for saves that find the file changed since
+//! it was read. Each task is merged field by field against the copy both
+//! sides started from; only a field changed differently on both sides is a
+//! conflict, which the caller settles. Tags and blockers are merged as sets.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use ulid::Ulid;
+
+use crate::task::Task;
+
+/// Fields derived from others, which never conflict: the later `updated_at`
+/// and higher `revision` win, and saving recomputes rollups.
+const DERIVED: [&str; 3] = ["updated_at", "revision", "rollup"];
+
+/// Fields holding sets of names or ids.
+const SETS: [&str; 2] = ["tags", "blocked_by"];
+
+/// A change made on both sides that cannot be merged.
+pub struct Conflict {
+    pub task: Ulid,
+    pub description: String,
+    /// The field changed on both sides; `None` when one side removed the
+    /// task for good and the other changed it.
+    pub field: Option<String>,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+fn fields(task: &Task) -> Map<String, Value> {
+    match serde_json::to_value(task).expect("could not convert to json") {
+        Value::Object(fields) => fields,
+        _ => unreachable!("tasks are objects"),
+    }
+}
+
+/// A conflict about the task with `fields`.
+fn conflict(
+    fields: &Map<String, Value>,
+    field: Option<String>,
+    ours: Value,
+    theirs: Value,
+) -> Conflict {
+    Conflict {
+        task: fields
+            .get("id")
+            .and_then(Value::as_str)
+            .and_then(|id| Ulid::from_string(id).ok())
+            .unwrap_or_default(),
+        description: fields
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned(),
+        field,
+        ours,
+        theirs,
+    }
+}
+
+fn strings(value: Option<&Value>) -> Option<Vec<&str>> {
+    match value {
+        None | Some(Value::Null) => Some(Vec::new()),
+        Some(Value::Array(items)) => items.iter().map(Value::as_str).collect(),
+        Some(_) => None,
+    }
+}
+
+/// `theirs` with what `ours` added to and removed from `base`, keeping the
+/// order of `theirs`.
+fn merge_set(base: &[&str], ours: &[&str], theirs: &[&str]) -> Value {
+    let removed = base
+        .iter()
+        .filter(|item| !ours.contains(item))
+        .collect::<BTreeSet<_>>();
+    let mut merged = theirs
+        .iter()
+        .filter(|item| !removed.contains(item))
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>();
+    for item in ours {
+        if !base.contains(item) && !merged.iter().any(|kept| kept == item) {
+            merged.push(item.to_string());
+        }
+    }
+    Value::from(merged)
+}
+
+fn merge_fields(
+    base: &Map<String, Value>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+    resolve: &mut impl FnMut(&Conflict) -> Option<Side>,
+) -> Option<Map<String, Value>> {
+    let mut names = ours.keys().chain(theirs.keys()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    let mut merged = Map::new();
+    for name in names {
+        let (b, o, t) = (base.get(name), ours.get(name), theirs.get(name));
+        let value = if o == b || o == t {
+            t
+        } else if t == b {
+            o
+        } else if name == "updated_at" {
+            // RFC 3339 text with other offsets or precisions does not sort
+            // by time, so the times are compared.
+            let time = |value: Option<&Value>| {
+                value.and_then(|value| DateTime::<Utc>::deserialize(value).ok())
+            };
+            if time(o) > time(t) {
+                o
+            } else {
+                t
+            }
+        } else if name == "revision" {
+            if o.and_then(Value::as_u64) > t.and_then(Value::as_u64) {
+                o
+            } else {
+                t
+            }
+        } else if DERIVED.contains(&name.as_str()) {
+            t
+        } else if let (true, Some(b), Some(o), Some(t)) = (
+            SETS.contains(&name.as_str()),
+            strings(b),
+            strings(o),
+            strings(t),
+        ) {
+            merged.insert(name.clone(), merge_set(&b, &o, &t));
+            continue;
+        } else {
+            let conflict = conflict(
+                ours,
+                Some(name.clone()),
+                o.cloned().unwrap_or(Value::Null),
+                t.cloned().unwrap_or(Value::Null),
+            );
+            match resolve(&conflict)? {
+                Side::Ours => o,
+                Side::Theirs => t,
+            }
+        };
+        if let Some(value) = value {
+            merged.insert(name.clone(), value.clone());
+        }
+    }
+    Some(merged)
+}
+
+/// Merges `ours` and `theirs`, both changed from `base`; each holds the
+/// tasks and the trash. `resolve` picks a side for each conflict, and
+/// returning `None` gives up the merge.
+pub fn merge(
+    base: &[Task],
+    ours: &[Task],
+    theirs: &[Task],
+    mut resolve: impl FnMut(&Conflict) -> Option<Side>,
+) -> Option<Vec<Task>> {
+    let by_id = |tasks: &[Task]| {
+        tasks
+            .iter()
+            .map(|task| (task.id, fields(task)))
+            .collect::<HashMap<_, _>>()
+    };
+    let (base, ours_by_id, theirs_by_id) = (by_id(base), by_id(ours), by_id(theirs));
+
+    // Their order first, then what only we have.
+    let mut ids = theirs.iter().map(|task| task.id).collect::<Vec<_>>();
+    ids.extend(
+        ours.iter()
+            .map(|task| task.id)
+            .filter(|id| !theirs_by_id.contains_key(id)),
+    );
+    // One side removed the task for good; the other changed it.
+    let removed = |kept: &Map<String, Value>, side: Side| {
+        let kept_value = Value::Object(kept.clone());
+        match side {
+            Side::Ours => conflict(kept, None, kept_value, Value::Null),
+            Side::Theirs => conflict(kept, None, Value::Null, kept_value),
+        }
+    };
+
+    let empty = Map::new();
+    let mut merged = Vec::new();
+    for id in ids {
+        let fields = match (base.get(&id), ours_by_id.get(&id), theirs_by_id.get(&id)) {
+            (_, None, None) => continue,
+            (None, Some(added), None) | (None, None, Some(added)) => added.clone(),
+            // Removed on one side and left alone on the other.
+            (Some(b), Some(kept), None) | (Some(b), None, Some(kept)) if b == kept => continue,
+            (Some(_), Some(o), None) => match resolve(&removed(o, Side::Ours))? {
+                Side::Ours => o.clone(),
+                Side::Theirs => continue,
+            },
+            (Some(_), None, Some(t)) => match resolve(&removed(t, Side::Theirs))? {
+                Side::Ours => continue,
+                Side::Theirs => t.clone(),
+            },
+            (b, Some(o), Some(t)) => merge_fields(b.unwrap_or(&empty), o, t, &mut resolve)?,
+        };
+        merged.push(serde_json::from_value(Value::Object(fields)).expect("merged tasks are valid"));
+    }
+
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{Priority, Status};
+
+    fn never(_: &Conflict) -> Option<Side> {
+        panic!("unexpected conflict")
+    }
+
+    #[test]
+    fn changes_to_different_fields_both_stay() {
+        let base = Task::new("write the report".to_owned());
+        let mut ours = base.clone();
+        ours.status = Status::Done;
+        ours.revision = 2;
+        let mut theirs = base.clone();
+        theirs.priority = Some(Priority::High);
+        theirs.revision = 3;
+        let added = Task::new("book the room".to_owned());
+
+        let merged = merge(
+            &[base],
+            &[ours, added.clone()],
+            std::slice::from_ref(&theirs),
+            never,
+        )
+        .unwrap();
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].status, Status::Done);
+        assert_eq!(merged[0].priority, Some(Priority::High));
+        assert_eq!(merged[0].revision, 3);
+        assert_eq!(merged[1].id, added.id);
+    }
+
+    #[test]
+    fn the_later_update_time_wins() {
+        let base = Task::new("renew the lease".to_owned());
+        let at = DateTime::parse_from_rfc3339("2026-03-01T09:00:00Z").unwrap();
+        let mut ours = base.clone();
+        ours.status = Status::Done;
+        // Written as `...09:00:00.500Z`, which sorts before `...09:00:00Z`.
+        ours.updated_at = (at + chrono::Duration::milliseconds(500)).into();
+        let mut theirs = base.clone();
+        theirs.priority = Some(Priority::High);
+        theirs.updated_at = at.into();
+
+        let merged = merge(&[base], &[ours.clone()], &[theirs], never).unwrap();
+        assert_eq!(merged[0].updated_at, ours.updated_at);
+    }
+
+    #[test]
+    fn tags_merge_as_sets() {
+        let mut base = Task::new("plan the trip".to_owned());
+        base.tags = vec!["a".to_owned(), "b".to_owned()];
+        let mut ours = base.clone();
+        ours.tags = vec!["b".to_owned(), "c".to_owned()];
+        let mut theirs = base.clone();
+        theirs.tags = vec!["a".to_owned(), "b".to_owned(), "d".to_owned()];
+
+        let merged = merge(&[base], &[ours], &[theirs], never).unwrap();
+        assert_eq!(merged[0].tags, ["b", "d", "c"]);
+    }
+
+    #[test]
+    fn conflicts_are_settled_by_the_caller() {
+        let base = Task::new("call the bank".to_owned());
+        let mut ours = base.clone();
+        ours.priority = Some(Priority::Low);
+        let mut theirs = base.clone();
+        theirs.priority = Some(Priority::Urgent);
+
+        let mut seen = Vec::new();
+        let merged = merge(
+            std::slice::from_ref(&base),
+            std::slice::from_ref(&ours),
+            std::slice::from_ref(&theirs),
+            |conflict| {
+                seen.push((conflict.field.clone(), conflict.ours.clone()));
+                Some(Side::Ours)
+            },
+        )
+        .unwrap();
+        assert_eq!(merged[0].priority, Some(Priority::Low));
+        assert_eq!(seen, [(Some("priority".to_owned()), Value::from("low"))]);
+
+        assert!(merge(&[base], &[ours], &[theirs], |_| None).is_none());
+    }
+
+    #[test]
+    fn a_task_removed_on_one_side_stays_removed_unless_changed() {
+        let base = Task::new("water the plants".to_owned());
+        let merged = merge(
+            std::slice::from_ref(&base),
+            &[],
+            std::slice::from_ref(&base),
+            never,
+        );
+        assert!(merged.unwrap().is_empty());
+
+        let mut theirs = base.clone();
+        theirs.status = Status::InProgress;
+        let merged = merge(&[base], &[], &[theirs], |conflict| {
+            assert_eq!(conflict.field, None);
+            Some(Side::Theirs)
+        });
+        assert_eq!(merged.unwrap()[0].status, Status::InProgress);
+    }
+}
@@ -0,0 +1,420 @@
+//! Stores kept in S3-compatible object storage: `s3://bucket/key` for AWS S3
+//! or MinIO and `gs://bucket/key` for Google Cloud Storage's XML API.
+//! Requests are signed by `curl --aws-sigv4` with the usual
+//! `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` (HMAC keys for GCS).
+//!
+//! Writes are conditional on the ETag seen when the store was read, so two
+//! machines saving at once cannot overwrite each other: the later one is
+//! refused and its command can simply be run again.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    blob, history,
+    remote::{blob_names, cached, local_error, write_atomically, Pulled},
+    store::Store,
+    CliError,
+};
+
+/// How often to retry appending to an event log another save appended to.
+const LOG_ATTEMPTS: usize = 5;
+
+pub struct Bucket {
+    scheme: &'static str,
+    /// Base URL requests go to, with the bucket as the first path segment.
+    endpoint: String,
+    region: String,
+    bucket: String,
+    key: String,
+}
+
+struct Response {
+    status: u16,
+    etag: Option<String>,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// A value in a curl config file, in double quotes.
+fn config_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// Percent-encodes a key, keeping the `/` between its segments.
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn key_of(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+impl Bucket {
+    /// The bucket and key `location` names, for `s3://` and `gs://` URLs.
+    pub fn parse(location: &str) -> Option<Self> {
+        let (scheme, rest) = location.split_once("://")?;
+        let scheme = match scheme {
+            "s3" => "s3",
+            "gs" => "gs",
+            _ => return None,
+        };
+        let (bucket, key) = rest
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .unwrap_or_else(|| {
+                panic!("invalid store url `{location}`, expected {scheme}://bucket/key")
+            });
+
+        let region = match scheme {
+            "gs" => "auto".to_owned(),
+            _ => env("AWS_REGION")
+                .or_else(|| env("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| "us-east-1".to_owned()),
+        };
+        let endpoint = env("TASK_CLI_S3_ENDPOINT")
+            .or_else(|| env("AWS_ENDPOINT_URL_S3"))
+            .or_else(|| env("AWS_ENDPOINT_URL"))
+            .unwrap_or_else(|| match scheme {
+                "gs" => "https://storage.googleapis.com".to_owned(),
+                _ => format!("https://s3.{region}.amazonaws.com"),
+            });
+
+        Some(Self {
+            scheme,
+            endpoint: endpoint.trim_end_matches('/').to_owned(),
+            region,
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}://{}/{}", self.scheme, self.bucket, self.key)
+    }
+
+    pub fn cache_path(&self) -> Result<PathBuf, CliError> {
+        cached(&Path::new(self.scheme).join(&self.bucket).join(&self.key))
+    }
+
+    /// Sends one request for `key`, with the body read from `upload` and
+    /// written to `download`. Credentials go to curl on stdin, not on its
+    /// command line.
+    fn request(
+        &self,
+        method: &str,
+        key: &str,
+        headers: &[String],
+        upload: Option<&Path>,
+        download: &Path,
+    ) -> Result<Response, CliError> {
+        let (Some(id), Some(secret)) = (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY"))
+        else {
+            return Err(CliError::Remote(
+                "set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY to use object storage".to_owned(),
+            ));
+        };
+        let mut config = format!(
+            "user = {}\naws-sigv4 = {}\n",
+            config_string(&format!("{id}:{secret}")),
+            config_string(&format!("aws:amz:{}:s3", self.region))
+        );
+        if let Some(token) = env("AWS_SESSION_TOKEN") {
+            let header = format!("x-amz-security-token: {token}");
+            config.push_str(&format!("header = {}\n", config_string(&header)));
+        }
+
+        let url = format!("{}/{}/{}", self.endpoint, self.bucket, encode_key(key));
+        let mut command = Command::new("curl");
+        command
+            .args(["-sS", "-K", "-", "-D", "-", "-o"])
+            .arg(download);
+        // curl waits for a body after `-X HEAD`.
+        match method {
+            "HEAD" => command.arg("-I"),
+            _ => command.args(["-X", method]),
+        };
+        for header in headers {
+            command.args(["-H", header]);
+        }
+        if let Some(upload) = upload {
+            command.arg("--upload-file").arg(upload);
+        }
+        let mut child = command
+            .arg(&url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| CliError::Remote(format!("cannot run curl: {err}")))?;
+        let _ = child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(config.as_bytes());
+        let output = child
+            .wait_with_output()
+            .map_err(|err| CliError::Remote(format!("cannot run curl: {err}")))?;
+        if !output.status.success() {
+            return Err(CliError::Remote(format!(
+                "cannot reach {} ({})",
+                self.endpoint, output.status
+            )));
+        }
+
+        // With redirects or `100 Continue` there are several header blocks;
+        // the last one is the answer.
+        let mut response = Response {
+            status: 0,
+            etag: None,
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if line.starts_with("HTTP/") {
+                response.status = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|status| status.parse().ok())
+                    .unwrap_or(0);
+                response.etag = None;
+            } else if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("etag") {
+                    response.etag = Some(value.trim().to_owned());
+                }
+            }
+        }
+        Ok(response)
+    }
+
+    fn failed(&self, key: &str, response: &Response, download: &Path) -> CliError {
+        // S3 explains errors in an XML body such as `<Code>AccessDenied</Code>`.
+        let body = std::fs::read_to_string(download).unwrap_or_default();
+        let code = body
+            .split_once("<Code>")
+            .and_then(|(_, rest)| rest.split_once("</Code>"))
+            .map(|(code, _)| format!(": {code}"))
+            .unwrap_or_default();
+        let _ = std::fs::remove_file(download);
+        CliError::Remote(format!(
+            "{}://{}/{key} answered {}{code}",
+            self.scheme, self.bucket, response.status
+        ))
+    }
+
+    /// The condition that makes a write fail if `etag` is no longer current,
+    /// or if the object appeared when there was none.
+    fn condition(etag: Option<&str>) -> String {
+        match etag {
+            Some(etag) => format!("If-Match: {etag}"),
+            None => "If-None-Match: *".to_owned(),
+        }
+    }
+
+    pub fn pull(&self, local: &Path) -> Result<Pulled, CliError> {
+        let temp = local.with_extension(format!("download-{}", std::process::id()));
+
+        let response = self.request("GET", &self.key, &[], None, &temp)?;
+        let store_etag = match response.status {
+            200 => {
+                std::fs::rename(&temp, local).map_err(local_error(local))?;
+                response.etag
+            }
+            404 => {
+                let _ = std::fs::remove_file(&temp);
+                write_atomically(local, &[])?;
+                None
+            }
+            _ => return Err(self.failed(&self.key, &response, &temp)),
+        };
+
+        // Only the part of the log added since the last pull is fetched.
+        let log = history::history_path(local);
+        let log_key = key_of(&history::history_path(Path::new(&self.key)));
+        let have = std::fs::metadata(&log).map_or(0, |metadata| metadata.len());
+        let range = [format!("Range: bytes={have}-")];
+        let headers = if have > 0 { &range[..] } else { &[] };
+        let response = self.request("GET", &log_key, headers, None, &temp)?;
+        let log_etag = match response.status {
+            200 => {
+                std::fs::rename(&temp, &log).map_err(local_error(&log))?;
+                response.etag
+            }
+            206 => {
+                let added = std::fs::read(&temp).map_err(local_error(&temp))?;
+                let _ = std::fs::remove_file(&temp);
+                std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&log)
+                    .and_then(|mut file| file.write_all(&added))
+                    .map_err(local_error(&log))?;
+                response.etag
+            }
+            // Nothing new; a HEAD request tells the version.
+            416 => {
+                let _ = std::fs::remove_file(&temp);
+                let head = self.request("HEAD", &log_key, &[], None, &temp)?;
+                let _ = std::fs::remove_file(&temp);
+                head.etag
+            }
+            404 => {
+                let _ = std::fs::remove_file(&temp);
+                write_atomically(&log, &[])?;
+                None
+            }
+            _ => return Err(self.failed(&log_key, &response, &temp)),
+        };
+
+        let store = Store::open(local.to_owned());
+        let names = blob_names(&store);
+        let dir = blob::blob_dir(local);
+        let blob_prefix = blob::blob_dir(Path::new(&self.key));
+        // Whoever can write to the bucket picks these names, so a path is
+        // never fetched or written to.
+        if let Some(name) = names.iter().find(|name| !blob::is_blob_name(name)) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(CliError::Remote(format!(
+                "{} refers to a blob named `{name}`, which is not a blob name",
+                self.url()
+            )));
+        }
+        if !names.is_empty() {
+            std::fs::create_dir_all(&dir).map_err(local_error(&dir))?;
+        }
+        // Blobs are named after their texts, so only those missing here
+        // are fetched.
+        for name in names.iter().filter(|name| !dir.join(name).exists()) {
+            let key = key_of(&blob_prefix.join(name));
+            let response = self.request("GET", &key, &[], None, &temp)?;
+            if response.status != 200 {
+                return Err(self.failed(&key, &response, &temp));
+            }
+            let target = dir.join(name);
+            std::fs::rename(&temp, &target).map_err(local_error(&target))?;
+        }
+
+        Ok(Pulled {
+            log_len: std::fs::metadata(&log).map_or(0, |metadata| metadata.len()),
+            blobs: names,
+            versions: (store_etag, log_etag),
+        })
+    }
+
+    pub fn push(&self, store: &Store, pulled: &Pulled) -> Result<(), CliError> {
+        let temp = store
+            .path
+            .with_extension(format!("response-{}", std::process::id()));
+        let dir = blob::blob_dir(&store.path);
+        let blob_prefix = blob::blob_dir(Path::new(&self.key));
+        let names = blob_names(store);
+
+        for name in names.iter().filter(|name| !pulled.blobs.contains(*name)) {
+            let key = key_of(&blob_prefix.join(name));
+            let response = self.request("PUT", &key, &[], Some(&dir.join(name)), &temp)?;
+            if response.status != 200 {
+                return Err(self.failed(&key, &response, &temp));
+            }
+        }
+
+        let (store_etag, log_etag) = &pulled.versions;
+        let condition = [Self::condition(store_etag.as_deref())];
+        let response = self.request("PUT", &self.key, &condition, Some(&store.path), &temp)?;
+        let log = history::history_path(&store.path);
+        match response.status {
+            200 => {}
+            // Someone saved in between; forget the events of this save, which
+            // the next pull would contradict.
+            409 | 412 => {
+                let _ = std::fs::remove_file(&temp);
+                if let Ok(file) = std::fs::OpenOptions::new().write(true).open(&log) {
+                    let _ = file.set_len(pulled.log_len);
+                }
+                return Err(CliError::Remote(format!(
+                    "{} changed since it was read, so nothing was saved; run the command again",
+                    self.url()
+                )));
+            }
+            _ => return Err(self.failed(&self.key, &response, &temp)),
+        }
+
+        self.append_log(&log, pulled.log_len, log_etag.clone(), &temp)?;
+
+        for name in pulled.blobs.iter().filter(|name| !names.contains(*name)) {
+            let key = key_of(&blob_prefix.join(name));
+            self.request("DELETE", &key, &[], None, &temp)?;
+        }
+        let _ = std::fs::remove_file(&temp);
+
+        Ok(())
+    }
+
+    /// Writes the local log, whose first `pulled_len` bytes came from the
+    /// bucket, back to it. When another save appended in the meantime its
+    /// events are fetched and ours go after them.
+    fn append_log(
+        &self,
+        log: &Path,
+        pulled_len: u64,
+        mut etag: Option<String>,
+        temp: &Path,
+    ) -> Result<(), CliError> {
+        let contents = std::fs::read(log).unwrap_or_default();
+        let added = contents
+            .get(pulled_len as usize..)
+            .unwrap_or_default()
+            .to_vec();
+        if added.is_empty() {
+            return Ok(());
+        }
+        let log_key = key_of(&history::history_path(Path::new(&self.key)));
+
+        for _ in 0..LOG_ATTEMPTS {
+            let condition = [Self::condition(etag.as_deref())];
+            let response = self.request("PUT", &log_key, &condition, Some(log), temp)?;
+            match response.status {
+                200 => return Ok(()),
+                409 | 412 => {
+                    let response = self.request("GET", &log_key, &[], None, temp)?;
+                    if response.status != 200 {
+                        return Err(self.failed(&log_key, &response, temp));
+                    }
+                    let mut merged = std::fs::read(temp).map_err(local_error(temp))?;
+                    merged.extend_from_slice(&added);
+                    write_atomically(log, &merged)?;
+                    etag = response.etag;
+                }
+                _ => return Err(self.failed(&log_key, &response, temp)),
+            }
+        }
+
+        Err(CliError::Remote(format!(
+            "the event log of {} kept changing; its latest events were not sent",
+            self.url()
+        )))
+    }
+
+    pub fn upload(&self, contents: &[u8]) -> Result<(), CliError> {
+        let local = self.cache_path()?;
+        let source = local.with_extension(format!("upload-{}", std::process::id()));
+        let temp = local.with_extension(format!("response-{}", std::process::id()));
+        std::fs::write(&source, contents).map_err(local_error(&source))?;
+
+        let response = self.request("PUT", &self.key, &[], Some(&source), &temp);
+        let _ = std::fs::remove_file(&source);
+        let response = response?;
+        if response.status != 200 {
+            return Err(self.failed(&self.key, &response, &temp));
+        }
+        let _ = std::fs::remove_file(&temp);
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! `pause --until <date>`: time off. Until then reminders and follow-ups
+//! are held back, rules on the clock (`due within`, `overdue`) do not fire,
+//! idle tasks do not age and quotas do not nag. The first command after the
+//! pause runs out, or `pause none`, prints one digest of what came due in
+//! the meantime instead.
+//!
+//! The pause belongs to the person, not the store, so it is kept in the
+//! config directory and covers every store.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{
+    config::config_dir,
+    remind,
+    task::{Status, Task},
+    time::{format_duration, format_local},
+    CliError,
+};
+
+/// The current pause, or the last one once it is over.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Pause {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// When the pause ended and its digest was shown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed: Option<DateTime<Utc>>,
+}
+
+pub fn path() -> PathBuf {
+    config_dir().join("pause.json")
+}
+
+/// The pause, ongoing or last, if there ever was one.
+pub fn load() -> Option<Pause> {
+    let bytes = std::fs::read(path()).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// The pause ongoing at `now`, if any.
+pub fn active(now: DateTime<Utc>) -> Option<Pause> {
+    load().filter(|pause| pause.active(now))
+}
+
+impl Pause {
+    pub fn active(&self, now: DateTime<Utc>) -> bool {
+        self.resumed.is_none() && now < self.until
+    }
+
+    /// When the pause ended, or is to end.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.resumed
+            .map_or(self.until, |resumed| resumed.min(self.until))
+    }
+
+    /// Whether `at` fell within the pause.
+    pub fn covers(&self, at: DateTime<Utc>) -> bool {
+        self.since <= at && at < self.end()
+    }
+
+    /// How much of the time from `from` to `to` was spent paused.
+    pub fn overlap(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Duration {
+        let start = from.max(self.since);
+        let end = to.min(self.end());
+        (end - start).max(Duration::zero())
+    }
+
+    pub fn save(&self) -> Result<(), CliError> {
+        let path = path();
+        let write = std::fs::create_dir_all(config_dir()).and_then(|()| {
+            std::fs::write(
+                &path,
+                serde_json::to_vec_pretty(self).expect("could not convert to json"),
+            )
+        });
+        write.map_err(|error| CliError::Write { path, error })
+    }
+
+    /// Ends the pause at `now` and says what came due meanwhile in `tasks`.
+    pub fn resume(mut self, tasks: &[Task], now: DateTime<Utc>) -> Result<String, CliError> {
+        self.resumed = Some(now.min(self.until));
+        self.save()?;
+        Ok(digest(&self, tasks, now))
+    }
+}
+
+/// Ends a pause that ran out before `now`, once, with its digest.
+pub fn resume_if_over(tasks: &[Task], now: DateTime<Utc>) -> Result<Option<String>, CliError> {
+    match load() {
+        Some(pause) if pause.resumed.is_none() && now >= pause.until => {
+            pause.resume(tasks, now).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn section(out: &mut String, title: &str, lines: Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+    out.push_str(&format!("{title} ({}):\n", lines.len()));
+    for line in lines {
+        out.push_str(&format!("    {line}\n"));
+    }
+}
+
+/// What came due in `tasks` during `pause`, and what is overdue at `now`.
+pub fn digest(pause: &Pause, tasks: &[Task], now: DateTime<Utc>) -> String {
+    let end = pause.end();
+    let mut out = format!(
+        "welcome back: paused from {} to {} ({})\n",
+        format_local(&pause.since),
+        format_local(&end),
+        format_duration(end - pause.since)
+    );
+    let open = || tasks.iter().filter(|task| task.status != Status::Done);
+
+    let reminders = open()
+        .filter(|task| remind::reminder_time(task).is_some_and(|at| pause.covers(at)))
+        .map(|task| remind::summary_line(task, now))
+        .collect();
+    section(&mut out, "reminders that came due", reminders);
+
+    let follow_ups = open()
+        .filter(|task| {
+            task.delegated
+                .as_ref()
+                .and_then(|delegated| delegated.follow_up)
+                .is_some_and(|at| pause.covers(at))
+        })
+        .map(|task| remind::follow_up_line(task, now))
+        .collect();
+    section(&mut out, "follow-ups that came due", follow_ups);
+
+    let added = open()
+        .filter(|task| pause.covers(task.created_at))
+        .map(|task| format!("{}. {}", task.id, task.description))
+        .collect();
+    section(&mut out, "tasks added meanwhile", added);
+
+    let overdue = open()
+        .filter(|task| task.due.is_some_and(|due| due < now))
+        .count();
+    match overdue {
+        0 => out.push_str("nothing is overdue\n"),
+        1 => out.push_str("1 task is overdue now\n"),
+        n => out.push_str(&format!("{n} tasks are overdue now\n")),
+    }
+    out
+}
@@ -0,0 +1,339 @@
+//! Natural-language time phrases for filters such as `--due "before next
+//! standup"` or `--created "last week"`.
+//!
+//! A phrase is a day (`today`, `friday`, `2024-06-01`), a period (`this
+//! week`, `last month`, `past 3 days`), a named time from the config file
+//! (`standup`, `next review`) or any date accepted elsewhere, optionally
+//! prefixed with `before`, `after`, `since` or `until`.
+
+use crate::{
+    config::Config,
+    locale::Locale,
+    task::TimeBlock,
+    time::{local_to_utc, parse_datetime},
+    CliError,
+};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, Utc, Weekday};
+
+/// Half-open interval `[start, end)`; a missing bound is unbounded.
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl Span {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|start| at >= start) && self.end.is_none_or(|end| at < end)
+    }
+}
+
+enum When {
+    Range(DateTime<Utc>, DateTime<Utc>),
+    Point(DateTime<Utc>),
+}
+
+impl When {
+    fn start(&self) -> DateTime<Utc> {
+        match self {
+            Self::Range(start, _) | Self::Point(start) => *start,
+        }
+    }
+
+    fn end(&self) -> DateTime<Utc> {
+        match self {
+            Self::Range(_, end) => *end,
+            Self::Point(at) => *at + Duration::seconds(1),
+        }
+    }
+}
+
+fn invalid(phrase: &str) -> CliError {
+    CliError::InvalidPhrase(phrase.to_owned())
+}
+
+fn day(date: NaiveDate) -> Result<When, CliError> {
+    let start = local_to_utc(date.and_time(NaiveTime::MIN))?;
+    let end = local_to_utc((date + Duration::days(1)).and_time(NaiveTime::MIN))?;
+    Ok(When::Range(start, end))
+}
+
+fn days(from: NaiveDate, count: i64) -> Result<When, CliError> {
+    let start = local_to_utc(from.and_time(NaiveTime::MIN))?;
+    let end = local_to_utc((from + Duration::days(count)).and_time(NaiveTime::MIN))?;
+    Ok(When::Range(start, end))
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    word.get(..3)?.parse::<Weekday>().ok()
+}
+
+fn month_start(year: i32, month: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, 1).expect("months are 1-12")
+}
+
+fn month(today: NaiveDate, offset: i32) -> Result<When, CliError> {
+    let index = today.year() * 12 + today.month0() as i32 + offset;
+    let start = month_start(index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    let next = index + 1;
+    let end = month_start(next.div_euclid(12), next.rem_euclid(12) as u32 + 1);
+    days(start, (end - start).num_days())
+}
+
+/// Next (or, with `backwards`, previous) occurrence of a named time. Values
+/// are `HH:MM` for daily times or `<weekday> HH:MM` for weekly ones.
+fn named_time(value: &str, now: DateTime<Local>, backwards: bool) -> Option<DateTime<Utc>> {
+    let mut words = value.split_whitespace();
+    let (day, time) = match (words.next()?, words.next()) {
+        (time, None) => (None, time),
+        (day, Some(time)) => (Some(weekday(&day.to_lowercase())?), time),
+    };
+    let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+
+    let step = if backwards { -1 } else { 1 };
+    (0..=7)
+        .map(|offset| now.date_naive() + Duration::days(offset * step))
+        .filter(|date| day.is_none_or(|day| date.weekday() == day))
+        .filter_map(|date| local_to_utc(date.and_time(time)).ok())
+        .find(|at| if backwards { *at <= now } else { *at > now })
+}
+
+fn parse_when(phrase: &str, config: &Config) -> Result<When, CliError> {
+    let named = &config.named_times;
+    let now = Local::now();
+    let today = now.date_naive();
+    let words = phrase.split_whitespace().collect::<Vec<_>>();
+    let week_start = Locale::new(config).week_of(today);
+
+    let when = match words.as_slice() {
+        ["today"] => day(today)?,
+        ["yesterday"] => day(today - Duration::days(1))?,
+        ["tomorrow"] => day(today + Duration::days(1))?,
+        ["now"] => When::Point(now.with_timezone(&Utc)),
+        ["this", "week"] => days(week_start, 7)?,
+        ["last", "week"] => days(week_start - Duration::weeks(1), 7)?,
+        ["next", "week"] => days(week_start + Duration::weeks(1), 7)?,
+        ["this", "month"] => month(today, 0)?,
+        ["last", "month"] => month(today, -1)?,
+        ["next", "month"] => month(today, 1)?,
+        ["this", "year"] => {
+            let start = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap();
+            let end = NaiveDate::from_ymd_opt(today.year() + 1, 1, 1).unwrap();
+            days(start, (end - start).num_days())?
+        }
+        [direction @ ("last" | "past" | "next"), count, unit] => {
+            let count = count.parse::<i64>().map_err(|_| invalid(phrase))?;
+            let unit = match unit.trim_end_matches('s') {
+                "minute" => Duration::minutes(1),
+                "hour" => Duration::hours(1),
+                "day" => Duration::days(1),
+                "week" => Duration::weeks(1),
+                "month" => Duration::days(30),
+                _ => return Err(invalid(phrase)),
+            };
+            let now = now.with_timezone(&Utc);
+            if *direction == "next" {
+                When::Range(now, now + unit * count as i32)
+            } else {
+                When::Range(now - unit * count as i32, now)
+            }
+        }
+        [name] | ["next" | "last", name] if named.contains_key(*name) => {
+            let backwards = words[0] == "last";
+            let at = named_time(&named[*name], now, backwards).ok_or_else(|| {
+                CliError::InvalidPhrase(format!(
+                    "named time `{name}` = {:?}, expected `HH:MM` or `<weekday> HH:MM`",
+                    named[*name]
+                ))
+            })?;
+            When::Point(at)
+        }
+        [direction @ ("last" | "next"), name] if weekday(name).is_some() => {
+            let target = weekday(name).unwrap();
+            let step = if *direction == "next" { 1 } else { -1 };
+            let date = (1..=7)
+                .map(|offset| today + Duration::days(offset * step))
+                .find(|date| date.weekday() == target)
+                .unwrap();
+            day(date)?
+        }
+        [name] if weekday(name).is_some_and(|_| name.len() >= 3) => {
+            // The coming occurrence, today included.
+            let target = weekday(name).unwrap();
+            let date = (0..7)
+                .map(|offset| today + Duration::days(offset))
+                .find(|date| date.weekday() == target)
+                .unwrap();
+            day(date)?
+        }
+        _ => {
+            if let Ok(date) = NaiveDate::parse_from_str(phrase, "%Y-%m-%d") {
+                day(date)?
+            } else {
+                When::Point(parse_datetime(phrase).map_err(|_| invalid(phrase))?)
+            }
+        }
+    };
+
+    Ok(when)
+}
+
+/// Parses a time block such as `tomorrow 14:00-15:30`, `friday 9:00-10:00`
+/// or just `14:00-15:00` for today. A block ending before it starts runs
+/// past midnight.
+pub fn parse_block(text: &str, config: &Config) -> Result<TimeBlock, CliError> {
+    let text = text.trim();
+    let (day, range) = text.rsplit_once(' ').unwrap_or(("today", text));
+    let (from, to) = range.split_once('-').ok_or_else(|| invalid(text))?;
+    let time = |value: &str| NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| invalid(text));
+    let (from, to) = (time(from)?, time(to)?);
+
+    let date = match parse_when(&day.to_lowercase(), config)? {
+        When::Range(start, _) => start.with_timezone(&Local).date_naive(),
+        When::Point(_) => return Err(invalid(text)),
+    };
+    let end_date = if to <= from {
+        date + Duration::days(1)
+    } else {
+        date
+    };
+
+    Ok(TimeBlock {
+        start: local_to_utc(date.and_time(from))?,
+        end: local_to_utc(end_date.and_time(to))?,
+    })
+}
+
+/// Parses a phrase into the span of time it covers. A bare point in time
+/// (`next standup`) covers everything up to it.
+pub fn parse_span(phrase: &str, config: &Config) -> Result<Span, CliError> {
+    let normalized = phrase.trim().to_lowercase();
+    let (prefix, rest) = match normalized.split_once(' ') {
+        Some((prefix @ ("before" | "after" | "since" | "until" | "by"), rest)) => {
+            (Some(prefix), rest)
+        }
+        _ => (None, normalized.as_str()),
+    };
+    let when = parse_when(rest, config)?;
+
+    Ok(match (prefix, &when) {
+        (Some("before"), _) => Span {
+            start: None,
+            end: Some(when.start()),
+        },
+        (Some("after"), _) => Span {
+            start: Some(when.end()),
+            end: None,
+        },
+        (Some("since"), _) => Span {
+            start: Some(when.start()),
+            end: None,
+        },
+        (Some(_), _) | (None, When::Point(_)) => Span {
+            start: None,
+            end: Some(when.end()),
+        },
+        (None, When::Range(start, end)) => Span {
+            start: Some(*start),
+            end: Some(*end),
+        },
+    })
+}
+
+/// A deadline given as a date or a phrase: the last second of the day or
+/// period it names, so `friday` is due by the end of Friday.
+pub fn parse_deadline(phrase: &str, config: &Config) -> Result<DateTime<Utc>, CliError> {
+    if let Ok(at) = parse_datetime(phrase) {
+        return Ok(at);
+    }
+    parse_span(phrase, config)?
+        .end
+        .map(|end| end - Duration::seconds(1))
+        .ok_or_else(|| invalid(phrase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(date: &str, time: &str) -> DateTime<Utc> {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let time = NaiveTime::parse_from_str(time, "%H:%M:%S").unwrap();
+        local_to_utc(date.and_time(time)).unwrap()
+    }
+
+    #[test]
+    fn dates_cover_the_whole_day_and_prefixes_open_one_end() {
+        let config = Config::default();
+        let span = |phrase| parse_span(phrase, &config).unwrap();
+        let (start, end) = (
+            local("2024-06-01", "00:00:00"),
+            local("2024-06-02", "00:00:00"),
+        );
+
+        let day = span("2024-06-01");
+        assert_eq!((day.start, day.end), (Some(start), Some(end)));
+        assert!(day.contains(local("2024-06-01", "23:59:59")));
+        assert!(!day.contains(end));
+
+        assert_eq!(
+            (
+                span("Before 2024-06-01").start,
+                span("before 2024-06-01").end
+            ),
+            (None, Some(start))
+        );
+        assert_eq!(
+            (span("after 2024-06-01").start, span("after 2024-06-01").end),
+            (Some(end), None)
+        );
+        assert_eq!(span("since 2024-06-01").start, Some(start));
+        assert_eq!(
+            (span("by 2024-06-01").start, span("by 2024-06-01").end),
+            (None, Some(end))
+        );
+    }
+
+    #[test]
+    fn named_times_and_weekdays() {
+        let mut config = Config::default();
+        config
+            .named_times
+            .insert("standup".to_owned(), "09:30".to_owned());
+        let now = Utc::now();
+
+        let next = parse_span("before next standup", &config)
+            .unwrap()
+            .end
+            .unwrap();
+        assert!(next > now && next <= now + Duration::days(1));
+        assert_eq!(
+            next.with_timezone(&Local).time(),
+            NaiveTime::from_hms_opt(9, 30, 0).unwrap()
+        );
+
+        let last = parse_span("since last standup", &config)
+            .unwrap()
+            .start
+            .unwrap();
+        assert!(last <= now && last > now - Duration::days(1));
+
+        let friday = parse_deadline("friday", &config)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(friday.weekday(), Weekday::Fri);
+        assert_eq!(friday.time(), NaiveTime::from_hms_opt(23, 59, 59).unwrap());
+    }
+
+    #[test]
+    fn blocks_and_invalid_phrases() {
+        let config = Config::default();
+        let block = parse_block("2024-06-01 23:00-01:30", &config).unwrap();
+        assert_eq!(block.start, local("2024-06-01", "23:00:00"));
+        assert_eq!(block.end, local("2024-06-02", "01:30:00"));
+
+        assert!(parse_block("2024-06-01 23:00", &config).is_err());
+        assert!(parse_span("past few days", &config).is_err());
+        assert!(parse_span("next 3 fortnights", &config).is_err());
+    }
+}
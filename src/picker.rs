@@ -0,0 +1,40 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Whether the user can be asked to choose between candidates.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Shows a numbered menu on stderr and reads the choice from stdin. Returns
+/// `None` when the user enters nothing or an invalid number.
+pub fn pick(prompt: &str, items: &[String]) -> Option<usize> {
+    let mut stderr = io::stderr();
+
+    let _ = writeln!(stderr, "{prompt}");
+    for (index, item) in items.iter().enumerate() {
+        let _ = writeln!(stderr, "  {}) {item}", index + 1);
+    }
+    let _ = write!(stderr, "choose 1-{} (enter to cancel): ", items.len());
+    let _ = stderr.flush();
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).ok()?;
+
+    let choice = answer.trim().parse::<usize>().ok()?;
+    (1..=items.len()).contains(&choice).then(|| choice - 1)
+}
+
+/// Asks a yes/no question on stderr; anything but an empty answer or `y`
+/// counts as no.
+pub fn confirm(question: &str) -> bool {
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "{question} [Y/n] ");
+    let _ = stderr.flush();
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
+}
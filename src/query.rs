@@ -0,0 +1,465 @@
+//! Filter expressions for `list --where`, e.g.
+//!
+//! ```text
+//! status != done and (tag:work or priority >= high) and created > 2024-01-01
+//! ```
+//!
+//! Comparisons are `field op value` with `=`, `!=`, `<`, `<=`, `>`, `>=` and
+//! `~` (contains). `tag:name` is shorthand for `tag = name`. Terms combine
+//! with `and`, `or`, `not` and parentheses. The value `none` matches tasks
+//! where an optional field is unset.
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::{
+    task::{Priority, Status, Task},
+    time::parse_datetime,
+};
+
+#[derive(Debug)]
+pub struct QueryError(pub String);
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid filter: {}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Field {
+    Id,
+    Description,
+    Status,
+    Priority,
+    Tag,
+    Location,
+    Created,
+    Updated,
+    Due,
+}
+
+impl FromStr for Field {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Self::Id),
+            "description" | "desc" => Ok(Self::Description),
+            "status" => Ok(Self::Status),
+            "priority" => Ok(Self::Priority),
+            "tag" | "tags" => Ok(Self::Tag),
+            "location" => Ok(Self::Location),
+            "created" => Ok(Self::Created),
+            "updated" => Ok(Self::Updated),
+            "due" => Ok(Self::Due),
+            _ => Err(QueryError(format!("unknown field `{s}`"))),
+        }
+    }
+}
+
+/// Right-hand side of a comparison, checked against the field's type while
+/// parsing so evaluation cannot fail.
+#[derive(Debug)]
+enum Value {
+    None,
+    Text(String),
+    Status(Status),
+    Priority(Priority),
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Colon,
+    Open,
+    Close,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ':' | '~' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ':' => Token::Colon,
+                    _ => Token::Op(Op::Contains),
+                });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, eq) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => return Err(QueryError("expected `!=`".to_owned())),
+                }));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => text.push(ch),
+                        None => return Err(QueryError("unterminated quote".to_owned())),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek() {
+                    // `tag:work` splits at the colon, `14:00` does not.
+                    let tag_colon =
+                        ch == ':' && !word.ends_with(|last: char| last.is_ascii_digit());
+                    if ch.is_whitespace() || "()~=!<>\"'".contains(ch) || tag_colon {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, QueryError> {
+        if self.keyword("not") {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, QueryError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(QueryError("expected `)`".to_owned())),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let field = name.to_ascii_lowercase().parse::<Field>()?;
+                let op = match self.next() {
+                    Some(Token::Colon) => Op::Eq,
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(QueryError(format!("expected an operator after `{name}`"))),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value) | Token::Quoted(value)) => value,
+                    _ => return Err(QueryError(format!("expected a value after `{name}`"))),
+                };
+                Ok(Expr::Compare(field, op, parse_value(field, op, &value)?))
+            }
+            Some(token) => Err(QueryError(format!("unexpected {token:?}"))),
+            None => Err(QueryError("unexpected end of filter".to_owned())),
+        }
+    }
+}
+
+fn parse_value(field: Field, op: Op, value: &str) -> Result<Value, QueryError> {
+    let invalid = || QueryError(format!("invalid value `{value}`"));
+
+    if value.eq_ignore_ascii_case("none") {
+        return match op {
+            Op::Eq | Op::Ne => Ok(Value::None),
+            _ => Err(QueryError("`none` only works with `=` and `!=`".to_owned())),
+        };
+    }
+
+    let value = match field {
+        Field::Id | Field::Description | Field::Tag | Field::Location => {
+            Value::Text(value.to_owned())
+        }
+        Field::Status => Value::Status(Status::from_str(value).map_err(|_| invalid())?),
+        Field::Priority => Value::Priority(Priority::from_str(value).map_err(|_| invalid())?),
+        Field::Created | Field::Updated | Field::Due => {
+            if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                Value::Date(date)
+            } else {
+                Value::DateTime(parse_datetime(value).map_err(|_| invalid())?)
+            }
+        }
+    };
+
+    let ordered = matches!(
+        value,
+        Value::Priority(_) | Value::Date(_) | Value::DateTime(_)
+    );
+    match op {
+        Op::Lt | Op::Le | Op::Gt | Op::Ge if !ordered => Err(QueryError(
+            "ordering only works on priorities and dates".to_owned(),
+        )),
+        Op::Contains if !matches!(value, Value::Text(_)) => {
+            Err(QueryError("`~` only works on text fields".to_owned()))
+        }
+        _ => Ok(value),
+    }
+}
+
+/// A parsed filter expression.
+#[derive(Debug)]
+pub struct Filter(Expr);
+
+impl Filter {
+    pub fn matches(&self, task: &Task) -> bool {
+        self.0.matches(task)
+    }
+}
+
+pub fn parse(input: &str) -> Result<Filter, QueryError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let expr = parser.or()?;
+
+    match parser.peek() {
+        None => Ok(Filter(expr)),
+        Some(token) => Err(QueryError(format!("unexpected {token:?}"))),
+    }
+}
+
+fn compare(op: Op, ordering: Option<Ordering>) -> bool {
+    let Some(ordering) = ordering else {
+        // Unset fields match only `!= value`.
+        return op == Op::Ne;
+    };
+
+    match op {
+        Op::Eq => ordering.is_eq(),
+        Op::Ne => ordering.is_ne(),
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        Op::Ge => ordering.is_ge(),
+        Op::Contains => unreachable!("checked while parsing"),
+    }
+}
+
+fn matches_text(op: Op, text: &str, value: &str) -> bool {
+    let text = text.to_lowercase();
+    let value = value.to_lowercase();
+
+    match op {
+        Op::Contains => text.contains(&value),
+        Op::Ne => text != value,
+        _ => text == value,
+    }
+}
+
+fn date_field(task: &Task, field: Field) -> Option<DateTime<Utc>> {
+    match field {
+        Field::Created => Some(task.created_at),
+        Field::Updated => Some(task.updated_at),
+        Field::Due => task.due,
+        _ => None,
+    }
+}
+
+fn is_set(task: &Task, field: Field) -> bool {
+    match field {
+        Field::Priority => task.priority.is_some(),
+        Field::Tag => !task.tags.is_empty(),
+        Field::Due => task.due.is_some(),
+        Field::Description => !task.description.is_empty(),
+        Field::Location => task.location.is_some(),
+        Field::Id | Field::Status | Field::Created | Field::Updated => true,
+    }
+}
+
+impl Expr {
+    fn matches(&self, task: &Task) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(task) && b.matches(task),
+            Self::Or(a, b) => a.matches(task) || b.matches(task),
+            Self::Not(expr) => !expr.matches(task),
+            Self::Compare(field, op, value) => match (field, value) {
+                (_, Value::None) => is_set(task, *field) == (*op == Op::Ne),
+                (Field::Tag, Value::Text(tag)) => match op {
+                    Op::Contains => task.tags.iter().any(|t| matches_text(*op, t, tag)),
+                    Op::Ne => !task.has_tag(tag),
+                    _ => task.has_tag(tag),
+                },
+                (Field::Id, Value::Text(id)) => {
+                    let id = id.to_ascii_uppercase();
+                    let own = task.id.to_string();
+                    match op {
+                        Op::Ne => !own.starts_with(&id),
+                        _ => own.starts_with(&id),
+                    }
+                }
+                (Field::Description, Value::Text(text)) => {
+                    matches_text(*op, &task.description, text)
+                }
+                (Field::Location, Value::Text(text)) => {
+                    matches_text(*op, task.location.as_deref().unwrap_or(""), text)
+                }
+                (Field::Status, Value::Status(status)) => {
+                    (task.status == *status) == (*op == Op::Eq)
+                }
+                (Field::Priority, Value::Priority(priority)) => {
+                    compare(*op, task.priority.map(|own| own.cmp(priority)))
+                }
+                (_, Value::Date(date)) => compare(
+                    *op,
+                    date_field(task, *field)
+                        .map(|at| at.with_timezone(&Local).date_naive().cmp(date)),
+                ),
+                (_, Value::DateTime(at)) => {
+                    compare(*op, date_field(task, *field).map(|own| own.cmp(at)))
+                }
+                _ => unreachable!("value types are checked while parsing"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(description: &str, status: Status, priority: Option<Priority>, tags: &[&str]) -> Task {
+        let mut task = Task::new(description.to_owned());
+        task.status = status;
+        task.priority = priority;
+        task.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        task
+    }
+
+    #[test]
+    fn terms_combine_with_precedence() {
+        let filter = parse("status != done and (tag:work or priority >= high)").unwrap();
+
+        assert!(filter.matches(&task("a", Status::ToDo, None, &["Work"])));
+        assert!(filter.matches(&task("b", Status::InProgress, Some(Priority::Urgent), &[])));
+        assert!(!filter.matches(&task("c", Status::ToDo, Some(Priority::Low), &[])));
+        assert!(!filter.matches(&task("d", Status::Done, None, &["work"])));
+
+        // `and` binds tighter than `or`.
+        let filter = parse("tag:a or tag:b and not tag:c").unwrap();
+        assert!(filter.matches(&task("e", Status::ToDo, None, &["a", "c"])));
+        assert!(!filter.matches(&task("f", Status::ToDo, None, &["b", "c"])));
+    }
+
+    #[test]
+    fn text_dates_and_none() {
+        let mut due = task("Pay the Rent", Status::ToDo, None, &[]);
+        due.due = Some(parse_datetime("2024-03-01T12:00:00Z").unwrap());
+        let undated = task("call mum", Status::ToDo, None, &[]);
+
+        let filter = parse(r#"desc ~ "the rent""#).unwrap();
+        assert!(filter.matches(&due));
+        assert!(!filter.matches(&undated));
+
+        let filter = parse("due < 2024-06-01").unwrap();
+        assert!(filter.matches(&due));
+        assert!(!filter.matches(&undated));
+
+        let filter = parse("due = none").unwrap();
+        assert!(!filter.matches(&due));
+        assert!(filter.matches(&undated));
+    }
+
+    #[test]
+    fn values_are_checked_while_parsing() {
+        let error = |input| parse(input).unwrap_err().to_string();
+
+        assert_eq!(
+            error("colour = red"),
+            "invalid filter: unknown field `colour`"
+        );
+        assert_eq!(
+            error("status = later"),
+            "invalid filter: invalid value `later`"
+        );
+        assert!(error("tag > work").contains("ordering only works"));
+        assert!(error("priority ~ high").contains("`~` only works"));
+        assert!(error("due > none").contains("`none` only works"));
+        assert!(parse("(tag:a").is_err());
+        assert!(parse("tag:a tag:b").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! `quick-add`: a one-line prompt for getting a thought into the inbox
+//! without leaving what you are doing, meant to be bound to a global hotkey
+//! next to a running `task-cli daemon`, e.g. in sway or i3:
+//!
+//! ```text
+//! bindsym $mod+i exec task-cli quick-add
+//! ```
+//!
+//! In a terminal the prompt is a line editor. Started without one, as from a
+//! hotkey, builds with the `gui` feature show a dialog instead (zenity or
+//! kdialog, or AppleScript on macOS) and confirm with a notification.
+
+use std::io::IsTerminal;
+
+use crate::{
+    line_editor::{LineEditor, ReadResult},
+    CliError,
+};
+
+/// Asks for the text of the new task; `None` when the prompt was cancelled
+/// or left empty.
+pub fn prompt() -> Result<Option<String>, CliError> {
+    let text = if std::io::stdin().is_terminal() {
+        let line = LineEditor::new(Vec::new())
+            .read_line("inbox> ", |_| Vec::new())
+            .map_err(|error| CliError::Read {
+                path: "<stdin>".into(),
+                error,
+            })?;
+        match line {
+            ReadResult::Line(line) => line,
+            ReadResult::Interrupted | ReadResult::Eof => return Ok(None),
+        }
+    } else {
+        dialog()?.unwrap_or_default()
+    };
+
+    Ok(Some(text.trim().to_owned()).filter(|text| !text.is_empty()))
+}
+
+#[cfg(feature = "gui")]
+fn dialog() -> Result<Option<String>, CliError> {
+    use std::process::Command;
+
+    let title = "Quick add";
+    let label = "Add to the inbox:";
+    let commands: Vec<(&str, Vec<String>)> = if cfg!(target_os = "macos") {
+        let script = format!(
+            "text returned of (display dialog {label:?} default answer \"\" with title {title:?})"
+        );
+        vec![("osascript", vec!["-e".to_owned(), script])]
+    } else {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect();
+        vec![
+            (
+                "zenity",
+                args(&["--entry", "--title", title, "--text", label]),
+            ),
+            ("kdialog", args(&["--title", title, "--inputbox", label])),
+        ]
+    };
+
+    for (program, args) in commands {
+        match Command::new(program).args(&args).output() {
+            // Cancelling exits non-zero.
+            Ok(output) if output.status.success() => {
+                return Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()));
+            }
+            Ok(_) => return Ok(None),
+            Err(_) => continue,
+        }
+    }
+
+    Err(CliError::Prompt(
+        "no dialog program found; install zenity or kdialog".to_owned(),
+    ))
+}
+
+#[cfg(not(feature = "gui"))]
+fn dialog() -> Result<Option<String>, CliError> {
+    Err(CliError::Prompt(
+        "no terminal to prompt in; run it in one, e.g. `xterm -e task-cli quick-add`, \
+         or use a build with the `gui` feature for a dialog"
+            .to_owned(),
+    ))
+}
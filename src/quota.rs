@@ -0,0 +1,72 @@
+//! Soft limits on how much may pile up. Over a limit nothing is refused;
+//! every command just ends with a line saying so.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{
+    capture::INBOX_TAG,
+    pause,
+    task::{section, Section, Status, Task},
+};
+
+/// The `[quotas]` table of the config file; unset limits are not checked.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Quotas {
+    /// Open tasks tagged `inbox`.
+    pub inbox: Option<usize>,
+    /// Open tasks past their due date.
+    pub overdue: Option<usize>,
+    /// Tasks in the active section of `list`.
+    pub active: Option<usize>,
+    pub in_progress: Option<usize>,
+}
+
+/// The quotas `tasks` are over, as one line, or `None` when all is well.
+/// Nothing nags during a pause.
+pub fn nag(tasks: &[Task], quotas: &Quotas, now: DateTime<Utc>) -> Option<String> {
+    if pause::active(now).is_some() {
+        return None;
+    }
+    let open = || tasks.iter().filter(|task| task.status != Status::Done);
+    let checks = [
+        (
+            quotas.inbox,
+            open().filter(|task| task.has_tag(INBOX_TAG)).count(),
+            "in the inbox",
+        ),
+        (
+            quotas.overdue,
+            open()
+                .filter(|task| task.due.is_some_and(|due| due < now))
+                .count(),
+            "overdue",
+        ),
+        (
+            quotas.active,
+            tasks
+                .iter()
+                .filter(|task| section(task, tasks, now) == Section::Active)
+                .count(),
+            "active",
+        ),
+        (
+            quotas.in_progress,
+            open()
+                .filter(|task| task.status == Status::InProgress)
+                .count(),
+            "in progress",
+        ),
+    ];
+
+    let over = checks
+        .iter()
+        .filter_map(|(limit, count, what)| {
+            let limit = (*limit)?;
+            (*count > limit).then(|| format!("{count} {what} (limit {limit})"))
+        })
+        .collect::<Vec<_>>();
+
+    (!over.is_empty()).then(|| format!("over quota: {}", over.join(", ")))
+}
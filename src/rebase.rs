@@ -0,0 +1,235 @@
+//! `rebase`: cleans up many tasks at once from one editor buffer, in the
+//! manner of `git rebase -i`. Each line is a task with what to do with it:
+//!
+//! ```text
+//! keep   #12 Write quarterly report
+//! done   #15 Reply to the vendor about the contract
+//! delete #18 Clean out the garage
+//! snooze #21 Renew car insurance
+//! snooze=+2w #22 Plan the database migration
+//! ```
+//!
+//! Editing a description renames the task, and moving lines reorders the
+//! tasks. Everything is applied together in a single save.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use ulid::Ulid;
+
+use crate::{
+    store::Store,
+    task::{Status, Task},
+    time::{format_local, parse_datetime},
+    CliError,
+};
+
+const HEADER: &str = "\
+# Change the word at the start of a line, then save and quit:
+#
+#   keep        leave the task as it is
+#   done        mark it done
+#   delete      move it to the trash
+#   snooze      hide it until the `--snooze` time (tomorrow unless given)
+#   snooze=<t>  hide it until <t>, e.g. snooze=+3d or snooze=2024-09-01
+#
+# Editing a description renames the task and moving lines reorders the
+# tasks. Removing a line keeps its task; removing them all does nothing.
+";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Keep,
+    Done,
+    Delete,
+    Snooze(DateTime<Utc>),
+}
+
+/// One line of the buffer.
+pub struct Directive {
+    pub id: Ulid,
+    pub action: Action,
+    /// The new description, when it was edited.
+    pub description: Option<String>,
+}
+
+fn invalid(message: String) -> CliError {
+    CliError::InvalidEdit(message)
+}
+
+/// How a task is named in the buffer: its number, or else its id.
+fn name(task: &Task) -> String {
+    match task.number {
+        Some(number) => format!("#{number}"),
+        None => task.id.to_string(),
+    }
+}
+
+/// Whether the description fits on the line, so it can be edited there.
+fn editable(task: &Task) -> bool {
+    task.description_blob.is_none() && !task.description.contains('\n')
+}
+
+pub fn render(tasks: &[&Task]) -> String {
+    let width = tasks.iter().map(|task| name(task).len()).max().unwrap_or(0);
+    let mut out = HEADER.to_owned();
+    out.push('\n');
+
+    for task in tasks {
+        let description = task.description.lines().next().unwrap_or_default();
+        out.push_str(&format!("keep   {:width$} {description}\n", name(task)));
+    }
+
+    out
+}
+
+/// Reads the directives, in the order of their lines; `snooze` without a
+/// time snoozes until `snooze_until`.
+pub fn parse(
+    buffer: &str,
+    selected: &[&Task],
+    snooze_until: DateTime<Utc>,
+) -> Result<Vec<Directive>, CliError> {
+    let mut seen = HashSet::new();
+    let mut directives = Vec::new();
+
+    for (index, line) in buffer.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at = |message: String| invalid(format!("line {line_number}: {message}"));
+
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let (reference, description) = rest
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .unwrap_or((rest.trim_start(), ""));
+
+        let action = match word.split_once('=') {
+            None if word == "keep" => Action::Keep,
+            None if word == "done" => Action::Done,
+            None if word == "delete" => Action::Delete,
+            None if word == "snooze" => Action::Snooze(snooze_until),
+            Some(("snooze", time)) => Action::Snooze(
+                parse_datetime(time).map_err(|_| at(format!("invalid snooze time `{time}`")))?,
+            ),
+            _ => {
+                return Err(at(format!(
+                    "unknown action `{word}`, expected keep, done, delete or snooze"
+                )))
+            }
+        };
+
+        let task = selected
+            .iter()
+            .find(|task| name(task) == reference || task.id.to_string() == reference)
+            .ok_or_else(|| {
+                at(format!(
+                    "`{reference}` is not one of the tasks being rebased"
+                ))
+            })?;
+        if !seen.insert(task.id) {
+            return Err(at(format!("`{reference}` appears twice")));
+        }
+
+        let description = description.trim();
+        let first_line = task.description.lines().next().unwrap_or_default();
+        let description = if description.is_empty() || description == first_line {
+            None
+        } else if editable(task) {
+            Some(description.to_owned())
+        } else {
+            return Err(at(format!(
+                "the description of `{reference}` has several lines and cannot be edited here"
+            )));
+        };
+
+        directives.push(Directive {
+            id: task.id,
+            action,
+            description,
+        });
+    }
+
+    Ok(directives)
+}
+
+/// Applies the directives and describes each change, one line per task;
+/// tasks whose lines were moved take the new order.
+pub fn apply(store: &mut Store, rendered: &[Ulid], directives: Vec<Directive>) -> Vec<String> {
+    let mut summary = Vec::new();
+    let now = Utc::now();
+
+    let order = directives
+        .iter()
+        .map(|directive| directive.id)
+        .collect::<Vec<_>>();
+    let kept_order = rendered
+        .iter()
+        .filter(|id| order.contains(id))
+        .copied()
+        .collect::<Vec<_>>();
+
+    for directive in directives {
+        let Some(task) = store.find_mut(directive.id) else {
+            continue;
+        };
+        let mut changes = Vec::new();
+
+        if let Some(description) = directive.description {
+            changes.push(format!("renamed to {description:?}"));
+            task.set_description(description);
+        }
+        match directive.action {
+            Action::Keep => {}
+            Action::Done if task.status != Status::Done => {
+                changes.push("done".to_owned());
+                task.set_status(Status::Done);
+            }
+            Action::Done => {}
+            Action::Snooze(until) => {
+                changes.push(format!("snoozed until {}", format_local(&until)));
+                task.wait_until = Some(until);
+            }
+            Action::Delete => {
+                let description = task.description.clone();
+                if store.trash(directive.id).is_some() {
+                    summary.push(format!("- {}. {description}", directive.id));
+                }
+                continue;
+            }
+        }
+
+        if !changes.is_empty() {
+            task.updated_at = now;
+            summary.push(format!("~ {}. {}", task.id, changes.join(", ")));
+        }
+    }
+
+    if order != kept_order {
+        // The moved tasks take each other's places in the store.
+        let order = order
+            .into_iter()
+            .filter(|id| store.find(*id).is_some())
+            .collect::<Vec<_>>();
+        let places = store
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| order.contains(&task.id))
+            .map(|(place, _)| place)
+            .collect::<Vec<_>>();
+        let mut moved = order
+            .iter()
+            .map(|id| store.find(*id).expect("checked above").clone())
+            .collect::<Vec<_>>()
+            .into_iter();
+        for place in places {
+            store.tasks[place] = moved.next().expect("one task per place");
+        }
+        summary.push(format!("reordered {} tasks", order.len()));
+    }
+
+    summary
+}
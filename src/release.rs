@@ -0,0 +1,297 @@
+//! Releases: `--version --check` says whether a newer release is out and
+//! `self-update` installs it. Every release on GitHub carries one binary per
+//! platform, `task-cli-<arch>-<os>` for Linux and macOS, and next to it an
+//! SSH signature of it, `<binary>.sig`, made with the release key:
+//!
+//! ```text
+//! ssh-keygen -Y sign -f release_key -n task-cli-release task-cli-x86_64-linux
+//! ```
+//!
+//! The public key is built in from `TASK_CLI_RELEASE_KEY` at compile time,
+//! and `self-update` only installs binaries that `ssh-keygen -Y verify`
+//! accepts with it; builds without a key cannot update themselves. GitHub is
+//! reached through curl, like object storage.
+
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{args::Args, CliError};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const REPOSITORY: &str = "bismitpanda/roadmap-sh-task-tracker";
+
+/// The `ssh-ed25519 AAAA…` key releases are signed with.
+const RELEASE_KEY: Option<&str> = option_env!("TASK_CLI_RELEASE_KEY");
+
+/// Identity and namespace of release signatures, as given to `ssh-keygen`.
+const SIGNER: &str = "release@task-cli";
+const NAMESPACE: &str = "task-cli-release";
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// The parts of a GitHub release that matter here.
+#[derive(Deserialize, Debug)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+/// `v1.2.3`, `1.2.3` and `1.2.3-rc.1` as numbers, ignoring the pre-release.
+fn parse_version(text: &str) -> Option<(u64, u64, u64)> {
+    let text = text.trim().strip_prefix('v').unwrap_or(text.trim());
+    let core = text.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>());
+    let version = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    parts.next().is_none().then_some(version)
+}
+
+impl Release {
+    pub fn version(&self) -> &str {
+        self.tag_name.strip_prefix('v').unwrap_or(&self.tag_name)
+    }
+
+    /// Whether the release is newer than the running binary.
+    pub fn is_newer(&self) -> bool {
+        parse_version(&self.tag_name) > parse_version(VERSION)
+    }
+
+    fn asset(&self, name: &str) -> Result<&Asset, CliError> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| CliError::Release(format!("release {} has no `{name}`", self.tag_name)))
+    }
+}
+
+/// The name of this platform's binary in a release.
+pub fn binary_name() -> String {
+    format!(
+        "task-cli-{}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+}
+
+/// Where the latest release is described; `TASK_CLI_RELEASES_URL` points
+/// elsewhere, such as a mirror.
+fn latest_url() -> String {
+    std::env::var("TASK_CLI_RELEASES_URL")
+        .unwrap_or_else(|_| format!("https://api.github.com/repos/{REPOSITORY}/releases/latest"))
+}
+
+fn is_github_api(url: &str) -> bool {
+    url.strip_prefix("https://")
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .is_some_and(|host| host.eq_ignore_ascii_case("api.github.com"))
+}
+
+/// Fetches `url` into `output`, or returns the body without one.
+fn curl(url: &str, output: Option<&Path>) -> Result<Vec<u8>, CliError> {
+    let mut command = Command::new("curl");
+    command
+        .args(["-fsSL", "-H", "Accept: application/vnd.github+json"])
+        .stdin(Stdio::null());
+    // Without a token GitHub allows sixty requests an hour. Mirrors and the
+    // hosts serving downloads never get to see it.
+    if let Some(token) = std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|_| is_github_api(url))
+    {
+        command.args(["-H", &format!("Authorization: Bearer {token}")]);
+    }
+    if let Some(output) = output {
+        command.arg("-o").arg(output);
+    }
+    let result = command
+        .arg(url)
+        .output()
+        .map_err(|err| CliError::Release(format!("cannot run curl: {err}")))?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(CliError::Release(format!(
+            "cannot fetch {url}: {}",
+            stderr.trim().trim_start_matches("curl: ")
+        )));
+    }
+    Ok(result.stdout)
+}
+
+pub fn latest() -> Result<Release, CliError> {
+    let url = latest_url();
+    let body = curl(&url, None)?;
+    serde_json::from_slice(&body)
+        .map_err(|err| CliError::Release(format!("cannot read the release at {url}: {err}")))
+}
+
+/// `--version`, and with `--check` whether a newer release is out.
+pub fn print_version(args: &Args) -> Result<(), CliError> {
+    println!("task-cli {VERSION}");
+    if !args.flag("check") {
+        return Ok(());
+    }
+
+    let release = latest()?;
+    if release.is_newer() {
+        println!("{} is available: {}", release.version(), release.html_url);
+        println!("`task-cli self-update` installs it");
+    } else {
+        println!("this is the latest release");
+    }
+    Ok(())
+}
+
+/// Checks the signature `signature` of `binary` against the release key.
+fn verify(binary: &Path, signature: &Path, key: &str, dir: &Path) -> Result<(), CliError> {
+    let signers = dir.join(".task-cli-release-signers");
+    std::fs::write(
+        &signers,
+        format!("{SIGNER} namespaces=\"{NAMESPACE}\" {key}\n"),
+    )
+    .map_err(|err| CliError::Release(format!("cannot write {}: {err}", signers.display())))?;
+    let input = std::fs::File::open(binary)
+        .map_err(|err| CliError::Release(format!("cannot read {}: {err}", binary.display())))?;
+
+    let result = Command::new("ssh-keygen")
+        .args(["-Y", "verify", "-I", SIGNER, "-n", NAMESPACE, "-f"])
+        .arg(&signers)
+        .arg("-s")
+        .arg(signature)
+        .stdin(input)
+        .output();
+    let _ = std::fs::remove_file(&signers);
+    match result {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(_) => Err(CliError::Release(
+            "the signature of the download does not match the release key; nothing was installed"
+                .to_owned(),
+        )),
+        Err(err) => Err(CliError::Release(format!(
+            "cannot run ssh-keygen to check the signature: {err}"
+        ))),
+    }
+}
+
+/// Downloads, verifies and tries out the binary of `release` at `download`.
+fn fetch(release: &Release, key: &str, download: &Path, dir: &Path) -> Result<(), CliError> {
+    let name = binary_name();
+    let binary = release.asset(&name)?;
+    let signature = release.asset(&format!("{name}.sig"))?;
+
+    std::fs::File::create(download).map_err(|err| {
+        CliError::Release(format!(
+            "cannot write to {}: {err}; run self-update as the user who installed task-cli",
+            dir.display()
+        ))
+    })?;
+    curl(&binary.browser_download_url, Some(download))?;
+    let signature_path = download.with_extension("sig");
+    let verified = curl(&signature.browser_download_url, Some(&signature_path))
+        .and_then(|_| verify(download, &signature_path, key, dir));
+    let _ = std::fs::remove_file(&signature_path);
+    verified?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(download, std::fs::Permissions::from_mode(0o755)).map_err(
+            |err| CliError::Release(format!("cannot make the download runnable: {err}")),
+        )?;
+    }
+
+    // A binary for the wrong platform is signed too.
+    let runs = Command::new(download)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).trim()
+                == format!("task-cli {}", release.version())
+        });
+    if !runs {
+        return Err(CliError::Release(format!(
+            "the {name} of release {} does not run here",
+            release.tag_name
+        )));
+    }
+    Ok(())
+}
+
+/// `self-update [--force]`: installs the latest release in place of the
+/// running binary; `--force` installs it even when it is not newer.
+pub fn self_update(args: &Args) -> Result<(), CliError> {
+    if let Some(other) = args.positional(1) {
+        panic!("unexpected `{other}`, self-update takes no arguments");
+    }
+    let Some(key) = RELEASE_KEY else {
+        return Err(CliError::Release(format!(
+            "this build has no release key to check updates with; download a release from \
+             https://github.com/{REPOSITORY}/releases or rebuild it"
+        )));
+    };
+
+    let release = latest()?;
+    if !release.is_newer() && !args.flag("force") {
+        println!("task-cli {VERSION} is the latest release");
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .map_err(|err| CliError::Release(format!("cannot find the running binary: {err}")))?;
+    let dir = exe.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+    // Next to the binary, so that replacing it is one rename on one file
+    // system and a failed update leaves the old binary.
+    let download = dir.join(format!(".{}.download", binary_name()));
+
+    let installed = fetch(&release, key, &download, &dir).and_then(|()| {
+        std::fs::rename(&download, &exe)
+            .map_err(|err| CliError::Release(format!("cannot replace {}: {err}", exe.display())))
+    });
+    if installed.is_err() {
+        let _ = std::fs::remove_file(&download);
+    }
+    installed?;
+
+    println!("updated task-cli {VERSION} to {}", release.version());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_token_only_goes_to_the_github_api() {
+        assert!(is_github_api(
+            "https://api.github.com/repos/a/b/releases/latest"
+        ));
+        assert!(!is_github_api("http://api.github.com/repos/a/b"));
+        assert!(!is_github_api("https://mirror.example/api.github.com/"));
+        assert!(!is_github_api("https://api.github.com.example/"));
+        assert!(!is_github_api(
+            "https://objects.githubusercontent.com/release-assets/1"
+        ));
+    }
+
+    #[test]
+    fn versions_compare_by_number() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("0.10.0-rc.1"), Some((0, 10, 0)));
+        assert_eq!(parse_version("1.2"), None);
+        assert!(parse_version("v0.10.0") > parse_version("0.9.9"));
+    }
+}
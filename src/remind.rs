@@ -0,0 +1,101 @@
+use chrono::{DateTime, Duration, Utc};
+use std::process::Command;
+
+use crate::{
+    task::{Status, Task},
+    time::{format_duration, format_local},
+};
+
+/// Moment the reminder for `task` should fire, if it has a due date.
+pub fn reminder_time(task: &Task) -> Option<DateTime<Utc>> {
+    let due = task.due?;
+    Some(due - Duration::seconds(task.remind_before_secs.unwrap_or(0)))
+}
+
+/// Open tasks whose reminder fires before `now + within`, soonest first.
+pub fn due_reminders(tasks: &[Task], now: DateTime<Utc>, within: Duration) -> Vec<&Task> {
+    let mut pending = tasks
+        .iter()
+        .filter(|task| task.status != Status::Done)
+        .filter(|task| reminder_time(task).is_some_and(|at| at <= now + within))
+        .collect::<Vec<_>>();
+
+    pending.sort_by_key(|task| task.due);
+    pending
+}
+
+/// Open delegated tasks whose follow-up is before `now + within`, earliest
+/// first.
+pub fn due_follow_ups(tasks: &[Task], now: DateTime<Utc>, within: Duration) -> Vec<&Task> {
+    let follow_up = |task: &Task| {
+        task.delegated
+            .as_ref()
+            .and_then(|delegated| delegated.follow_up)
+    };
+    let mut pending = tasks
+        .iter()
+        .filter(|task| task.status != Status::Done)
+        .filter(|task| follow_up(task).is_some_and(|at| at <= now + within))
+        .collect::<Vec<_>>();
+
+    pending.sort_by_key(|task| follow_up(task));
+    pending
+}
+
+pub fn follow_up_line(task: &Task, now: DateTime<Utc>) -> String {
+    let delegated = task.delegated.as_ref().expect("follow-ups are delegated");
+    format!(
+        "{}. {} (follow up with {}, delegated {} ago)",
+        task.id,
+        task.description,
+        delegated.to,
+        format_duration(now - delegated.at)
+    )
+}
+
+pub fn summary_line(task: &Task, now: DateTime<Utc>) -> String {
+    let due = task.due.expect("reminders always have a due date");
+    let label = if due < now { "overdue" } else { "due" };
+
+    format!(
+        "{}. {} ({label} {})",
+        task.id,
+        task.description,
+        format_local(&due)
+    )
+}
+
+/// Shows a desktop notification, returning whether one could be sent.
+pub fn notify(task: &Task, now: DateTime<Utc>) -> bool {
+    let due = task.due.expect("reminders always have a due date");
+    let title = if due < now {
+        "Task overdue"
+    } else {
+        "Task due"
+    };
+    let body = format!("{} ({})", task.description, format_local(&due));
+
+    notify_message(title, &body)
+}
+
+/// Shows a desktop notification with `title` and `body`, returning whether
+/// one could be sent.
+pub fn notify_message(title: &str, body: &str) -> bool {
+    let status = if cfg!(target_os = "macos") {
+        Command::new("osascript")
+            .arg("-e")
+            .arg(format!(
+                "display notification {:?} with title {:?}",
+                body, title
+            ))
+            .status()
+    } else {
+        Command::new("notify-send")
+            .arg("--app-name=task-cli")
+            .arg(title)
+            .arg(body)
+            .status()
+    };
+
+    status.is_ok_and(|status| status.success())
+}
@@ -0,0 +1,584 @@
+//! Stores on another machine, named by URL instead of a path, such as
+//! `--file ssh://me@homeserver/~/tasks.json`, `--file s3://bucket/tasks.json`
+//! or `--file redis://localhost/standup`.
+//! Commands work on a local copy in the cache directory: it is brought up to
+//! date before every command, and changes are sent back afterwards. Over
+//! ssh a lock on the host keeps other writers out; object stores refuse a
+//! write when the store changed since it was read (see [`crate::object`]),
+//! and so do Redis boards (see [`crate::redis`]).
+
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::{blob, commands::shell_quote, history, signal, store::Store, sync, CliError};
+
+/// How long to wait for another writer's remote lock.
+const WAIT: Duration = Duration::from_secs(5);
+
+/// How often `list --watch` pulls a store that cannot announce changes.
+pub const POLL: Duration = Duration::from_secs(10);
+
+/// How long `list --watch` trusts a subscription before pulling anyway, in
+/// case the connection silently went away.
+#[cfg(feature = "redis")]
+const WATCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub enum Remote {
+    Ssh(Ssh),
+    #[cfg(feature = "object-storage")]
+    Object(crate::object::Bucket),
+    #[cfg(feature = "redis")]
+    Redis(crate::redis::Board),
+}
+
+/// A store reached with `ssh`, which is all the host needs besides `sh` and
+/// `tar`.
+pub struct Ssh {
+    /// `user@host` or `host`, as passed to `ssh`.
+    destination: String,
+    port: Option<u16>,
+    /// Path on the host; relative paths start at the login directory.
+    path: PathBuf,
+}
+
+const SCHEMES: [&str; 4] = ["ssh://", "s3://", "gs://", "redis://"];
+
+/// Whether `location` names a remote store rather than a file.
+pub fn is_remote(location: &Path) -> bool {
+    let location = location.to_string_lossy();
+    SCHEMES.iter().any(|scheme| location.starts_with(scheme))
+}
+
+/// Refuses remote stores in `command`, which keeps a store open for longer
+/// than a single command can hold it.
+pub fn local_only(location: &Path, command: &str) {
+    if is_remote(location) {
+        panic!("`{command}` needs a local store; remote stores work with single commands");
+    }
+}
+
+/// The local copy of a remote store under the cache directory, at
+/// `relative`; its directory is created if need be.
+pub(crate) fn cached(relative: &Path) -> Result<PathBuf, CliError> {
+    let path = dirs::cache_dir()
+        .unwrap()
+        .join("task-cli/remote")
+        .join(relative);
+
+    let dir = path.parent().expect("remote paths name a file");
+    std::fs::create_dir_all(dir).map_err(local_error(dir))?;
+    Ok(path)
+}
+
+impl Remote {
+    /// The remote store `location` names, or `None` for a local path.
+    pub fn parse(location: &Path) -> Option<Self> {
+        let text = location.to_string_lossy();
+        if text.starts_with("ssh://") {
+            return Ssh::parse(&text).map(Self::Ssh);
+        }
+
+        #[cfg(feature = "object-storage")]
+        if let Some(bucket) = crate::object::Bucket::parse(&text) {
+            return Some(Self::Object(bucket));
+        }
+        #[cfg(feature = "redis")]
+        if let Some(board) = crate::redis::Board::parse(&text) {
+            return Some(Self::Redis(board));
+        }
+        if text.starts_with("redis://") {
+            panic!("this build has no redis support; rebuild with `--features redis`");
+        }
+        if is_remote(location) {
+            panic!("this build has no object storage; rebuild with `--features object-storage`");
+        }
+        None
+    }
+
+    pub fn url(&self) -> String {
+        match self {
+            Self::Ssh(ssh) => ssh.url(),
+            #[cfg(feature = "object-storage")]
+            Self::Object(bucket) => bucket.url(),
+            #[cfg(feature = "redis")]
+            Self::Redis(board) => board.url(),
+        }
+    }
+
+    pub fn cache_path(&self) -> Result<PathBuf, CliError> {
+        match self {
+            Self::Ssh(ssh) => ssh.cache_path(),
+            #[cfg(feature = "object-storage")]
+            Self::Object(bucket) => bucket.cache_path(),
+            #[cfg(feature = "redis")]
+            Self::Redis(board) => board.cache_path(),
+        }
+    }
+
+    /// Keeps other writers out until the lock is dropped, where the store
+    /// can be locked at all; object stores and Redis boards check for
+    /// changes on write instead.
+    pub fn lock(&self) -> Result<Option<RemoteLock<'_>>, CliError> {
+        let lock = match self {
+            Self::Ssh(ssh) => ssh.lock().map(Some),
+            #[cfg(feature = "object-storage")]
+            Self::Object(_) => Ok(None),
+            #[cfg(feature = "redis")]
+            Self::Redis(_) => Ok(None),
+        };
+        if let Err(err) = &lock {
+            sync::failed(self, "lock", err);
+        }
+        lock
+    }
+
+    /// Brings the local copy at `local` up to date: the store, the new part
+    /// of the event log and the blobs in use.
+    pub fn pull(&self, local: &Path) -> Result<Pulled, CliError> {
+        let pulled = match self {
+            Self::Ssh(ssh) => ssh.pull(local),
+            #[cfg(feature = "object-storage")]
+            Self::Object(bucket) => bucket.pull(local),
+            #[cfg(feature = "redis")]
+            Self::Redis(board) => board.pull(local),
+        };
+        match &pulled {
+            Ok(pulled) => sync::pulled(self, pulled),
+            Err(err) => sync::failed(self, "pull", err),
+        }
+        pulled
+    }
+
+    /// Sends a saved `store` back: the store file, the events logged since
+    /// the pull and blobs that were added or are no longer used.
+    pub fn push(&self, store: &Store, pulled: &Pulled) -> Result<(), CliError> {
+        let pushed = match self {
+            Self::Ssh(ssh) => ssh.push(store, pulled),
+            #[cfg(feature = "object-storage")]
+            Self::Object(bucket) => bucket.push(store, pulled),
+            #[cfg(feature = "redis")]
+            Self::Redis(board) => board.push(store, pulled),
+        };
+        match &pushed {
+            Ok(()) => sync::pushed(self),
+            Err(err) => sync::failed(self, "push", err),
+        }
+        pushed
+    }
+
+    /// What to check when syncing fails for no reason the error gives.
+    pub fn hint(&self) -> String {
+        match self {
+            Self::Ssh(ssh) => format!(
+                "check that `ssh {}` logs in without asking for anything",
+                ssh.destination
+            ),
+            #[cfg(feature = "object-storage")]
+            Self::Object(_) => "check AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_REGION \
+                               and TASK_CLI_S3_ENDPOINT"
+                .to_owned(),
+            #[cfg(feature = "redis")]
+            Self::Redis(_) => {
+                "check that the Redis server is up and the password in the url".to_owned()
+            }
+        }
+    }
+
+    /// Writes `contents` to the remote path as they are, replacing whatever
+    /// is there, as `backup` does.
+    pub fn upload(&self, contents: &[u8]) -> Result<(), CliError> {
+        match self {
+            Self::Ssh(ssh) => ssh.upload(contents),
+            #[cfg(feature = "object-storage")]
+            Self::Object(bucket) => bucket.upload(contents),
+            #[cfg(feature = "redis")]
+            Self::Redis(board) => board.upload(contents),
+        }
+    }
+
+    /// Waits for someone to change the store, for `list --watch`. Redis
+    /// boards announce their changes; other stores are pulled again after
+    /// [`POLL`].
+    pub fn wait_for_change(&self) -> Result<(), CliError> {
+        match self {
+            #[cfg(feature = "redis")]
+            Self::Redis(board) => board.wait_for_change(WATCH_TIMEOUT),
+            _ => {
+                std::thread::sleep(POLL);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Blobs the store file refers to; tasks in memory have their texts inline.
+pub(crate) fn blob_names(store: &Store) -> HashSet<String> {
+    store
+        .saved_tasks()
+        .flat_map(|task| [&task.description_blob, &task.notes_blob])
+        .flatten()
+        .cloned()
+        .collect()
+}
+
+fn scripted(path: &Path) -> String {
+    shell_quote(&path.to_string_lossy())
+}
+
+pub(crate) fn local_error(path: &Path) -> impl Fn(std::io::Error) -> CliError + '_ {
+    move |error| CliError::Write {
+        path: path.to_owned(),
+        error,
+    }
+}
+
+impl Ssh {
+    fn parse(location: &str) -> Option<Self> {
+        let rest = location.strip_prefix("ssh://")?;
+        let (authority, path) = rest
+            .split_once('/')
+            .unwrap_or_else(|| panic!("invalid store url `{location}`, expected ssh://host/path"));
+
+        let (destination, port) = match authority.rsplit_once(':') {
+            Some((destination, port)) => {
+                let port = port
+                    .parse()
+                    .unwrap_or_else(|_| panic!("invalid port in `{location}`"));
+                (destination, Some(port))
+            }
+            None => (authority, None),
+        };
+        if destination.is_empty() || destination.starts_with('-') || path.is_empty() {
+            panic!("invalid store url `{location}`, expected ssh://host/path");
+        }
+
+        // `ssh://host/~/tasks.json` is relative to the login directory.
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => PathBuf::from(relative),
+            None => Path::new("/").join(path),
+        };
+
+        Some(Self {
+            destination: destination.to_owned(),
+            port,
+            path,
+        })
+    }
+
+    fn url(&self) -> String {
+        let port = self.port.map(|port| format!(":{port}")).unwrap_or_default();
+        let path = self.path.to_string_lossy();
+        let path = path
+            .strip_prefix('/')
+            .map_or(format!("~/{path}"), str::to_owned);
+        format!("ssh://{}{port}/{path}", self.destination)
+    }
+
+    fn cache_path(&self) -> Result<PathBuf, CliError> {
+        let mut host = self.destination.clone();
+        if let Some(port) = self.port {
+            host.push_str(&format!("_{port}"));
+        }
+        let relative = self.path.strip_prefix("/").unwrap_or(&self.path);
+        cached(&Path::new(&host).join(relative))
+    }
+
+    fn ssh(&self, script: &str) -> Command {
+        let mut command = Command::new("ssh");
+        if let Some(port) = self.port {
+            command.args(["-p", &port.to_string()]);
+        }
+        command.arg("--").arg(&self.destination).arg(script);
+        command
+    }
+
+    fn failed(&self, status: std::process::ExitStatus) -> CliError {
+        CliError::Remote(format!("`ssh {}` failed ({status})", self.destination))
+    }
+
+    /// Runs `script` on the host with `input` on its stdin.
+    fn run(&self, script: &str, input: &[u8]) -> Result<Vec<u8>, CliError> {
+        let mut child = self
+            .ssh(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| CliError::Remote(format!("cannot run ssh: {err}")))?;
+
+        // The script may not read its input, which closes the pipe early.
+        let _ = child.stdin.take().expect("stdin is piped").write_all(input);
+        let output = child
+            .wait_with_output()
+            .map_err(|err| CliError::Remote(format!("cannot run ssh: {err}")))?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(self.failed(output.status))
+        }
+    }
+
+    /// Takes the lock on the host, waiting a little for another writer.
+    fn lock(&self) -> Result<RemoteLock<'_>, CliError> {
+        let path = self.path.with_extension("lock");
+        let lock = scripted(&path);
+        let owner = format!("{}:{}", hostname(), std::process::id());
+        // Exit status 3 means someone else holds the lock; their name is
+        // printed instead.
+        let script = format!(
+            "mkdir -p \"$(dirname {lock})\" && \
+             if (set -C; echo {} > {lock}) 2>/dev/null; then :; else cat {lock}; exit 3; fi",
+            shell_quote(&owner)
+        );
+        let started = Instant::now();
+
+        loop {
+            let output = self
+                .ssh(&script)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|err| CliError::Remote(format!("cannot run ssh: {err}")))?;
+
+            match output.status.code() {
+                Some(0) => {
+                    signal::register_exit_command(Some(&self.unlock_command(&path)));
+                    return Ok(RemoteLock { remote: self, path });
+                }
+                Some(3) if started.elapsed() < WAIT => {
+                    std::thread::sleep(Duration::from_millis(250));
+                }
+                Some(3) => {
+                    let holder = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+                    return Err(CliError::Remote(format!(
+                        "{} is locked by {holder}; if that process is gone, remove {} on the host",
+                        self.url(),
+                        path.display()
+                    )));
+                }
+                _ => return Err(self.failed(output.status)),
+            }
+        }
+    }
+
+    fn unlock_command(&self, lock: &Path) -> Command {
+        self.ssh(&format!("rm -f {}", scripted(lock)))
+    }
+
+    fn pull(&self, local: &Path) -> Result<Pulled, CliError> {
+        let remote = scripted(&self.path);
+        let contents = self.run(&format!("if [ -e {remote} ]; then cat {remote}; fi"), &[])?;
+        write_atomically(local, &contents)?;
+
+        // The log only grows, so only what was added since the last pull is
+        // fetched. The first line of the reply is the remote log's size; a
+        // smaller log than ours was started over and is fetched whole.
+        let log = history::history_path(local);
+        let have = std::fs::metadata(&log).map_or(0, |metadata| metadata.len());
+        let remote_log = scripted(&history::history_path(&self.path));
+        let reply = self.run(
+            &format!(
+                "if [ -e {remote_log} ]; then size=$(wc -c < {remote_log}); echo $size; \
+                 if [ $size -ge {have} ]; then tail -c +{} {remote_log}; else cat {remote_log}; fi; \
+                 else echo 0; fi",
+                have + 1
+            ),
+            &[],
+        )?;
+        let (size, added) = match reply.iter().position(|byte| *byte == b'\n') {
+            Some(newline) => (&reply[..newline], &reply[newline + 1..]),
+            None => (&reply[..], &[][..]),
+        };
+        let size = String::from_utf8_lossy(size)
+            .trim()
+            .parse::<u64>()
+            .unwrap_or(0);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(size >= have)
+            .truncate(size < have)
+            .open(&log)
+            .map_err(local_error(&log))?;
+        file.write_all(added).map_err(local_error(&log))?;
+
+        // Blobs are named after their texts, so only those missing here
+        // are fetched, in one go.
+        let store = Store::open(local.to_owned());
+        let blobs = blob_names(&store);
+        let dir = blob::blob_dir(local);
+        let missing = blobs
+            .iter()
+            .filter(|name| !dir.join(name).exists())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let names = missing
+                .iter()
+                .map(|name| shell_quote(name))
+                .collect::<Vec<_>>();
+            let archive = self.run(
+                &format!(
+                    "cd {} && tar -cf - -- {}",
+                    scripted(&blob::blob_dir(&self.path)),
+                    names.join(" ")
+                ),
+                &[],
+            )?;
+            std::fs::create_dir_all(&dir).map_err(local_error(&dir))?;
+            tar(&["-xf", "-", "-C", &dir.to_string_lossy()], &archive, &dir)?;
+        }
+
+        Ok(Pulled {
+            log_len: std::fs::metadata(&log).map_or(0, |metadata| metadata.len()),
+            blobs,
+            versions: (None, None),
+        })
+    }
+
+    /// Needs the lock, which `main` holds until after the push.
+    fn push(&self, store: &Store, pulled: &Pulled) -> Result<(), CliError> {
+        let dir = blob::blob_dir(&store.path);
+        let remote_dir = blob::blob_dir(&self.path);
+        let blobs = blob_names(store);
+        let written = blobs
+            .iter()
+            .filter(|name| !pulled.blobs.contains(*name))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !written.is_empty() {
+            let dir_arg = dir.to_string_lossy();
+            let mut tar_args = vec!["-cf", "-", "-C", &dir_arg, "--"];
+            tar_args.extend(&written);
+            let archive = tar(&tar_args, &[], &dir)?;
+            let remote_dir = scripted(&remote_dir);
+            self.run(
+                &format!("mkdir -p {remote_dir} && tar -xf - -C {remote_dir}"),
+                &archive,
+            )?;
+        }
+
+        let log = history::history_path(&store.path);
+        let events = std::fs::read(&log).map_err(|error| CliError::Read {
+            path: log.clone(),
+            error,
+        })?;
+        let added = events.get(pulled.log_len as usize..).unwrap_or_default();
+        if !added.is_empty() {
+            let remote_log = scripted(&history::history_path(&self.path));
+            self.run(&format!("cat >> {remote_log}"), added)?;
+        }
+
+        let contents = std::fs::read(&store.path).map_err(|error| CliError::Read {
+            path: store.path.clone(),
+            error,
+        })?;
+        self.upload(&contents)?;
+
+        let unused = pulled
+            .blobs
+            .iter()
+            .filter(|name| !blobs.contains(*name))
+            .map(|name| scripted(&remote_dir.join(name)))
+            .collect::<Vec<_>>();
+        if !unused.is_empty() {
+            self.run(&format!("rm -f {}", unused.join(" ")), &[])?;
+        }
+
+        Ok(())
+    }
+
+    fn upload(&self, contents: &[u8]) -> Result<(), CliError> {
+        let remote = scripted(&self.path);
+        let temp = scripted(&self.path.with_extension("tmp"));
+        self.run(
+            &format!("mkdir -p \"$(dirname {remote})\" && cat > {temp} && mv {temp} {remote}"),
+            contents,
+        )?;
+        Ok(())
+    }
+}
+
+/// What [`Remote::pull`] found, to work out what a save has to send back.
+pub struct Pulled {
+    pub(crate) log_len: u64,
+    /// Blobs in use, which the store holds already.
+    pub(crate) blobs: HashSet<String>,
+    /// Versions of the store and its log as read, for object stores to
+    /// check against on write.
+    pub(crate) versions: (Option<String>, Option<String>),
+}
+
+/// The lock on a remote store, released when dropped.
+pub struct RemoteLock<'r> {
+    remote: &'r Ssh,
+    path: PathBuf,
+}
+
+/// Released when dropped, or by the signal handler if the command is
+/// interrupted.
+impl Drop for RemoteLock<'_> {
+    fn drop(&mut self) {
+        let _ = self
+            .remote
+            .unlock_command(&self.path)
+            .stdin(Stdio::null())
+            .output();
+        signal::register_exit_command(None);
+    }
+}
+
+/// Runs the local `tar`, which packs and unpacks blobs for the host's.
+fn tar(args: &[&str], input: &[u8], dir: &Path) -> Result<Vec<u8>, CliError> {
+    let mut child = Command::new("tar")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(local_error(dir))?;
+    let _ = child.stdin.take().expect("stdin is piped").write_all(input);
+    let output = child.wait_with_output().map_err(local_error(dir))?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(CliError::Remote(format!(
+            "tar failed on {} ({})",
+            dir.display(),
+            output.status
+        )))
+    }
+}
+
+fn hostname() -> String {
+    let mut name = [0u8; 256];
+    let len = unsafe {
+        if libc::gethostname(name.as_mut_ptr().cast(), name.len()) != 0 {
+            return "unknown".to_owned();
+        }
+        name.iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len())
+    };
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+/// The local copy is replaced whole, as the store is when saved.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), CliError> {
+    if contents.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(local_error(path)(error))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    let temp = path.with_extension(format!("tmp-{}", std::process::id()));
+    std::fs::write(&temp, contents)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .map_err(local_error(path))
+}
@@ -0,0 +1,196 @@
+use chrono::Utc;
+use std::{panic, path::PathBuf, str::FromStr};
+
+use crate::{
+    args::{split_words, Args},
+    commands::{self, Commands, COMMAND_NAMES},
+    config::Config,
+    line_editor::{LineEditor, ReadResult},
+    lock::StoreLock,
+    quota, remote, rules,
+    store::{self, Store},
+    task::Task,
+    usage,
+};
+
+const STATUS_NAMES: &[&str] = &["todo", "in-progress", "done"];
+const PRIORITY_NAMES: &[&str] = &["low", "medium", "high", "urgent", "none"];
+const HISTORY_LIMIT: usize = 1000;
+
+type PanicHook = Box<dyn Fn(&panic::PanicHookInfo<'_>) + Sync + Send>;
+
+fn history_path() -> PathBuf {
+    dirs::home_dir().unwrap().join(".task-cli-history")
+}
+
+/// Commands report bad input by panicking; this swaps the panic hook for one
+/// that only prints the message so a session can carry on. Returns the hook
+/// to restore afterwards.
+pub(crate) fn show_panic_messages() -> PanicHook {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "command failed".to_owned());
+        eprintln!("error: {message}");
+    }));
+    default_hook
+}
+
+pub(crate) fn completions(before_cursor: &str, tasks: &[Task]) -> Vec<String> {
+    let words = before_cursor.split_whitespace().collect::<Vec<_>>();
+    let current = if before_cursor.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        words.last().copied().unwrap_or("")
+    };
+    let index = if current.is_empty() {
+        words.len()
+    } else {
+        words.len() - 1
+    };
+
+    let candidates = if index == 0 {
+        COMMAND_NAMES
+            .iter()
+            .chain(&["help", "exit"])
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>()
+    } else if index > 0 && words[index - 1] == "--status"
+        || (index == 2 && words[0] == "mark")
+        || (index == 1 && words[0] == "list")
+    {
+        STATUS_NAMES.iter().map(|name| name.to_string()).collect()
+    } else if index == 2 && words[0] == "priority" {
+        PRIORITY_NAMES.iter().map(|name| name.to_string()).collect()
+    } else {
+        tasks.iter().map(|task| task.id.to_string()).collect()
+    };
+
+    let current = current.to_ascii_uppercase();
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.to_ascii_uppercase().starts_with(&current))
+        .collect()
+}
+
+/// Runs commands from a prompt against a store that is loaded once and saved
+/// after every change.
+pub fn run(args: &Args) {
+    let path = store::tasks_path(args);
+    remote::local_only(&path, "interactive");
+    let mut store = Store::open(path);
+    let config = Config::load();
+
+    let history = std::fs::read_to_string(history_path())
+        .map(|history| history.lines().map(str::to_owned).collect())
+        .unwrap_or_default();
+    let mut editor = LineEditor::new(history);
+
+    let default_hook = show_panic_messages();
+
+    println!("task-cli interactive mode; type `help` for commands, `exit` to quit");
+
+    loop {
+        let line = match editor.read_line("task> ", |before| completions(before, &store.tasks)) {
+            Ok(ReadResult::Line(line)) => line,
+            Ok(ReadResult::Interrupted) => continue,
+            Ok(ReadResult::Eof) | Err(_) => break,
+        };
+
+        let history = editor.history();
+        let skip = history.len().saturating_sub(HISTORY_LIMIT);
+        let _ = std::fs::write(history_path(), history[skip..].join("\n"));
+
+        let words = match split_words(&line) {
+            Ok(words) => words,
+            Err(_) => {
+                eprintln!("error: unterminated quote");
+                continue;
+            }
+        };
+
+        let Some(name) = words.first() else {
+            continue;
+        };
+
+        match name.as_str() {
+            "exit" | "quit" => break,
+            "help" => {
+                commands::print_help();
+                continue;
+            }
+            _ => {}
+        }
+
+        let cmd = match Commands::from_str(name) {
+            Ok(
+                Commands::Daemon
+                | Commands::Interactive
+                | Commands::Serve
+                | Commands::Tour
+                | Commands::Demo
+                | Commands::Generate
+                | Commands::ShellInit
+                | Commands::Context
+                | Commands::Greet
+                | Commands::Sync
+                | Commands::SelfUpdate,
+            ) => {
+                eprintln!("error: `{name}` cannot be run from the prompt");
+                continue;
+            }
+            Ok(cmd) => cmd,
+            Err(_) => {
+                eprintln!("error: unknown command `{name}`");
+                continue;
+            }
+        };
+
+        let args = Args::parse(words);
+        let _lock = if cmd.mutates(&args) {
+            match store
+                .check_writable()
+                .and_then(|()| StoreLock::acquire(&store.path))
+            {
+                Ok(lock) => Some(lock),
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        let before = (store.tasks.clone(), store.trash.clone());
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            commands::execute(cmd, &args, &config, &mut store)
+        }));
+
+        match result {
+            Ok(Err(err)) => {
+                eprintln!("error: {err}");
+                (store.tasks, store.trash) = before;
+            }
+            Ok(Ok(())) if cmd.mutates(&args) => match rules::save(&mut store, &config) {
+                Ok(()) => usage::record(&args, &config),
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    (store.tasks, store.trash) = before;
+                }
+            },
+            Ok(Ok(())) => usage::record(&args, &config),
+            Err(_) => (store.tasks, store.trash) = before,
+        }
+
+        if let Some(nag) = quota::nag(&store.tasks, &config.quotas, Utc::now()) {
+            eprintln!("{nag}");
+        }
+    }
+
+    panic::set_hook(default_hook);
+}
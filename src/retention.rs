@@ -0,0 +1,248 @@
+//! Optional retention policy: tasks done for long enough move to the trash
+//! when the store is saved, and tasks in the trash for long enough are
+//! purged for good. `[retention]` sets it for every list and
+//! `[retention.lists]` for single stores, by path:
+//!
+//! ```toml
+//! [retention]
+//! trash-days = 30
+//!
+//! [retention.lists."~/clients/acme/.tasks.json"]
+//! done-days = 730
+//! trash-days = 365
+//! ```
+//!
+//! Tasks on hold (`task-cli hold <id>`) are kept whatever the policy says,
+//! and do not age either, until the hold is released.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+use ulid::Ulid;
+
+use crate::{history, store::Store, task::Task, CliError};
+
+/// A hold on a task, exempting it from purges and aging.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct Hold {
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// How long tasks are kept; unset limits keep them forever.
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Limits {
+    /// Days a done task is kept after it was done.
+    pub done_days: Option<u32>,
+    /// Days a task is kept in the trash.
+    pub trash_days: Option<u32>,
+}
+
+/// The `[retention]` table of the config file.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Policy {
+    #[serde(flatten)]
+    pub limits: Limits,
+    /// Limits of single stores, by path; `~/` is the home directory.
+    pub lists: BTreeMap<String, Limits>,
+}
+
+fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || matches!(
+            (a.canonicalize(), b.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+impl Policy {
+    /// The limits of the store at `path`: its own where set, and otherwise
+    /// those of every list.
+    pub fn limits(&self, path: &Path) -> Limits {
+        let own = self
+            .lists
+            .iter()
+            .find(|(list, _)| same_file(&expand(list), path))
+            .map(|(_, limits)| *limits)
+            .unwrap_or_default();
+        Limits {
+            done_days: own.done_days.or(self.limits.done_days),
+            trash_days: own.trash_days.or(self.limits.trash_days),
+        }
+    }
+}
+
+fn older_than(at: DateTime<Utc>, days: Option<u32>, now: DateTime<Utc>) -> bool {
+    days.is_some_and(|days| at < now - Duration::days(days.into()))
+}
+
+/// Tasks the limits say to trash or purge at `now`: done tasks and tasks in
+/// the trash. A done task stays while it has subtasks that stay.
+pub fn due_to_purge(store: &Store, limits: Limits, now: DateTime<Utc>) -> Vec<Ulid> {
+    let expired = |task: &Task| {
+        task.hold.is_none()
+            && task
+                .done_at()
+                .is_some_and(|at| older_than(at, limits.done_days, now))
+    };
+    let parents = store
+        .tasks
+        .iter()
+        .filter(|task| !expired(task))
+        .filter_map(|task| task.parent)
+        .collect::<HashSet<_>>();
+
+    let done = store
+        .tasks
+        .iter()
+        .filter(|task| expired(task) && !parents.contains(&task.id));
+    let trashed = store.trash.iter().filter(|task| {
+        task.hold.is_none()
+            && older_than(
+                task.deleted_at.unwrap_or(task.updated_at),
+                limits.trash_days,
+                now,
+            )
+    });
+    done.chain(trashed).map(|task| task.id).collect()
+}
+
+/// What the policy did to a store.
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct Outcome {
+    /// Done tasks moved to the trash.
+    pub trashed: usize,
+    /// Tasks purged from the trash, whose logged changes are cleared by
+    /// [`redact`] once the purge is saved.
+    pub purged: HashSet<Ulid>,
+}
+
+/// Moves the done tasks due to into the trash, where they get the trash's
+/// time before they are purged, and purges the trashed ones due to.
+pub fn apply(store: &mut Store, policy: &Policy, now: DateTime<Utc>) -> Outcome {
+    let mut outcome = Outcome::default();
+    for id in due_to_purge(store, policy.limits(&store.path), now) {
+        if store.trash(id).is_some() {
+            outcome.trashed += 1;
+        } else {
+            store.trash.retain(|task| task.id != id);
+            outcome.purged.insert(id);
+        }
+    }
+    outcome
+}
+
+/// Clears what the event log kept of the tasks the policy purged: the
+/// policy limits how long they are kept, and the log would keep them
+/// forever.
+pub fn redact(store: &Store, outcome: &Outcome) -> Result<(), CliError> {
+    history::redact(&history::history_path(&store.path), &outcome.purged)
+}
+
+fn tasks(count: usize) -> String {
+    match count {
+        1 => "1 task".to_owned(),
+        n => format!("{n} tasks"),
+    }
+}
+
+/// What to tell the user after the policy ran, if it did anything.
+pub fn notice(outcome: &Outcome) -> Option<String> {
+    let mut parts = Vec::new();
+    if outcome.trashed > 0 {
+        parts.push(format!("{} moved to the trash", tasks(outcome.trashed)));
+    }
+    if !outcome.purged.is_empty() {
+        parts.push(format!(
+            "{} purged from the trash",
+            tasks(outcome.purged.len())
+        ));
+    }
+    (!parts.is_empty()).then(|| format!("past the retention policy: {}", parts.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Status;
+
+    fn policy(done_days: u32, trash_days: Option<u32>) -> Policy {
+        Policy {
+            limits: Limits {
+                done_days: Some(done_days),
+                trash_days,
+            },
+            lists: BTreeMap::new(),
+        }
+    }
+
+    fn store(tasks: Vec<Task>) -> Store {
+        Store::new(PathBuf::from("/nonexistent/tasks.json"), tasks)
+    }
+
+    #[test]
+    fn finishing_a_long_idle_task_keeps_it() {
+        let mut task = Task::new("renew the passport".to_owned());
+        task.updated_at = Utc::now() - Duration::days(400);
+        task.set_status(Status::Done);
+        let mut store = store(vec![task]);
+
+        let outcome = apply(&mut store, &policy(30, Some(30)), Utc::now());
+        assert_eq!(outcome, Outcome::default());
+        assert_eq!(store.tasks.len(), 1);
+    }
+
+    #[test]
+    fn old_done_tasks_go_to_the_trash_first() {
+        let mut task = Task::new("file the taxes".to_owned());
+        task.set_status(Status::Done);
+        task.completed_at = Some(Utc::now() - Duration::days(40));
+        let mut store = store(vec![task]);
+
+        let outcome = apply(&mut store, &policy(30, Some(30)), Utc::now());
+        assert_eq!(
+            outcome,
+            Outcome {
+                trashed: 1,
+                purged: HashSet::new()
+            }
+        );
+        assert!(store.tasks.is_empty());
+        assert_eq!(store.trash.len(), 1);
+
+        let later = Utc::now() + Duration::days(31);
+        let outcome = apply(&mut store, &policy(30, Some(30)), later);
+        assert_eq!(outcome.purged.len(), 1);
+        assert!(store.trash.is_empty());
+    }
+
+    #[test]
+    fn held_tasks_are_kept() {
+        let mut task = Task::new("keep for the audit".to_owned());
+        task.set_status(Status::Done);
+        task.completed_at = Some(Utc::now() - Duration::days(40));
+        task.hold = Some(Hold {
+            at: Utc::now(),
+            reason: None,
+        });
+        let mut store = store(vec![task]);
+
+        assert_eq!(
+            apply(&mut store, &policy(30, None), Utc::now()),
+            Outcome::default()
+        );
+    }
+}
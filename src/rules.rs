@@ -0,0 +1,484 @@
+//! Automation rules, read from `rules.toml` in the config directory and
+//! applied to every change before it is saved:
+//!
+//! ```toml
+//! [[rule]]
+//! name = "client delivery"
+//! when = "status becomes done"
+//! where = "tag:client"
+//! then = ["note Delivered to the client", "webhook https://hooks.example.com/x"]
+//! ```
+//!
+//! `when` is `created`, `status becomes <status>`, `priority becomes
+//! <level>`, `tagged <tag>`, `due within <duration>` or `overdue`. The first
+//! four fire when a change makes them true, the last two whenever a change
+//! is saved while they hold, so their `where` should stop matching once the
+//! actions ran; they do not fire during a pause (see [`crate::pause`]).
+//! `then` lists actions: `note <text>`, `tag <tag>`, `untag <tag>`,
+//! `priority <level>`, `escalate`, `mark <status>` and `webhook <url>`.
+//!
+//! Actions can trigger further rules. Each rule runs at most once per task
+//! and save, and evaluation stops after [`MAX_PASSES`] rounds of changes.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+use ulid::Ulid;
+
+use crate::{
+    aging, blob,
+    config::{config_dir, Config},
+    lock, pause, picker,
+    query::{self, Filter},
+    retention,
+    store::Store,
+    task::{Priority, Status, Task},
+    template,
+    time::parse_duration,
+    toml, CliError,
+};
+
+pub const MAX_PASSES: usize = 8;
+
+pub fn path() -> PathBuf {
+    config_dir().join("rules.toml")
+}
+
+#[derive(Deserialize, Default)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    name: String,
+    when: String,
+    #[serde(default, rename = "where")]
+    filter: Option<String>,
+    then: Vec<String>,
+}
+
+enum Trigger {
+    Created,
+    Status(Status),
+    Priority(Priority),
+    Tagged(String),
+    DueWithin(Duration),
+    Overdue,
+}
+
+impl FromStr for Trigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let invalid = || format!("unknown trigger `{s}`");
+
+        match words.as_slice() {
+            ["created"] => Ok(Self::Created),
+            ["overdue"] => Ok(Self::Overdue),
+            ["status", "becomes", status] => {
+                Ok(Self::Status(status.parse().map_err(|_| invalid())?))
+            }
+            ["priority", "becomes", level] => {
+                Ok(Self::Priority(level.parse().map_err(|_| invalid())?))
+            }
+            ["tagged", tag] => Ok(Self::Tagged((*tag).to_owned())),
+            ["due", "within", duration] => Ok(Self::DueWithin(
+                parse_duration(duration).map_err(|_| invalid())?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Trigger {
+    /// Whether the trigger fires for `task`, which was `before` when the
+    /// change being looked at started.
+    fn fires(&self, before: Option<&Task>, task: &Task, now: DateTime<Utc>) -> bool {
+        let open = task.status != Status::Done;
+
+        match self {
+            Self::Created => before.is_none(),
+            Self::Status(status) => {
+                task.status == *status && before.is_none_or(|before| before.status != *status)
+            }
+            Self::Priority(level) => {
+                task.priority == Some(*level)
+                    && before.is_none_or(|before| before.priority != Some(*level))
+            }
+            Self::Tagged(tag) => {
+                task.has_tag(tag) && before.is_none_or(|before| !before.has_tag(tag))
+            }
+            Self::DueWithin(within) => {
+                open && task
+                    .due
+                    .is_some_and(|due| due >= now && due <= now + *within)
+            }
+            Self::Overdue => open && task.due.is_some_and(|due| due < now),
+        }
+    }
+
+    /// Whether the trigger fires as time passes, rather than on a change.
+    fn on_the_clock(&self) -> bool {
+        matches!(self, Self::DueWithin(_) | Self::Overdue)
+    }
+}
+
+enum Action {
+    Note(String),
+    Tag(String),
+    Untag(String),
+    Priority(Priority),
+    Escalate,
+    Mark(Status),
+    Webhook(String),
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rest) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+        let rest = rest.trim();
+        let invalid = || format!("invalid action `{s}`");
+
+        match name {
+            "note" if !rest.is_empty() => Ok(Self::Note(rest.to_owned())),
+            "tag" if !rest.is_empty() => Ok(Self::Tag(rest.to_owned())),
+            "untag" if !rest.is_empty() => Ok(Self::Untag(rest.to_owned())),
+            "priority" => Ok(Self::Priority(rest.parse().map_err(|_| invalid())?)),
+            "escalate" if rest.is_empty() => Ok(Self::Escalate),
+            "mark" => Ok(Self::Mark(rest.parse().map_err(|_| invalid())?)),
+            "webhook" if rest.starts_with("http://") || rest.starts_with("https://") => {
+                Ok(Self::Webhook(rest.to_owned()))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Note(text) => write!(f, "note {text:?}"),
+            Self::Tag(tag) => write!(f, "tag {tag}"),
+            Self::Untag(tag) => write!(f, "untag {tag}"),
+            Self::Priority(level) => write!(f, "priority {level}"),
+            Self::Escalate => write!(f, "escalate"),
+            Self::Mark(status) => write!(f, "mark {status}"),
+            Self::Webhook(url) => write!(f, "webhook {url}"),
+        }
+    }
+}
+
+impl Action {
+    /// Changes `task`, returning whether anything changed. Webhooks are
+    /// only sent once the store has been saved.
+    fn apply(&self, task: &mut Task, blob_dir: &Path) -> bool {
+        match self {
+            Self::Note(text) => {
+                let full = blob::inline(task, blob_dir);
+                // Adding to the preview of notes that cannot be read would
+                // lose the rest of them.
+                if full.notes_blob.is_some() {
+                    return false;
+                }
+                let notes = match full.notes {
+                    Some(notes) if !notes.is_empty() => format!("{}\n{text}", notes.trim_end()),
+                    _ => text.clone(),
+                };
+                task.set_notes(Some(notes));
+                true
+            }
+            Self::Tag(tag) if !task.has_tag(tag) => {
+                task.add_tag(tag);
+                true
+            }
+            Self::Untag(tag) if task.has_tag(tag) => {
+                task.remove_tag(tag);
+                true
+            }
+            Self::Priority(level) if task.priority != Some(*level) => {
+                task.set_priority(Some(*level));
+                true
+            }
+            Self::Escalate => {
+                // Unset priorities count as medium.
+                let raised = match task.priority {
+                    Some(Priority::Low) => Priority::Medium,
+                    None | Some(Priority::Medium) => Priority::High,
+                    Some(Priority::High | Priority::Urgent) => Priority::Urgent,
+                };
+                let changed = task.priority != Some(raised);
+                task.set_priority(Some(raised));
+                changed
+            }
+            Self::Mark(status) if task.status != *status => {
+                task.set_status(status.clone());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+pub struct Rule {
+    pub name: String,
+    when: String,
+    trigger: Trigger,
+    filter_text: Option<String>,
+    filter: Option<Filter>,
+    actions: Vec<Action>,
+}
+
+impl Rule {
+    /// The rule as written in the rules file, on one line.
+    pub fn summary(&self) -> String {
+        let filter = self
+            .filter_text
+            .as_ref()
+            .map(|filter| format!(" where {filter}"))
+            .unwrap_or_default();
+        let actions = self.actions.iter().map(Action::to_string);
+        format!(
+            "{}: when {}{filter} then {}",
+            self.name,
+            self.when,
+            actions.collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+/// Reads the rules file; without one there are no rules.
+pub fn load() -> Result<Vec<Rule>, CliError> {
+    let Ok(contents) = std::fs::read_to_string(path()) else {
+        return Ok(Vec::new());
+    };
+
+    let invalid_file = |err: String| CliError::Rule(format!("invalid rules file: {err}"));
+    let value = toml::parse(&contents).map_err(|err| invalid_file(err.to_string()))?;
+    let file =
+        serde_json::from_value::<RulesFile>(value).map_err(|err| invalid_file(err.to_string()))?;
+
+    file.rule
+        .into_iter()
+        .map(|spec| {
+            let invalid = |message: String| {
+                CliError::Rule(format!("invalid rule `{}`: {message}", spec.name))
+            };
+            Ok(Rule {
+                trigger: spec.when.parse().map_err(invalid)?,
+                filter: spec
+                    .filter
+                    .as_deref()
+                    .map(query::parse)
+                    .transpose()
+                    .map_err(|err| invalid(err.to_string()))?,
+                actions: spec
+                    .then
+                    .iter()
+                    .map(|action| action.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(invalid)?,
+                name: spec.name.clone(),
+                when: spec.when.clone(),
+                filter_text: spec.filter.clone(),
+            })
+        })
+        .collect()
+}
+
+/// A rule that ran on a task, with what it did.
+pub struct Firing {
+    pub rule: String,
+    pub task: Ulid,
+    pub actions: Vec<String>,
+}
+
+pub struct Webhook {
+    pub url: String,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Default)]
+pub struct Outcome {
+    pub fired: Vec<Firing>,
+    pub webhooks: Vec<Webhook>,
+    /// Rules were still changing tasks after [`MAX_PASSES`] rounds.
+    pub exhausted: bool,
+}
+
+/// Runs `rules` against the changes made to `store` since it was last
+/// read or saved, and against the changes the rules make in turn.
+pub fn apply(store: &mut Store, rules: &[Rule], now: DateTime<Utc>) -> Outcome {
+    let mut outcome = Outcome::default();
+    if rules.is_empty() {
+        return outcome;
+    }
+
+    let blob_dir = store.blob_dir();
+    let mut before = store
+        .saved_tasks()
+        .map(|task| (task.id, task.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut fired = HashSet::new();
+
+    for _ in 0..MAX_PASSES {
+        let snapshot = store
+            .tasks
+            .iter()
+            .map(|task| (task.id, task.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut changed = false;
+
+        for (index, rule) in rules.iter().enumerate() {
+            for task in &mut store.tasks {
+                if fired.contains(&(index, task.id))
+                    || !rule.trigger.fires(before.get(&task.id), task, now)
+                    || rule
+                        .filter
+                        .as_ref()
+                        .is_some_and(|filter| !filter.matches(task))
+                {
+                    continue;
+                }
+                fired.insert((index, task.id));
+
+                for action in &rule.actions {
+                    if let Action::Webhook(url) = action {
+                        outcome.webhooks.push(Webhook {
+                            url: url.clone(),
+                            payload: json!({
+                                "rule": rule.name,
+                                "task": blob::inline(task, &blob_dir),
+                            }),
+                        });
+                    } else if action.apply(task, &blob_dir) {
+                        task.updated_at = now;
+                        changed = true;
+                    }
+                }
+                outcome.fired.push(Firing {
+                    rule: rule.name.clone(),
+                    task: task.id,
+                    actions: rule.actions.iter().map(Action::to_string).collect(),
+                });
+            }
+        }
+
+        if !changed {
+            return outcome;
+        }
+        before = snapshot;
+    }
+
+    outcome.exhausted = true;
+    outcome
+}
+
+/// POSTs the payloads with `curl`, reporting deliveries that failed.
+pub fn deliver(webhooks: &[Webhook]) {
+    for webhook in webhooks {
+        let sent = Command::new("curl")
+            .args(["-fs", "-m", "10", "-X", "POST"])
+            .args(["-H", "Content-Type: application/json"])
+            .args(["--data-binary", "@-", &webhook.url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .and_then(|mut child| {
+                let body = serde_json::to_vec(&webhook.payload).expect("could not convert to json");
+                child
+                    .stdin
+                    .take()
+                    .expect("stdin is piped")
+                    .write_all(&body)?;
+                child.wait()
+            });
+
+        if !sent.is_ok_and(|status| status.success()) {
+            eprintln!("warning: webhook {} could not be delivered", webhook.url);
+        }
+    }
+}
+
+/// Ages idle tasks, purges those past retention, applies the rules, saves
+/// the store and then sends the webhooks. This is how every command, the
+/// prompt and the HTTP API save their changes.
+pub fn save(store: &mut Store, config: &Config) -> Result<(), CliError> {
+    let mut rules = load()?;
+    let now = Utc::now();
+    let pause = pause::load();
+    if pause.as_ref().is_some_and(|pause| pause.active(now)) {
+        rules.retain(|rule| !rule.trigger.on_the_clock());
+    }
+
+    let aged = aging::apply(store, &config.aging, pause.as_ref(), now);
+    if let Some(message) = aging::notice(&aged, &config.aging, now) {
+        eprintln!("{message}");
+    }
+    let retained = retention::apply(store, &config.retention, now);
+    if let Some(message) = retention::notice(&retained) {
+        eprintln!("{message}");
+    }
+    for (done, then) in template::chain(store)? {
+        eprintln!("`{done}` is done; follow-up `{then}` created");
+    }
+    let outcome = apply(store, &rules, now);
+
+    for firing in &outcome.fired {
+        let task = store.find(firing.task);
+        eprintln!(
+            "rule `{}` ran on {}",
+            firing.rule,
+            task.map_or_else(|| firing.task.to_string(), |task| task.description.clone())
+        );
+    }
+    if outcome.exhausted {
+        eprintln!("warning: rules were still changing tasks after {MAX_PASSES} rounds; stopped");
+    }
+
+    check_editing(store)?;
+    store.save()?;
+    retention::redact(store, &retained)?;
+    deliver(&outcome.webhooks);
+    Ok(())
+}
+
+/// Warns before changing tasks that are open in an editor elsewhere, and
+/// asks whether to go ahead when someone can answer. The editing session
+/// sees the change when it is done and asks how to merge it.
+fn check_editing(store: &Store) -> Result<(), CliError> {
+    let held = lock::editing_elsewhere(&store.path, &store.changed_ids());
+    if held.is_empty() {
+        return Ok(());
+    }
+
+    for holder in &held {
+        let task = store
+            .find(holder.task)
+            .or_else(|| store.trash.iter().find(|task| task.id == holder.task));
+        let description =
+            task.map_or_else(|| holder.task.to_string(), |task| task.description.clone());
+        eprintln!("warning: `{description}` is being edited elsewhere ({holder})");
+    }
+    if picker::is_interactive()
+        && !picker::confirm("change it anyway? the editor will be asked to merge")
+    {
+        return Err(CliError::Conflict(
+            "nothing was saved; try again once the other edit is done".to_owned(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+//! Runbooks: ordered checklists for incidents, kept as TOML files next to
+//! the code they are about and started with `runbook start`. A run is a task
+//! with a subtask per step; who acknowledged and finished each step, and
+//! when, comes from the event log, which also gives the timeline for the
+//! postmortem.
+//!
+//! ```toml
+//! title = "Outage: {service}"
+//! tags = ["incident"]
+//!
+//! [[step]]
+//! description = "Page the on-call engineer"
+//! owner = "oncall"
+//! within = "5m"
+//! ```
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    history::{Event, EventKind},
+    task::{Priority, Status, Task},
+    template::fill,
+    time::{format_duration, parse_duration},
+    toml, CliError,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Runbook {
+    /// May contain `{placeholders}`; the file name when unset.
+    pub title: Option<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, rename = "step")]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub description: String,
+    pub owner: Option<String>,
+    /// How long after the start the step is due, e.g. `15m`.
+    pub within: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Reads the runbook at `path`.
+pub fn load(path: &Path) -> Result<Runbook, CliError> {
+    let contents = std::fs::read_to_string(path).map_err(|error| CliError::Read {
+        path: path.to_owned(),
+        error,
+    })?;
+
+    let invalid =
+        |err: String| CliError::Runbook(format!("invalid runbook {}: {err}", path.display()));
+    let value = toml::parse(&contents).map_err(|err| invalid(err.to_string()))?;
+    let runbook =
+        serde_json::from_value::<Runbook>(value).map_err(|err| invalid(err.to_string()))?;
+    if runbook.steps.is_empty() {
+        return Err(invalid("no [[step]] tables".to_owned()));
+    }
+    Ok(runbook)
+}
+
+impl Runbook {
+    /// The task for a run started now, named after `name`, followed by one
+    /// subtask per step in order. Placeholders are filled from `vars`.
+    pub fn start(
+        &self,
+        name: &str,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<Vec<Task>, CliError> {
+        let mut run = Task::new(fill(self.title.as_deref().unwrap_or(name), vars)?);
+        run.runbook = Some(name.to_owned());
+        run.priority = self.priority;
+        run.tags = self.tags.clone();
+        let started = run.created_at;
+
+        let mut tasks = vec![run];
+        for step in &self.steps {
+            let mut task = Task::new(fill(&step.description, vars)?);
+            task.parent = Some(tasks[0].id);
+            task.owner = step.owner.clone();
+            task.notes = step.notes.clone();
+            if let Some(within) = &step.within {
+                let within = parse_duration(within).map_err(|_| {
+                    CliError::Runbook(format!("invalid `within = \"{within}\"` in runbook {name}"))
+                })?;
+                task.due = Some(started + within);
+            }
+            tasks.push(task);
+        }
+
+        Ok(tasks)
+    }
+}
+
+/// The steps of `run`, in the runbook's order.
+pub fn steps<'t>(run: &Task, tasks: &'t [Task]) -> Vec<&'t Task> {
+    tasks
+        .iter()
+        .filter(|task| task.parent == Some(run.id))
+        .collect()
+}
+
+/// When a step was acknowledged and finished, from its latest status
+/// changes; reopening a step forgets both.
+#[derive(Default)]
+pub struct Progress<'e> {
+    pub acknowledged: Option<&'e Event>,
+    pub done: Option<&'e Event>,
+}
+
+pub fn progress<'e>(step: &Task, events: &'e [Event]) -> Progress<'e> {
+    let mut progress = Progress::default();
+    for event in events.iter().filter(|event| event.task == step.id) {
+        let to = event.changes.get("status").map(|change| &change.to);
+        match to.and_then(|status| status.as_str()) {
+            Some("in-progress") if event.kind != EventKind::Created => {
+                progress.acknowledged = Some(event);
+                progress.done = None;
+            }
+            Some("done") => progress.done = Some(event),
+            Some("to-do") => progress = Progress::default(),
+            _ => {}
+        }
+    }
+    progress
+}
+
+/// One line of the postmortem timeline.
+#[derive(Serialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    /// Number of the step the entry is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<u32>,
+    pub what: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by: Option<String>,
+}
+
+/// Everything that happened during `run`, oldest first: the start, each
+/// acknowledgement, hand-over and completion, and the timers that ran out
+/// before their step was done.
+pub fn timeline(run: &Task, tasks: &[Task], events: &[Event], now: DateTime<Utc>) -> Vec<Entry> {
+    let by = |id| {
+        events
+            .iter()
+            .find(|event| event.task == id && event.kind == EventKind::Created)
+            .and_then(|event| event.by.clone())
+    };
+    let mut entries = vec![Entry {
+        at: run.created_at,
+        step: None,
+        what: format!("started runbook {}", run.runbook.as_deref().unwrap_or("")),
+        by: by(run.id),
+    }];
+
+    let steps = steps(run, tasks);
+    for step in &steps {
+        let entry = |event: &Event, what: String| Entry {
+            at: event.at,
+            step: step.number,
+            what,
+            by: event.by.clone(),
+        };
+        for event in events.iter().filter(|event| event.task == step.id) {
+            if event.kind == EventKind::Created {
+                continue;
+            }
+            // Taking an unowned step is part of acknowledging it.
+            if let Some(change) = event
+                .changes
+                .get("owner")
+                .filter(|change| !change.from.is_null())
+            {
+                let to = change.to.as_str().unwrap_or("nobody");
+                entries.push(entry(
+                    event,
+                    format!("handed to {to}: {}", step.description),
+                ));
+            }
+            match event
+                .changes
+                .get("status")
+                .and_then(|change| change.to.as_str())
+            {
+                Some("in-progress") => {
+                    entries.push(entry(event, format!("acknowledged: {}", step.description)));
+                }
+                Some("done") => entries.push(entry(event, format!("done: {}", step.description))),
+                Some("to-do") => {
+                    entries.push(entry(event, format!("reopened: {}", step.description)))
+                }
+                _ => {}
+            }
+        }
+
+        let finished = progress(step, events).done.map_or(now, |event| event.at);
+        if let Some(due) = step.due.filter(|due| *due < finished) {
+            entries.push(Entry {
+                at: due,
+                step: step.number,
+                what: format!("timer ran out: {}", step.description),
+                by: None,
+            });
+        }
+    }
+
+    if steps.iter().all(|step| step.status == Status::Done) {
+        let last = steps
+            .iter()
+            .filter_map(|step| progress(step, events).done.map(|event| event.at))
+            .max();
+        if let Some(at) = last {
+            entries.push(Entry {
+                at,
+                step: None,
+                what: format!(
+                    "all steps done after {}",
+                    format_duration(at - run.created_at)
+                ),
+                by: None,
+            });
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.at);
+    entries
+}
+
+/// The timeline as a Markdown section to paste into a postmortem.
+pub fn markdown(run: &Task, entries: &[Entry]) -> String {
+    let mut out = format!(
+        "## Timeline: {}\n\nStarted {} from runbook `{}`. Times are UTC.\n\n",
+        run.description,
+        run.created_at.format("%Y-%m-%d %H:%M:%S"),
+        run.runbook.as_deref().unwrap_or("")
+    );
+    out.push_str("| Time | Since start | Step | What | Who |\n");
+    out.push_str("|------|-------------|------|------|-----|\n");
+    for entry in entries {
+        let since = entry.at - run.created_at;
+        out.push_str(&format!(
+            "| {} | +{} | {} | {} | {} |\n",
+            entry.at.format("%Y-%m-%d %H:%M:%S"),
+            format_duration(since.max(Duration::zero())),
+            entry
+                .step
+                .map(|number| format!("#{number}"))
+                .unwrap_or_default(),
+            entry.what.replace('|', "\\|"),
+            entry.by.as_deref().unwrap_or(""),
+        ));
+    }
+    out
+}
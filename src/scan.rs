@@ -0,0 +1,241 @@
+//! `task-cli scan`: turns TODO-style comments in a source tree into tasks and
+//! keeps them in step with the code on later scans.
+
+use chrono::Utc;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    store::Store,
+    task::{Source, Status, Task},
+};
+
+/// Tag given to every task created by a scan; only these are updated or
+/// closed by later scans.
+pub const TAG: &str = "scan";
+
+/// Directories that never contain code worth scanning.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "vendor", "dist", "build"];
+const MAX_FILE_SIZE: u64 = 1 << 20;
+
+#[derive(Debug)]
+pub struct Comment {
+    pub source: Source,
+    /// The keyword that matched, e.g. `FIXME`.
+    pub keyword: String,
+    pub text: String,
+}
+
+/// Splits a pattern like `TODO|FIXME` into its keywords.
+pub fn keywords(pattern: &str) -> Vec<String> {
+    pattern
+        .split('|')
+        .map(str::trim)
+        .filter(|keyword| !keyword.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Finds the first keyword in `line` standing as a word of its own and
+/// returns it with the rest of the line, minus markers like `(name):` and
+/// comment terminators.
+fn match_line(line: &str, keywords: &[String]) -> Option<(String, String)> {
+    for keyword in keywords {
+        for (start, _) in line.match_indices(keyword.as_str()) {
+            let before = line[..start].chars().next_back();
+            let rest = &line[start + keyword.len()..];
+            if before.is_some_and(is_word_char) || rest.starts_with(is_word_char) {
+                continue;
+            }
+
+            let mut text = rest.trim_start();
+            if let Some(after) = text.strip_prefix('(') {
+                text = after.split_once(')').map_or(after, |(_, after)| after);
+            }
+            let text = text
+                .trim_start_matches([':', '-', ' ', '\t'])
+                .trim_end()
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim_end();
+
+            return Some((keyword.clone(), text.to_owned()));
+        }
+    }
+
+    None
+}
+
+fn scan_file(path: &Path, keywords: &[String], comments: &mut Vec<Comment>) {
+    let Ok(contents) = std::fs::read(path) else {
+        return;
+    };
+    // Binary files.
+    if contents.contains(&0) {
+        return;
+    }
+
+    let contents = String::from_utf8_lossy(&contents);
+    for (index, line) in contents.lines().enumerate() {
+        if let Some((keyword, text)) = match_line(line, keywords) {
+            comments.push(Comment {
+                source: Source {
+                    file: path.to_owned(),
+                    line: index as u32 + 1,
+                },
+                text: if text.is_empty() {
+                    keyword.clone()
+                } else {
+                    text
+                },
+                keyword,
+            });
+        }
+    }
+}
+
+fn walk(path: &Path, keywords: &[String], comments: &mut Vec<Comment>) -> io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_file() {
+        if metadata.len() <= MAX_FILE_SIZE {
+            scan_file(path, keywords, comments);
+        }
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for entry in entries {
+        let name = entry
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if name.starts_with('.') || (entry.is_dir() && SKIPPED_DIRS.contains(&name)) {
+            continue;
+        }
+        // Unreadable entries are skipped rather than failing the scan.
+        let _ = walk(&entry, keywords, comments);
+    }
+
+    Ok(())
+}
+
+/// Collects the matching comments under `root`, a directory or a single
+/// file, in path order.
+pub fn scan(root: &Path, keywords: &[String]) -> io::Result<Vec<Comment>> {
+    let mut comments = Vec::new();
+    walk(root, keywords, &mut comments)?;
+    Ok(comments)
+}
+
+/// Brings the scan tasks under `root` in line with `comments`: a comment
+/// that moved keeps its task, edited comments update it, new ones add a task
+/// and tasks whose comment is gone are marked done. Returns one summary line
+/// per change.
+pub fn sync(store: &mut Store, root: &Path, comments: Vec<Comment>) -> Vec<String> {
+    let now = Utc::now();
+    let mut summary = Vec::new();
+    let mut seen = Vec::new();
+
+    let scanned = |task: &Task| {
+        task.has_tag(TAG)
+            && task
+                .source
+                .as_ref()
+                .is_some_and(|source| source.file.starts_with(root))
+    };
+
+    for comment in comments {
+        let candidates = |task: &&mut Task| scanned(task) && !seen.contains(&task.id);
+        let file = &comment.source.file;
+        let same_file = |task: &Task| task.source.as_ref().is_some_and(|s| s.file == *file);
+
+        // Prefer the task with the same text (the comment may have moved),
+        // then the one on the same line (the comment was reworded).
+        let existing = store
+            .tasks
+            .iter_mut()
+            .filter(candidates)
+            .filter(|task| same_file(task))
+            .min_by_key(|task| {
+                let line = task.source.as_ref().map_or(0, |source| source.line);
+                (
+                    task.description != comment.text,
+                    line.abs_diff(comment.source.line),
+                )
+            })
+            .filter(|task| {
+                task.description == comment.text
+                    || task.source.as_ref().map(|source| source.line) == Some(comment.source.line)
+            });
+
+        match existing {
+            Some(task) => {
+                let mut changes = Vec::new();
+                if task.description != comment.text {
+                    changes.push(format!(
+                        "description {:?} -> {:?}",
+                        task.description, comment.text
+                    ));
+                    task.set_description(comment.text);
+                }
+                if task.source.as_ref() != Some(&comment.source) {
+                    changes.push(format!("moved to {}", comment.source));
+                    task.source = Some(comment.source);
+                }
+                if task.status == Status::Done {
+                    changes.push("reopened".to_owned());
+                    task.set_status(Status::ToDo);
+                }
+                if !changes.is_empty() {
+                    task.updated_at = now;
+                    summary.push(format!(
+                        "~ {}. {} ({})",
+                        task.id,
+                        task.description,
+                        changes.join(", ")
+                    ));
+                }
+                seen.push(task.id);
+            }
+            None => {
+                let mut task = Task::new(comment.text);
+                task.tags = vec![TAG.to_owned(), comment.keyword.to_lowercase()];
+                summary.push(format!(
+                    "+ {}. {} ({})",
+                    task.id, task.description, comment.source
+                ));
+                task.source = Some(comment.source);
+                seen.push(task.id);
+                store.tasks.push(task);
+            }
+        }
+    }
+
+    for task in store.tasks.iter_mut() {
+        if scanned(task) && !seen.contains(&task.id) && task.status != Status::Done {
+            task.set_status(Status::Done);
+            summary.push(format!(
+                "✓ {}. {} (comment removed)",
+                task.id, task.description
+            ));
+        }
+    }
+
+    summary
+}
+
+/// The absolute form of `path`, as stored in [`Source`].
+pub fn root(path: &str) -> io::Result<PathBuf> {
+    std::path::absolute(path)
+}
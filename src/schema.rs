@@ -0,0 +1,310 @@
+//! JSON Schemas for the formats other tools read and write: tasks (as in
+//! `export --format json` and `import json`) and the events in the event
+//! log.
+//!
+//! Within a schema version the formats only grow: new optional fields and
+//! new enum values may appear, but no field is removed, renamed, made
+//! required or given another type. Readers should ignore fields they do not
+//! know. Anything else bumps [`VERSION`].
+
+use chrono::DateTime;
+use serde_json::{json, Value};
+use ulid::Ulid;
+
+use crate::blob;
+
+pub const VERSION: u32 = 1;
+
+fn id(name: &str) -> String {
+    format!("urn:task-cli:schema:{name}:{VERSION}")
+}
+
+fn definitions() -> Value {
+    json!({
+        "ulid": {
+            "type": "string",
+            "format": "ulid",
+            "description": "26-character ULID in Crockford base32"
+        },
+        "date-time": {
+            "type": "string",
+            "format": "date-time",
+            "description": "RFC 3339 timestamp"
+        },
+        "priority": { "enum": ["low", "medium", "high", "urgent"] },
+        "blob": {
+            "type": "string",
+            "format": "blob",
+            "description": "file next to the store holding the full text, ignored on import"
+        },
+        "follow-up": {
+            "type": "object",
+            "required": ["description"],
+            "properties": {
+                "description": { "type": "string" },
+                "priority": { "$ref": "#/$defs/priority" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "due_after": { "type": "string" },
+                "subtasks": { "type": "array", "items": { "type": "string" } },
+                "then": { "$ref": "#/$defs/follow-up" }
+            }
+        }
+    })
+}
+
+pub fn task() -> Value {
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    let source = json!({
+        "type": "object",
+        "required": ["file", "line"],
+        "properties": {
+            "file": { "type": "string" },
+            "line": { "type": "integer", "minimum": 0 }
+        }
+    });
+    let blocks = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "required": ["start", "end"],
+            "properties": {
+                "start": { "$ref": "#/$defs/date-time" },
+                "end": { "$ref": "#/$defs/date-time" }
+            }
+        }
+    });
+    let defaults = json!({
+        "type": "object",
+        "properties": {
+            "priority": { "$ref": "#/$defs/priority" },
+            "tags": strings
+        }
+    });
+    let inherited = json!({
+        "type": "object",
+        "properties": {
+            "priority": { "type": "boolean" },
+            "tags": strings
+        }
+    });
+    let aged = json!({
+        "type": "object",
+        "required": ["at"],
+        "properties": {
+            "at": { "$ref": "#/$defs/date-time" },
+            "priority": { "$ref": "#/$defs/priority" },
+            "someday": { "type": "boolean" }
+        }
+    });
+    let hold = json!({
+        "type": "object",
+        "required": ["at"],
+        "properties": {
+            "at": { "$ref": "#/$defs/date-time" },
+            "reason": { "type": "string" }
+        }
+    });
+    let delegated = json!({
+        "type": "object",
+        "required": ["to", "at"],
+        "properties": {
+            "to": { "type": "string" },
+            "at": { "$ref": "#/$defs/date-time" },
+            "follow_up": { "$ref": "#/$defs/date-time" }
+        }
+    });
+    let rollup = json!({
+        "type": "object",
+        "description": "derived from the subtasks; ignored on import",
+        "required": ["estimate_secs", "tracked_secs", "done", "total"],
+        "properties": {
+            "estimate_secs": { "type": "integer" },
+            "tracked_secs": { "type": "integer" },
+            "done": { "type": "integer", "minimum": 0 },
+            "total": { "type": "integer", "minimum": 0 }
+        }
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": id("task"),
+        "title": "task",
+        "type": "object",
+        "required": ["id", "description", "status", "created_at", "updated_at"],
+        "properties": {
+            "id": { "$ref": "#/$defs/ulid" },
+            "number": { "type": "integer", "minimum": 1 },
+            "description": { "type": "string" },
+            "status": { "enum": ["to-do", "in-progress", "done"] },
+            "created_at": { "$ref": "#/$defs/date-time" },
+            "updated_at": { "$ref": "#/$defs/date-time" },
+            "revision": { "type": "integer", "minimum": 0 },
+            "priority": { "$ref": "#/$defs/priority" },
+            "tags": strings,
+            "parent": { "$ref": "#/$defs/ulid" },
+            "due": { "$ref": "#/$defs/date-time" },
+            "remind_before_secs": { "type": "integer" },
+            "notes": { "type": "string" },
+            "description_blob": { "$ref": "#/$defs/blob" },
+            "notes_blob": { "$ref": "#/$defs/blob" },
+            "completed_at": { "$ref": "#/$defs/date-time" },
+            "deleted_at": { "$ref": "#/$defs/date-time" },
+            "blocked_by": { "type": "array", "items": { "$ref": "#/$defs/ulid" } },
+            "wait_until": { "$ref": "#/$defs/date-time" },
+            "source": source,
+            "import_id": { "type": "string" },
+            "blocks": blocks,
+            "defaults": defaults,
+            "inherited": inherited,
+            "aged": aged,
+            "hold": hold,
+            "owner": { "type": "string" },
+            "location": { "type": "string" },
+            "delegated": delegated,
+            "on_done": { "$ref": "#/$defs/follow-up" },
+            "runbook": { "type": "string" },
+            "estimate_secs": { "type": "integer", "minimum": 0 },
+            "tracked_secs": { "type": "integer" },
+            "rollup": rollup
+        },
+        "$defs": definitions()
+    })
+}
+
+pub fn event() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": id("event"),
+        "title": "event",
+        "type": "object",
+        "required": ["schema", "at", "task", "kind"],
+        "properties": {
+            "schema": { "type": "integer", "minimum": 1 },
+            "at": { "$ref": "#/$defs/date-time" },
+            "task": { "$ref": "#/$defs/ulid" },
+            "kind": { "enum": ["created", "updated", "deleted", "restored", "purged"] },
+            "by": { "type": "string" },
+            "changes": {
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "required": ["from", "to"],
+                    "properties": { "from": {}, "to": {} }
+                }
+            }
+        },
+        "$defs": definitions()
+    })
+}
+
+/// The schema called `name`, as accepted by `task-cli schema`.
+pub fn named(name: &str) -> Option<Value> {
+    match name {
+        "task" => Some(task()),
+        "event" => Some(event()),
+        _ => None,
+    }
+}
+
+fn type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+/// Checks `value` against `schema`, returning one message per problem,
+/// each starting with the JSON pointer to the offending value. Only the
+/// keywords the schemas here use are supported.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    validate_at(schema, value, "")
+}
+
+/// [`validate`] for a value found at `path` in a larger document.
+pub fn validate_at(schema: &Value, value: &Value, path: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, schema, value, path, &mut errors);
+    errors
+}
+
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+            .unwrap_or_else(|| panic!("unresolved schema reference {reference}"));
+        check(root, target, value, path, errors);
+        return;
+    }
+
+    let mut fail = |message: String| {
+        let at = if path.is_empty() { "/" } else { path };
+        errors.push(format!("{at}: {message}"));
+    };
+
+    if let Some(name) = schema["type"].as_str() {
+        if !type_matches(name, value) {
+            fail(format!("expected {name}"));
+            return;
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>();
+            fail(format!("expected one of {}", allowed.join(", ")));
+            return;
+        }
+    }
+    if let (Some(minimum), Some(number)) = (schema["minimum"].as_i64(), value.as_i64()) {
+        if number < minimum {
+            fail(format!("must be at least {minimum}"));
+        }
+    }
+    if let (Some(format), Some(text)) = (schema["format"].as_str(), value.as_str()) {
+        let valid = match format {
+            "ulid" => Ulid::from_string(text).is_ok(),
+            "date-time" => DateTime::parse_from_rfc3339(text).is_ok(),
+            "blob" => blob::is_blob_name(text),
+            _ => true,
+        };
+        if !valid {
+            fail(format!("expected a {format}"));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for name in schema["required"].as_array().into_iter().flatten() {
+            let name = name.as_str().expect("required names are strings");
+            if !object.contains_key(name) {
+                fail(format!("missing required field `{name}`"));
+            }
+        }
+        for (name, field) in object {
+            let field_path = format!("{path}/{name}");
+            match schema["properties"].get(name) {
+                Some(field_schema) => check(root, field_schema, field, &field_path, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        errors.push(format!("{field_path}: unknown field"));
+                    }
+                    Some(extra) if extra.is_object() => {
+                        check(root, extra, field, &field_path, errors);
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            check(root, items, item, &format!("{path}/{index}"), errors);
+        }
+    }
+}
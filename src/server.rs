@@ -0,0 +1,845 @@
+//! `task-cli serve`: a small HTTP/JSON API over the same store, for editor
+//! extensions and dashboards.
+//!
+//! Connections are handled one at a time and every request re-reads the
+//! store, so changes made from the command line show up immediately and
+//! writes take the store lock like any other command.
+//!
+//! Changes to a task can name the revision they were made from, in an
+//! `If-Match` header or a `revision` field; a task at another revision is
+//! left alone and answered with 409, so stale clients do not overwrite newer
+//! edits. `--require-revision` refuses changes that name none.
+//!
+//! Web pages in the browser can reach 127.0.0.1 too, so requests naming
+//! another `Host` (DNS rebinding) and requests from pages whose `Origin` is
+//! not allowed are refused, and bodies have to be sent as
+//! `application/json`, which forms cannot do. Pages are only allowed with a
+//! token, from the origins in the config file:
+//!
+//! ```toml
+//! [serve]
+//! allowed-origins = ["http://localhost:5173"]
+//! ```
+
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+use ulid::Ulid;
+
+use crate::{
+    args::Args,
+    blob,
+    config::Config,
+    lock::StoreLock,
+    query, remote, rules,
+    store::{self, Store},
+    task::{filter_by_status, inherit, parse_tags, Priority, Source, Status, Task},
+    time::{parse_datetime, parse_duration},
+    CliError,
+};
+
+const MAX_BODY: usize = 1 << 20;
+
+/// The `[serve]` table of the config file.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    /// Origins such as `https://tasks.example.com` whose pages may call the
+    /// API; they still need the token.
+    pub allowed_origins: Vec<String>,
+}
+
+/// Who may talk to the server.
+struct Access {
+    token: Option<String>,
+    /// The address the server listens on, a valid `Host` besides loopback.
+    host: String,
+    origins: Vec<String>,
+}
+
+impl Access {
+    fn host_allowed(&self, request: &Request) -> bool {
+        let Some(host) = request.header("host") else {
+            return false;
+        };
+        // `[::1]:8080`, `localhost:8080` or a bare name.
+        let name = match host.strip_prefix('[') {
+            Some(rest) => rest.split(']').next().unwrap_or(rest),
+            None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+        };
+        let name = name.to_ascii_lowercase();
+        matches!(name.as_str(), "localhost" | "127.0.0.1" | "::1")
+            || name == self.host.trim_matches(['[', ']']).to_ascii_lowercase()
+    }
+
+    /// The origin of a page allowed to call the server, to answer with.
+    /// Pages are never allowed without a token.
+    fn allowed_origin<'r>(&self, request: &'r Request) -> Option<&'r str> {
+        let origin = request.header("origin")?;
+        (self.token.is_some() && self.origins.iter().any(|allowed| allowed == origin))
+            .then_some(origin)
+    }
+
+    fn check(&self, request: &Request) -> Result<(), ApiError> {
+        if !self.host_allowed(request) {
+            return Err(ApiError::new(421, "unexpected Host header"));
+        }
+        if request.header("origin").is_some() && self.allowed_origin(request).is_none() {
+            return Err(ApiError::new(
+                403,
+                "requests from web pages need a token and an origin in [serve] allowed-origins",
+            ));
+        }
+        if !authorized(request, self.token.as_deref()) {
+            return Err(ApiError::new(401, "missing or invalid bearer token"));
+        }
+        Ok(())
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body as a JSON object, which has to say it is one.
+    fn fields(&self) -> Result<Map<String, Value>, ApiError> {
+        let json = self.header("content-type").is_some_and(|kind| {
+            kind.split(';')
+                .next()
+                .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/json"))
+        });
+        if !json {
+            return Err(ApiError::new(
+                415,
+                "expected Content-Type: application/json",
+            ));
+        }
+        match serde_json::from_slice(&self.body) {
+            Ok(Value::Object(fields)) => Ok(fields),
+            Ok(_) => Err(ApiError::bad_request("expected a JSON object")),
+            Err(err) => Err(ApiError::bad_request(format!("invalid JSON: {err}"))),
+        }
+    }
+}
+
+struct Response {
+    status: u16,
+    body: Option<Value>,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            body: Some(body),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+}
+
+impl From<CliError> for ApiError {
+    fn from(err: CliError) -> Self {
+        let status = match err {
+            CliError::NoMatch(_) => 404,
+            CliError::Ambiguous { .. } => 409,
+            CliError::Locked { .. } => 503,
+            CliError::Write { .. } => 500,
+            _ => 400,
+        };
+        Self::new(status, err.to_string())
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        421 => "Misdirected Request",
+        428 => "Precondition Required",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' if rest.len() >= 2 => {
+                let hex = std::str::from_utf8(&rest[..2]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(decoded) => {
+                        bytes.push(decoded);
+                        rest = &rest[2..];
+                    }
+                    Err(_) => bytes.push(byte),
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn read_request(stream: impl Read) -> Result<Request, ApiError> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let read_line = |reader: &mut dyn BufRead, line: &mut String| {
+        line.clear();
+        reader
+            .read_line(line)
+            .map_err(|_| ApiError::bad_request("could not read request"))
+    };
+
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(ApiError::bad_request("malformed request line"));
+    };
+    let method = method.to_owned();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path);
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    let mut headers = Vec::new();
+    loop {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        query,
+        headers,
+        body: Vec::new(),
+    };
+
+    let length = request
+        .header("content-length")
+        .map(|length| length.parse::<usize>())
+        .transpose()
+        .map_err(|_| ApiError::bad_request("invalid Content-Length"))?
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(ApiError::new(413, "request body too large"));
+    }
+    request.body = vec![0; length];
+    reader
+        .read_exact(&mut request.body)
+        .map_err(|_| ApiError::bad_request("request body cut short"))?;
+
+    Ok(request)
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response, origin: Option<&str>) {
+    let body = response
+        .body
+        .as_ref()
+        .map(|body| serde_json::to_string_pretty(body).expect("could not convert to json"))
+        .unwrap_or_default();
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        body.len()
+    );
+    if let Some(origin) = origin {
+        head.push_str(&format!(
+            "Access-Control-Allow-Origin: {origin}\r\nVary: Origin\r\n\
+             Access-Control-Allow-Methods: GET, POST, PATCH, DELETE, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Authorization, Content-Type, If-Match\r\n"
+        ));
+    }
+    if response.body.is_some() {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    head.push_str("\r\n");
+
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(body.as_bytes());
+}
+
+fn task_json(task: &Task, blob_dir: &Path) -> Value {
+    serde_json::to_value(blob::inline(task, blob_dir)).expect("could not convert to json")
+}
+
+fn string_field<'a>(name: &str, value: &'a Value) -> Result<&'a str, ApiError> {
+    value
+        .as_str()
+        .ok_or_else(|| ApiError::bad_request(format!("`{name}` must be a string")))
+}
+
+/// Applies the JSON fields of a create or update request to `task`. A `null`
+/// clears optional fields.
+fn apply_fields(
+    store: &Store,
+    task: &mut Task,
+    fields: &Map<String, Value>,
+) -> Result<(), ApiError> {
+    for (name, value) in fields {
+        let invalid = |what: &str| ApiError::bad_request(format!("invalid {name}: {what}"));
+
+        match (name.as_str(), value) {
+            ("description", value) => {
+                let description = string_field(name, value)?;
+                if description.trim().is_empty() {
+                    return Err(invalid("must not be empty"));
+                }
+                task.set_description(description.to_owned());
+            }
+            ("status", value) => {
+                let status = string_field(name, value)?;
+                task.set_status(
+                    Status::from_str(status)
+                        .map_err(|_| invalid("expected todo, in-progress or done"))?,
+                );
+            }
+            ("priority", Value::Null) => task.set_priority(None),
+            ("priority", value) => {
+                let priority = string_field(name, value)?;
+                task.set_priority(Some(
+                    Priority::from_str(priority)
+                        .map_err(|_| invalid("expected low, medium, high or urgent"))?,
+                ));
+            }
+            ("tags", Value::String(tags)) => {
+                task.tags = parse_tags(tags);
+                task.inherited.tags.clear();
+            }
+            ("tags", Value::Array(tags)) => {
+                task.tags = tags
+                    .iter()
+                    .map(|tag| string_field(name, tag).map(str::to_owned))
+                    .collect::<Result<_, _>>()?;
+                task.inherited.tags.clear();
+            }
+            ("due", Value::Null) => task.due = None,
+            ("due", value) => {
+                let due = string_field(name, value)?;
+                task.due = Some(parse_datetime(due).map_err(|_| invalid(due))?);
+            }
+            ("remind", Value::Null) => task.remind_before_secs = None,
+            ("remind", value) => {
+                let offset = string_field(name, value)?;
+                task.remind_before_secs = Some(
+                    parse_duration(offset)
+                        .map_err(|_| invalid(offset))?
+                        .num_seconds(),
+                );
+            }
+            ("notes", Value::Null) => task.set_notes(None),
+            ("notes", value) => task.set_notes(Some(string_field(name, value)?.to_owned())),
+            ("source", Value::Null) => task.source = None,
+            ("source", value) => {
+                let source = string_field(name, value)?;
+                task.source = Some(Source::from_str(source).map_err(|_| invalid(source))?);
+            }
+            // Checked by `check_revision` before any change.
+            ("revision", _) => {}
+            ("parent", Value::Null) => task.parent = None,
+            ("parent", value) => {
+                let parent = store.resolve_id(string_field(name, value)?)?;
+                if parent == task.id {
+                    return Err(invalid("a task cannot be its own parent"));
+                }
+                // Walking up from the new parent must not come back to the
+                // task, or the subtree would hang from itself.
+                let mut seen = HashSet::new();
+                let mut above = Some(parent);
+                while let Some(id) = above.filter(|id| seen.insert(*id)) {
+                    if id == task.id {
+                        return Err(invalid("a task cannot be moved under its own subtask"));
+                    }
+                    above = store.find(id).and_then(|task| task.parent);
+                }
+                task.parent = Some(parent);
+            }
+            _ => return Err(ApiError::bad_request(format!("unknown field `{name}`"))),
+        }
+    }
+
+    Ok(())
+}
+
+fn list(request: &Request, store: &Store, blob_dir: &Path) -> Result<Response, ApiError> {
+    let mut tasks = match request.param("status") {
+        Some(status) => {
+            let status = Status::from_str(status)
+                .map_err(|_| ApiError::bad_request(format!("invalid status `{status}`")))?;
+            filter_by_status(&store.tasks, &status)
+        }
+        None => store.tasks.clone(),
+    };
+    if let Some(tag) = request.param("tag") {
+        tasks.retain(|task| task.has_tag(tag));
+    }
+    if let Some(filter) = request.param("where") {
+        let filter = query::parse(filter).map_err(|err| ApiError::bad_request(err.to_string()))?;
+        tasks.retain(|task| filter.matches(task));
+    }
+
+    let tasks = tasks.iter().map(|task| task_json(task, blob_dir)).collect();
+    Ok(Response::json(200, Value::Array(tasks)))
+}
+
+/// Runs `change` on the freshly read store under the store lock and saves
+/// the result. `change` returns the status and the task to answer with,
+/// which is read back after the save so it carries its new revision.
+fn mutate(
+    path: &Path,
+    change: impl FnOnce(&mut Store) -> Result<(u16, Ulid), ApiError>,
+) -> Result<Response, ApiError> {
+    store::check_writable(path)?;
+    let _lock = StoreLock::acquire(path)?;
+
+    let mut store = Store::open(path.to_owned());
+    let (status, id) = change(&mut store)?;
+    rules::save(&mut store, &Config::load())?;
+
+    let task = store
+        .tasks
+        .iter()
+        .chain(&store.trash)
+        .find(|task| task.id == id)
+        .ok_or_else(|| ApiError::new(404, format!("task {id} is gone")))?;
+    Ok(Response::json(status, task_json(task, &store.blob_dir())))
+}
+
+/// The revision a change was made from: an `If-Match` header, with or
+/// without the quotes of an entity tag, or else a `revision` field.
+fn expected_revision(
+    request: &Request,
+    fields: Option<&Map<String, Value>>,
+) -> Result<Option<u64>, ApiError> {
+    if let Some(value) = request.header("if-match") {
+        return value
+            .trim()
+            .trim_matches('"')
+            .parse()
+            .map(Some)
+            .map_err(|_| ApiError::bad_request(format!("invalid If-Match `{value}`")));
+    }
+    match fields.and_then(|fields| fields.get("revision")) {
+        None => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| ApiError::bad_request("`revision` must be a non-negative integer")),
+    }
+}
+
+fn check_revision(task: &Task, expected: Option<u64>, required: bool) -> Result<(), ApiError> {
+    match expected {
+        None if required => Err(ApiError::new(
+            428,
+            "send the revision the change was made from, in If-Match or a `revision` field",
+        )),
+        Some(revision) if revision != task.revision => Err(ApiError::new(
+            409,
+            format!(
+                "`{}` is at revision {}, not {revision}; fetch it and try again",
+                task.description, task.revision
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn route(request: &Request, path: &Path, require_revision: bool) -> Result<Response, ApiError> {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    let blob_dir = blob::blob_dir(path);
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("OPTIONS", _) => Ok(Response {
+            status: 204,
+            body: None,
+        }),
+
+        ("GET", ["tasks"]) => list(request, &Store::open(path.to_owned()), &blob_dir),
+
+        ("GET", ["tasks", id]) => {
+            let store = Store::open(path.to_owned());
+            let id = store.resolve_id(id)?;
+            let task = store.find(id).expect("resolved ids exist");
+            Ok(Response::json(200, task_json(task, &blob_dir)))
+        }
+
+        ("POST", ["tasks"]) => {
+            let fields = request.fields()?;
+            if !fields.contains_key("description") {
+                return Err(ApiError::bad_request("missing field `description`"));
+            }
+
+            mutate(path, |store| {
+                let mut task = Task::new(String::new());
+                apply_fields(store, &mut task, &fields)?;
+                let id = task.id;
+                store.tasks.push(task);
+                inherit(&mut store.tasks, id);
+                store.assign_numbers();
+                Ok((201, id))
+            })
+        }
+
+        ("PATCH", ["tasks", id]) => {
+            let fields = request.fields()?;
+            let expected = expected_revision(request, Some(&fields))?;
+
+            mutate(path, |store| {
+                let id = store.resolve_id(id)?;
+                let mut task = store.find(id).expect("resolved ids exist").clone();
+                check_revision(&task, expected, require_revision)?;
+                apply_fields(store, &mut task, &fields)?;
+                task.updated_at = Utc::now();
+
+                *store.find_mut(id).expect("resolved ids exist") = task;
+                Ok((200, id))
+            })
+        }
+
+        ("POST", ["tasks", id, "status"]) => {
+            let fields = request.fields()?;
+            let status = fields
+                .get("status")
+                .ok_or_else(|| ApiError::bad_request("missing field `status`"))?;
+            let status = Status::from_str(string_field("status", status)?)
+                .map_err(|_| ApiError::bad_request("invalid status"))?;
+            let expected = expected_revision(request, Some(&fields))?;
+
+            mutate(path, |store| {
+                let id = store.resolve_id(id)?;
+                let task = store.find_mut(id).expect("resolved ids exist");
+                check_revision(task, expected, require_revision)?;
+                task.set_status(status);
+                Ok((200, id))
+            })
+        }
+
+        ("DELETE", ["tasks", id]) => {
+            let expected = expected_revision(request, None)?;
+
+            mutate(path, |store| {
+                let id = store.resolve_id(id)?;
+                check_revision(
+                    store.find(id).expect("resolved ids exist"),
+                    expected,
+                    require_revision,
+                )?;
+                store.trash(id);
+                Ok((200, id))
+            })
+        }
+
+        (_, ["tasks"] | ["tasks", _] | ["tasks", _, "status"]) => {
+            Err(ApiError::new(405, "method not allowed"))
+        }
+        _ => Err(ApiError::new(404, format!("no route for {}", request.path))),
+    }
+}
+
+fn authorized(request: &Request, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    request.method == "OPTIONS"
+        || request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| given.trim() == token)
+}
+
+fn handle(stream: TcpStream, path: &Path, access: &Access, require_revision: bool) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+
+    let mut origin = None;
+    let result = read_request(&stream).and_then(|request| {
+        access.check(&request)?;
+        origin = access.allowed_origin(&request).map(str::to_owned);
+        // Commands report bad input by panicking; answer with an error
+        // instead of taking the server down.
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            route(&request, path, require_revision)
+        }))
+        .unwrap_or_else(|_| Err(ApiError::new(500, "internal error")))
+    });
+
+    let response = result.unwrap_or_else(|err| {
+        Response::json(
+            err.status,
+            json!({ "error": err.message, "status": err.status }),
+        )
+    });
+    write_response(&stream, &response, origin.as_deref());
+}
+
+/// Serves the API until the process is stopped.
+pub fn run(args: &Args) -> ! {
+    let port = args.value("port").unwrap_or("8080");
+    let host = args.value("host").unwrap_or("127.0.0.1");
+    let token = args
+        .value("token")
+        .map(str::to_owned)
+        .or_else(|| std::env::var("TASK_CLI_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+    let require_revision = args.flag("require-revision");
+    let path = store::tasks_path(args);
+    remote::local_only(&path, "serve");
+
+    let listener = TcpListener::bind(format!("{host}:{port}"))
+        .unwrap_or_else(|err| panic!("cannot listen on {host}:{port}: {err}"));
+    eprintln!(
+        "serving {} on http://{host}:{port}{}",
+        path.display(),
+        if token.is_some() {
+            " (token required)"
+        } else {
+            ""
+        }
+    );
+
+    let access = Access {
+        token,
+        host: host.to_owned(),
+        origins: Config::load().serve.allowed_origins,
+    };
+    loop {
+        if let Ok((stream, _)) = listener.accept() {
+            handle(stream, &path, &access, require_revision);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(raw: &str) -> Result<Request, ApiError> {
+        read_request(raw.as_bytes())
+    }
+
+    fn status(result: Result<impl Sized, ApiError>) -> u16 {
+        result.err().map_or(200, |err| err.status)
+    }
+
+    #[test]
+    fn requests_are_split_and_decoded() {
+        let request = request(
+            "PATCH /tasks/01H%2F2?where=tag%3Awork+or+x&flag HTTP/1.1\r\n\
+             Host: localhost:8080\r\n\
+             content-type:  application/json; charset=utf-8 \r\n\
+             Content-Length: 17\r\n\
+             \r\n\
+             {\"status\":\"done\"}trailing",
+        )
+        .unwrap();
+
+        assert_eq!(request.method, "PATCH");
+        assert_eq!(request.path, "/tasks/01H/2");
+        assert_eq!(request.param("where"), Some("tag:work or x"));
+        assert_eq!(request.param("flag"), Some(""));
+        assert_eq!(
+            request.header("Content-Type"),
+            Some("application/json; charset=utf-8")
+        );
+        assert_eq!(request.fields().unwrap()["status"], "done");
+    }
+
+    #[test]
+    fn broken_requests_are_refused() {
+        assert_eq!(status(request("\r\n")), 400);
+        assert_eq!(
+            status(request("GET /tasks HTTP/1.1\r\nContent-Length: x\r\n\r\n")),
+            400
+        );
+        assert_eq!(
+            status(request(
+                "POST /tasks HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}"
+            )),
+            400
+        );
+        let too_large = format!(
+            "POST /tasks HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(status(request(&too_large)), 413);
+    }
+
+    #[test]
+    fn bodies_have_to_be_json_objects() {
+        let body = |content_type: &str, body: &str| {
+            request(&format!(
+                "POST /tasks HTTP/1.1\r\n{content_type}Content-Length: {}\r\n\r\n{body}",
+                body.len()
+            ))
+            .unwrap()
+            .fields()
+        };
+
+        assert_eq!(status(body("", "{}")), 415);
+        assert_eq!(status(body("Content-Type: text/plain\r\n", "{}")), 415);
+        assert_eq!(
+            status(body("Content-Type: Application/JSON\r\n", "{}")),
+            200
+        );
+        assert_eq!(
+            status(body("Content-Type: application/json\r\n", "[1]")),
+            400
+        );
+        assert_eq!(status(body("Content-Type: application/json\r\n", "{")), 400);
+    }
+
+    #[test]
+    fn parents_cannot_be_subtasks() {
+        let top = Task::new("move house".to_owned());
+        let mut middle = Task::new("pack".to_owned());
+        middle.parent = Some(top.id);
+        let mut bottom = Task::new("buy boxes".to_owned());
+        bottom.parent = Some(middle.id);
+        let store = Store::new(
+            std::path::PathBuf::from("/nonexistent/tasks.json"),
+            vec![top.clone(), middle.clone(), bottom.clone()],
+        );
+        let reparent = |task: &Task, parent: &Task| {
+            let fields = serde_json::json!({ "parent": parent.id.to_string() });
+            let Value::Object(fields) = fields else {
+                unreachable!()
+            };
+            status(apply_fields(&store, &mut task.clone(), &fields))
+        };
+
+        assert_eq!(reparent(&top, &top), 400);
+        assert_eq!(reparent(&top, &middle), 400);
+        assert_eq!(reparent(&top, &bottom), 400);
+        assert_eq!(reparent(&bottom, &top), 200);
+    }
+
+    #[test]
+    fn hosts_origins_and_tokens() {
+        let access = |token: Option<&str>| Access {
+            token: token.map(str::to_owned),
+            host: "0.0.0.0".to_owned(),
+            origins: vec!["http://localhost:5173".to_owned()],
+        };
+        let check = |access: &Access, headers: &str| {
+            status(
+                access.check(&request(&format!("GET /tasks HTTP/1.1\r\n{headers}\r\n")).unwrap()),
+            )
+        };
+
+        let open = access(None);
+        assert_eq!(check(&open, "Host: localhost:8080\r\n"), 200);
+        assert_eq!(check(&open, "Host: [::1]:8080\r\n"), 200);
+        assert_eq!(check(&open, "Host: 0.0.0.0\r\n"), 200);
+        assert_eq!(check(&open, "Host: evil.example:8080\r\n"), 421);
+        assert_eq!(check(&open, ""), 421);
+        // Without a token no page may call, not even a listed one.
+        assert_eq!(
+            check(
+                &open,
+                "Host: localhost\r\nOrigin: http://localhost:5173\r\n"
+            ),
+            403
+        );
+
+        let locked = access(Some("s3cret"));
+        assert_eq!(check(&locked, "Host: localhost\r\n"), 401);
+        assert_eq!(
+            check(
+                &locked,
+                "Host: localhost\r\nAuthorization: Bearer s3cret\r\n"
+            ),
+            200
+        );
+        assert_eq!(
+            check(
+                &locked,
+                "Host: localhost\r\nOrigin: http://localhost:5173\r\nAuthorization: Bearer s3cret\r\n"
+            ),
+            200
+        );
+        assert_eq!(
+            check(
+                &locked,
+                "Host: localhost\r\nOrigin: https://evil.example\r\nAuthorization: Bearer s3cret\r\n"
+            ),
+            403
+        );
+    }
+}
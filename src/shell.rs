@@ -0,0 +1,149 @@
+//! `shell-init` hooks that switch stores with the working directory. A
+//! directory has its own context when it or a parent holds a `.taskcli`
+//! marker or a `.tasks.json` store; the hook then points `TASK_CLI_FILE` at
+//! that store and names the context in `TASK_CLI_CONTEXT`.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{args::Args, commands::shell_quote, toml, CliError};
+
+pub const MARKER: &str = ".taskcli";
+const STORE_NAME: &str = ".tasks.json";
+/// Set alongside `TASK_CLI_FILE` so the hook can tell its own value from
+/// one set by hand, which it leaves alone.
+const AUTO_VAR: &str = "TASK_CLI_AUTO_FILE";
+
+/// Contents of a `.taskcli` marker, all optional; an empty marker uses
+/// `.tasks.json` next to it.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Marker {
+    /// Store path, relative to the marker's directory.
+    file: Option<String>,
+    /// Context name; defaults to the directory name.
+    name: Option<String>,
+}
+
+pub struct Context {
+    pub name: String,
+    pub file: PathBuf,
+    /// The marker or store that set the context.
+    pub source: PathBuf,
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name().map_or_else(
+        || dir.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// The context of `dir`, from the nearest directory with a marker or store.
+/// The home directory's store is the default one, not a context.
+pub fn detect(dir: &Path) -> Result<Option<Context>, CliError> {
+    let home = dirs::home_dir();
+
+    for dir in dir.ancestors() {
+        let marker = dir.join(MARKER);
+        if marker.is_file() {
+            let contents = std::fs::read_to_string(&marker).map_err(|error| CliError::Read {
+                path: marker.clone(),
+                error,
+            })?;
+            let value = toml::parse(&contents)
+                .unwrap_or_else(|err| panic!("invalid {}: {err}", marker.display()));
+            let settings: Marker = serde_json::from_value(value)
+                .unwrap_or_else(|err| panic!("invalid {}: {err}", marker.display()));
+
+            return Ok(Some(Context {
+                name: settings.name.unwrap_or_else(|| dir_name(dir)),
+                file: dir.join(settings.file.as_deref().unwrap_or(STORE_NAME)),
+                source: marker,
+            }));
+        }
+
+        let store = dir.join(STORE_NAME);
+        if store.is_file() && home.as_deref() != Some(dir) {
+            return Ok(Some(Context {
+                name: dir_name(dir),
+                file: store.clone(),
+                source: store,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+const ZSH_HOOK: &str = r#"_task_cli_chpwd() {
+  eval "$(command task-cli context --export)"
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _task_cli_chpwd
+_task_cli_chpwd
+"#;
+
+const BASH_HOOK: &str = r#"_task_cli_chpwd() {
+  if [[ "$PWD" != "${_task_cli_pwd-}" ]]; then
+    _task_cli_pwd=$PWD
+    eval "$(command task-cli context --export)"
+  fi
+}
+PROMPT_COMMAND="_task_cli_chpwd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+"#;
+
+/// `shell-init <shell>`: prints the hook to `eval` from the shell's rc file.
+pub fn init(args: &Args) -> Result<(), CliError> {
+    let shell = args
+        .positional(1)
+        .expect("missing shell, expected zsh or bash");
+    let hook = match shell {
+        "zsh" => ZSH_HOOK,
+        "bash" => BASH_HOOK,
+        _ => panic!("unknown shell `{shell}`, expected zsh or bash"),
+    };
+    print!("{hook}");
+    Ok(())
+}
+
+/// `context [--export]`: shows the working directory's context, or with
+/// `--export` prints the shell commands that switch to it.
+pub fn context(args: &Args) -> Result<(), CliError> {
+    let dir = std::env::current_dir().map_err(|error| CliError::Read {
+        path: PathBuf::from("."),
+        error,
+    })?;
+    let context = detect(&dir)?;
+
+    if !args.flag("export") {
+        match context {
+            Some(context) => println!(
+                "context `{}`: {} (from {})",
+                context.name,
+                context.file.display(),
+                context.source.display()
+            ),
+            None => println!("no directory context here"),
+        }
+        return Ok(());
+    }
+
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let auto = var(AUTO_VAR);
+    if var("TASK_CLI_FILE").is_some_and(|file| Some(&file) != auto.as_ref()) {
+        return Ok(());
+    }
+
+    match context {
+        Some(context) => {
+            let file = shell_quote(&context.file.to_string_lossy());
+            println!("export TASK_CLI_FILE={file} {AUTO_VAR}={file}");
+            println!("export TASK_CLI_CONTEXT={}", shell_quote(&context.name));
+        }
+        None if auto.is_some() => println!("unset TASK_CLI_FILE TASK_CLI_CONTEXT {AUTO_VAR}"),
+        None => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! SIGINT/SIGTERM/SIGHUP handling. Long-running modes register the terminal
+//! state and files they own here; the handler restores the terminal and
+//! removes lock and temporary files before the process dies, so an
+//! interrupted command never leaves a stale lock or half-written store. A
+//! lock on another host is released by a command registered ahead, which
+//! the handler runs and waits for.
+
+use std::{
+    ffi::{CString, OsStr},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::Command,
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+static LOCK_FILE: AtomicPtr<libc::c_char> = AtomicPtr::new(ptr::null_mut());
+static TEMP_FILE: AtomicPtr<libc::c_char> = AtomicPtr::new(ptr::null_mut());
+static TERMINAL: AtomicPtr<libc::termios> = AtomicPtr::new(ptr::null_mut());
+static EXIT_COMMAND: AtomicPtr<ExitCommand> = AtomicPtr::new(ptr::null_mut());
+
+/// A command ready to `execv` from the handler, where nothing may allocate.
+struct ExitCommand {
+    program: *const libc::c_char,
+    /// Null-terminated.
+    argv: *const *const libc::c_char,
+}
+
+extern "C" fn handle(signal: libc::c_int) {
+    // Only async-signal-safe calls from here on.
+    unsafe {
+        let terminal = TERMINAL.load(Ordering::SeqCst);
+        if !terminal.is_null() {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, terminal);
+        }
+
+        for slot in [&TEMP_FILE, &LOCK_FILE] {
+            let path = slot.load(Ordering::SeqCst);
+            if !path.is_null() {
+                libc::unlink(path);
+            }
+        }
+
+        let command = EXIT_COMMAND.load(Ordering::SeqCst);
+        if !command.is_null() {
+            match libc::fork() {
+                0 => {
+                    let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY);
+                    if null >= 0 {
+                        libc::dup2(null, libc::STDIN_FILENO);
+                    }
+                    libc::execv((*command).program, (*command).argv);
+                    libc::_exit(127);
+                }
+                pid if pid > 0 => {
+                    libc::waitpid(pid, ptr::null_mut(), 0);
+                }
+                _ => {}
+            }
+        }
+
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+pub fn install() {
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe {
+            libc::signal(signal, handle as *const () as libc::sighandler_t);
+        }
+    }
+}
+
+/// Paths registered so far, by their bytes. They are never freed: the
+/// handler may be reading the previous one when it is replaced, and a
+/// process only ever registers a few distinct paths.
+static PATHS: Mutex<Vec<(Vec<u8>, usize)>> = Mutex::new(Vec::new());
+
+fn c_path(path: &Path) -> *mut libc::c_char {
+    let bytes = path.as_os_str().as_bytes();
+    let mut paths = PATHS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((_, pointer)) = paths.iter().find(|(known, _)| known == bytes) {
+        return *pointer as *mut libc::c_char;
+    }
+    let Ok(path) = CString::new(bytes) else {
+        return ptr::null_mut();
+    };
+    let pointer = CString::into_raw(path);
+    paths.push((bytes.to_vec(), pointer as usize));
+    pointer
+}
+
+fn register_file(slot: &AtomicPtr<libc::c_char>, path: Option<&Path>) {
+    slot.store(path.map_or(ptr::null_mut(), c_path), Ordering::SeqCst);
+}
+
+/// Lock file to remove if the process is interrupted.
+pub fn register_lock_file(path: Option<&Path>) {
+    register_file(&LOCK_FILE, path);
+}
+
+/// Temporary file to remove if the process is interrupted mid-save.
+pub fn register_temp_file(path: Option<&Path>) {
+    register_file(&TEMP_FILE, path);
+}
+
+fn leak(text: &OsStr) -> Option<*const libc::c_char> {
+    CString::new(text.as_bytes())
+        .ok()
+        .map(|text| CString::into_raw(text).cast_const())
+}
+
+/// `program` as found on `PATH`, which the handler cannot search.
+fn resolve(program: &OsStr) -> Option<PathBuf> {
+    if program.as_bytes().contains(&b'/') {
+        return Some(PathBuf::from(program));
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Command to run, and wait for, if the process is interrupted, such as one
+/// removing a lock on another host. Its environment and working directory
+/// are not kept. Like the paths, replaced commands are never freed.
+pub fn register_exit_command(command: Option<&Command>) {
+    let value = command
+        .and_then(|command| {
+            let program = leak(resolve(command.get_program())?.as_os_str())?;
+            let mut argv = vec![leak(command.get_program())?];
+            for arg in command.get_args() {
+                argv.push(leak(arg)?);
+            }
+            argv.push(ptr::null());
+            let argv = Box::leak(argv.into_boxed_slice()).as_ptr();
+            Some(Box::into_raw(Box::new(ExitCommand { program, argv })))
+        })
+        .unwrap_or(ptr::null_mut());
+    EXIT_COMMAND.store(value, Ordering::SeqCst);
+}
+
+/// Terminal settings to restore if the process is interrupted.
+pub fn register_terminal(original: Option<&libc::termios>) {
+    let value = original.map_or(ptr::null_mut(), |original| {
+        Box::into_raw(Box::new(*original))
+    });
+
+    // Leaked like the paths, for the same reason; terminals are registered
+    // once per prompt.
+    TERMINAL.store(value, Ordering::SeqCst);
+}
@@ -0,0 +1,146 @@
+//! `stats rhythm`: when tasks get created and completed, as hour-of-day by
+//! day-of-week heatmaps in local time. Completions come from the event
+//! log; done tasks from before it are counted at their last update.
+
+use chrono::{DateTime, Datelike, Local, Timelike, Utc, Weekday};
+use std::collections::HashSet;
+
+use crate::{
+    history::{Event, EventKind},
+    locale::Locale,
+    task::Task,
+};
+
+/// From no tasks at all to the busiest hour.
+const SHADES: [char; 5] = [' ', '.', ':', '*', '#'];
+
+/// Counts by day of the week, from Monday, and hour of the day.
+#[derive(Default)]
+pub struct Heatmap {
+    counts: [[usize; 24]; 7],
+}
+
+impl Heatmap {
+    fn add(&mut self, at: DateTime<Utc>) {
+        let local = at.with_timezone(&Local);
+        self.counts[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += 1;
+    }
+
+    fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    fn max(&self) -> usize {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// The busiest day and hour, the earliest in the week on a tie.
+    fn peak(&self) -> Option<(Weekday, u32, usize)> {
+        let mut peak = None;
+        for (day, hours) in self.counts.iter().enumerate() {
+            for (hour, &count) in hours.iter().enumerate() {
+                if count > peak.map_or(0, |(_, _, most)| most) {
+                    peak = Some((day, hour, count));
+                }
+            }
+        }
+        peak.map(|(day, hour, count)| {
+            let day = Weekday::try_from(day as u8).expect("seven days");
+            (day, hour as u32, count)
+        })
+    }
+
+    fn render(&self, title: &str, locale: &Locale) -> String {
+        let total = self.total();
+        let mut out = format!("{title} ({total})\n");
+        if total == 0 {
+            return out;
+        }
+
+        out.push_str("     ");
+        for hour in (0..24).step_by(3) {
+            out.push_str(&format!("{hour:<6}"));
+        }
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+
+        let max = self.max();
+        let mut day = locale.week_start;
+        for _ in 0..7 {
+            let mut row = format!("{:<5}", day.to_string());
+            for &count in &self.counts[day.num_days_from_monday() as usize] {
+                let shade = SHADES[(count * (SHADES.len() - 1)).div_ceil(max)];
+                row.push(shade);
+                row.push(shade);
+            }
+            out.push_str(row.trim_end());
+            out.push('\n');
+            day = day.succ();
+        }
+
+        if let Some((day, hour, count)) = self.peak() {
+            out.push_str(&format!(
+                "     busiest: {day} {hour:02}:00-{:02}:00 ({count}); # is {max}\n",
+                (hour + 1) % 24
+            ));
+        }
+        out
+    }
+}
+
+pub struct Rhythm {
+    pub created: Heatmap,
+    pub completed: Heatmap,
+}
+
+/// Whether `event` marks its task done. Tasks created done, as by imports,
+/// were finished elsewhere: their last update says when.
+fn completes(event: &Event) -> bool {
+    event.kind == EventKind::Updated
+        && event
+            .changes
+            .get("status")
+            .is_some_and(|change| change.to == "done")
+}
+
+/// The rhythm of `tasks` since `since`, with their completions taken from
+/// `events`.
+pub fn rhythm(tasks: &[Task], events: &[Event], since: Option<DateTime<Utc>>) -> Rhythm {
+    let counted = |at: &DateTime<Utc>| since.is_none_or(|since| *at >= since);
+    let ids = tasks.iter().map(|task| task.id).collect::<HashSet<_>>();
+
+    let mut created = Heatmap::default();
+    for task in tasks.iter().filter(|task| counted(&task.created_at)) {
+        created.add(task.created_at);
+    }
+
+    let mut completed = Heatmap::default();
+    let mut logged = HashSet::new();
+    for event in events
+        .iter()
+        .filter(|event| ids.contains(&event.task) && completes(event))
+    {
+        logged.insert(event.task);
+        if counted(&event.at) {
+            completed.add(event.at);
+        }
+    }
+    for at in tasks
+        .iter()
+        .filter(|task| !logged.contains(&task.id))
+        .filter_map(Task::done_at)
+        .filter(counted)
+    {
+        completed.add(at);
+    }
+
+    Rhythm { created, completed }
+}
+
+pub fn render(rhythm: &Rhythm, locale: &Locale) -> String {
+    format!(
+        "{}\n{}",
+        rhythm.created.render("Created", locale),
+        rhythm.completed.render("Completed", locale)
+    )
+}
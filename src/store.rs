@@ -0,0 +1,708 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    args::Args,
+    blob, fuzzy, greet, history,
+    lock::{self, StoreLock},
+    merge::{self, Conflict, Side},
+    picker, signal,
+    task::{update_rollups, Status, Task},
+    CliError,
+};
+
+/// Store location: `--file`, then `TASK_CLI_FILE`, then `~/.tasks.json`.
+pub fn tasks_path(args: &Args) -> PathBuf {
+    if let Some(file) = args.value("file") {
+        PathBuf::from(file)
+    } else if let Some(file) = std::env::var_os("TASK_CLI_FILE").filter(|file| !file.is_empty()) {
+        PathBuf::from(file)
+    } else {
+        dirs::home_dir().unwrap().join(".tasks.json")
+    }
+}
+
+/// File layout; stores written before the trash existed are a bare array.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoreFile {
+    Current(Contents),
+    Legacy(Vec<Task>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Contents {
+    tasks: Vec<Task>,
+    #[serde(default)]
+    trash: Vec<Task>,
+}
+
+pub struct Store {
+    pub path: PathBuf,
+    pub tasks: Vec<Task>,
+    /// Deleted tasks, each with `deleted_at` set, kept until purged.
+    pub trash: Vec<Task>,
+    /// Tasks and trash as last read or written, to log what a save changes.
+    saved: (Vec<Task>, Vec<Task>),
+    /// Modification time and length of the file as last read or written,
+    /// to notice saves made by others in the meantime.
+    stamp: Option<(SystemTime, u64)>,
+    /// Lock taken by [`Self::lock`], released with the store.
+    lock: Option<StoreLock>,
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl Store {
+    /// A store that has not been saved yet; saving logs every task as
+    /// created.
+    pub fn new(path: PathBuf, tasks: Vec<Task>) -> Self {
+        let stamp = stamp(&path);
+        Self {
+            path,
+            tasks,
+            trash: Vec::new(),
+            saved: (Vec::new(), Vec::new()),
+            stamp,
+            lock: None,
+        }
+    }
+
+    pub fn open(path: PathBuf) -> Self {
+        let stamp = stamp(&path);
+        let contents = if let Ok(contents) = std::fs::read(&path) {
+            match serde_json::from_slice::<StoreFile>(&contents).expect("invalid json format") {
+                StoreFile::Current(contents) => contents,
+                StoreFile::Legacy(tasks) => Contents {
+                    tasks,
+                    trash: Vec::new(),
+                },
+            }
+        } else {
+            Contents {
+                tasks: Vec::new(),
+                trash: Vec::new(),
+            }
+        };
+
+        let mut store = Self {
+            path,
+            tasks: contents.tasks,
+            trash: contents.trash,
+            saved: (Vec::new(), Vec::new()),
+            stamp,
+            lock: None,
+        };
+        store.assign_numbers();
+        // Stores from before rollups were kept have none cached yet.
+        let uncached = store.tasks.iter().any(|task| {
+            task.parent
+                .and_then(|parent| store.find(parent))
+                .is_some_and(|parent| parent.rollup.is_none())
+        });
+        if uncached {
+            let all = store.tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+            update_rollups(&mut store.tasks, all);
+        }
+        store.saved = (store.tasks.clone(), store.trash.clone());
+        store
+    }
+
+    /// Numbers tasks that do not have one yet, continuing after the highest
+    /// number in use. Tasks from before numbering get theirs in creation
+    /// order.
+    pub fn assign_numbers(&mut self) {
+        let mut next = self
+            .tasks
+            .iter()
+            .chain(&self.trash)
+            .filter_map(|task| task.number)
+            .max()
+            .unwrap_or(0);
+
+        let mut unnumbered = self
+            .tasks
+            .iter_mut()
+            .chain(&mut self.trash)
+            .filter(|task| task.number.is_none())
+            .collect::<Vec<_>>();
+        unnumbered.sort_by_key(|task| (task.created_at, task.id));
+        for task in unnumbered {
+            next += 1;
+            task.number = Some(next);
+        }
+    }
+
+    pub fn find_number(&self, number: u32) -> Option<&Task> {
+        self.tasks.iter().find(|task| task.number == Some(number))
+    }
+
+    /// Resolves a `#12` number, a full id, a unique case-insensitive id prefix
+    /// or, failing those, the open task whose description best matches
+    /// `query`.
+    pub fn resolve(&self, query: &str, confirm: Confirm) -> Result<Ulid, CliError> {
+        match resolve_in(&self.tasks, query) {
+            Err(CliError::NoMatch(_)) => self.resolve_description(query, confirm),
+            result => result,
+        }
+    }
+
+    /// Resolves a full id or a unique id prefix, never a description.
+    pub fn resolve_id(&self, query: &str) -> Result<Ulid, CliError> {
+        resolve_in(&self.tasks, query)
+    }
+
+    /// Trashed tasks are only ever addressed by id.
+    pub fn resolve_trashed(&self, query: &str) -> Result<Ulid, CliError> {
+        resolve_in(&self.trash, query)
+    }
+
+    fn resolve_description(&self, query: &str, confirm: Confirm) -> Result<Ulid, CliError> {
+        let mut scored = self
+            .tasks
+            .iter()
+            .filter(|task| task.status != Status::Done)
+            .filter_map(|task| fuzzy::score(query, &task.description).map(|score| (score, task)))
+            .collect::<Vec<_>>();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        let best = match scored.as_slice() {
+            [] => return Err(CliError::NoMatch(query.to_owned())),
+            [(first, _), (second, _), ..] if first == second => {
+                let tied = scored
+                    .iter()
+                    .take_while(|(score, _)| score == first)
+                    .map(|(_, task)| *task)
+                    .collect::<Vec<_>>();
+                return pick_one(query, &tied);
+            }
+            [(_, task), ..] => *task,
+        };
+
+        let summary = format!("{}. {} ({})", best.id, best.description, best.status);
+        match confirm {
+            Confirm::NotNeeded | Confirm::Given => Ok(best.id),
+            Confirm::Ask if picker::is_interactive() => {
+                if picker::confirm(&format!("`{query}` matches {summary}; use it?")) {
+                    Ok(best.id)
+                } else {
+                    Err(CliError::NoMatch(query.to_owned()))
+                }
+            }
+            Confirm::Ask => Err(CliError::Unconfirmed {
+                query: query.to_owned(),
+                candidate: summary,
+            }),
+        }
+    }
+
+    pub fn find(&self, id: Ulid) -> Option<&Task> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+
+    pub fn find_mut(&mut self, id: Ulid) -> Option<&mut Task> {
+        self.tasks.iter_mut().find(|task| task.id == id)
+    }
+
+    /// Moves a task into the trash.
+    pub fn trash(&mut self, id: Ulid) -> Option<&Task> {
+        let index = self.tasks.iter().position(|task| task.id == id)?;
+        let mut task = self.tasks.remove(index);
+        task.deleted_at = Some(Utc::now());
+
+        self.trash.push(task);
+        self.trash.last()
+    }
+
+    pub fn restore(&mut self, id: Ulid) -> Option<&Task> {
+        let index = self.trash.iter().position(|task| task.id == id)?;
+        let mut task = self.trash.remove(index);
+        task.deleted_at = None;
+
+        self.tasks.push(task);
+        self.tasks.last()
+    }
+
+    /// Puts back a copy of a task kept elsewhere, such as in a backup: over
+    /// the task as it is now, out of the trash, or anew if it was purged.
+    /// Links to tasks that are gone are dropped, as is a number another
+    /// task has taken since.
+    pub fn put_back(&mut self, mut task: Task) {
+        let (index, current) = match self.tasks.iter().position(|other| other.id == task.id) {
+            Some(index) => (Some(index), Some(self.tasks.remove(index))),
+            None => {
+                let trashed = self.trash.iter().position(|other| other.id == task.id);
+                (None, trashed.map(|index| self.trash.remove(index)))
+            }
+        };
+
+        let taken = |number| {
+            self.tasks
+                .iter()
+                .chain(&self.trash)
+                .any(|other| other.number == Some(number))
+        };
+        if task.number.is_some_and(taken) {
+            task.number = None;
+        }
+        let exists = |id: &Ulid| self.tasks.iter().any(|other| other.id == *id);
+        task.parent = task.parent.filter(exists);
+        task.blocked_by.retain(exists);
+        task.deleted_at = None;
+        task.updated_at = Utc::now();
+        // Saving moves the revision on from the current one.
+        if let Some(current) = current {
+            task.revision = task.revision.max(current.revision);
+        }
+
+        match index {
+            Some(index) => self.tasks.insert(index, task),
+            None => self.tasks.push(task),
+        }
+    }
+
+    /// A read-only copy of the store as it was at `at`, rebuilt from the
+    /// event log.
+    pub fn as_of(&self, at: DateTime<Utc>) -> Result<Store, CliError> {
+        let events = history::load(&history::history_path(&self.path))?;
+        let dir = self.blob_dir();
+        let (mut tasks, trash) = history::rewind(
+            &blob::inline_all(&self.tasks, &dir),
+            &blob::inline_all(&self.trash, &dir),
+            &events,
+            at,
+        );
+        let all = tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        update_rollups(&mut tasks, all);
+
+        let mut past = Store::new(self.path.clone(), tasks);
+        past.trash = trash;
+        Ok(past)
+    }
+
+    /// For commands that work on the store unlocked for a while, such as
+    /// `edit` while the editor is open: takes the lock, unless this process
+    /// holds it already. What others saved meanwhile is merged in on save.
+    pub fn lock(&mut self) -> Result<(), CliError> {
+        if !lock::held(&self.path) {
+            self.lock = Some(StoreLock::acquire(&self.path)?);
+        }
+        Ok(())
+    }
+
+    /// Merges in what others saved to the file since it was read, field by
+    /// field. Fields changed differently on both sides are settled at the
+    /// terminal, or fail the save when nobody can answer.
+    pub fn merge_saved(&mut self) -> Result<(), CliError> {
+        let current = stamp(&self.path);
+        if current.is_none() || current == self.stamp {
+            return Ok(());
+        }
+
+        let theirs = Store::open(self.path.clone());
+        let all = |tasks: &[Task], trash: &[Task]| [tasks, trash].concat();
+        let base = all(&self.saved.0, &self.saved.1);
+        let ours = all(&self.tasks, &self.trash);
+        let mut unresolved = None;
+        let merged = merge::merge(
+            &base,
+            &ours,
+            &all(&theirs.tasks, &theirs.trash),
+            |conflict| {
+                let side = if picker::is_interactive() {
+                    pick_side(conflict)
+                } else {
+                    None
+                };
+                if side.is_none() {
+                    unresolved = Some(describe_conflict(conflict));
+                }
+                side
+            },
+        );
+        let Some(mut merged) = merged else {
+            return Err(CliError::Conflict(format!(
+                "{}; nothing was saved",
+                unresolved.unwrap_or_default()
+            )));
+        };
+
+        // Tasks added on both sides may have taken the same numbers.
+        let taken = theirs
+            .saved_tasks()
+            .filter_map(|task| task.number)
+            .collect::<HashSet<_>>();
+        for task in &mut merged {
+            let added_here = !base
+                .iter()
+                .chain(theirs.saved_tasks())
+                .any(|old| old.id == task.id);
+            if added_here && task.number.is_some_and(|number| taken.contains(&number)) {
+                task.number = None;
+            }
+        }
+
+        let (trash, tasks) = merged
+            .into_iter()
+            .partition::<Vec<_>, _>(|task| task.deleted_at.is_some());
+        self.tasks = tasks;
+        self.trash = trash;
+        self.saved = theirs.saved.clone();
+        self.stamp = theirs.stamp;
+        self.assign_numbers();
+        Ok(())
+    }
+
+    /// Ids of the tasks added, changed or deleted since the last read or
+    /// save.
+    pub fn changed_ids(&self) -> Vec<Ulid> {
+        let json = |task: &Task| serde_json::to_value(task).expect("could not convert to json");
+        let saved = self
+            .saved
+            .0
+            .iter()
+            .map(|task| (task.id, json(task)))
+            .collect::<HashMap<_, _>>();
+
+        let mut changed = self
+            .tasks
+            .iter()
+            .filter(|task| saved.get(&task.id) != Some(&json(task)))
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        changed.extend(
+            self.saved
+                .0
+                .iter()
+                .filter(|old| self.find(old.id).is_none())
+                .map(|old| old.id),
+        );
+        changed
+    }
+
+    /// Tasks and trash as they were when last read or saved.
+    pub fn saved_tasks(&self) -> impl Iterator<Item = &Task> {
+        self.saved.0.iter().chain(&self.saved.1)
+    }
+
+    /// The store file's contents with blob texts inlined, so the copy stands
+    /// on its own; `backup` writes these.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let dir = self.blob_dir();
+        let contents = Contents {
+            tasks: blob::inline_all(&self.tasks, &dir),
+            trash: blob::inline_all(&self.trash, &dir),
+        };
+        serde_json::to_vec(&contents).expect("could not convert to json")
+    }
+
+    pub fn blob_dir(&self) -> PathBuf {
+        blob::blob_dir(&self.path)
+    }
+
+    pub fn check_writable(&self) -> Result<(), CliError> {
+        check_writable(&self.path)
+    }
+
+    /// Tasks whose rollup, or whose ancestors' rollups, may be out of date
+    /// since the last save: new tasks, tasks whose parent, status, estimate
+    /// or tracked time changed, and the former parents of tasks that moved
+    /// or left the list.
+    fn rollup_changes(&self) -> Vec<Ulid> {
+        let saved = self
+            .saved
+            .0
+            .iter()
+            .map(|task| (task.id, task))
+            .collect::<HashMap<_, _>>();
+        let current = self
+            .tasks
+            .iter()
+            .map(|task| task.id)
+            .collect::<HashSet<_>>();
+
+        let mut changed = Vec::new();
+        for task in &self.tasks {
+            match saved.get(&task.id) {
+                None => changed.push(task.id),
+                Some(old)
+                    if old.parent != task.parent
+                        || old.status != task.status
+                        || old.estimate_secs != task.estimate_secs
+                        || old.tracked_secs != task.tracked_secs =>
+                {
+                    changed.push(task.id);
+                    changed.extend(old.parent);
+                }
+                Some(_) => {}
+            }
+        }
+        for old in self.saved.0.iter().filter(|old| !current.contains(&old.id)) {
+            changed.extend(old.parent);
+        }
+
+        changed
+    }
+
+    /// Gives new tasks their first revision and changed ones the next.
+    /// Imported copies keep a revision that is higher already.
+    fn bump_revisions(&mut self) {
+        let saved = self
+            .saved
+            .0
+            .iter()
+            .chain(&self.saved.1)
+            .map(|task| (task.id, task))
+            .collect::<HashMap<_, _>>();
+        for task in self.tasks.iter_mut().chain(&mut self.trash) {
+            match saved.get(&task.id) {
+                None => task.revision = task.revision.max(1),
+                Some(old) if **old != *task => {
+                    task.revision = task.revision.max(old.revision + 1);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), CliError> {
+        self.merge_saved()?;
+        self.assign_numbers();
+        self.bump_revisions();
+        let changed = self.rollup_changes();
+        update_rollups(&mut self.tasks, changed);
+
+        let path = &self.path;
+        let dir = self.blob_dir();
+        let write_error = |error| CliError::Write {
+            path: path.to_owned(),
+            error,
+        };
+
+        let externalize = |tasks: &[Task]| {
+            tasks
+                .iter()
+                .map(|task| blob::externalize(task, &dir))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let contents = Contents {
+            tasks: externalize(&self.tasks).map_err(write_error)?,
+            trash: externalize(&self.trash).map_err(write_error)?,
+        };
+
+        // Write a sibling file and rename it over the store so readers and
+        // interrupted saves only ever see a complete file.
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = parent_dir(path).join(format!(".{file_name}.tmp-{}", std::process::id()));
+        signal::register_temp_file(Some(&temp));
+
+        let result = File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(&serde_json::to_vec(&contents).expect("could not convert to json"))?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&temp, path));
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        signal::register_temp_file(None);
+        result.map_err(write_error)?;
+
+        let events = history::diff(
+            (&self.saved.0, &self.saved.1),
+            (&contents.tasks, &contents.trash),
+            Utc::now(),
+            |task| blob::inline(task, &dir),
+        );
+        history::append(&history::history_path(path), &events)?;
+
+        let referenced = contents.tasks.iter().chain(&contents.trash);
+        blob::collect_garbage(referenced, &dir);
+        // Texts just moved out to blobs are kept as written, as when the
+        // store is opened, so that later saves compare like with like.
+        let stored = contents.tasks.iter().chain(&contents.trash);
+        for (task, stored) in self.tasks.iter_mut().chain(&mut self.trash).zip(stored) {
+            if task.description_blob != stored.description_blob
+                || task.notes_blob != stored.notes_blob
+            {
+                *task = stored.clone();
+            }
+        }
+        self.saved = (contents.tasks, contents.trash);
+        self.stamp = stamp(path);
+        greet::Summary::refresh(path, &self.saved.0);
+        Ok(())
+    }
+}
+
+fn describe_conflict(conflict: &Conflict) -> String {
+    match &conflict.field {
+        Some(field) => format!(
+            "`{}`: {field} was changed both here and elsewhere",
+            conflict.description
+        ),
+        None if conflict.ours.is_null() => format!(
+            "`{}` was changed elsewhere but removed here",
+            conflict.description
+        ),
+        None => format!(
+            "`{}` was changed here but removed elsewhere",
+            conflict.description
+        ),
+    }
+}
+
+/// Asks which side of a conflict to keep.
+fn pick_side(conflict: &Conflict) -> Option<Side> {
+    let choices = match &conflict.field {
+        Some(_) => vec![
+            format!("keep mine: {}", conflict.ours),
+            format!("keep theirs: {}", conflict.theirs),
+        ],
+        None if conflict.ours.is_null() => vec!["remove it".to_owned(), "keep it".to_owned()],
+        None => vec!["keep it".to_owned(), "remove it".to_owned()],
+    };
+    match picker::pick(&format!("{}:", describe_conflict(conflict)), &choices)? {
+        0 => Some(Side::Ours),
+        _ => Some(Side::Theirs),
+    }
+}
+
+/// Whether a task found by its description may be used without asking.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Confirm {
+    /// The command only reads the task.
+    NotNeeded,
+    /// Ask at the terminal; refuse when nobody can answer.
+    Ask,
+    /// The user passed `--yes`.
+    Given,
+}
+
+/// Ambiguous prefixes are settled with a menu when a user is at the
+/// terminal and rejected with the candidates otherwise.
+fn resolve_in(tasks: &[Task], query: &str) -> Result<Ulid, CliError> {
+    if let Some(number) = query.strip_prefix('#') {
+        let number = number.parse::<u32>().ok();
+        return tasks
+            .iter()
+            .find(|task| number.is_some() && task.number == number)
+            .map(|task| task.id)
+            .ok_or_else(|| CliError::NoMatch(query.to_owned()));
+    }
+
+    if let Ok(id) = Ulid::from_string(query) {
+        if tasks.iter().any(|task| task.id == id) {
+            return Ok(id);
+        }
+    }
+
+    let prefix = query.to_ascii_uppercase();
+    let matches = tasks
+        .iter()
+        .filter(|task| !prefix.is_empty() && task.id.to_string().starts_with(&prefix))
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [] => Err(CliError::NoMatch(query.to_owned())),
+        [task] => Ok(task.id),
+        _ => pick_one(query, &matches),
+    }
+}
+
+fn pick_one(query: &str, matches: &[&Task]) -> Result<Ulid, CliError> {
+    let candidates = matches
+        .iter()
+        .map(|task| format!("{}. {} ({})", task.id, task.description, task.status))
+        .collect::<Vec<_>>();
+
+    if picker::is_interactive() {
+        let prompt = format!("`{query}` matches {} tasks:", matches.len());
+        if let Some(index) = picker::pick(&prompt, &candidates) {
+            return Ok(matches[index].id);
+        }
+    }
+
+    Err(CliError::Ambiguous {
+        query: query.to_owned(),
+        candidates,
+    })
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Fails early, before any change is made, when the store could not be
+/// written back. Saving replaces the file, so its directory has to be
+/// writable too.
+pub fn check_writable(path: &Path) -> Result<(), CliError> {
+    let probe = parent_dir(path).join(format!(".task-cli-probe-{}", std::process::id()));
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|error| CliError::Write {
+            path: path.to_owned(),
+            error,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_past_the_preview_are_logged_and_rewound() {
+        let dir = std::env::temp_dir().join(format!("task-cli-store-{}", Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tasks.json");
+        let long = |end: char| format!("{}{end}", "x".repeat(blob::THRESHOLD));
+
+        let mut store = Store::new(path.clone(), vec![Task::new(long('a'))]);
+        store.save().unwrap();
+        let edited = Utc::now();
+        let mut store = Store::open(path.clone());
+        store.tasks[0].set_description(long('b'));
+        store.save().unwrap();
+
+        let events = history::load(&history::history_path(&path)).unwrap();
+        assert_eq!(events.len(), 2);
+        let past = Store::open(path).as_of(edited).unwrap();
+        assert_eq!(past.tasks[0].description, long('a'));
+    }
+
+    #[test]
+    fn saving_again_leaves_large_tasks_alone() {
+        let dir = std::env::temp_dir().join(format!("task-cli-store-{}", Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let long = "x".repeat(blob::THRESHOLD + 1);
+
+        let mut store = Store::new(dir.join("tasks.json"), vec![Task::new(long)]);
+        store.save().unwrap();
+        store.tasks.push(Task::new("something else".to_owned()));
+        store.save().unwrap();
+        store.save().unwrap();
+
+        assert_eq!(store.tasks[0].revision, 1);
+        assert!(store.changed_ids().is_empty());
+    }
+}
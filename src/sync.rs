@@ -0,0 +1,276 @@
+//! `sync status`: how each remote store last synced, so a broken one shows
+//! up without digging through logs. Every pull, push and lock of a remote
+//! store is recorded next to its local copy (`tasks.sync` for a cached
+//! `tasks.json`); the status reads these records and never goes over the
+//! network itself.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    args::Args,
+    history,
+    remote::{Pulled, Remote},
+    store,
+    time::{format_duration, format_local},
+    CliError,
+};
+
+/// A sync that went wrong.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Failure {
+    pub at: DateTime<Utc>,
+    /// `lock`, `pull` or `push`.
+    pub step: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// What is known about one remote store.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct State {
+    pub url: String,
+    /// The local copy in the cache directory.
+    #[serde(default)]
+    pub local: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pulled_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pushed_at: Option<DateTime<Utc>>,
+    /// Bytes of the event log both copies had after the last sync; local
+    /// events past it have not been sent.
+    #[serde(default)]
+    pub cursor: u64,
+    /// The ETag or board version the store had when last pulled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The last sync that went wrong, kept after the store syncs again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Failure>,
+    /// When syncing started to fail, unless it works again since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failing_since: Option<DateTime<Utc>>,
+}
+
+pub fn state_path(local: &Path) -> PathBuf {
+    local.with_extension("sync")
+}
+
+fn log_len(local: &Path) -> u64 {
+    std::fs::metadata(history::history_path(local)).map_or(0, |metadata| metadata.len())
+}
+
+impl State {
+    fn load(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Best effort: a sync that cannot be recorded still happened.
+    fn write(&self, path: &Path) {
+        let _ = std::fs::write(
+            path,
+            serde_json::to_vec_pretty(self).expect("could not convert to json"),
+        );
+    }
+
+    pub fn last_sync(&self) -> Option<DateTime<Utc>> {
+        self.pulled_at.max(self.pushed_at)
+    }
+
+    /// Events saved in the local copy that were not sent.
+    pub fn pending(&self) -> usize {
+        let log = std::fs::read(history::history_path(&self.local)).unwrap_or_default();
+        log.get(self.cursor as usize..).map_or(0, |unsent| {
+            unsent.iter().filter(|byte| **byte == b'\n').count()
+        })
+    }
+}
+
+/// Updates the record of `remote` with `change`.
+fn record(remote: &Remote, change: impl FnOnce(&mut State, &Path)) {
+    let Ok(local) = remote.cache_path() else {
+        return;
+    };
+    let path = state_path(&local);
+    let mut state = State::load(&path).unwrap_or_default();
+    state.url = remote.url();
+    state.local = local.clone();
+    change(&mut state, &local);
+    state.write(&path);
+}
+
+pub(crate) fn pulled(remote: &Remote, pulled: &Pulled) {
+    record(remote, |state, _| {
+        state.pulled_at = Some(Utc::now());
+        state.cursor = pulled.log_len;
+        state.version = pulled.versions.0.clone();
+        // Pulling says nothing about whether pushes work again.
+        if state
+            .error
+            .as_ref()
+            .is_none_or(|error| error.step != "push")
+        {
+            state.failing_since = None;
+        }
+    });
+}
+
+pub(crate) fn pushed(remote: &Remote) {
+    record(remote, |state, local| {
+        state.pushed_at = Some(Utc::now());
+        state.cursor = log_len(local);
+        state.failing_since = None;
+    });
+}
+
+/// Messages that already say what to do, after a `;`, get no hint.
+pub(crate) fn failed(remote: &Remote, step: &str, error: &CliError) {
+    let message = error.message();
+    let hint = error
+        .suggestion()
+        .or_else(|| (!message.contains("; ")).then(|| remote.hint()));
+    record(remote, |state, _| {
+        let now = Utc::now();
+        state.failing_since.get_or_insert(now);
+        state.error = Some(Failure {
+            at: now,
+            step: step.to_owned(),
+            message,
+            hint,
+        });
+    });
+}
+
+/// The records of every remote store used on this machine, by url.
+fn states() -> Vec<State> {
+    fn walk(dir: &Path, found: &mut Vec<State>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                walk(&path, found);
+            } else if path.extension().is_some_and(|ext| ext == "sync") {
+                if let Some(state) = State::load(&path) {
+                    found.push(state);
+                }
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    walk(
+        &dirs::cache_dir().unwrap().join("task-cli/remote"),
+        &mut found,
+    );
+    found.sort_by(|a, b| a.url.cmp(&b.url));
+    found
+}
+
+fn when(at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> String {
+    match at {
+        Some(at) => format!("{} ({} ago)", format_local(&at), format_duration(now - at)),
+        None => "never".to_owned(),
+    }
+}
+
+/// Prints the status of every remote store, marking the current one. The
+/// current store is listed even before its first sync.
+pub fn status(args: &Args) -> Result<(), CliError> {
+    match args.positional(1) {
+        Some("status") | None => {}
+        Some(other) => panic!("unknown sync command `{other}`, expected status"),
+    }
+
+    let current = Remote::parse(&store::tasks_path(args))
+        .as_ref()
+        .map(Remote::url);
+    let mut states = states()
+        .into_iter()
+        .map(|state| {
+            let pending = state.pending();
+            (state, pending)
+        })
+        .collect::<Vec<_>>();
+    if let Some(url) = current
+        .as_ref()
+        .filter(|url| !states.iter().any(|(s, _)| s.url == **url))
+    {
+        let state = State {
+            url: url.clone(),
+            ..State::default()
+        };
+        states.push((state, 0));
+    }
+
+    if args.value("format") == Some("json") {
+        let json = states
+            .iter()
+            .map(|(state, pending)| {
+                let mut value = serde_json::to_value(state).expect("could not convert to json");
+                value["pending"] = (*pending).into();
+                value
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json).expect("could not convert to json")
+        );
+        return Ok(());
+    }
+
+    if states.is_empty() {
+        println!("no remote stores have been used on this machine");
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    for (index, (state, pending)) in states.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let marker = if current.as_ref() == Some(&state.url) {
+            " (current)"
+        } else {
+            ""
+        };
+        println!("{}{marker}", state.url);
+
+        let health = match (state.failing_since, state.last_sync()) {
+            (Some(since), _) => format!("failing since {}", format_local(&since)),
+            (None, Some(_)) => "ok".to_owned(),
+            (None, None) => "never synced".to_owned(),
+        };
+        println!("    status:   {health}");
+        println!("    pulled:   {}", when(state.pulled_at, now));
+        println!("    pushed:   {}", when(state.pushed_at, now));
+        match pending {
+            0 => println!("    pending:  none"),
+            1 => println!("    pending:  1 local change not sent"),
+            n => println!("    pending:  {n} local changes not sent"),
+        }
+        match &state.version {
+            Some(version) => println!(
+                "    cursor:   event log at byte {}, store version {version}",
+                state.cursor
+            ),
+            None => println!("    cursor:   event log at byte {}", state.cursor),
+        }
+        if let Some(error) = &state.error {
+            println!(
+                "    error:    {} during {}: {}",
+                format_local(&error.at),
+                error.step,
+                error.message
+            );
+            if let Some(hint) = &error.hint {
+                println!("    hint:     {hint}");
+            }
+        }
+    }
+    Ok(())
+}
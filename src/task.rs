@@ -0,0 +1,704 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+use ulid::Ulid;
+
+use crate::{aging::Aged, retention::Hold, template::Template, vocabulary, CliError};
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    InProgress,
+    Done,
+    ToDo,
+}
+
+impl Status {
+    /// The built-in name, whatever the vocabulary says.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Done => "done",
+            Self::ToDo => "todo",
+            Self::InProgress => "in-progress",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match vocabulary::resolve("status", &vocabulary::STATUSES, s) {
+            Some("in-progress") => Ok(Self::InProgress),
+            Some("done") => Ok(Self::Done),
+            Some("todo") => Ok(Self::ToDo),
+            _ => Err(CliError::invalid_args(
+                s,
+                format!("invalid status `{s}`, expected todo, in-progress or done"),
+            )),
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&vocabulary::display("status", self.name()))
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    /// The built-in name, whatever the vocabulary says.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Urgent => "urgent",
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match vocabulary::resolve("priority", &vocabulary::PRIORITIES, s) {
+            Some("low") => Ok(Self::Low),
+            Some("medium") => Ok(Self::Medium),
+            Some("high") => Ok(Self::High),
+            Some("urgent") => Ok(Self::Urgent),
+            _ => Err(CliError::invalid_args(
+                s,
+                format!("invalid priority `{s}`, expected low, medium, high or urgent"),
+            )),
+        }
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&vocabulary::display("priority", self.name()))
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Task {
+    pub id: Ulid,
+    /// Short number for `#12`-style references, assigned by the store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<u32>,
+    pub description: String,
+    pub status: Status,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Bumped by the store on every saved change, so that API clients and
+    /// imports can tell whether their copy is current.
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Task this one is a subtask of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Ulid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+    /// How long before `due` a reminder fires, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind_before_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Blob file holding the full description when it is too large to keep
+    /// inline; `description` is then only a preview.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_blob: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes_blob: Option<String>,
+    /// When the task was last marked done, while it is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Set while the task is in the trash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Tasks that have to be done before this one can start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_by: Vec<Ulid>,
+    /// The task is parked until then, e.g. while waiting on someone else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_until: Option<DateTime<Utc>>,
+    /// Where in the code the task came from, for `task-cli jump`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    /// Identifier of the item the task was imported from, such as
+    /// `ics:<uid>`, so importing again updates it instead of duplicating it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_id: Option<String>,
+    /// Planned time blocks for working on the task.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<TimeBlock>,
+    /// Priority and tags handed down to subtasks created under this task.
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+    /// Which of the priority and tags came from a parent's defaults rather
+    /// than being set on the task itself.
+    #[serde(default, skip_serializing_if = "Inherited::is_empty")]
+    pub inherited: Inherited,
+    /// Set when the aging policy last demoted the task or moved it to
+    /// someday.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aged: Option<Aged>,
+    /// Set while the task is on hold, which keeps it from being purged or
+    /// aged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold: Option<Hold>,
+    /// Who is responsible for the task, by name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Where the task has to be done: a place name or a `geo:` URI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Set while someone else is doing the task and it waits on them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegated: Option<Delegation>,
+    /// Follow-up created when the task is done; its own `then` continues
+    /// the chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_done: Option<Template>,
+    /// Name of the runbook a task was started from, set on the task for the
+    /// run; its subtasks are the steps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runbook: Option<String>,
+    /// Expected effort, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_secs: Option<i64>,
+    /// Time logged on the task with `track`, in seconds.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub tracked_secs: i64,
+    /// Totals over the task's subtasks, kept up to date by the store as
+    /// tasks change. Unset for tasks without subtasks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollup: Option<Rollup>,
+}
+
+fn is_zero(value: &i64) -> bool {
+    *value == 0
+}
+
+/// Who a task was handed to and when to chase them.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Delegation {
+    pub to: String,
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_up: Option<DateTime<Utc>>,
+}
+
+/// Estimates, tracked time and completion summed over all subtasks of a
+/// task, at any depth, leaving out the task itself.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct Rollup {
+    pub estimate_secs: i64,
+    pub tracked_secs: i64,
+    pub done: u32,
+    pub total: u32,
+}
+
+impl Rollup {
+    pub fn percent_done(&self) -> u32 {
+        (u64::from(self.done) * 100 / u64::from(self.total.max(1))) as u32
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Default, Debug)]
+pub struct Defaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Defaults {
+    pub fn is_empty(&self) -> bool {
+        self.priority.is_none() && self.tags.is_empty()
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Default, Debug)]
+pub struct Inherited {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub priority: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Inherited {
+    pub fn is_empty(&self) -> bool {
+        !self.priority && self.tags.is_empty()
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TimeBlock {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// A `file:line` location, with `file` made absolute when it is recorded.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct Source {
+    pub file: PathBuf,
+    pub line: u32,
+}
+
+impl FromStr for Source {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (file, line) = match s.rsplit_once(':') {
+            Some((file, line)) if !file.is_empty() => {
+                let line = line.parse().map_err(|_| {
+                    CliError::invalid_args(s, format!("invalid line number in `{s}`"))
+                })?;
+                (file, line)
+            }
+            _ => (s, 1),
+        };
+        let file = std::path::absolute(file)
+            .map_err(|error| CliError::invalid_args(s, format!("invalid path `{s}`: {error}")))?;
+
+        Ok(Self { file, line })
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+impl Task {
+    pub fn new(description: String) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: Ulid::new(),
+            number: None,
+            description,
+            status: Status::ToDo,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            priority: None,
+            tags: Vec::new(),
+            parent: None,
+            due: None,
+            remind_before_secs: None,
+            notes: None,
+            description_blob: None,
+            notes_blob: None,
+            completed_at: None,
+            deleted_at: None,
+            blocked_by: Vec::new(),
+            wait_until: None,
+            source: None,
+            import_id: None,
+            blocks: Vec::new(),
+            defaults: Defaults::default(),
+            inherited: Inherited::default(),
+            aged: None,
+            hold: None,
+            owner: None,
+            location: None,
+            delegated: None,
+            on_done: None,
+            runbook: None,
+            estimate_secs: None,
+            tracked_secs: 0,
+            rollup: None,
+        }
+    }
+
+    /// Changes the status, keeping track of when the task was done.
+    pub fn set_status(&mut self, status: Status) {
+        if status == self.status {
+            return;
+        }
+        let now = Utc::now();
+        self.completed_at = (status == Status::Done).then_some(now);
+        self.status = status;
+        self.updated_at = now;
+    }
+
+    /// When the task was done, for tasks done before that was recorded
+    /// the last time they changed.
+    pub fn done_at(&self) -> Option<DateTime<Utc>> {
+        (self.status == Status::Done).then(|| self.completed_at.unwrap_or(self.updated_at))
+    }
+
+    pub fn set_description(&mut self, description: String) {
+        self.description = description;
+        self.description_blob = None;
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn set_notes(&mut self, notes: Option<String>) {
+        self.notes = notes;
+        self.notes_blob = None;
+    }
+
+    /// Sets the priority by hand, overriding an inherited one.
+    pub fn set_priority(&mut self, priority: Option<Priority>) {
+        self.priority = priority;
+        self.inherited.priority = false;
+    }
+
+    /// Adds a tag by hand; an inherited copy of it becomes the task's own.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_owned());
+        }
+        self.inherited.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+        self.inherited.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+    }
+}
+
+/// Updates the cached [`Rollup`] of every ancestor of the `changed` tasks,
+/// from the nearest up, so each is summed from already current children.
+/// `changed` should include the former parents of tasks that moved or went.
+pub fn update_rollups(tasks: &mut [Task], changed: impl IntoIterator<Item = Ulid>) {
+    let index = tasks
+        .iter()
+        .enumerate()
+        .map(|(index, task)| (task.id, index))
+        .collect::<std::collections::HashMap<_, _>>();
+    let mut children = std::collections::HashMap::<Ulid, Vec<usize>>::new();
+    for (position, task) in tasks.iter().enumerate() {
+        if let Some(parent) = task.parent.filter(|parent| index.contains_key(parent)) {
+            children.entry(parent).or_default().push(position);
+        }
+    }
+
+    for id in changed {
+        let mut seen = Vec::new();
+        // The changed task's own rollup is current unless it is a former
+        // parent, so start there and walk up.
+        let mut next = Some(id);
+        while let Some(id) = next.filter(|id| !seen.contains(id)) {
+            seen.push(id);
+            let Some(&position) = index.get(&id) else {
+                break;
+            };
+
+            let rollup = children.get(&id).map(|kids| {
+                kids.iter().fold(Rollup::default(), |mut sum, &kid| {
+                    let kid = &tasks[kid];
+                    let below = kid.rollup.unwrap_or_default();
+                    sum.estimate_secs += kid.estimate_secs.unwrap_or(0) + below.estimate_secs;
+                    sum.tracked_secs += kid.tracked_secs + below.tracked_secs;
+                    sum.done += u32::from(kid.status == Status::Done) + below.done;
+                    sum.total += 1 + below.total;
+                    sum
+                })
+            });
+            tasks[position].rollup = rollup;
+            next = tasks[position].parent;
+        }
+    }
+}
+
+/// The defaults a new subtask of `parent` inherits: the nearest ancestor's
+/// priority and the tags of all ancestors.
+pub fn inherited_defaults(parent: Ulid, tasks: &[Task]) -> Defaults {
+    let mut defaults = Defaults::default();
+    let mut seen = Vec::new();
+    let mut next = Some(parent);
+
+    while let Some(id) = next.filter(|id| !seen.contains(id)) {
+        seen.push(id);
+        let Some(ancestor) = tasks.iter().find(|task| task.id == id) else {
+            break;
+        };
+
+        defaults.priority = defaults.priority.or(ancestor.defaults.priority);
+        for tag in &ancestor.defaults.tags {
+            if !defaults.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                defaults.tags.push(tag.clone());
+            }
+        }
+        next = ancestor.parent;
+    }
+
+    defaults
+}
+
+/// Fills in what the new task `id` inherits from its parent, leaving a
+/// priority and tags it was given explicitly alone.
+pub fn inherit(tasks: &mut [Task], id: Ulid) {
+    let Some(parent) = tasks
+        .iter()
+        .find(|task| task.id == id)
+        .and_then(|task| task.parent)
+    else {
+        return;
+    };
+    let defaults = inherited_defaults(parent, tasks);
+    let task = tasks
+        .iter_mut()
+        .find(|task| task.id == id)
+        .expect("id was found above");
+
+    if task.priority.is_none() && defaults.priority.is_some() {
+        task.priority = defaults.priority;
+        task.inherited.priority = true;
+    }
+    for tag in defaults.tags {
+        if !task.has_tag(&tag) {
+            task.tags.push(tag.clone());
+            task.inherited.tags.push(tag);
+        }
+    }
+}
+
+/// Passes a change of `id`'s defaults from `old` on to its subtasks and
+/// theirs. Inherited values follow the change; values set on a subtask
+/// itself, and inherited tags that were removed from it, stay as they are.
+pub fn pass_down_defaults(tasks: &mut [Task], id: Ulid, old: &Defaults) {
+    let has = |tags: &[String], tag: &str| tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+    let Some(current) = tasks.iter().find(|task| task.id == id) else {
+        return;
+    };
+    let added = current
+        .defaults
+        .tags
+        .iter()
+        .filter(|tag| !has(&old.tags, tag))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut parents = vec![id];
+    let mut seen = vec![id];
+
+    while let Some(parent) = parents.pop() {
+        let children = tasks
+            .iter()
+            .filter(|task| task.parent == Some(parent) && !seen.contains(&task.id))
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+
+        for child in children {
+            seen.push(child);
+            parents.push(child);
+
+            let new = inherited_defaults(parent, tasks);
+            let task = tasks
+                .iter_mut()
+                .find(|task| task.id == child)
+                .expect("children were found above");
+
+            if task.inherited.priority || task.priority.is_none() {
+                task.priority = new.priority;
+                task.inherited.priority = new.priority.is_some();
+            }
+            for tag in &old.tags {
+                if !has(&new.tags, tag) && has(&task.inherited.tags, tag) {
+                    task.remove_tag(tag);
+                }
+            }
+            for tag in &added {
+                if !task.has_tag(tag) {
+                    task.tags.push(tag.clone());
+                    task.inherited.tags.push(tag.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Splits a comma separated `--tag` value into tag names.
+pub fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Field a task listing can be ordered by.
+#[derive(Clone, Copy, Debug)]
+pub enum SortKey {
+    Created,
+    Updated,
+    Status,
+    Description,
+    Priority,
+    Due,
+}
+
+impl FromStr for SortKey {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(Self::Created),
+            "updated" => Ok(Self::Updated),
+            "status" => Ok(Self::Status),
+            "description" => Ok(Self::Description),
+            "priority" => Ok(Self::Priority),
+            "due" => Ok(Self::Due),
+            _ => Err(CliError::invalid_args(
+                s,
+                format!(
+                    "invalid sort key `{s}`, expected created, updated, status, description, \
+                     priority or due"
+                ),
+            )),
+        }
+    }
+}
+
+/// Numbers of the `#12`-style task references in `text`, in order.
+pub fn references(text: &str) -> Vec<u32> {
+    reference_spans(text)
+        .into_iter()
+        .map(|(_, number)| number)
+        .collect()
+}
+
+/// Byte ranges of `#12`-style references in `text` with their numbers. A
+/// reference stands on its own, so `a#1` and `#1a` are not references.
+pub fn reference_spans(text: &str) -> Vec<(std::ops::Range<usize>, u32)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut spans = Vec::new();
+
+    for (start, _) in text.match_indices('#') {
+        let word_start = text[..start]
+            .rfind(char::is_whitespace)
+            .map_or(0, |index| index + 1);
+        // Neither `a#1` nor a URL fragment such as `https://host/#1`.
+        if text[..start].chars().next_back().is_some_and(is_word)
+            || text[word_start..start].contains("://")
+        {
+            continue;
+        }
+        let digits = text[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(text.len(), |end| start + 1 + end);
+        if digits == start + 1 || text[digits..].starts_with(is_word) {
+            continue;
+        }
+        if let Ok(number) = text[start + 1..digits].parse() {
+            spans.push((start..digits, number));
+        }
+    }
+
+    spans
+}
+
+/// Groups of the default `list` output, in display order.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Section {
+    Active,
+    Blocked,
+    Waiting,
+    Someday,
+    Done,
+}
+
+impl Section {
+    pub const ALL: [Self; 5] = [
+        Self::Active,
+        Self::Blocked,
+        Self::Waiting,
+        Self::Someday,
+        Self::Done,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Active => "Active",
+            Self::Blocked => "Blocked",
+            Self::Waiting => "Waiting",
+            Self::Someday => "Someday",
+            Self::Done => "Done",
+        }
+    }
+}
+
+/// Open tasks in `tasks` that `task` still waits on.
+pub fn open_blockers<'a>(task: &Task, tasks: &'a [Task]) -> Vec<&'a Task> {
+    task.blocked_by
+        .iter()
+        .filter_map(|id| tasks.iter().find(|other| other.id == *id))
+        .filter(|other| other.status != Status::Done)
+        .collect()
+}
+
+/// Where a task belongs in the list: blocked by open tasks, waiting until a
+/// later date, tagged `someday`, or otherwise active.
+pub fn section(task: &Task, tasks: &[Task], now: DateTime<Utc>) -> Section {
+    if task.status == Status::Done {
+        Section::Done
+    } else if !open_blockers(task, tasks).is_empty() {
+        Section::Blocked
+    } else if task.wait_until.is_some_and(|until| until > now) || task.delegated.is_some() {
+        Section::Waiting
+    } else if task.has_tag("someday") {
+        Section::Someday
+    } else {
+        Section::Active
+    }
+}
+
+/// Sort key putting the most urgent task first: highest priority, then
+/// soonest due, then oldest.
+pub fn urgency(task: &Task) -> impl Ord {
+    (
+        std::cmp::Reverse(task.priority),
+        task.due.is_none(),
+        task.due,
+        task.created_at,
+    )
+}
+
+pub fn filter_by_status(tasks: &[Task], status: &Status) -> Vec<Task> {
+    tasks
+        .iter()
+        .filter(|task| task.status == *status)
+        .cloned()
+        .collect()
+}
+
+pub fn sort_tasks(tasks: &mut [Task], key: SortKey) {
+    // Tasks are large, so the keys are sorted and each task moved once.
+    match key {
+        SortKey::Created => tasks.sort_by_cached_key(|task| task.created_at),
+        SortKey::Updated => tasks.sort_by_cached_key(|task| std::cmp::Reverse(task.updated_at)),
+        SortKey::Status => tasks.sort_by_cached_key(|task| match task.status {
+            Status::InProgress => 0,
+            Status::ToDo => 1,
+            Status::Done => 2,
+        }),
+        SortKey::Description => tasks.sort_by_cached_key(|task| task.description.clone()),
+        SortKey::Priority => tasks.sort_by_cached_key(|task| std::cmp::Reverse(task.priority)),
+        // Tasks without a due date go last.
+        SortKey::Due => tasks.sort_by_cached_key(|task| (task.due.is_none(), task.due)),
+    }
+}
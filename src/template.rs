@@ -0,0 +1,218 @@
+//! Reusable task blueprints, stored in `templates.json` in the config
+//! directory.
+
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::{
+    config::config_dir,
+    store::Store,
+    task::{inherit, Priority, Status, Task},
+    time::{format_duration, parse_duration},
+    CliError,
+};
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Template {
+    /// May contain `{placeholders}`, filled in when the template is used.
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Due date relative to creation, e.g. `3d`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_after: Option<String>,
+    /// Descriptions of subtasks created along with the task.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<String>,
+    /// Follow-up to create once the task is done, continuing the chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub then: Option<Box<Template>>,
+}
+
+pub type Templates = BTreeMap<String, Template>;
+
+pub fn path() -> PathBuf {
+    config_dir().join("templates.json")
+}
+
+pub fn load() -> Templates {
+    match std::fs::read(path()) {
+        Ok(contents) => serde_json::from_slice(&contents).expect("invalid templates file"),
+        Err(_) => Templates::new(),
+    }
+}
+
+pub fn save(templates: &Templates) -> Result<(), CliError> {
+    let path = path();
+    let write = || {
+        std::fs::create_dir_all(config_dir())?;
+        std::fs::write(
+            &path,
+            serde_json::to_vec_pretty(templates).expect("could not convert to json"),
+        )
+    };
+
+    write().map_err(|error| CliError::Write { path, error })
+}
+
+impl Template {
+    /// Captures `task` and the descriptions of its subtasks.
+    pub fn from_task(task: &Task, subtasks: &[&Task]) -> Self {
+        Self {
+            description: task.description.clone(),
+            priority: task.priority,
+            tags: task.tags.clone(),
+            due_after: task
+                .due
+                .map(|due| format_duration((due - task.created_at).max(Duration::minutes(1)))),
+            subtasks: subtasks
+                .iter()
+                .map(|subtask| subtask.description.clone())
+                .collect(),
+            then: task.on_done.clone().map(Box::new),
+        }
+    }
+
+    /// Adds `step` at the end of the chain of follow-ups.
+    pub fn append(&mut self, step: Template) {
+        match &mut self.then {
+            Some(next) => next.append(step),
+            None => self.then = Some(Box::new(step)),
+        }
+    }
+
+    /// Creates the task and its subtasks, filling placeholders from `vars`.
+    /// `{date}` always expands to today's date.
+    pub fn instantiate(&self, vars: &BTreeMap<String, String>) -> Result<Vec<Task>, CliError> {
+        let mut vars = vars.clone();
+        vars.entry("date".to_owned())
+            .or_insert_with(|| Local::now().format("%Y-%m-%d").to_string());
+
+        let mut task = Task::new(fill(&self.description, &vars)?);
+        task.priority = self.priority;
+        task.tags = self.tags.clone();
+        task.on_done = self.then.as_deref().cloned();
+        if let Some(offset) = &self.due_after {
+            let offset = parse_duration(offset).map_err(|_| {
+                CliError::Template(format!("invalid due offset `{offset}` in template"))
+            })?;
+            task.due = Some(task.created_at + offset);
+        }
+
+        let mut tasks = vec![task];
+        for description in &self.subtasks {
+            let mut subtask = Task::new(fill(description, &vars)?);
+            subtask.parent = Some(tasks[0].id);
+            tasks.push(subtask);
+        }
+
+        Ok(tasks)
+    }
+}
+
+/// Creates the follow-ups of tasks done since the store was last saved, next
+/// to them under the same parent, and takes them off the finished tasks. `{task}` in a follow-up is the description
+/// of the task it follows. Returns each finished task's description with
+/// that of its follow-up.
+pub fn chain(store: &mut Store) -> Result<Vec<(String, String)>, CliError> {
+    let was_done = store
+        .saved_tasks()
+        .filter(|task| task.status == Status::Done)
+        .map(|task| task.id)
+        .collect::<HashSet<_>>();
+    // The follow-up is used up, so reopening and finishing the task again
+    // does not create it twice.
+    let finished = store
+        .tasks
+        .iter_mut()
+        .filter(|task| task.status == Status::Done && !was_done.contains(&task.id))
+        .filter_map(|task| {
+            let then = task.on_done.take()?;
+            Some((task.description.clone(), task.parent, then))
+        })
+        .collect::<Vec<_>>();
+
+    let mut created = Vec::new();
+    for (description, parent, then) in finished {
+        let vars = BTreeMap::from([("task".to_owned(), description.clone())]);
+        let mut tasks = then.instantiate(&vars)?;
+        tasks[0].parent = parent;
+        created.push((description, tasks[0].description.clone()));
+
+        let ids = tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        store.tasks.extend(tasks);
+        for id in ids {
+            inherit(&mut store.tasks, id);
+        }
+    }
+    Ok(created)
+}
+
+/// `send invoice (due +7d) → chase payment`.
+pub fn describe_chain(then: &Template) -> String {
+    let mut steps = Vec::new();
+    let mut next = Some(then);
+    while let Some(step) = next {
+        let mut text = step.description.clone();
+        if let Some(offset) = &step.due_after {
+            text.push_str(&format!(" (due +{offset})"));
+        }
+        steps.push(text);
+        next = step.then.as_deref();
+    }
+    steps.join(" → ")
+}
+
+pub(crate) fn fill(text: &str, vars: &BTreeMap<String, String>) -> Result<String, CliError> {
+    let mut out = String::new();
+    let mut missing = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+
+        out.push_str(&rest[..start]);
+        match vars.get(name) {
+            Some(value) => out.push_str(value),
+            None => missing.push(name.to_owned()),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+
+    if missing.is_empty() {
+        Ok(out)
+    } else {
+        Err(CliError::Template(format!(
+            "missing values for {}; pass them with --var name=value",
+            missing
+                .iter()
+                .map(|name| format!("{{{name}}}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))
+    }
+}
+
+/// Parses `--var name=value,other=value`.
+pub fn parse_vars(value: &str) -> Result<BTreeMap<String, String>, CliError> {
+    value
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').ok_or_else(|| {
+                CliError::invalid_args(pair, format!("invalid --var `{pair}`, expected name=value"))
+            })?;
+            Ok((name.trim().to_owned(), value.trim().to_owned()))
+        })
+        .collect()
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+use crate::CliError;
+
+/// Parses durations like `15m`, `1h30m`, `2d` or `1w`.
+pub fn parse_duration(s: &str) -> Result<Duration, CliError> {
+    let invalid =
+        || CliError::invalid_args(s, format!("invalid duration `{s}`, expected e.g. 1h30m"));
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let amount = digits.parse::<i64>().map_err(|_| invalid())?;
+        digits.clear();
+
+        total += match c {
+            's' => Duration::seconds(amount),
+            'm' => Duration::minutes(amount),
+            'h' => Duration::hours(amount),
+            'd' => Duration::days(amount),
+            'w' => Duration::weeks(amount),
+            _ => return Err(invalid()),
+        };
+    }
+
+    if !digits.is_empty() || total.is_zero() {
+        return Err(invalid());
+    }
+
+    Ok(total)
+}
+
+/// Parses a point in time given in local time: `today`, `tomorrow`, `+3d`,
+/// `2024-08-20`, `2024-08-20 14:00` or an RFC 3339 timestamp.
+pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, CliError> {
+    let s = s.trim();
+    let now = Local::now();
+
+    if let Some(offset) = s.strip_prefix('+') {
+        return Ok((now + parse_duration(offset)?).with_timezone(&Utc));
+    }
+
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+    let naive = match s {
+        "today" => now.date_naive().and_time(end_of_day),
+        "tomorrow" => (now.date_naive() + Duration::days(1)).and_time(end_of_day),
+        _ => {
+            if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+                return Ok(datetime.with_timezone(&Utc));
+            }
+
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_time(end_of_day))
+                })
+                .map_err(|_| {
+                    CliError::invalid_args(
+                        s,
+                        format!("invalid date `{s}`, expected e.g. 2024-08-20 14:00 or +3d"),
+                    )
+                })?
+        }
+    };
+
+    local_to_utc(naive)
+}
+
+pub fn local_to_utc(naive: NaiveDateTime) -> Result<DateTime<Utc>, CliError> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok_or_else(|| {
+            CliError::invalid_args(naive.to_string(), format!("{naive} does not exist here"))
+        })
+}
+
+pub fn format_local(datetime: &DateTime<Utc>) -> String {
+    datetime
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Formats a duration using the largest fitting units, e.g. `1h30m`.
+pub fn format_duration(duration: Duration) -> String {
+    let mut secs = duration.num_seconds().abs();
+    let mut out = String::new();
+
+    for (unit, size) in [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)] {
+        if secs >= size {
+            out.push_str(&format!("{}{unit}", secs / size));
+            secs %= size;
+        }
+    }
+
+    if out.is_empty() {
+        out.push_str("0m");
+    }
+
+    out
+}
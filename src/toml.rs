@@ -0,0 +1,432 @@
+//! Parser for the subset of TOML used by the configuration files: tables,
+//! arrays of tables, dotted keys, strings, numbers, booleans, arrays and
+//! inline tables. Documents are returned as JSON values so they can be
+//! deserialized with `serde_json::from_value`. Dates are kept as strings.
+
+use serde_json::{Map, Value};
+
+#[derive(Debug)]
+pub struct TomlError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for TomlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+pub fn parse(input: &str) -> Result<Value, TomlError> {
+    let mut parser = Parser {
+        chars: input.chars().peekable(),
+        line: 1,
+    };
+    let mut root = Map::new();
+    let mut current = Vec::<String>::new();
+
+    loop {
+        parser.skip_whitespace_and_comments(true);
+
+        match parser.chars.peek() {
+            None => break,
+            Some('[') => {
+                parser.chars.next();
+                let is_array = parser.eat('[');
+                let path = parser.key_path()?;
+                parser.expect(']')?;
+                if is_array {
+                    parser.expect(']')?;
+                }
+                parser.end_of_line()?;
+
+                let (last, parents) = path.split_last().expect("key paths are never empty");
+                let parent = parser.table_at(&mut root, parents)?;
+
+                if is_array {
+                    let entry = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    let Value::Array(items) = entry else {
+                        return Err(parser.error(format!("`{last}` is not an array of tables")));
+                    };
+                    items.push(Value::Object(Map::new()));
+                } else {
+                    parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Object(Map::new()));
+                }
+
+                current = path;
+            }
+            Some(_) => {
+                let path = parser.key_path()?;
+                parser.expect('=')?;
+                let value = parser.value()?;
+                parser.end_of_line()?;
+
+                let table = parser.table_at(&mut root, &current)?;
+                let (last, parents) = path.split_last().expect("key paths are never empty");
+                let table = parser.table_at(table, parents)?;
+
+                if table.insert(last.clone(), value).is_some() {
+                    return Err(parser.error(format!("duplicate key `{last}`")));
+                }
+            }
+        }
+    }
+
+    Ok(Value::Object(root))
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> TomlError {
+        TomlError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), TomlError> {
+        self.skip_spaces();
+        if self.eat(c) {
+            self.skip_spaces();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{c}`")))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+    }
+
+    fn skip_whitespace_and_comments(&mut self, newlines: bool) {
+        loop {
+            match self.chars.peek() {
+                Some(' ' | '\t' | '\r') => {
+                    self.chars.next();
+                }
+                Some('\n') if newlines => {
+                    self.chars.next();
+                    self.line += 1;
+                }
+                Some('#') => while self.chars.next_if(|c| *c != '\n').is_some() {},
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), TomlError> {
+        self.skip_whitespace_and_comments(false);
+        match self.chars.peek().copied() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(self.error(format!("unexpected `{c}`"))),
+        }
+    }
+
+    /// Walks (and creates) nested tables, descending into the last element of
+    /// arrays of tables.
+    fn table_at<'m>(
+        &self,
+        mut table: &'m mut Map<String, Value>,
+        path: &[String],
+    ) -> Result<&'m mut Map<String, Value>, TomlError> {
+        for key in path {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            let entry = match entry {
+                Value::Array(items) => items.last_mut().ok_or_else(|| self.error("empty array"))?,
+                entry => entry,
+            };
+
+            table = match entry {
+                Value::Object(map) => map,
+                _ => return Err(self.error(format!("`{key}` is not a table"))),
+            };
+        }
+
+        Ok(table)
+    }
+
+    fn key_path(&mut self) -> Result<Vec<String>, TomlError> {
+        let mut path = Vec::new();
+
+        loop {
+            self.skip_spaces();
+            let key = match self.chars.peek() {
+                Some('"') => {
+                    self.chars.next();
+                    self.basic_string()?
+                }
+                Some('\'') => {
+                    self.chars.next();
+                    self.literal_string()?
+                }
+                _ => {
+                    let mut key = String::new();
+                    while let Some(c) = self
+                        .chars
+                        .next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                    {
+                        key.push(c);
+                    }
+                    if key.is_empty() {
+                        return Err(self.error("expected a key"));
+                    }
+                    key
+                }
+            };
+            path.push(key);
+            self.skip_spaces();
+
+            if !self.eat('.') {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, TomlError> {
+        self.skip_spaces();
+
+        match self.chars.peek() {
+            Some('"') => {
+                self.chars.next();
+                if self.eat('"') {
+                    if self.eat('"') {
+                        return self.multiline_string().map(Value::String);
+                    }
+                    return Ok(Value::String(String::new()));
+                }
+                self.basic_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.chars.next();
+                self.literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.chars.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace_and_comments(true);
+                    if self.eat(']') {
+                        break;
+                    }
+                    items.push(self.value()?);
+                    self.skip_whitespace_and_comments(true);
+                    if !self.eat(',') {
+                        self.skip_whitespace_and_comments(true);
+                        if !self.eat(']') {
+                            return Err(self.error("expected `,` or `]`"));
+                        }
+                        break;
+                    }
+                }
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.chars.next();
+                let mut table = Map::new();
+                self.skip_spaces();
+                if self.eat('}') {
+                    return Ok(Value::Object(table));
+                }
+                loop {
+                    let path = self.key_path()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    let (last, parents) = path.split_last().expect("key paths are never empty");
+                    self.table_at(&mut table, parents)?
+                        .insert(last.clone(), value);
+                    self.skip_spaces();
+                    if self.eat('}') {
+                        break;
+                    }
+                    self.expect(',')?;
+                }
+                Ok(Value::Object(table))
+            }
+            _ => {
+                let mut raw = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|c| !matches!(c, ',' | ']' | '}' | '#' | '\n' | '\r'))
+                {
+                    raw.push(c);
+                }
+                let raw = raw.trim();
+
+                match raw {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => {
+                        let number = raw.replace('_', "");
+                        if let Ok(int) = number.parse::<i64>() {
+                            Ok(Value::from(int))
+                        } else if let Ok(float) = number.parse::<f64>() {
+                            Ok(Value::from(float))
+                        } else if raw.starts_with(|c: char| c.is_ascii_digit()) {
+                            // Dates and times.
+                            Ok(Value::String(raw.to_owned()))
+                        } else {
+                            Err(self.error(format!("invalid value `{raw}`")))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, TomlError> {
+        let c = self
+            .chars
+            .next()
+            .ok_or_else(|| self.error("unterminated escape"))?;
+        Ok(match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '"' => '"',
+            '\\' => '\\',
+            'u' | 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex = (0..len)
+                    .filter_map(|_| self.chars.next())
+                    .collect::<String>();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error("invalid unicode escape"))?
+            }
+            _ => return Err(self.error(format!("invalid escape `\\{c}`"))),
+        })
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => out.push(self.escape()?),
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some('\'') => return Ok(out),
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn multiline_string(&mut self) -> Result<String, TomlError> {
+        let mut out = String::new();
+        if self.eat('\n') {
+            self.line += 1;
+        }
+        loop {
+            match self.chars.next() {
+                Some('"') if self.chars.peek() == Some(&'"') => {
+                    self.chars.next();
+                    if self.eat('"') {
+                        return Ok(out);
+                    }
+                    out.push_str("\"\"");
+                }
+                Some('\\') => out.push(self.escape()?),
+                Some('\n') => {
+                    self.line += 1;
+                    out.push('\n');
+                }
+                Some(c) => out.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn tables_keys_and_values() {
+        let document = parse(
+            r#"
+            # comment
+            name = "task-cli" # trailing
+            [aging]
+            demote_days = 1_000
+            ratio = 0.5
+            enabled = true
+            [rules.mark]
+            tags = ["a", 'b\c',]
+            when = { status = "done", list.name = "x" }
+            since = 2026-10-14
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            document,
+            json!({
+                "name": "task-cli",
+                "aging": { "demote_days": 1000, "ratio": 0.5, "enabled": true },
+                "rules": { "mark": {
+                    "tags": ["a", "b\\c"],
+                    "when": { "status": "done", "list": { "name": "x" } },
+                    "since": "2026-10-14",
+                } },
+            })
+        );
+    }
+
+    #[test]
+    fn arrays_of_tables_and_strings() {
+        let document = parse(
+            "[[hook]]\nrun = \"a\\tb\\u00e9\"\n[[hook]]\nrun = \"\"\"\nfirst\nsecond\"\"\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            document,
+            json!({ "hook": [{ "run": "a\tbé" }, { "run": "first\nsecond" }] })
+        );
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let error = parse("a = 1\n\nb = nope\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: invalid value `nope`");
+
+        let error = parse("a = 1\na = 2\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("duplicate key `a`"));
+
+        assert!(parse("a = \"open\n").is_err());
+        assert!(parse("a = [1, 2\n").is_err());
+    }
+}
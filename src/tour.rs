@@ -0,0 +1,195 @@
+//! `task-cli tour`: a guided walk through the everyday commands against a
+//! throwaway store, so nothing touches the real task list.
+
+use std::{panic, str::FromStr};
+
+use crate::{
+    args::{split_words, Args},
+    commands::{self, Commands},
+    config::Config,
+    line_editor::{LineEditor, ReadResult},
+    repl,
+    store::{self, Store},
+};
+
+struct Step {
+    /// What the step teaches.
+    intro: &'static str,
+    /// Suggested input; `{id}` becomes the id prefix of the first task.
+    example: &'static str,
+    /// Running this command completes the step.
+    command: &'static str,
+}
+
+const STEPS: &[Step] = &[
+    Step {
+        intro: "Tasks start with `add` and a description. Quote descriptions\n\
+                with spaces in them.",
+        example: r#"add "Buy groceries" --priority high"#,
+        command: "add",
+    },
+    Step {
+        intro: "`list` shows your tasks. Every task has an id; a few leading\n\
+                characters are enough to refer to it.",
+        example: "list",
+        command: "list",
+    },
+    Step {
+        intro: "`mark` changes the status of a task to todo, in-progress or done.",
+        example: "mark {id} --status in-progress",
+        command: "mark",
+    },
+    Step {
+        intro: "`due` gives a task a due date such as `tomorrow`, `+3d` or\n\
+                `2024-08-20 14:00`. Overdue tasks stand out in the list.",
+        example: "due {id} tomorrow",
+        command: "due",
+    },
+    Step {
+        intro: "`tag` groups tasks; `-tag` removes one again.",
+        example: "tag {id} errands",
+        command: "tag",
+    },
+    Step {
+        intro: "`list` filters by status, `--tag` and more (see `help`).",
+        example: "list --tag errands",
+        command: "list",
+    },
+    Step {
+        intro: "`done` finishes a task. Words from the description work as well\n\
+                as ids.",
+        example: "done groceries",
+        command: "done",
+    },
+];
+
+fn example(step: &Step, store: &Store) -> String {
+    let id = store
+        .tasks
+        .first()
+        .map(|task| task.id.to_string()[..6].to_owned())
+        .unwrap_or_else(|| "<id>".to_owned());
+    step.example.replace("{id}", &id)
+}
+
+/// Runs one line of input against the sandbox, returning the command name
+/// when it succeeded.
+fn run_line(line: &str, config: &Config, store: &mut Store) -> Option<String> {
+    let words = split_words(line)
+        .map_err(|_| eprintln!("error: unterminated quote"))
+        .ok()?;
+    let name = words.first()?.clone();
+
+    let cmd = match Commands::from_str(&name) {
+        Ok(
+            Commands::Daemon
+            | Commands::Interactive
+            | Commands::Serve
+            | Commands::Tour
+            | Commands::Demo
+            | Commands::Generate
+            | Commands::ShellInit
+            | Commands::Context
+            | Commands::Greet
+            | Commands::Sync
+            | Commands::SelfUpdate,
+        ) => {
+            eprintln!("error: `{name}` is not part of the tour");
+            return None;
+        }
+        Ok(cmd) => cmd,
+        Err(_) => {
+            eprintln!("error: unknown command `{name}`");
+            return None;
+        }
+    };
+
+    // Confirmation prompts would interrupt the lesson; trust the best match.
+    let mut words = words;
+    if cmd.mutates(&Args::parse(words.clone())) && !words.iter().any(|word| word == "--yes") {
+        words.push("--yes".to_owned());
+    }
+    let args = Args::parse(words);
+
+    let before = (store.tasks.clone(), store.trash.clone());
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        commands::execute(cmd, &args, config, store)
+    }));
+
+    match result {
+        Ok(Ok(())) => Some(name),
+        Ok(Err(err)) => {
+            eprintln!("error: {err}");
+            (store.tasks, store.trash) = before;
+            None
+        }
+        Err(_) => {
+            (store.tasks, store.trash) = before;
+            None
+        }
+    }
+}
+
+pub fn run(args: &Args) {
+    // The sandbox is never saved, so the path only needs to not exist.
+    let path = std::env::temp_dir().join(format!("task-cli-tour-{}.json", std::process::id()));
+    let mut store = Store::open(path);
+    let config = Config::load();
+    let mut editor = LineEditor::new(Vec::new());
+    let default_hook = repl::show_panic_messages();
+
+    println!(
+        "Welcome to task-cli! This tour uses a scratch task list, so try anything.\n\
+         Type the suggested command (or just press enter to run it), `skip` to\n\
+         move on and `exit` to leave.\n"
+    );
+
+    let mut steps = STEPS.iter().enumerate().peekable();
+    'steps: while let Some((index, step)) = steps.peek() {
+        let suggestion = example(step, &store);
+        println!("[{}/{}] {}", index + 1, STEPS.len(), step.intro);
+        println!("    try: {suggestion}");
+
+        loop {
+            let line = match editor
+                .read_line("tour> ", |before| repl::completions(before, &store.tasks))
+            {
+                Ok(ReadResult::Line(line)) => line,
+                Ok(ReadResult::Interrupted) => continue,
+                Ok(ReadResult::Eof) | Err(_) => break 'steps,
+            };
+            let line = match line.trim() {
+                "" => {
+                    println!("tour> {suggestion}");
+                    suggestion.clone()
+                }
+                "exit" | "quit" => break 'steps,
+                "skip" => break,
+                "help" => {
+                    commands::print_help();
+                    continue;
+                }
+                line => line.to_owned(),
+            };
+
+            match run_line(&line, &config, &mut store) {
+                Some(name) if name == step.command => break,
+                Some(_) => println!("nice; now try `{suggestion}`"),
+                None => println!("no harm done; try `{suggestion}`"),
+            }
+        }
+
+        println!();
+        steps.next();
+    }
+
+    panic::set_hook(default_hook);
+
+    if steps.peek().is_none() {
+        println!(
+            "That's the basics! Your real tasks live in {}; run `task-cli help`\n\
+             for everything else, or `task-cli interactive` for a prompt like this one.",
+            store::tasks_path(args).display()
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Opt-in log of the commands that were run, kept in `usage.jsonl` in the
+//! config directory and only ever read by `task-cli usage`.
+
+use chrono::{DateTime, Local, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io::Write, path::PathBuf};
+
+use crate::{
+    args::Args,
+    config::{config_dir, Config},
+    CliError,
+};
+
+/// Options whose values describe which tasks were shown.
+const FILTER_OPTIONS: &[&str] = &["tag", "where", "due", "created", "updated", "sort"];
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub command: String,
+    /// Names of the options given, without their values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    /// Filters such as `status=done` or `where=priority >= high`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub completes: bool,
+}
+
+pub fn path() -> PathBuf {
+    config_dir().join("usage.jsonl")
+}
+
+impl Entry {
+    fn new(args: &Args) -> Option<Self> {
+        let command = args
+            .positional(0)
+            .filter(|command| *command != "usage")?
+            .to_owned();
+
+        let mut options = args.option_names().map(str::to_owned).collect::<Vec<_>>();
+        options.sort();
+
+        let mut filters = Vec::new();
+        let status = match command.as_str() {
+            "list" => args.positional(1),
+            "edit" => args.value("status"),
+            _ => None,
+        };
+        if let Some(status) = status {
+            filters.push(format!("status={status}"));
+        }
+        if matches!(command.as_str(), "list" | "edit") {
+            for name in FILTER_OPTIONS {
+                if let Some(value) = args.value(name) {
+                    filters.push(format!("{name}={value}"));
+                }
+            }
+        }
+
+        let completes = command == "done"
+            || (command == "mark" && args.value("status").or(args.positional(2)) == Some("done"));
+
+        Some(Self {
+            at: Utc::now(),
+            command,
+            options,
+            filters,
+            completes,
+        })
+    }
+}
+
+/// Appends the command in `args` to the log when usage logging is enabled.
+/// Failing to write the log never fails the command.
+pub fn record(args: &Args, config: &Config) {
+    if !config.usage {
+        return;
+    }
+    let Some(entry) = Entry::new(args) else {
+        return;
+    };
+
+    let _ = std::fs::create_dir_all(config_dir()).and_then(|_| {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path())?;
+        let line = serde_json::to_string(&entry).expect("could not convert to json");
+        writeln!(file, "{line}")
+    });
+}
+
+pub fn load() -> Vec<Entry> {
+    let Ok(contents) = std::fs::read_to_string(path()) else {
+        return Vec::new();
+    };
+
+    // A line cut short by a crash is skipped rather than losing the log.
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+pub fn clear() -> Result<(), CliError> {
+    let path = path();
+    match std::fs::remove_file(&path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+            Err(CliError::Write { path, error })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Most frequent first, ties by name.
+fn ranked<'a>(items: impl Iterator<Item = &'a str>) -> Vec<(&'a str, usize)> {
+    let mut counts = HashMap::new();
+    for item in items {
+        *counts.entry(item).or_insert(0) += 1;
+    }
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts
+}
+
+fn hours<'a>(entries: impl Iterator<Item = &'a Entry>) -> [usize; 24] {
+    let mut hours = [0; 24];
+    for entry in entries {
+        hours[entry.at.with_timezone(&Local).hour() as usize] += 1;
+    }
+    hours
+}
+
+fn bar(count: usize, max: usize) -> String {
+    const WIDTH: usize = 20;
+    let filled = if max == 0 {
+        0
+    } else {
+        (count * WIDTH).div_ceil(max)
+    };
+    "#".repeat(filled)
+}
+
+/// Prints the most used commands, options and filters and when tasks get
+/// added and completed.
+pub fn report(entries: &[Entry], top: usize) {
+    let first = entries.iter().map(|entry| entry.at).min();
+    if let Some(first) = first {
+        println!(
+            "{} commands since {}",
+            entries.len(),
+            first.with_timezone(&Local).format("%Y-%m-%d")
+        );
+    }
+
+    let sections = [
+        (
+            "Commands",
+            ranked(entries.iter().map(|entry| entry.command.as_str())),
+        ),
+        (
+            "Options",
+            ranked(
+                entries
+                    .iter()
+                    .flat_map(|entry| entry.options.iter().map(String::as_str)),
+            ),
+        ),
+        (
+            "Filters",
+            ranked(
+                entries
+                    .iter()
+                    .flat_map(|entry| entry.filters.iter().map(String::as_str)),
+            ),
+        ),
+    ];
+    for (title, counts) in sections {
+        if counts.is_empty() {
+            continue;
+        }
+        println!("\n{title}:");
+        for (name, count) in counts.into_iter().take(top) {
+            println!("    {count:>5}  {name}");
+        }
+    }
+
+    let added = hours(entries.iter().filter(|entry| entry.command == "add"));
+    let completed = hours(entries.iter().filter(|entry| entry.completes));
+    let max = added.iter().chain(&completed).copied().max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+
+    println!("\nHour  {:<20}  completed", "added");
+    for hour in 0..24 {
+        if added[hour] + completed[hour] == 0 {
+            continue;
+        }
+        println!(
+            "{hour:02}:00 {:<20}  {}",
+            bar(added[hour], max),
+            bar(completed[hour], max)
+        );
+    }
+}
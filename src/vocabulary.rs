@@ -0,0 +1,109 @@
+//! Display names for statuses and priorities, such as German ones or the
+//! team's own jargon. Output uses the first name of each; every name is
+//! accepted as input, as are the built-in ones. The store keeps the
+//! built-in names, so it reads the same whatever the vocabulary.
+//!
+//! ```toml
+//! [vocabulary.status]
+//! todo = "offen"
+//! in-progress = ["in Arbeit", "läuft"]
+//! done = "erledigt"
+//!
+//! [vocabulary.priority]
+//! urgent = "P0"
+//! ```
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, sync::RwLock};
+
+pub const STATUSES: [&str; 3] = ["todo", "in-progress", "done"];
+pub const PRIORITIES: [&str; 4] = ["low", "medium", "high", "urgent"];
+
+/// One name, or several of which the first is shown.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Names {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Names {
+    fn all(&self) -> &[String] {
+        match self {
+            Self::One(name) => std::slice::from_ref(name),
+            Self::Many(names) => names,
+        }
+    }
+}
+
+/// The `[vocabulary]` table of the config file, by built-in name.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Vocabulary {
+    pub status: BTreeMap<String, Names>,
+    pub priority: BTreeMap<String, Names>,
+}
+
+/// The vocabulary of the config file, once it has been loaded.
+static ACTIVE: RwLock<Vocabulary> = RwLock::new(Vocabulary {
+    status: BTreeMap::new(),
+    priority: BTreeMap::new(),
+});
+
+impl Vocabulary {
+    /// Checks that every name given belongs to a built-in one.
+    pub fn check(&self) -> Result<(), String> {
+        let tables = [
+            ("status", &self.status, &STATUSES[..]),
+            ("priority", &self.priority, &PRIORITIES[..]),
+        ];
+        for (table, names, builtin) in tables {
+            if let Some(unknown) = names.keys().find(|name| !builtin.contains(&name.as_str())) {
+                return Err(format!(
+                    "unknown {table} `{unknown}` in [vocabulary.{table}], expected one of {}",
+                    builtin.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes `self` the vocabulary of the whole process.
+    pub fn install(self) {
+        *ACTIVE.write().unwrap_or_else(|err| err.into_inner()) = self;
+    }
+}
+
+fn table<'v>(vocabulary: &'v Vocabulary, kind: &str) -> &'v BTreeMap<String, Names> {
+    match kind {
+        "status" => &vocabulary.status,
+        _ => &vocabulary.priority,
+    }
+}
+
+/// How to show the built-in `name` of a `kind`, `status` or `priority`.
+pub fn display(kind: &str, name: &'static str) -> String {
+    let active = ACTIVE.read().unwrap_or_else(|err| err.into_inner());
+    table(&active, kind)
+        .get(name)
+        .and_then(|names| names.all().first().cloned())
+        .unwrap_or_else(|| name.to_owned())
+}
+
+/// The built-in name `input` stands for, ignoring case, among `builtin`.
+pub fn resolve(kind: &str, builtin: &[&'static str], input: &str) -> Option<&'static str> {
+    if let Some(name) = builtin.iter().find(|name| **name == input) {
+        return Some(name);
+    }
+    let active = ACTIVE.read().unwrap_or_else(|err| err.into_inner());
+    let names = table(&active, kind);
+    let input = input.to_lowercase();
+    builtin.iter().copied().find(|name| {
+        names.get(*name).is_some_and(|names| {
+            names
+                .all()
+                .iter()
+                .any(|alias| alias.to_lowercase() == input)
+        })
+    })
+}